@@ -1,7 +1,10 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use pog::blockchain::path::concat_tx_hash_with_to_hash_static;
+use pog::blockchain::block::{Block, Body};
+use pog::blockchain::path::{
+    concat_tx_hash_with_to_hash_static, AggregatedSignedPaths, TransactionPaths,
+};
 use pog::blockchain::transaction::Transaction;
-use pog::wallet::Wallet;
+use pog::wallet::{KeyRegistry, Wallet};
 
 fn sign_paths_with_bls(wallets: Vec<Wallet>, tx_hash: String, n: usize) {
     for i in 1..n + 1 {
@@ -71,5 +74,136 @@ fn bench_secp256k1_sign(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_bls_sign, bench_secp256k1_sign);
+fn gen_body_with_paths(tx_num: usize, hops: usize) -> (Body, Wallet) {
+    let wallets: Vec<Wallet> = (0..hops + 1).map(|_| Wallet::new()).collect();
+    let miner = wallets.last().unwrap().clone();
+    let mut transactions = vec![];
+    let mut paths = vec![];
+    for n in 0..tx_num {
+        // 接收方不同，保证同一毫秒内生成的交易哈希也不同
+        let transaction = Transaction::new(n.to_string(), 32, wallets[0].clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        for i in 1..hops + 1 {
+            transaction_paths.add_path(wallets[i].address.clone(), wallets[i - 1].clone());
+        }
+        transactions.push(transaction);
+        paths.push(transaction_paths.to_aggregated_signed_paths());
+    }
+    (Body::new(transactions, paths), miner)
+}
+
+fn gen_block_with_paths(tx_num: usize, hops: usize) -> Block {
+    let (body, miner) = gen_body_with_paths(tx_num, hops);
+    Block::new(1, 0, 1, "".to_string(), body, miner).unwrap()
+}
+
+fn bench_block_create(c: &mut Criterion) {
+    let (body, miner) = gen_body_with_paths(50, 10);
+    let registry = KeyRegistry::thread_default();
+
+    c.bench_function("block create 50 paths (10 hops) full path verify", |b| {
+        b.iter(|| Block::new(1, 0, 1, "".to_string(), body.clone(), miner.clone()).unwrap())
+    });
+
+    c.bench_function("block create 50 paths (10 hops) 10% path verify", |b| {
+        b.iter(|| {
+            Block::new_with_path_sample(
+                1,
+                0,
+                1,
+                "".to_string(),
+                body.clone(),
+                miner.clone(),
+                0.1,
+                &registry,
+            )
+            .unwrap()
+        })
+    });
+
+    c.bench_function("block create 50 paths (10 hops) no path verify", |b| {
+        b.iter(|| {
+            Block::new_with_path_sample(
+                1,
+                0,
+                1,
+                "".to_string(),
+                body.clone(),
+                miner.clone(),
+                0.0,
+                &registry,
+            )
+            .unwrap()
+        })
+    });
+}
+
+fn bench_block_verify(c: &mut Criterion) {
+    let block = gen_block_with_paths(50, 10);
+    let registry = KeyRegistry::thread_default();
+
+    c.bench_function("block verify 50 paths (10 hops) serial", |b| {
+        b.iter(|| block.verify_paths_serial(&registry))
+    });
+
+    c.bench_function("block verify 50 paths (10 hops) rayon", |b| {
+        b.iter(|| block.verify_paths(&registry))
+    });
+}
+
+fn bench_batch_verify(c: &mut Criterion) {
+    let block = gen_block_with_paths(50, 10);
+    let registry = KeyRegistry::thread_default();
+    let items: Vec<(Transaction, AggregatedSignedPaths, String)> = block
+        .body
+        .transactions
+        .iter()
+        .zip(block.body.paths.iter())
+        .map(|(t, p)| (t.clone(), p.clone(), block.header.miner.clone()))
+        .collect();
+
+    c.bench_function("verify 50 paths (10 hops) per path", |b| {
+        b.iter(|| {
+            items
+                .iter()
+                .map(|(t, p, miner)| p.verify(t.clone(), miner.clone(), &registry))
+                .collect::<Vec<bool>>()
+        })
+    });
+
+    c.bench_function("verify 50 paths (10 hops) batch", |b| {
+        b.iter(|| AggregatedSignedPaths::batch_verify(&items, &registry))
+    });
+}
+
+fn bench_pk_cache(c: &mut Criterion) {
+    let block = gen_block_with_paths(20, 50);
+    let miner = block.header.miner.clone();
+    let registry = KeyRegistry::thread_default();
+
+    c.bench_function("verify 20 paths (50 hops) without pk cache", |b| {
+        b.iter(|| {
+            block
+                .body
+                .transactions
+                .iter()
+                .zip(block.body.paths.iter())
+                .all(|(t, p)| p.verify(t.clone(), miner.clone(), &registry))
+        })
+    });
+
+    c.bench_function("verify 20 paths (50 hops) with shared pk cache", |b| {
+        b.iter(|| block.verify_paths_serial(&registry))
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_bls_sign,
+    bench_secp256k1_sign,
+    bench_block_create,
+    bench_block_verify,
+    bench_batch_verify,
+    bench_pk_cache
+);
 criterion_main!(benches);
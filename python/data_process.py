@@ -1,3 +1,4 @@
+import glob
 import json
 import os
 from dataclasses import dataclass, field
@@ -142,10 +143,17 @@ def get_project_root():
     return current_dir
 
 
+def get_latest_blockchain_json(project_root):
+    # 节点输出的文件名为 blockchain_node{编号}_{最新区块哈希}.json，取最近写入的一个
+    dumps = glob.glob(os.path.join(project_root, 'blockchain_node*_*.json'))
+    if not dumps:
+        return os.path.join(project_root, 'blockchain.json')
+    return max(dumps, key=os.path.getmtime)
+
+
 def get_blockchain_from_json(path=None):
     if path is None:
-        project_root = get_project_root()
-        path = os.path.join(project_root, 'blockchain.json')
+        path = get_latest_blockchain_json(get_project_root())
     with open(path, 'r') as f:
         block_list = json.load(f)
     # 创世区块去掉
@@ -1,12 +1,22 @@
 use crate::blockchain::path::{AggregatedSignedPaths, TransactionPaths};
 use crate::blockchain::transaction::Transaction;
 use crate::tools;
-use crate::wallet::Wallet;
+use crate::wallet::{KeyRegistry, Wallet};
 use hex::{decode, encode};
 use log::{error, info};
+use rand::Rng;
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
+
+/// 确定性创世块的时间戳
+pub const GENESIS_TIMESTAMP: u64 = 0;
+/// 创世块出块钱包的派生序号，避免与节点钱包冲突
+const GENESIS_WALLET_INDEX: u32 = u32::MAX;
+/// verify_paths 每次批量验证的路径数
+const VERIFY_BATCH_SIZE: usize = 64;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Block {
     pub header: Header,
@@ -22,7 +32,11 @@ pub struct Header {
     pub parent_hash: String,
     pub timestamp: u64,
     pub merkle_root: String,
+    // 旧版本的区块头没有该字段，为空时不参与序列化，旧区块的哈希保持不变
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub paths_root: String, // 交易路径的默克尔根，使区块哈希绑定传播路径
     pub miner: String,
+    pub signature: String, // 出块者对区块哈希的签名
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -37,6 +51,7 @@ impl Header {
         epoch: u64,
         slot: u64,
         merkle_root: String,
+        paths_root: String,
         miner: String,
         parent_hash: String,
     ) -> Header {
@@ -48,20 +63,40 @@ impl Header {
             parent_hash,
             timestamp: tools::get_timestamp(),
             merkle_root,
+            paths_root,
             miner,
+            signature: "".to_string(),
         };
         header.hash = header.get_hash();
         header
     }
 
+    /// 哈希不包含 hash 和 signature 字段
     pub fn get_hash(&self) -> String {
         let mut header = self.clone();
         header.hash = "".to_string();
+        header.signature = "".to_string();
         let t_json = serde_json::to_string(&header).unwrap();
         let hash = tools::Hasher::hash(t_json.as_bytes().to_vec());
         encode(hash)
     }
 
+    pub fn sign(&mut self, wallet: &Wallet) {
+        self.signature = wallet.sign(self.hash.as_bytes().to_vec());
+    }
+
+    /// 哈希与内容一致，且签名来自 miner
+    pub fn verify_signature(&self) -> bool {
+        if self.hash != self.get_hash() {
+            return false;
+        }
+        Wallet::verify_by_address(
+            self.hash.as_bytes().to_vec(),
+            self.signature.clone(),
+            self.miner.clone(),
+        )
+    }
+
     pub fn bytes(&self) -> u64 {
         let index = 8;
         let epoch = 8;
@@ -70,12 +105,24 @@ impl Header {
         let hash = self.hash.as_bytes().len() as u64;
         let parent_hash = self.parent_hash.as_bytes().len() as u64;
         let merkle_root = self.merkle_root.as_bytes().len() as u64;
+        let paths_root = self.paths_root.len() as u64;
         let miner = self.miner.as_bytes().len() as u64;
-        index + epoch + slot + timestamp + hash + parent_hash + merkle_root + miner
+        let signature = self.signature.len() as u64;
+        index
+            + epoch
+            + slot
+            + timestamp
+            + hash
+            + parent_hash
+            + merkle_root
+            + paths_root
+            + miner
+            + signature
     }
 }
 
 impl Block {
+    /// 创建区块并验证全部路径签名，公钥在当前线程的默认注册表中查找
     pub fn new(
         index: u64,
         epoch: u64,
@@ -83,21 +130,60 @@ impl Block {
         parent_hash: String,
         body: Body,
         wallet: Wallet,
+    ) -> Result<Block, BlockError> {
+        Block::new_with_path_sample(
+            index,
+            epoch,
+            slot,
+            parent_hash,
+            body,
+            wallet,
+            1.0,
+            &KeyRegistry::thread_default(),
+        )
+    }
+
+    /// 创建区块，按 sample_rate（0~1）随机抽样验证路径的 BLS 聚合签名，交易签名总是验证
+    /// 出块者信任自己的内存池时可以降低比例甚至设为0，省去出块时最昂贵的验证；
+    /// 代价是无效的路径会被打包并广播，只能依靠收到区块的节点按 block_verify_sample 抽样发现
+    /// 路径上节点的 BLS 公钥在 registry 中查找
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_path_sample(
+        index: u64,
+        epoch: u64,
+        slot: u64,
+        parent_hash: String,
+        body: Body,
+        wallet: Wallet,
+        sample_rate: f64,
+        registry: &KeyRegistry,
     ) -> Result<Block, BlockError> {
         if body.transactions.len() != body.paths.len() {
             return Err(BlockError::InvalidBlock);
         }
+        let sample_rate = sample_rate.clamp(0.0, 1.0);
+        let mut rng = rand::thread_rng();
         for (i, transaction) in body.transactions.iter().enumerate() {
             if !transaction.verify() {
                 return Err(BlockError::InvalidBlockTransactions);
             }
-            if !body.paths[i].verify(transaction.clone(), wallet.address.clone()) {
+            if sample_rate > 0.0
+                && (sample_rate == 1.0 || rng.gen_bool(sample_rate))
+                && !body.paths[i].verify(transaction.clone(), wallet.address.clone(), registry)
+            {
                 return Err(BlockError::InvalidBlockPath);
             }
         }
-        let hash_vec = body.transactions.iter().map(|t| t.hash.clone()).collect();
-        let merkle_root = Block::cal_merkle_root(hash_vec);
-        let header = Header::new(index, epoch, slot, merkle_root, wallet.address, parent_hash);
+        let mut header = Header::new(
+            index,
+            epoch,
+            slot,
+            body.merkle_root(),
+            body.paths_root(),
+            wallet.address.clone(),
+            parent_hash,
+        );
+        header.sign(&wallet);
         Ok(Block { header, body })
     }
 
@@ -106,6 +192,20 @@ impl Block {
             error!("{}", BlockError::InvalidBlock);
             return false;
         }
+        if !self.header.verify_signature() {
+            error!("{}", BlockError::InvalidBlockSignature);
+            return false;
+        }
+        // 头部的两个默克尔根必须与区块体一致，否则交易或路径被替换过
+        if self.header.merkle_root != self.body.merkle_root() {
+            error!("{}", BlockError::InvalidBlockTransactions);
+            return false;
+        }
+        // paths_root 为空的是旧版本的区块，哈希没有绑定路径
+        if !self.header.paths_root.is_empty() && self.header.paths_root != self.body.paths_root() {
+            error!("{}", BlockError::InvalidBlockPath);
+            return false;
+        }
         for (_i, transaction) in self.body.transactions.iter().enumerate() {
             if !transaction.verify() {
                 error!("{}", BlockError::InvalidBlockTransactions);
@@ -120,6 +220,101 @@ impl Block {
         true
     }
 
+    /// 验证所有路径签名（串行）
+    pub fn verify_paths_serial(&self, registry: &KeyRegistry) -> bool {
+        if self.body.transactions.len() != self.body.paths.len() {
+            return false;
+        }
+        let mut pk_cache = HashMap::new();
+        self.body
+            .transactions
+            .iter()
+            .zip(self.body.paths.iter())
+            .all(|(transaction, path)| {
+                path.verify_with_cache(
+                    transaction.clone(),
+                    self.header.miner.clone(),
+                    registry,
+                    &mut pk_cache,
+                )
+            })
+    }
+
+    /// 验证所有路径签名，每 VERIFY_BATCH_SIZE 条路径合并为一次批量验证，各批使用 rayon 多线程并行，
+    /// 遇到第一个失败即停止。路径上节点的 BLS 公钥在 registry 中查找
+    pub fn verify_paths(&self, registry: &KeyRegistry) -> bool {
+        if self.body.transactions.len() != self.body.paths.len() {
+            return false;
+        }
+        let items: Vec<(Transaction, AggregatedSignedPaths, String)> = self
+            .body
+            .transactions
+            .iter()
+            .zip(self.body.paths.iter())
+            .map(|(transaction, path)| {
+                (transaction.clone(), path.clone(), self.header.miner.clone())
+            })
+            .collect();
+        items.par_chunks(VERIFY_BATCH_SIZE).all(|batch| {
+            AggregatedSignedPaths::batch_verify(batch, registry)
+                .into_iter()
+                .all(|valid| valid)
+        })
+    }
+
+    /// 按比例随机抽样验证路径签名，sample_rate 取值 0~1
+    /// 完整验证每条路径代价太高，抽样可以在安全性和CPU开销之间折中
+    /// sample_rate 为 1 时并行验证全部路径
+    pub fn verify_paths_sample<R: Rng>(
+        &self,
+        sample_rate: f64,
+        rng: &mut R,
+        registry: &KeyRegistry,
+    ) -> bool {
+        // 路径数与交易数不一致的区块无效，也避免下面按交易下标取路径越界
+        if self.body.transactions.len() != self.body.paths.len() {
+            error!(
+                "{}: block[{}] has {} transactions but {} paths",
+                BlockError::InvalidBlockPath,
+                self.header.index,
+                self.body.transactions.len(),
+                self.body.paths.len()
+            );
+            return false;
+        }
+        let sample_rate = sample_rate.clamp(0.0, 1.0);
+        if sample_rate == 0.0 {
+            return true;
+        }
+        if sample_rate == 1.0 {
+            if !self.verify_paths(registry) {
+                error!(
+                    "{}: block[{}] failed full path verification",
+                    BlockError::InvalidBlockPath,
+                    self.header.index
+                );
+                return false;
+            }
+            return true;
+        }
+        for (i, transaction) in self.body.transactions.iter().enumerate() {
+            if !rng.gen_bool(sample_rate) {
+                continue;
+            }
+            if !self.body.paths[i].verify(transaction.clone(), self.header.miner.clone(), registry)
+            {
+                error!(
+                    "{}: sampled path of transaction[{}] in block[{}] failed",
+                    BlockError::InvalidBlockPath,
+                    transaction.hash,
+                    self.header.index
+                );
+                return false;
+            }
+        }
+        true
+    }
+
     pub fn cal_merkle_root(mut leaves: Vec<String>) -> String {
         // 使用迭代替代递归，避免深度递归导致栈溢出
         while leaves.len() > 1 {
@@ -149,6 +344,37 @@ impl Block {
         Block::new(0, 0, 0, "".to_string(), body, miner).unwrap()
     }
 
+    /// 确定性的创世块：出块钱包由 seed 派生，时间戳固定为 GENESIS_TIMESTAMP
+    /// 相同的 seed 和 initial_supply 总是得到相同的创世块
+    pub fn gen_genesis_block_with(seed: u64, initial_supply: i64) -> Block {
+        let miner = Wallet::new_deterministic(seed, GENESIS_WALLET_INDEX);
+        let transaction = Transaction::with_timestamp(
+            "000".to_string(),
+            initial_supply,
+            1.0,
+            GENESIS_TIMESTAMP,
+            miner.clone(),
+        );
+        let transaction_paths = TransactionPaths::new(transaction.clone());
+        let paths = AggregatedSignedPaths::from_transaction_paths(transaction_paths);
+        let body = Body::new(vec![transaction], vec![paths]);
+        let mut block = Block::new(0, 0, 0, "".to_string(), body, miner.clone()).unwrap();
+        block.header.timestamp = GENESIS_TIMESTAMP;
+        block.header.hash = block.header.get_hash();
+        block.header.sign(&miner);
+        block
+    }
+
+    /// 从文件加载创世块，文件内容为区块的 JSON
+    pub fn load_genesis_block(path: &str) -> Result<Block, BlockError> {
+        let json = std::fs::read(path).map_err(|_| BlockError::JSONError)?;
+        let block = Block::from_json(json)?;
+        if block.header.index != 0 || !block.verify() {
+            return Err(BlockError::InvalidBlock);
+        }
+        Ok(block)
+    }
+
     pub fn count_node_paths_map(&self) -> HashMap<String, usize> {
         let mut counts: HashMap<String, usize> = HashMap::new();
         for x in self.body.paths.clone() {
@@ -185,6 +411,28 @@ impl Block {
         paths
     }
 
+    /// 单条路径的长度：路径形如 [发送者, 中继..., 矿工]，长度为转发跳数，即节点数减一
+    pub fn path_length(path: &[String]) -> usize {
+        path.len().saturating_sub(1)
+    }
+
+    /// 一组路径的平均长度（定义见 path_length），没有路径时为 0
+    pub fn mean_path_length(paths: &[Vec<String>]) -> f64 {
+        if paths.is_empty() {
+            return 0.0;
+        }
+        paths
+            .iter()
+            .map(|path| Self::path_length(path))
+            .sum::<usize>() as f64
+            / paths.len() as f64
+    }
+
+    /// 区块内所有交易路径的平均长度，奖励惩罚、NTD 调整和指标统计统一使用该定义
+    pub fn average_path_length(&self) -> f64 {
+        Self::mean_path_length(&self.get_all_paths())
+    }
+
     pub fn from_json(json: Vec<u8>) -> Result<Block, BlockError> {
         let block: Block = serde_json::from_slice(json.as_slice())?;
         Ok(block)
@@ -253,6 +501,22 @@ impl Block {
 }
 
 impl Body {
+    /// 交易哈希的默克尔根
+    pub fn merkle_root(&self) -> String {
+        let leaves = self.transactions.iter().map(|t| t.hash.clone()).collect();
+        Block::cal_merkle_root(leaves)
+    }
+
+    /// 路径的默克尔根，叶子为每个 AggregatedSignedPaths 的 JSON 哈希（包含路径和聚合签名）
+    pub fn paths_root(&self) -> String {
+        let leaves = self
+            .paths
+            .iter()
+            .map(|p| encode(tools::Hasher::hash(p.to_json())))
+            .collect();
+        Block::cal_merkle_root(leaves)
+    }
+
     pub fn new(transactions: Vec<Transaction>, paths: Vec<AggregatedSignedPaths>) -> Body {
         Body {
             transactions,
@@ -271,6 +535,7 @@ pub enum BlockError {
     InvalidBlock,
     InvalidBlockPath,
     InvalidBlockTransactions,
+    InvalidBlockSignature,
     JSONError,
 }
 
@@ -287,6 +552,9 @@ impl fmt::Display for BlockError {
             BlockError::InvalidBlockTransactions => {
                 write!(f, "Invalid Block Transactions Error")
             }
+            BlockError::InvalidBlockSignature => {
+                write!(f, "Invalid Block Signature Error")
+            }
             BlockError::JSONError => {
                 write!(f, "Invalid Block Json Error")
             }
@@ -334,8 +602,262 @@ mod tests {
         block.simple_print();
     }
 
+    #[test]
+    fn test_average_path_length() {
+        let sender = Wallet::new();
+        let relay = Wallet::new();
+        let relay2 = Wallet::new();
+        let miner = Wallet::new();
+
+        // 路径一：sender -> relay -> relay2 -> miner，3 跳
+        let transaction = Transaction::new("123".to_string(), 32, sender.clone());
+        let mut long_paths = TransactionPaths::new(transaction.clone());
+        long_paths.add_path(relay.address.clone(), sender.clone());
+        long_paths.add_path(relay2.address.clone(), relay.clone());
+        long_paths.add_path(miner.address.clone(), relay2.clone());
+        // 路径二：sender -> miner，1 跳
+        let transaction2 = Transaction::new("123".to_string(), 33, sender.clone());
+        let mut short_paths = TransactionPaths::new(transaction2.clone());
+        short_paths.add_path(miner.address.clone(), sender.clone());
+
+        let body = Body::new(
+            vec![transaction, transaction2],
+            vec![
+                AggregatedSignedPaths::from_transaction_paths(long_paths),
+                AggregatedSignedPaths::from_transaction_paths(short_paths),
+            ],
+        );
+        let block = Block::new(1, 0, 0, String::from(""), body, miner).unwrap();
+        assert_eq!(block.count_all_paths(), 6);
+        // (3 + 1) / 2 = 2
+        assert_eq!(block.average_path_length(), 2.0);
+        assert_eq!(Block::path_length(&block.get_all_paths()[0]), 3);
+        assert_eq!(Block::mean_path_length(&[]), 0.0);
+        assert_eq!(Block::gen_genesis_block().average_path_length(), 0.0);
+    }
+
+    /// 替换区块中的路径会使 paths_root 与头部不一致，即使新路径本身签名合法
+    #[test]
+    fn test_paths_root_tamper() {
+        let sender = Wallet::new();
+        let relay = Wallet::new();
+        let miner = Wallet::new();
+        let transaction = Transaction::new("123".to_string(), 32, sender.clone());
+        let mut relayed = TransactionPaths::new(transaction.clone());
+        relayed.add_path(relay.address.clone(), sender.clone());
+        relayed.add_path(miner.address.clone(), relay.clone());
+        let mut direct = TransactionPaths::new(transaction.clone());
+        direct.add_path(miner.address.clone(), sender.clone());
+
+        let body = Body::new(
+            vec![transaction],
+            vec![AggregatedSignedPaths::from_transaction_paths(relayed)],
+        );
+        let block = Block::new(1, 0, 1, String::from(""), body, miner.clone()).unwrap();
+        assert!(block.verify());
+        assert_eq!(block.header.paths_root, block.body.paths_root());
+
+        // 去掉中继节点的路径
+        let mut tampered = block.clone();
+        tampered.body.paths = vec![AggregatedSignedPaths::from_transaction_paths(direct)];
+        assert!(tampered.body.paths[0].verify(
+            tampered.body.transactions[0].clone(),
+            miner.address,
+            &KeyRegistry::thread_default()
+        ));
+        assert!(!tampered.verify());
+
+        // 替换交易
+        let mut tampered = block.clone();
+        tampered.body.transactions = vec![Transaction::new("456".to_string(), 1, sender)];
+        assert!(!tampered.verify());
+    }
+
+    /// 没有 paths_root 字段的旧区块头仍能解析，paths_root 为空
+    #[test]
+    fn test_header_without_paths_root() {
+        let block = Block::gen_genesis_block();
+        let mut json = serde_json::to_value(&block.header).unwrap();
+        json.as_object_mut().unwrap().remove("paths_root");
+        let header: Header = serde_json::from_value(json).unwrap();
+        assert_eq!(header.paths_root, "");
+        assert_eq!(header.hash, block.header.hash);
+        assert_eq!(
+            header.bytes(),
+            block.header.bytes() - block.header.paths_root.len() as u64
+        );
+
+        // 旧版本的区块：哈希和签名不包含 paths_root
+        let miner = Wallet::new();
+        let transaction = Transaction::new("123".to_string(), 32, miner.clone());
+        let body = Body::new(
+            vec![transaction.clone()],
+            vec![AggregatedSignedPaths::from_transaction_paths(
+                TransactionPaths::new(transaction),
+            )],
+        );
+        let mut block = Block::new(1, 0, 1, String::from(""), body, miner.clone()).unwrap();
+        block.header.paths_root = String::new();
+        block.header.hash = block.header.get_hash();
+        block.header.sign(&miner);
+
+        let json = serde_json::to_value(&block.header).unwrap();
+        assert!(json.get("paths_root").is_none());
+        let header: Header = serde_json::from_value(json).unwrap();
+        assert_eq!(header.paths_root, "");
+        assert_eq!(header.get_hash(), block.header.hash);
+        let legacy = Block::from_json(block.to_json()).unwrap();
+        assert!(legacy.verify());
+
+        // 新区块的 paths_root 与区块体不一致时仍然拒绝
+        let mut tampered = legacy.clone();
+        tampered.header.paths_root = "00".to_string();
+        tampered.header.hash = tampered.header.get_hash();
+        tampered.header.sign(&miner);
+        assert!(!tampered.verify());
+    }
+
+    /// 不抽样验证时，带有无效路径的区块也能创建，由接收方的抽样验证发现
+    #[test]
+    fn test_new_with_path_sample() {
+        let sender = Wallet::new();
+        let miner = Wallet::new();
+        let transaction = Transaction::new("123".to_string(), 32, sender.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        // 路径终点不是出块者，验证失败
+        transaction_paths.add_path(Wallet::new().address, sender);
+        let body = Body::new(
+            vec![transaction],
+            vec![transaction_paths.to_aggregated_signed_paths()],
+        );
+        assert!(matches!(
+            Block::new(1, 0, 1, String::from(""), body.clone(), miner.clone()),
+            Err(BlockError::InvalidBlockPath)
+        ));
+        let block = Block::new_with_path_sample(
+            1,
+            0,
+            1,
+            String::from(""),
+            body,
+            miner,
+            0.0,
+            &KeyRegistry::thread_default(),
+        )
+        .unwrap();
+        assert!(block.verify());
+        assert!(!block.verify_paths(&KeyRegistry::thread_default()));
+    }
+
+    #[test]
+    fn test_block_signature() {
+        let miner = Wallet::new();
+        let block =
+            Block::new(1, 0, 1, String::from(""), Body::new(vec![], vec![]), miner).unwrap();
+        assert!(block.header.verify_signature());
+        assert!(block.verify());
+        // 签名不参与哈希计算
+        assert_eq!(block.header.hash, block.header.get_hash());
+
+        // 冒充其他出块者
+        let mut forged = block.clone();
+        forged.header.miner = Wallet::new().address;
+        forged.header.hash = forged.header.get_hash();
+        assert!(!forged.verify());
+
+        // 篡改区块内容
+        let mut tampered = block.clone();
+        tampered.header.slot = 2;
+        assert!(!tampered.verify());
+
+        // 篡改签名
+        let mut tampered = block.clone();
+        tampered.header.signature = Wallet::new().sign(block.header.hash.as_bytes().to_vec());
+        assert!(!tampered.verify());
+        tampered.header.signature = "0x1234".to_string();
+        assert!(!tampered.verify());
+    }
+
+    #[test]
+    fn test_deterministic_genesis_block() {
+        let a = Block::gen_genesis_block_with(7, 100);
+        let b = Block::gen_genesis_block_with(7, 100);
+        assert_eq!(a.header.hash, b.header.hash);
+        assert_eq!(a.header.timestamp, GENESIS_TIMESTAMP);
+        assert_eq!(a.body.transactions[0].amount, 100);
+        assert!(a.verify());
+        assert_ne!(
+            a.header.hash,
+            Block::gen_genesis_block_with(8, 100).header.hash
+        );
+
+        let path = std::env::temp_dir().join(format!("genesis_{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        std::fs::write(&path, a.to_json()).unwrap();
+        let loaded = Block::load_genesis_block(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.header.hash, a.header.hash);
+    }
+
     #[test]
     fn test_gen_genesis_block() {
         println!("{:#?}", Block::gen_genesis_block());
     }
+
+    #[test]
+    fn test_verify_paths_sample() {
+        let wallet = Wallet::new();
+        let wallet2 = Wallet::new();
+        let miner = Wallet::new();
+        let transaction = Transaction::new("123".to_string(), 32, wallet.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(wallet2.address.clone(), wallet);
+        transaction_paths.add_path(miner.address.clone(), wallet2);
+        let body = Body::new(
+            vec![transaction],
+            vec![transaction_paths.to_aggregated_signed_paths()],
+        );
+        let mut block = Block::new(1, 0, 1, String::from(""), body, miner).unwrap();
+        let mut rng = rand::thread_rng();
+        let registry = KeyRegistry::thread_default();
+        assert!(block.verify_paths_sample(1.0, &mut rng, &registry));
+        assert!(block.verify_paths(&registry));
+        assert!(block.verify_paths_serial(&registry));
+        // 公钥没有登记在给定的注册表中时验证失败
+        assert!(!block.verify_paths(&KeyRegistry::new()));
+
+        // 路径终点与出块者不符时，抽样验证失败
+        block.header.miner = Wallet::new().address;
+        assert!(!block.verify_paths_sample(1.0, &mut rng, &registry));
+        assert!(!block.verify_paths(&registry));
+        assert!(!block.verify_paths_serial(&registry));
+        assert!(block.verify_paths_sample(0.0, &mut rng, &registry));
+    }
+
+    /// 缺少路径的区块在抽样验证时返回 false，而不是按下标取路径时 panic
+    #[test]
+    fn test_verify_paths_sample_missing_path() {
+        let miner = Wallet::new();
+        let (transactions, paths): (Vec<Transaction>, Vec<_>) = (0..2)
+            .map(|i| {
+                let wallet = Wallet::new();
+                let transaction = Transaction::new(format!("to{}", i), 1, wallet.clone());
+                let mut transaction_paths = TransactionPaths::new(transaction.clone());
+                transaction_paths.add_path(miner.address.clone(), wallet);
+                (transaction, transaction_paths.to_aggregated_signed_paths())
+            })
+            .unzip();
+        let body = Body::new(transactions, paths);
+        let mut block = Block::new(1, 0, 1, String::from(""), body, miner).unwrap();
+        let mut rng = rand::thread_rng();
+        let registry = KeyRegistry::thread_default();
+        assert!(block.verify_paths_sample(0.5, &mut rng, &registry));
+
+        block.body.paths.pop();
+        for sample_rate in [0.0, 0.5, 1.0] {
+            assert!(!block.verify_paths_sample(sample_rate, &mut rng, &registry));
+        }
+        assert!(!block.verify_paths(&registry));
+        assert!(!block.verify_paths_serial(&registry));
+    }
 }
@@ -5,23 +5,156 @@ pub mod transaction;
 use crate::blockchain::block::Block;
 use log::error;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::fmt;
+use std::path::{Path, PathBuf};
 use tokio::io::AsyncWriteExt;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(from = "SerializedBlockchain")]
 pub struct Blockchain {
-    pub blocks: Vec<Block>,
+    blocks: Vec<Block>,
+    // 链上所有交易的哈希 -> 所在区块的 index，增删区块时同步维护
+    // 不参与序列化，反序列化时由区块重建
+    #[serde(skip)]
+    transaction_block_index: HashMap<String, u64>,
+}
+
+/// Blockchain 的序列化形式，只包含区块
+#[derive(Deserialize)]
+struct SerializedBlockchain {
+    blocks: Vec<Block>,
+}
+
+impl From<SerializedBlockchain> for Blockchain {
+    fn from(serialized: SerializedBlockchain) -> Self {
+        let mut blockchain = Blockchain {
+            blocks: serialized.blocks,
+            transaction_block_index: HashMap::new(),
+        };
+        blockchain.rebuild_index();
+        blockchain
+    }
 }
 
 impl Blockchain {
     pub fn new(genesis_block: Block) -> Blockchain {
-        let mut set = HashSet::new();
-        for x in genesis_block.clone().body.transactions {
-            set.insert(x.hash.to_string());
-        }
+        let transaction_block_index = genesis_block
+            .body
+            .transactions
+            .iter()
+            .map(|x| (x.hash.to_string(), genesis_block.header.index))
+            .collect();
         Blockchain {
             blocks: vec![genesis_block],
+            transaction_block_index,
+        }
+    }
+
+    /// 按当前区块重建交易索引
+    pub fn rebuild_index(&mut self) {
+        self.transaction_block_index = self
+            .blocks
+            .iter()
+            .flat_map(|block| {
+                block
+                    .body
+                    .transactions
+                    .iter()
+                    .map(|tx| (tx.hash.clone(), block.header.index))
+            })
+            .collect();
+    }
+
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// 返回从 start_index 开始（含）的所有区块，超出范围时返回空
+    pub fn get_blocks_from(&self, start_index: u64) -> Vec<Block> {
+        self.blocks
+            .get(start_index as usize..)
+            .map(|blocks| blocks.to_vec())
+            .unwrap_or_default()
+    }
+
+    /// 只保留前 index 个区块（即高度小于 index 的区块），被删除区块的交易哈希一并移除
+    pub fn truncate_to(&mut self, index: u64) {
+        let index = index as usize;
+        if index >= self.blocks.len() {
+            return;
+        }
+        for block in self.blocks.drain(index..) {
+            for t in &block.body.transactions {
+                self.transaction_block_index.remove(&t.hash);
+            }
+        }
+    }
+
+    /// 移除最新的区块，并移除其交易哈希
+    pub fn pop_block(&mut self) -> Option<Block> {
+        let block = self.blocks.pop()?;
+        for t in &block.body.transactions {
+            self.transaction_block_index.remove(&t.hash);
+        }
+        Some(block)
+    }
+
+    /// 用同步来的区块重组本地链
+    /// 在 sync_blocks 中找到与本地链相同的最高区块作为共同祖先，回滚到该祖先后依次添加后续区块
+    /// 回滚深度超过 max_depth 时拒绝重组；添加失败时恢复原链
+    /// 返回添加的区块数
+    pub fn reorg(
+        &mut self,
+        sync_blocks: &[Block],
+        max_depth: u64,
+    ) -> Result<usize, BlockChainError> {
+        let sync_last_index = sync_blocks.last().map_or(0, |b| b.header.index);
+        if sync_last_index <= self.get_last_index() {
+            return Err(BlockChainError::IndexTooSmall);
+        }
+        let ancestor = sync_blocks
+            .iter()
+            .rev()
+            .find(|b| {
+                self.blocks
+                    .get(b.header.index as usize)
+                    .is_some_and(|local| local.header.hash == b.header.hash)
+            })
+            .map(|b| b.header.index)
+            .ok_or(BlockChainError::NoCommonAncestor)?;
+        if self.get_last_index() - ancestor > max_depth {
+            return Err(BlockChainError::ReorgTooDeep);
+        }
+
+        let mut removed = vec![];
+        while self.get_last_index() > ancestor {
+            match self.pop_block() {
+                Some(block) => removed.push(block),
+                None => break,
+            }
+        }
+        let mut applied = 0;
+        for block in sync_blocks.iter().filter(|b| b.header.index > ancestor) {
+            if let Err(e) = self.add_block(block.clone()) {
+                self.truncate_to(ancestor + 1);
+                removed.reverse();
+                self.extend_unchecked(&removed);
+                return Err(e);
+            }
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    /// 不做校验地追加区块，用于用已验证的同步链替换本地链
+    pub fn extend_unchecked(&mut self, blocks: &[Block]) {
+        for block in blocks {
+            for t in &block.body.transactions {
+                self.transaction_block_index
+                    .insert(t.hash.clone(), block.header.index);
+            }
+            self.blocks.push(block.clone());
         }
     }
 
@@ -60,19 +193,29 @@ impl Blockchain {
                 return Err(BlockChainError::TransactionExists);
             }
         }
-        self.blocks.push(block.clone());
+        for x in &block.body.transactions {
+            self.transaction_block_index
+                .insert(x.hash.clone(), block.header.index);
+        }
+        self.blocks.push(block);
         Ok(())
     }
 
+    /// 链上高度 index 处是否为给定哈希的区块
+    pub fn contains_block(&self, index: u64, hash: &str) -> bool {
+        self.blocks
+            .get(index as usize)
+            .is_some_and(|b| b.header.hash == hash)
+    }
+
     pub fn exist_transaction(&self, hash: String) -> bool {
-        for b in &self.blocks {
-            for t in &b.body.transactions {
-                if t.hash == hash {
-                    return true;
-                }
-            }
-        }
-        false
+        self.transaction_block_index.contains_key(&hash)
+    }
+
+    /// 交易的确认数：包含该交易的区块及其之后的区块数，交易不在链上时返回 None
+    pub fn confirmations(&self, tx_hash: &str) -> Option<u64> {
+        let inclusion_index = self.transaction_block_index.get(tx_hash)?;
+        Some(self.get_last_index() - inclusion_index + 1)
     }
 
     pub fn get_last_block(&self) -> Block {
@@ -82,8 +225,23 @@ impl Blockchain {
     pub fn get_last_hash(&self) -> String {
         self.blocks.last().unwrap().header.hash.clone()
     }
+    /// 链为空时返回 0，避免计算最新高度时 panic 或下溢
     pub fn get_last_index(&self) -> u64 {
-        self.get_last_block().header.index
+        self.blocks.last().map_or(0, |b| b.header.index)
+    }
+
+    /// 区块数量（包含创世块）
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// 链高度，即区块数量（包含创世块）
+    pub fn height(&self) -> u64 {
+        self.blocks.len() as u64
     }
 
     pub fn get_last_epoch_slot(&self) -> (u64, u64) {
@@ -120,15 +278,29 @@ impl Blockchain {
         }
     }
 
-    pub async fn write_to_file_all_json(&self) {
-        let path = "blockchain.json";
-        let json = serde_json::to_string_pretty(&self.blocks).unwrap();
-        match tokio::fs::write(path, json).await {
-            Ok(_) => {}
-            Err(e) => {
-                error!("Error writing blockchain file: {}", e);
-            }
-        };
+    /// 整条链的 JSON 文件名，包含节点编号和最新区块哈希，不同节点（链可能分叉）的输出互不覆盖
+    pub fn dump_file_name(&self, node_index: u32) -> String {
+        let hash = self.get_last_hash();
+        format!(
+            "blockchain_node{}_{}.json",
+            node_index,
+            &hash[..8.min(hash.len())]
+        )
+    }
+
+    /// 将整条链写入 dir 下 dump_file_name 对应的文件，返回文件路径
+    /// 先写临时文件再重命名，读取方不会看到写了一半的文件
+    pub async fn write_to_file_all_json(
+        &self,
+        dir: &Path,
+        node_index: u32,
+    ) -> std::io::Result<PathBuf> {
+        let path = dir.join(self.dump_file_name(node_index));
+        let tmp_path = dir.join(format!("{}.tmp", self.dump_file_name(node_index)));
+        let json = serde_json::to_string_pretty(&self.blocks)?;
+        tokio::fs::write(&tmp_path, json).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+        Ok(path)
     }
 
     pub async fn write_to_file_last_block_simple(&self) {
@@ -165,6 +337,8 @@ pub enum BlockChainError {
     DuplicateBlocksReceived,
     TransactionExists,
     IndexTooSmall,
+    NoCommonAncestor,
+    ReorgTooDeep,
 }
 
 impl fmt::Display for BlockChainError {
@@ -196,6 +370,12 @@ impl fmt::Display for BlockChainError {
             BlockChainError::IndexTooSmall => {
                 write!(f, "Index Too Small Error")
             }
+            BlockChainError::NoCommonAncestor => {
+                write!(f, "No Common Ancestor Error")
+            }
+            BlockChainError::ReorgTooDeep => {
+                write!(f, "Reorg Too Deep Error")
+            }
         }
     }
 }
@@ -241,6 +421,213 @@ mod tests {
         )
         .unwrap();
         blockchain.add_block(block).unwrap();
+        assert_eq!(blockchain.len(), 2);
+        assert_eq!(blockchain.height(), 2);
+        assert_eq!(blockchain.get_last_index(), 1);
         blockchain.simple_print_last_five_block();
     }
+
+    #[test]
+    fn test_truncate_keeps_transaction_set() {
+        let mut blockchain = Blockchain::new(Block::gen_genesis_block());
+        let miner = Wallet::new();
+        let mut hashes = vec![];
+        for i in 1..=3 {
+            let wallet = Wallet::new();
+            let transaction = Transaction::new("123".to_string(), i, wallet.clone());
+            hashes.push(transaction.hash.clone());
+            let mut transaction_paths = TransactionPaths::new(transaction.clone());
+            transaction_paths.add_path(miner.address.clone(), wallet);
+            let block = Block::new(
+                i as u64,
+                0,
+                i as u64,
+                blockchain.get_last_hash(),
+                Body::new(
+                    vec![transaction],
+                    vec![AggregatedSignedPaths::from_transaction_paths(
+                        transaction_paths,
+                    )],
+                ),
+                miner.clone(),
+            )
+            .unwrap();
+            blockchain.add_block(block).unwrap();
+        }
+        assert_eq!(blockchain.get_blocks_from(2).len(), 2);
+        assert!(blockchain.get_blocks_from(10).is_empty());
+
+        // 截断后被删除区块的交易不再被视为已上链
+        let removed = blockchain.get_blocks_from(2);
+        blockchain.truncate_to(2);
+        assert_eq!(blockchain.get_last_index(), 1);
+        assert!(blockchain.exist_transaction(hashes[0].clone()));
+        assert!(!blockchain.exist_transaction(hashes[1].clone()));
+        assert!(!blockchain.exist_transaction(hashes[2].clone()));
+
+        // 被删除的区块可以重新加入
+        blockchain.add_block(removed[0].clone()).unwrap();
+        assert!(blockchain.exist_transaction(hashes[1].clone()));
+
+        // 反序列化后交易索引由区块重建
+        let json = serde_json::to_string(&blockchain).unwrap();
+        let loaded: Blockchain = serde_json::from_str(&json).unwrap();
+        assert!(loaded.exist_transaction(hashes[1].clone()));
+        assert!(!loaded.exist_transaction(hashes[2].clone()));
+    }
+
+    fn extend_chain(blockchain: &mut Blockchain, n: u64, miner: &Wallet) {
+        for _ in 0..n {
+            let index = blockchain.get_last_index() + 1;
+            let block = Block::new(
+                index,
+                0,
+                index,
+                blockchain.get_last_hash(),
+                Body::new(vec![], vec![]),
+                miner.clone(),
+            )
+            .unwrap();
+            blockchain.add_block(block).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_confirmations() {
+        let mut blockchain = Blockchain::new(Block::gen_genesis_block());
+        let miner = Wallet::new();
+        let mut hashes = vec![];
+        for i in 1..=3 {
+            let wallet = Wallet::new();
+            let transaction = Transaction::new("123".to_string(), i, wallet.clone());
+            hashes.push(transaction.hash.clone());
+            let mut transaction_paths = TransactionPaths::new(transaction.clone());
+            transaction_paths.add_path(miner.address.clone(), wallet);
+            let block = Block::new(
+                i as u64,
+                0,
+                i as u64,
+                blockchain.get_last_hash(),
+                Body::new(
+                    vec![transaction],
+                    vec![AggregatedSignedPaths::from_transaction_paths(
+                        transaction_paths,
+                    )],
+                ),
+                miner.clone(),
+            )
+            .unwrap();
+            blockchain.add_block(block).unwrap();
+        }
+        // 最新区块中的交易有 1 个确认
+        assert_eq!(blockchain.confirmations(&hashes[0]), Some(3));
+        assert_eq!(blockchain.confirmations(&hashes[1]), Some(2));
+        assert_eq!(blockchain.confirmations(&hashes[2]), Some(1));
+        assert_eq!(blockchain.confirmations("unknown"), None);
+
+        // 后续区块增加确认数
+        extend_chain(&mut blockchain, 2, &miner);
+        assert_eq!(blockchain.confirmations(&hashes[0]), Some(5));
+        assert_eq!(blockchain.confirmations(&hashes[2]), Some(3));
+
+        // 回滚后被删除区块中的交易没有确认
+        blockchain.truncate_to(3);
+        assert_eq!(blockchain.confirmations(&hashes[1]), Some(1));
+        assert_eq!(blockchain.confirmations(&hashes[2]), None);
+
+        // 从 JSON 加载的链同样能查询确认数
+        let json = serde_json::to_string(&blockchain).unwrap();
+        let loaded: Blockchain = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.confirmations(&hashes[0]), Some(2));
+        assert_eq!(loaded.confirmations(&hashes[1]), Some(1));
+        assert_eq!(loaded.confirmations(&hashes[2]), None);
+    }
+
+    #[test]
+    fn test_reorg_within_depth() {
+        let mut local = Blockchain::new(Block::gen_genesis_block());
+        extend_chain(&mut local, 2, &Wallet::new());
+        let mut peer = local.clone();
+        // 本地链和对端链在 #2 之后分叉，本地领先共同祖先 3 个块
+        extend_chain(&mut local, 3, &Wallet::new());
+        extend_chain(&mut peer, 4, &Wallet::new());
+        let sync_blocks = peer.get_blocks_from(1);
+
+        let mut shallow = local.clone();
+        assert_eq!(
+            shallow.reorg(&sync_blocks, 2),
+            Err(BlockChainError::ReorgTooDeep)
+        );
+        assert_eq!(shallow.get_last_hash(), local.get_last_hash());
+
+        assert_eq!(local.reorg(&sync_blocks, 3), Ok(4));
+        assert_eq!(local.get_last_index(), 6);
+        assert_eq!(local.get_last_hash(), peer.get_last_hash());
+
+        // 同步块中没有共同祖先
+        let mut other = Blockchain::new(Block::gen_genesis_block());
+        extend_chain(&mut other, 1, &Wallet::new());
+        assert_eq!(
+            other.reorg(&peer.get_blocks_from(3), 10),
+            Err(BlockChainError::NoCommonAncestor)
+        );
+    }
+
+    #[test]
+    fn test_empty_blockchain_index() {
+        let blockchain = Blockchain {
+            blocks: vec![],
+            transaction_block_index: HashMap::new(),
+        };
+        assert!(blockchain.is_empty());
+        assert_eq!(blockchain.len(), 0);
+        assert_eq!(blockchain.height(), 0);
+        assert_eq!(blockchain.get_last_index(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_write_to_file_all_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut blockchain = Blockchain::new(Block::gen_genesis_block());
+        let genesis_path = blockchain
+            .write_to_file_all_json(dir.path(), 1)
+            .await
+            .unwrap();
+        assert_eq!(genesis_path, dir.path().join(blockchain.dump_file_name(1)));
+        assert!(genesis_path.ends_with(format!(
+            "blockchain_node1_{}.json",
+            &blockchain.get_last_hash()[..8]
+        )));
+        // 同一末端的另一个节点写自己的文件，不覆盖
+        let path = blockchain
+            .write_to_file_all_json(dir.path(), 2)
+            .await
+            .unwrap();
+        assert_ne!(path, genesis_path);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        // 链增长后写入新的文件，之前的输出不被覆盖
+        let wallet = Wallet::new();
+        let block = Block::new(
+            1,
+            0,
+            1,
+            blockchain.get_last_hash(),
+            Body::new(vec![], vec![]),
+            wallet,
+        )
+        .unwrap();
+        blockchain.add_block(block).unwrap();
+        let path = blockchain
+            .write_to_file_all_json(dir.path(), 1)
+            .await
+            .unwrap();
+        assert_ne!(path, genesis_path);
+        let blocks: Vec<Block> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(blocks.len(), 2);
+        let blocks: Vec<Block> =
+            serde_json::from_str(&std::fs::read_to_string(&genesis_path).unwrap()).unwrap();
+        assert_eq!(blocks.len(), 1);
+    }
 }
@@ -1,9 +1,10 @@
 use crate::blockchain::transaction::Transaction;
-use crate::wallet::Wallet;
-use crate::{tools, wallet};
+use crate::tools;
+use crate::wallet::{KeyRegistry, Wallet};
 use blst::min_sig::{PublicKey, Signature};
 use hex::decode;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -11,6 +12,9 @@ pub struct Path {
     pub to: String,
     //此处使用bls的签名
     pub signature: String,
+    // 交易到达 to 的时间（毫秒），只用于分析传播过程，不参与签名
+    #[serde(default)]
+    pub timestamp: u64,
 }
 
 /// 传播交易时使用
@@ -20,11 +24,23 @@ pub struct TransactionPaths {
     pub paths: Vec<Path>,
 }
 
+/// 验证路径签名前的检查结果
+enum PathCheck {
+    // 出块者自己发起的交易，没有需要验证的签名
+    Direct,
+    Invalid,
+    // 需要验证的消息和对应签名者的公钥
+    Signed(Vec<Vec<u8>>, Vec<PublicKey>),
+}
+
 /// 打包到区块时使用
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct AggregatedSignedPaths {
     pub signature: String,
     pub paths: Vec<String>,
+    // paths[1..] 中每一跳的到达时间（毫秒），不参与签名，也不计入区块大小
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hop_timestamps: Vec<u64>,
 }
 
 impl TransactionPaths {
@@ -54,6 +70,7 @@ impl TransactionPaths {
         self.paths.push(Path {
             to,
             signature: sign.clone(),
+            timestamp: tools::get_timestamp_millis(),
         });
     }
 
@@ -85,7 +102,7 @@ impl TransactionPaths {
     //     true
     // }
 
-    pub fn verify(&self, current_address: String) -> bool {
+    pub fn verify(&self, current_address: String, registry: &KeyRegistry) -> bool {
         if !self.transaction.clone().verify() {
             return false;
         }
@@ -97,7 +114,7 @@ impl TransactionPaths {
         for path in &self.paths {
             to = path.to.clone();
             let signature = path.signature.clone();
-            let pk = match wallet::get_bls_pub_key(from) {
+            let pk = match registry.get(&from) {
                 Some(pk) => pk,
                 None => {
                     return false;
@@ -117,7 +134,7 @@ impl TransactionPaths {
     }
 
     //只需要验证上一个节点的签名就行，出块时才需要全部验证
-    pub fn verify_last(&self, current_address: String) -> bool {
+    pub fn verify_last(&self, current_address: String, registry: &KeyRegistry) -> bool {
         if !self.transaction.clone().verify() {
             return false;
         }
@@ -138,7 +155,7 @@ impl TransactionPaths {
             return false;
         }
         let signature = path.signature.clone();
-        let pk = match wallet::get_bls_pub_key(from) {
+        let pk = match registry.get(&from) {
             Some(pk) => pk,
             None => {
                 return false;
@@ -196,21 +213,96 @@ impl AggregatedSignedPaths {
         AggregatedSignedPaths {
             signature: aggregated_sign,
             paths: path_string_vec,
+            hop_timestamps: paths.paths.iter().map(|p| p.timestamp).collect(),
         }
     }
 
-    pub fn verify(&self, transaction: Transaction, miner: String) -> bool {
+    /// 在给定的公钥注册表中查找路径上节点的 BLS 公钥并验证聚合签名
+    pub fn verify(&self, transaction: Transaction, miner: String, registry: &KeyRegistry) -> bool {
+        self.verify_with_cache(transaction, miner, registry, &mut HashMap::new())
+    }
+
+    /// 与 verify 相同，但 BLS 公钥先从 pk_cache 中查找，未命中再查注册表并写入缓存
+    /// 同一区块内验证多条路径时共享缓存，可以避免重复访问注册表
+    /// 公钥未注册的节点验证失败
+    pub fn verify_with_cache(
+        &self,
+        transaction: Transaction,
+        miner: String,
+        registry: &KeyRegistry,
+        pk_cache: &mut HashMap<String, PublicKey>,
+    ) -> bool {
+        match self.signed_messages(&transaction, &miner, registry, pk_cache) {
+            PathCheck::Direct => true,
+            PathCheck::Invalid => false,
+            PathCheck::Signed(messages, pks) => {
+                Wallet::bls_aggregated_verify(messages, pks, self.signature.clone())
+            }
+        }
+    }
+
+    /// 批量验证多条路径，每项为 (交易, 路径, 出块者)，返回每条路径是否有效
+    /// 各路径的聚合签名乘以不同的随机系数后合并为一次验证，签名放错路径时批量验证失败
+    /// 批量验证失败时逐条验证，找出无效的路径
+    pub fn batch_verify(
+        blocks_paths: &[(Transaction, AggregatedSignedPaths, String)],
+        registry: &KeyRegistry,
+    ) -> Vec<bool> {
+        let mut results = vec![false; blocks_paths.len()];
+        let mut pk_cache = HashMap::new();
+        let mut batch_index: Vec<usize> = vec![];
+        let mut batch = vec![];
+        for (i, (transaction, path, miner)) in blocks_paths.iter().enumerate() {
+            match path.signed_messages(transaction, miner, registry, &mut pk_cache) {
+                PathCheck::Direct => results[i] = true,
+                PathCheck::Invalid => {}
+                PathCheck::Signed(path_messages, path_pks) => {
+                    let Ok(signature) = Wallet::bls_signature_from_string(path.signature.clone())
+                    else {
+                        continue;
+                    };
+                    batch_index.push(i);
+                    batch.push((path_messages, path_pks, signature));
+                }
+            }
+        }
+        if batch.is_empty() {
+            return results;
+        }
+
+        if Wallet::bls_batch_aggregated_verify(&batch) {
+            for i in batch_index {
+                results[i] = true;
+            }
+            return results;
+        }
+        for i in batch_index {
+            let (transaction, path, miner) = &blocks_paths[i];
+            results[i] =
+                path.verify_with_cache(transaction.clone(), miner.clone(), registry, &mut pk_cache);
+        }
+        results
+    }
+
+    /// 路径签名对应的消息和签名者的公钥，不验证签名本身
+    fn signed_messages(
+        &self,
+        transaction: &Transaction,
+        miner: &str,
+        registry: &KeyRegistry,
+        pk_cache: &mut HashMap<String, PublicKey>,
+    ) -> PathCheck {
         if self.paths.is_empty() {
-            return false;
+            return PathCheck::Invalid;
         }
         //miner和发起是一个节点
-        if transaction.from == miner && self.paths.first().unwrap().to_string() == miner {
-            return true;
+        if transaction.from == miner && self.paths.first().unwrap() == miner {
+            return PathCheck::Direct;
         }
 
         //miner必须是最后一个path
-        if self.paths.last().unwrap().to_string() != miner {
-            return false;
+        if self.paths.last().unwrap() != miner {
+            return PathCheck::Invalid;
         }
         //聚合签名验证
         //先还原message
@@ -224,15 +316,22 @@ impl AggregatedSignedPaths {
             messages.push(hash.to_vec());
         }
 
-        //再去找公钥
-        let mut pks: Vec<PublicKey> = self
-            .paths
-            .iter()
-            .map(|p| wallet::get_bls_pub_key(p.clone()).unwrap())
-            .collect();
-        //miner并没有传播交易，所以去掉
-        pks.remove(pks.len() - 1);
-        Wallet::bls_aggregated_verify(messages, pks, self.signature.clone())
+        //再去找公钥，miner并没有传播交易，所以不需要
+        let mut pks: Vec<PublicKey> = Vec::with_capacity(self.paths.len() - 1);
+        for p in &self.paths[..self.paths.len() - 1] {
+            let pk = match pk_cache.get(p) {
+                Some(pk) => *pk,
+                None => match registry.get(p) {
+                    Some(pk) => {
+                        pk_cache.insert(p.clone(), pk);
+                        pk
+                    }
+                    None => return PathCheck::Invalid,
+                },
+            };
+            pks.push(pk);
+        }
+        PathCheck::Signed(messages, pks)
     }
 
     pub fn bytes(&self) -> u64 {
@@ -291,30 +390,155 @@ impl From<serde_json::error::Error> for PathError {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::wallet;
 
     #[test]
     fn test_transaction_paths_bls() {
-        let wallet = Wallet::new();
-        let wallet2 = Wallet::new();
-        let wallet3 = Wallet::new();
-        let miner = Wallet::new();
+        let registry = KeyRegistry::new();
+        let wallet = Wallet::new_in(&registry);
+        let wallet2 = Wallet::new_in(&registry);
+        let wallet3 = Wallet::new_in(&registry);
+        let miner = Wallet::new_in(&registry);
         let transaction = Transaction::new("123".to_string(), 32, wallet.clone());
         let mut transaction_paths = TransactionPaths::new(transaction.clone());
         transaction_paths.add_path(wallet2.address.clone(), wallet.clone());
         transaction_paths.add_path(wallet3.address.clone(), wallet2.clone());
         transaction_paths.add_path(miner.address.clone(), wallet3.clone());
         println!("{:#?}", transaction_paths);
-        wallet::insert_bls_pub_key(wallet.address.clone(), wallet.bls_public_key.clone());
-        wallet::insert_bls_pub_key(wallet2.address.clone(), wallet2.bls_public_key.clone());
-        wallet::insert_bls_pub_key(wallet3.address.clone(), wallet3.bls_public_key.clone());
-        wallet::insert_bls_pub_key(miner.address.clone(), miner.bls_public_key.clone());
-        assert!(transaction_paths.verify(miner.address.clone()));
+        assert!(transaction_paths.verify(miner.address.clone(), &registry));
+        assert!(transaction_paths.verify_last(miner.address.clone(), &registry));
+        // 公钥不在给定注册表中时验证失败
+        assert!(!transaction_paths.verify(miner.address.clone(), &KeyRegistry::new()));
 
         //check aggregated_signed_paths
         let aggregated_signed_paths =
             AggregatedSignedPaths::from_transaction_paths(transaction_paths);
-        assert!(aggregated_signed_paths.verify(transaction.clone(), miner.address.clone()));
+        assert!(aggregated_signed_paths.verify(
+            transaction.clone(),
+            miner.address.clone(),
+            &registry
+        ));
         println!("{:#?}", aggregated_signed_paths);
     }
+
+    #[test]
+    fn test_aggregated_verify_with_cache() {
+        let wallet = Wallet::new();
+        let wallet2 = Wallet::new();
+        let miner = Wallet::new();
+        let transaction = Transaction::new("123".to_string(), 32, wallet.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(wallet2.address.clone(), wallet.clone());
+        transaction_paths.add_path(miner.address.clone(), wallet2.clone());
+        let aggregated_signed_paths = transaction_paths.to_aggregated_signed_paths();
+
+        let registry = KeyRegistry::thread_default();
+        let mut pk_cache = HashMap::new();
+        assert!(aggregated_signed_paths.verify_with_cache(
+            transaction.clone(),
+            miner.address.clone(),
+            &registry,
+            &mut pk_cache
+        ));
+        assert_eq!(pk_cache.len(), 2);
+        assert!(aggregated_signed_paths.verify_with_cache(
+            transaction.clone(),
+            miner.address.clone(),
+            &registry,
+            &mut pk_cache
+        ));
+
+        // 路径中有未注册公钥的节点时返回 false 而不是 panic
+        let mut unknown = aggregated_signed_paths.clone();
+        unknown.paths.insert(1, "0xunregistered".to_string());
+        assert!(!unknown.verify(transaction, miner.address, &registry));
+    }
+
+    #[test]
+    fn test_batch_verify() {
+        let registry = KeyRegistry::new();
+        let wallets: Vec<Wallet> = (0..4).map(|_| Wallet::new_in(&registry)).collect();
+        let miner = wallets[3].clone();
+        let signed_path = |to: &str| {
+            let transaction = Transaction::new(to.to_string(), 32, wallets[0].clone());
+            let mut transaction_paths = TransactionPaths::new(transaction.clone());
+            for i in 1..4 {
+                transaction_paths.add_path(wallets[i].address.clone(), wallets[i - 1].clone());
+            }
+            (
+                transaction,
+                transaction_paths.to_aggregated_signed_paths(),
+                miner.address.clone(),
+            )
+        };
+        let valid: Vec<(Transaction, AggregatedSignedPaths, String)> =
+            ["a", "b", "c"].iter().map(|to| signed_path(to)).collect();
+        assert_eq!(
+            AggregatedSignedPaths::batch_verify(&valid, &registry),
+            vec![true; 3]
+        );
+
+        let mut items = valid.clone();
+        // 签名换成其他路径的签名
+        items[1].1.signature = valid[0].1.signature.clone();
+        // 出块者自己发起的交易
+        let direct = Transaction::new("d".to_string(), 32, miner.clone());
+        items.push((
+            direct.clone(),
+            AggregatedSignedPaths::from_transaction_paths(TransactionPaths::new(direct)),
+            miner.address.clone(),
+        ));
+        // 最后一个节点不是出块者
+        let mut wrong_miner = signed_path("e");
+        wrong_miner.2 = wallets[1].address.clone();
+        items.push(wrong_miner);
+        // 同一条路径出现两次
+        items.push(valid[2].clone());
+        assert_eq!(
+            AggregatedSignedPaths::batch_verify(&items, &registry),
+            vec![true, false, true, true, false, true]
+        );
+        for (result, (transaction, path, miner)) in
+            AggregatedSignedPaths::batch_verify(&items, &registry)
+                .into_iter()
+                .zip(items.iter())
+        {
+            assert_eq!(
+                result,
+                path.verify(transaction.clone(), miner.clone(), &registry)
+            );
+        }
+
+        // 两条路径的签名互换后聚合结果不变，按随机系数合并时仍能发现
+        let mut swapped = valid.clone();
+        swapped[0].1.signature = valid[1].1.signature.clone();
+        swapped[1].1.signature = valid[0].1.signature.clone();
+        assert_eq!(
+            AggregatedSignedPaths::batch_verify(&swapped, &registry),
+            vec![false, false, true]
+        );
+    }
+
+    #[test]
+    fn test_verify_after_key_removed() {
+        let registry = KeyRegistry::new();
+        let wallet = Wallet::new_in(&registry);
+        let relay = Wallet::new_in(&registry);
+        let miner = Wallet::new_in(&registry);
+        let transaction = Transaction::new("123".to_string(), 32, wallet.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(relay.address.clone(), wallet);
+        transaction_paths.add_path(miner.address.clone(), relay.clone());
+        let aggregated_signed_paths = transaction_paths.to_aggregated_signed_paths();
+        assert!(aggregated_signed_paths.verify(
+            transaction.clone(),
+            miner.address.clone(),
+            &registry
+        ));
+
+        // 中继节点退出后公钥被删除，验证返回 false 而不是 panic
+        assert!(registry.remove(&relay.address).is_some());
+        assert!(!transaction_paths.verify(miner.address.clone(), &registry));
+        assert!(!transaction_paths.verify_last(miner.address.clone(), &registry));
+        assert!(!aggregated_signed_paths.verify(transaction, miner.address, &registry));
+    }
 }
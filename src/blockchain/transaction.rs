@@ -9,7 +9,9 @@ pub struct Transaction {
     pub from: String,
     pub to: String,
     pub amount: i64,
-    pub fee: f64,  // 交易手续费
+    pub fee: f64, // 基础手续费，按共识的费用机制分配
+    #[serde(default)]
+    pub tip: f64, // 小费（优先费），全部归出块者，打包时按小费排序
     pub hash: String,
     pub signature: String,
     pub timestamp: u64,
@@ -22,6 +24,33 @@ impl Transaction {
     }
 
     pub fn with_fee(to: String, amount: i64, fee: f64, wallet: Wallet) -> Transaction {
+        Self::with_timestamp(to, amount, fee, get_timestamp(), wallet)
+    }
+
+    /// 基础手续费之外附带给出块者的小费
+    pub fn with_tip(to: String, amount: i64, fee: f64, tip: f64, wallet: Wallet) -> Transaction {
+        Self::build(to, amount, fee, tip, get_timestamp(), wallet)
+    }
+
+    /// 指定时间戳创建交易，相同参数和钱包得到相同的交易（用于确定性的创世块）
+    pub fn with_timestamp(
+        to: String,
+        amount: i64,
+        fee: f64,
+        timestamp: u64,
+        wallet: Wallet,
+    ) -> Transaction {
+        Self::build(to, amount, fee, 0.0, timestamp, wallet)
+    }
+
+    fn build(
+        to: String,
+        amount: i64,
+        fee: f64,
+        tip: f64,
+        timestamp: u64,
+        wallet: Wallet,
+    ) -> Transaction {
         let from = wallet.address.clone();
 
         let mut t = Transaction {
@@ -29,9 +58,10 @@ impl Transaction {
             to: to.clone(),
             amount,
             fee,
+            tip,
             hash: "".to_string(),
             signature: "".to_string(),
-            timestamp: get_timestamp(),
+            timestamp,
             data: Vec::new(),
         };
         let t_json = serde_json::to_string(&t).unwrap();
@@ -51,6 +81,7 @@ impl Transaction {
             to: to.clone(),
             amount: self.amount,
             fee: self.fee,
+            tip: self.tip,
             hash: "".to_string(),
             signature: "".to_string(),
             timestamp: self.timestamp,
@@ -64,6 +95,11 @@ impl Transaction {
         Wallet::verify_by_address(Vec::from(hash), self.signature.clone(), from)
     }
 
+    /// 发送者支付的全部费用：基础手续费 + 小费
+    pub fn total_fee(&self) -> f64 {
+        self.fee + self.tip
+    }
+
     pub fn bytes(&self) -> u64 {
         let hash = self.hash.as_bytes().len() as u64;
         let from = self.from.as_bytes().len() as u64;
@@ -87,4 +123,17 @@ mod tests {
         info!("{:#?}", transaction);
         assert!(transaction.verify());
     }
+
+    #[test]
+    fn test_transaction_tip() {
+        let wallet = Wallet::new();
+        let transaction = Transaction::with_tip("123".to_string(), 32, 1.0, 0.5, wallet);
+        assert!(transaction.verify());
+        assert_eq!(transaction.total_fee(), 1.5);
+
+        // 小费参与签名，篡改后验证失败
+        let mut tampered = transaction.clone();
+        tampered.tip = 5.0;
+        assert!(!tampered.verify());
+    }
 }
@@ -1,10 +1,13 @@
 use crate::blockchain::block::Block;
 use crate::blockchain::Blockchain;
-use crate::consensus::{Consensus, Validator, ValidatorError};
+use crate::consensus::{
+    block_tips, cumulative_select, network_fees_for_relays, pay_base_fee_pool, pay_relayer_subsidy,
+    pay_universal_reward, sort_validators, Consensus, Validator, ValidatorError,
+};
+use crate::tools::{HashAlgo, Hasher};
 use log::{debug, info, warn};
 use rand::prelude::StdRng;
 use rand::{Rng, RngCore, SeedableRng};
-use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
@@ -30,7 +33,13 @@ pub struct MinotaurConsensus {
     pow_blocks: HashMap<u64, Vec<PowBlock>>,
     base_reward: f64,
     pow_weight: f64,
-    block_index: u64,
+    pub(crate) block_index: u64,
+    /// 区块补贴中分给路径参与者的比例
+    relayer_reward_fraction: f64,
+    /// 奖励中由所有验证者平分的比例
+    universal_reward_fraction: f64,
+    /// 工作量计算使用的哈希算法，默认 SHA-256
+    hasher: Hasher,
     /// 后台计算任务：存储线程句柄和结果存储位置
     background_task: Arc<Mutex<Option<(u64, JoinHandle<Vec<PowBlock>>, Arc<AtomicBool>)>>>,
 }
@@ -43,21 +52,36 @@ impl MinotaurConsensus {
             base_reward,
             pow_weight: 0.5, // 默认50%权重
             block_index: 0,
+            relayer_reward_fraction: 0.0,
+            universal_reward_fraction: 0.0,
+            hasher: Hasher::with_algo(HashAlgo::Sha256),
             background_task: Arc::new(Mutex::new(None)),
         }
     }
 
+    pub fn set_relayer_reward_fraction(&mut self, fraction: f64) {
+        self.relayer_reward_fraction = fraction;
+    }
+
+    pub fn set_universal_reward_fraction(&mut self, fraction: f64) {
+        self.universal_reward_fraction = fraction;
+    }
+
+    pub fn set_hash_algo(&mut self, algo: HashAlgo) {
+        self.hasher = Hasher::with_algo(algo);
+    }
+
     /// 执行PoW计算：计算指定次数的哈希
     #[allow(dead_code)]
     fn perform_pow_computation(&self, address: &str, slot: u64, max_attempts: u64) -> u64 {
         let mut hash_count = 0u64;
         for nonce in 0..max_attempts {
             hash_count += 1;
-            let mut hasher = Sha256::new();
-            hasher.update(address.as_bytes());
-            hasher.update(slot.to_le_bytes());
-            hasher.update(nonce.to_le_bytes());
-            let _hash = hasher.finalize();
+            let _hash = self.hasher.digest(&[
+                address.as_bytes(),
+                &slot.to_le_bytes(),
+                &nonce.to_le_bytes(),
+            ]);
         }
         hash_count
     }
@@ -88,32 +112,16 @@ impl MinotaurConsensus {
     pub fn get_pow_blocks(&self, index: u64) -> Vec<PowBlock> {
         self.pow_blocks.get(&index).cloned().unwrap_or_default()
     }
-}
-
-impl Consensus for MinotaurConsensus {
-    fn name(&self) -> &'static str {
-        "Minotaur"
-    }
 
-    fn select_proposer(
-        &mut self,
+    /// 上一个 index 的 PoW 块中各验证者的算力占比和 stake 占比
+    fn ratio_scores(
+        &self,
         validators: &[Validator],
-        combines_seed: [u8; 32],
-        _blockchain: &Blockchain,
-    ) -> Result<Validator, ValidatorError> {
-        if validators.is_empty() {
-            return Err(ValidatorError::NOValidatorError);
-        }
-        // 实现混合选择逻辑（PoW + PoS）
-        // 查询 最新的pow块
-        if self.block_index == 0 {
-            warn!("Block index is 0, no pow blocks available");
-            return Ok(validators[0].clone());
-        }
-        let pow_blocks = self.get_pow_blocks(self.block_index - 1);
-        if pow_blocks.is_empty() {
-            warn!("No PoW blocks available for index {}", self.block_index - 1);
-        }
+    ) -> (HashMap<String, f64>, HashMap<String, f64>) {
+        let pow_blocks = match self.block_index {
+            0 => vec![],
+            index => self.get_pow_blocks(index - 1),
+        };
         // 计算每个验证者的pow得分
         let mut pow_scores: HashMap<String, f64> = HashMap::new();
         for block in pow_blocks {
@@ -147,7 +155,16 @@ impl Consensus for MinotaurConsensus {
             pos_ratio_scores.insert(validator.address.clone(), pos_ratio);
         }
         debug!("PoS Ratio Scores: {:?}", pos_ratio_scores);
-        // 根据pow_weight 计算综合得分
+        (pow_ratio_scores, pos_ratio_scores)
+    }
+
+    /// 根据pow_weight 计算综合得分，即选择出块者时使用的虚拟 stake
+    fn combine_scores(
+        &self,
+        validators: &[Validator],
+        pow_ratio_scores: &HashMap<String, f64>,
+        pos_ratio_scores: &HashMap<String, f64>,
+    ) -> HashMap<String, f64> {
         let mut combined_scores: HashMap<String, f64> = HashMap::new();
         for validator in validators {
             let pow_ratio = pow_ratio_scores
@@ -161,43 +178,129 @@ impl Consensus for MinotaurConsensus {
             let combined_score = self.pow_weight * pow_ratio + (1.0 - self.pow_weight) * pos_ratio;
             combined_scores.insert(validator.address.clone(), combined_score);
         }
+        combined_scores
+    }
+}
+
+impl Consensus for MinotaurConsensus {
+    fn name(&self) -> &'static str {
+        "Minotaur"
+    }
+
+    fn select_proposer(
+        &mut self,
+        validators: &[Validator],
+        combines_seed: [u8; 32],
+        _blockchain: &Blockchain,
+    ) -> Result<Validator, ValidatorError> {
+        if validators.is_empty() {
+            return Err(ValidatorError::NOValidatorError);
+        }
+        let validators = &sort_validators(validators);
+        // 实现混合选择逻辑（PoW + PoS）
+        // 查询 最新的pow块
+        if self.block_index == 0 {
+            warn!("Block index is 0, no pow blocks available");
+            return Ok(validators[0].clone());
+        }
+        if self.get_pow_blocks(self.block_index - 1).is_empty() {
+            warn!("No PoW blocks available for index {}", self.block_index - 1);
+        }
+        let (pow_ratio_scores, pos_ratio_scores) = self.ratio_scores(validators);
+        let combined_scores = self.combine_scores(validators, &pow_ratio_scores, &pos_ratio_scores);
         debug!("Combined Scores: {:?}", combined_scores);
         // 将combined_scores 视作vitual_stake,算出出块者
         let mut rng = StdRng::from_seed(combines_seed);
-        let total_combined_score: f64 = combined_scores.values().sum();
-        let mut pick = rng.gen_range(0.0..total_combined_score);
-        for validator in validators {
-            let score = combined_scores
-                .get(&validator.address)
-                .cloned()
-                .unwrap_or(0.0);
-            let pow_score = pow_ratio_scores
-                .get(&validator.address)
-                .cloned()
-                .unwrap_or(0.0);
-            let pos_score = pos_ratio_scores
-                .get(&validator.address)
-                .cloned()
-                .unwrap_or(0.0);
-            if pick < score {
-                info!(
-                    "Minotaur Selected proposer: {} with virtual stake {:.6},pow_score {:.6},pos_score {:.6}",
-                    validator.address, score, pow_score, pos_score
-                );
-                return Ok(validator.clone());
-            }
-            pick -= score;
+        // 按验证者顺序求和，与累加选择的顺序一致
+        let scores: Vec<f64> = validators
+            .iter()
+            .map(|v| combined_scores.get(&v.address).cloned().unwrap_or(0.0))
+            .collect();
+        let total_combined_score: f64 = scores.iter().sum();
+        if total_combined_score <= 0.0 {
+            // 没有 PoW 块且 stake 全为0时，gen_range 会 panic，改为均匀随机选择
+            let index = rng.gen_range(0..validators.len());
+            warn!(
+                "Minotaur total score is zero, selecting validator {} uniformly",
+                validators[index].address
+            );
+            return Ok(validators[index].clone());
         }
-
-        // 临时返回第一个验证者
-        warn!("Fallback: selecting first validator as proposer");
-        Ok(validators[0].clone())
+        let pick = rng.gen_range(0.0..total_combined_score);
+        let index = cumulative_select(&scores, pick).ok_or(ValidatorError::NOValidatorError)?;
+        let validator = &validators[index];
+        let pow_score = pow_ratio_scores
+            .get(&validator.address)
+            .cloned()
+            .unwrap_or(0.0);
+        let pos_score = pos_ratio_scores
+            .get(&validator.address)
+            .cloned()
+            .unwrap_or(0.0);
+        info!(
+            "Minotaur Selected proposer: {} with virtual stake {:.6},pow_score {:.6},pos_score {:.6}",
+            validator.address, scores[index], pow_score, pos_score
+        );
+        Ok(validator.clone())
     }
 
     fn on_epoch_end(&mut self, _blocks: &[Block]) {}
 
+    /// PoW 算力占比和 stake 占比按 pow_weight 组合的综合得分
+    fn effective_weights(&self, validators: &[Validator]) -> HashMap<String, f64> {
+        // 与 select_proposer 一致，还没有 PoW 块时总是选排序后的第一个验证者
+        if self.block_index == 0 {
+            let first = sort_validators(validators)
+                .first()
+                .map(|v| v.address.clone());
+            return validators
+                .iter()
+                .map(|v| {
+                    let weight = if Some(&v.address) == first.as_ref() {
+                        1.0
+                    } else {
+                        0.0
+                    };
+                    (v.address.clone(), weight)
+                })
+                .collect();
+        }
+        let (pow_ratio_scores, pos_ratio_scores) = self.ratio_scores(validators);
+        self.combine_scores(validators, &pow_ratio_scores, &pos_ratio_scores)
+    }
+
     fn state_summary(&self) -> String {
-        format!("minotaur(pow_w:{:.2})", self.pow_weight)
+        // 与 select_proposer 一致，统计上一个 index 收集到的 PoW 块
+        let pow_blocks = match self.block_index {
+            0 => vec![],
+            index => self.get_pow_blocks(index - 1),
+        };
+        let max_difficulty = pow_blocks
+            .iter()
+            .map(|b| b.max_difficulty)
+            .max()
+            .unwrap_or(0);
+        let hash_count: u64 = pow_blocks.iter().map(|b| b.hash_count).sum();
+        format!(
+            "minotaur(pow_w:{:.2}_blocks:{}_max_diff:{}_hashes:{})",
+            self.pow_weight,
+            pow_blocks.len(),
+            max_difficulty,
+            hash_count
+        )
+    }
+
+    fn distribute_network_fees(
+        &self,
+        block: &Block,
+        validators: &[Validator],
+    ) -> HashMap<String, f64> {
+        network_fees_for_relays(
+            block,
+            validators,
+            self.base_reward,
+            self.relayer_reward_fraction,
+        )
     }
 
     fn distribute_rewards(
@@ -206,18 +309,38 @@ impl Consensus for MinotaurConsensus {
         validators: &mut [Validator],
         _nodes_index: HashMap<String, u32>,
     ) {
-        // Minotaur: 基础奖励 + 交易费用
+        // Minotaur: 基础奖励 + 小费，基础手续费进入网络费用池，由路径参与者分得
+        let base_reward = pay_relayer_subsidy(
+            block,
+            validators,
+            self.base_reward,
+            self.relayer_reward_fraction,
+        );
+        let burned_fees = pay_base_fee_pool(block, validators);
+        // 奖励中按比例分出的普惠部分由所有验证者平分
+        let miner_reward =
+            pay_universal_reward(validators, base_reward, self.universal_reward_fraction);
+        // 小费全部归出块者
+        let tips = block_tips(block);
         if let Some(validator) = validators
             .iter_mut()
             .find(|v| v.address == block.header.miner)
         {
-            let base_reward = self.base_reward;
-            let tx_fees: f64 = block.body.transactions.iter().map(|tx| tx.fee).sum();
-            let total_reward = base_reward + tx_fees;
-            validator.stake += total_reward;
+            validator.stake += miner_reward + tips;
             info!(
-                "Minotaur: Miner {} received reward: base={:.6} + fees={:.6} = {:.6}, new stake: {:.6}",
-                validator.address, base_reward, tx_fees, total_reward, validator.stake
+                "Minotaur: Miner {} received reward: base={:.6} - universal={:.6} + tips={:.6} = {:.6}, new stake: {:.6}",
+                validator.address,
+                base_reward,
+                base_reward - miner_reward,
+                tips,
+                miner_reward + tips,
+                validator.stake
+            );
+        }
+        if burned_fees > 0.0 {
+            debug!(
+                "Minotaur: burned {:.6} base fees of block {} without relays",
+                burned_fees, block.header.index
             );
         }
     }
@@ -272,6 +395,7 @@ impl Consensus for MinotaurConsensus {
         let validators_clone: Vec<Validator> = validators.to_vec();
         let stop_signal = Arc::new(AtomicBool::new(false));
         let stop_signal_clone = Arc::clone(&stop_signal);
+        let hasher = self.hasher;
 
         let handle = thread::spawn(move || {
             let pow_blocks = Arc::new(Mutex::new(HashMap::new()));
@@ -309,11 +433,11 @@ impl Consensus for MinotaurConsensus {
                             }
                         }
 
-                        let mut hasher = Sha256::new();
-                        hasher.update(address.as_bytes());
-                        hasher.update(index.to_le_bytes());
-                        hasher.update(nonce.to_le_bytes());
-                        let hash = hasher.finalize();
+                        let hash = hasher.digest(&[
+                            address.as_bytes(),
+                            &index.to_le_bytes(),
+                            &nonce.to_le_bytes(),
+                        ]);
 
                         // 计算当前hash的难度（leading zeros数量）
                         let difficulty = Self::calculate_difficulty(&hash.to_vec());
@@ -395,4 +519,144 @@ impl Consensus for MinotaurConsensus {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::consensus::test_fixtures::{fixed_validators, select_with_fixed_seeds};
+
+    /// 没有 PoW 块时按 stake 加权选择，固定种子下结果确定
+    #[test]
+    fn test_select_proposer_deterministic() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators = fixed_validators();
+        let mut minotaur = MinotaurConsensus::new(1.0);
+        assert_eq!(
+            minotaur
+                .select_proposer(&validators, [1u8; 32], &blockchain)
+                .unwrap()
+                .address,
+            "a"
+        );
+
+        minotaur.block_index = 1;
+        let selected = select_with_fixed_seeds(|seed| {
+            minotaur
+                .select_proposer(&validators, seed, &blockchain)
+                .unwrap()
+                .address
+        });
+        assert_eq!(selected, vec!["c", "d", "d", "c", "b", "d"]);
+    }
+
+    /// 验证者顺序不同时，同样的种子选出同一个出块者
+    #[test]
+    fn test_select_ignores_validator_order() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators: Vec<Validator> = (1..=6)
+            .map(|i| Validator::new(crate::wallet::Wallet::new().address, i as f64, 1.0))
+            .collect();
+        let mut reversed = validators.clone();
+        reversed.reverse();
+        let mut minotaur = MinotaurConsensus::new(1.0);
+        minotaur.block_index = 1;
+        for seed in 0..16u8 {
+            assert_eq!(
+                minotaur
+                    .select_proposer(&validators, [seed; 32], &blockchain)
+                    .unwrap()
+                    .address,
+                minotaur
+                    .select_proposer(&reversed, [seed; 32], &blockchain)
+                    .unwrap()
+                    .address
+            );
+        }
+    }
+
+    /// stake 大小悬殊时，任意种子都能选出出块者，且不会选中 stake 为0的验证者
+    #[test]
+    fn test_select_always_returns_proposer() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators: Vec<Validator> = [1e-12, 0.1, 0.2, 0.3, 1e6 / 3.0, 7.0 / 3.0, 0.0]
+            .iter()
+            .map(|stake| Validator::new(crate::wallet::Wallet::new().address, *stake, 1.0))
+            .collect();
+        let mut minotaur = MinotaurConsensus::new(1.0);
+        minotaur.block_index = 1;
+        for i in 0..2000u64 {
+            let mut seed = [0u8; 32];
+            seed[..8].copy_from_slice(&i.to_le_bytes());
+            let proposer = minotaur
+                .select_proposer(&validators, seed, &blockchain)
+                .unwrap();
+            assert!(proposer.stake > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_state_summary() {
+        let mut minotaur = MinotaurConsensus::new(1.0);
+        assert_eq!(
+            minotaur.state_summary(),
+            "minotaur(pow_w:0.50_blocks:0_max_diff:0_hashes:0)"
+        );
+        for (address, hash_count, max_difficulty) in [("a", 100, 5), ("b", 250, 9)] {
+            minotaur.add_pow_block(PowBlock {
+                address: address.to_string(),
+                hash_count,
+                index: 2,
+                nonce: 0,
+                max_difficulty,
+            });
+        }
+        // 其他 index 的 PoW 块不计入
+        minotaur.add_pow_block(PowBlock {
+            address: "c".to_string(),
+            hash_count: 1000,
+            index: 1,
+            nonce: 0,
+            max_difficulty: 20,
+        });
+        minotaur.block_index = 3;
+        let summary = minotaur.state_summary();
+        assert_eq!(
+            summary,
+            "minotaur(pow_w:0.50_blocks:2_max_diff:9_hashes:350)"
+        );
+        // CSV 列中不能出现逗号
+        assert!(!summary.contains(','));
+    }
+
+    #[test]
+    fn test_effective_weights() {
+        let validators = vec![
+            Validator::new("a".to_string(), 3.0, 1.0),
+            Validator::new("b".to_string(), 1.0, 1.0),
+        ];
+        let mut minotaur = MinotaurConsensus::new(1.0);
+        // a 的 PoW 得分为 2^1，b 为 2^3
+        for (address, max_difficulty) in [("a", 1), ("b", 3)] {
+            minotaur.add_pow_block(PowBlock {
+                address: address.to_string(),
+                hash_count: 10,
+                index: 0,
+                nonce: 0,
+                max_difficulty,
+            });
+        }
+        // block_index 为 0 时与 select_proposer 一样只选排序后的第一个验证者
+        let weights = minotaur.effective_weights(&validators);
+        assert_eq!((weights["a"], weights["b"]), (1.0, 0.0));
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let reversed: Vec<Validator> = validators.iter().rev().cloned().collect();
+        let selected = minotaur
+            .select_proposer(&reversed, [7u8; 32], &blockchain)
+            .unwrap();
+        assert_eq!(selected.address, "a");
+        assert_eq!(minotaur.effective_weights(&reversed), weights);
+
+        minotaur.block_index = 1;
+        let weights = minotaur.effective_weights(&validators);
+        assert!((weights["a"] - (0.5 * 0.2 + 0.5 * 0.75)).abs() < 1e-9);
+        assert!((weights["b"] - (0.5 * 0.8 + 0.5 * 0.25)).abs() < 1e-9);
+    }
+}
@@ -5,24 +5,31 @@ use crate::tools;
 use crate::wallet::Wallet;
 use clap::ValueEnum;
 use log::error;
-use rand::rngs::OsRng;
-use rand::RngCore;
+use rand::rngs::{OsRng, StdRng};
+use rand::seq::SliceRandom;
+use rand::{RngCore, SeedableRng};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 pub mod minotaur;
 pub mod pog;
 pub mod pos;
 pub mod pow;
+pub mod scripted;
+pub mod seed;
+#[cfg(test)]
+pub(crate) mod test_fixtures;
 
-#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConsensusType {
     POS,
     POG,
     POW,
     MINOTAUR,
+    SCRIPTED, // 按 --schedule 给定的出块者序列出块，用完后由 --schedule-fallback 指定的共识选择
 }
 
 impl Display for ConsensusType {
@@ -40,7 +47,42 @@ impl Display for ConsensusType {
             ConsensusType::MINOTAUR => {
                 write!(f, "minotaur")
             }
+            ConsensusType::SCRIPTED => {
+                write!(f, "scripted")
+            }
+        }
+    }
+}
+
+/// 在某个 epoch 开始时切换到的共识，命令行格式为 <type>@<epoch>，如 pog@10
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConsensusSwitch {
+    pub consensus: ConsensusType,
+    pub epoch: u64,
+}
+
+impl FromStr for ConsensusSwitch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (consensus, epoch) = s
+            .trim()
+            .split_once('@')
+            .ok_or_else(|| format!("expected <type>@<epoch>: {}", s))?;
+        let consensus = ConsensusType::from_str(consensus, true)?;
+        let epoch: u64 = epoch
+            .parse()
+            .map_err(|_| format!("invalid epoch: {}", epoch))?;
+        if epoch == 0 {
+            return Err(format!("switch epoch must be greater than 0: {}", s));
         }
+        Ok(ConsensusSwitch { consensus, epoch })
+    }
+}
+
+impl Display for ConsensusSwitch {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}@{}", self.consensus, self.epoch)
     }
 }
 
@@ -52,11 +94,29 @@ pub trait Consensus: Send + Sync {
         combines_seed: [u8; 32],
         blockchain: &Blockchain,
     ) -> Result<Validator, ValidatorError>;
+    /// 每个时隙选择出块者之前调用一次，推进共识的内部状态（如 POG 的贡献历史）
+    /// select_proposer 不应重复推进这些状态
+    fn advance_state(&mut self, _validators: &[Validator], _blockchain: &Blockchain) {}
     fn on_epoch_end(&mut self, blocks: &[Block]);
     fn apply_block_feedback(&mut self, _block: &Block) {}
     fn state_summary(&self) -> String {
         String::new()
     }
+    /// 当前的网络传播阈值（NTD），只有 POG 有
+    fn ntd(&self) -> Option<usize> {
+        None
+    }
+    /// 各节点归一化后的网络贡献，不计算贡献的共识返回空
+    fn contribution(&self) -> HashMap<String, f64> {
+        HashMap::new()
+    }
+    /// 选择出块者时各验证者实际使用的权重（如 POG 的虚拟 stake），默认为真实 stake
+    fn effective_weights(&self, validators: &[Validator]) -> HashMap<String, f64> {
+        validators
+            .iter()
+            .map(|v| (v.address.clone(), v.stake))
+            .collect()
+    }
 
     /// 分配区块奖励给验证者
     ///
@@ -75,7 +135,158 @@ pub trait Consensus: Send + Sync {
     ) {
     }
 
+    /// 分配给非验证者（中继节点）的网络费用，返回 地址 -> 奖励
+    /// 默认不分配，POG 按路径位置权重从网络费用池中分出中继节点的份额
+    fn distribute_network_fees(
+        &self,
+        _block: &Block,
+        _validators: &[Validator],
+    ) -> HashMap<String, f64> {
+        HashMap::new()
+    }
+
     fn next_slot(&mut self, _validators: &[Validator], _block_index: u64) {}
+
+    /// 每个 epoch 结束时历史贡献乘以的系数，只有 POG 使用
+    fn set_epoch_decay(&mut self, _decay: f64) {}
+
+    /// 运行中切换共识时用 consensus 替换自身，包装其他共识的实现（如 Scripted）只替换内部共识
+    fn switch_to(self: Box<Self>, consensus: Box<dyn Consensus>) -> Box<dyn Consensus> {
+        consensus
+    }
+}
+
+/// 按地址排序验证者，保证不同节点对同样的种子和验证者集合选出同一个出块者
+/// 验证者注册顺序取决于消息到达顺序，累加权重前必须先排序
+pub fn sort_validators(validators: &[Validator]) -> Vec<Validator> {
+    let mut sorted = validators.to_vec();
+    sorted.sort_by(|a, b| a.address.cmp(&b.address));
+    sorted
+}
+
+/// 按累加权重选择，返回累加权重首次超过 random_value 的下标
+/// random_value 应小于按同样顺序求和的总权重；浮点累加误差使循环走完仍未命中时，
+/// 返回最后一个权重为正的下标，权重全不为正时返回 None
+pub fn cumulative_select(weights: &[f64], random_value: f64) -> Option<usize> {
+    let mut accumulated_weight = 0f64;
+    for (index, weight) in weights.iter().enumerate() {
+        accumulated_weight += weight;
+        if accumulated_weight > random_value {
+            return Some(index);
+        }
+    }
+    weights.iter().rposition(|weight| *weight > 0.0)
+}
+
+/// 区块补贴中分给路径参与者的奖励，返回 地址 -> 奖励
+/// amount 平均分到每条路径，再由路径上除矿工外的节点平分；没有路径时返回空
+pub fn relayer_subsidy(block: &Block, amount: f64) -> HashMap<String, f64> {
+    let mut rewards: HashMap<String, f64> = HashMap::new();
+    let paths = block.get_all_paths();
+    if paths.is_empty() || amount <= 0.0 {
+        return rewards;
+    }
+    let pool_per_path = amount / paths.len() as f64;
+    for path in paths.iter() {
+        let participants: Vec<&String> = path
+            .iter()
+            .filter(|node| **node != block.header.miner)
+            .collect();
+        if participants.is_empty() {
+            continue;
+        }
+        let reward = pool_per_path / participants.len() as f64;
+        for node in participants {
+            *rewards.entry(node.clone()).or_insert(0.0) += reward;
+        }
+    }
+    rewards
+}
+
+/// 奖励分配比例限制在 [0, 1]，避免分出的部分超过奖励本身或为负
+fn clamp_fraction(fraction: f64) -> f64 {
+    if fraction.is_nan() {
+        return 0.0;
+    }
+    fraction.clamp(0.0, 1.0)
+}
+
+/// 从 base_reward 中按 fraction 分出路径参与者的补贴，验证者的部分直接加到 stake 上
+/// 返回矿工剩余的补贴；非验证者的部分由 relayer_subsidy_for_relays 返回
+pub fn pay_relayer_subsidy(
+    block: &Block,
+    validators: &mut [Validator],
+    base_reward: f64,
+    fraction: f64,
+) -> f64 {
+    let rewards = relayer_subsidy(block, base_reward * clamp_fraction(fraction));
+    for validator in validators.iter_mut() {
+        if let Some(reward) = rewards.get(&validator.address) {
+            validator.stake += reward;
+        }
+    }
+    base_reward - rewards.values().sum::<f64>()
+}
+
+/// 区块中所有交易的小费之和，全部归出块者，不参与基础手续费的分配
+pub fn block_tips(block: &Block) -> f64 {
+    block.body.transactions.iter().map(|tx| tx.tip).sum()
+}
+
+/// 从 reward 中按 fraction 分出普惠奖励，由所有验证者平分，返回出块者剩余的奖励
+/// 作为不依赖网络贡献的去中心化基线，与 POG 按贡献的再分配对比
+pub fn pay_universal_reward(validators: &mut [Validator], reward: f64, fraction: f64) -> f64 {
+    let fraction = clamp_fraction(fraction);
+    if fraction == 0.0 || validators.is_empty() {
+        return reward;
+    }
+    let universal = reward * fraction;
+    let share = universal / validators.len() as f64;
+    for validator in validators.iter_mut() {
+        validator.stake += share;
+    }
+    reward - universal
+}
+
+/// 路径参与者中非验证者（中继节点）获得的补贴，供 distribute_network_fees 返回
+pub fn relayer_subsidy_for_relays(
+    block: &Block,
+    validators: &[Validator],
+    base_reward: f64,
+    fraction: f64,
+) -> HashMap<String, f64> {
+    let validator_set: HashSet<&String> = validators.iter().map(|v| &v.address).collect();
+    relayer_subsidy(block, base_reward * clamp_fraction(fraction))
+        .into_iter()
+        .filter(|(address, _)| !validator_set.contains(address))
+        .collect()
+}
+
+/// 区块中所有交易的基础手续费之和
+pub fn block_base_fees(block: &Block) -> f64 {
+    block.body.transactions.iter().map(|tx| tx.fee).sum()
+}
+
+/// 基础手续费进入网络费用池，按路径分给除出块者外的路径参与者，验证者的部分直接加到 stake 上
+/// 返回没有参与者可分的部分（没有路径或路径上只有出块者），这部分被销毁，不归出块者
+pub fn pay_base_fee_pool(block: &Block, validators: &mut [Validator]) -> f64 {
+    pay_relayer_subsidy(block, validators, block_base_fees(block), 1.0)
+}
+
+/// 中继节点（非验证者）获得的区块补贴和网络费用池份额，供 distribute_network_fees 返回
+pub fn network_fees_for_relays(
+    block: &Block,
+    validators: &[Validator],
+    base_reward: f64,
+    fraction: f64,
+) -> HashMap<String, f64> {
+    let mut rewards = relayer_subsidy_for_relays(block, validators, base_reward, fraction);
+    for (address, reward) in
+        relayer_subsidy_for_relays(block, validators, block_base_fees(block), 1.0)
+    {
+        *rewards.entry(address).or_insert(0.0) += reward;
+    }
+    rewards
 }
 
 pub fn combine_seed(validators: Vec<Validator>, vdf_seeds: Vec<RandaoSeed>) -> [u8; 32] {
@@ -88,8 +299,7 @@ pub fn combine_seed(validators: Vec<Validator>, vdf_seeds: Vec<RandaoSeed>) -> [
             error!("Randao combine seed warning: this seed is not from validators");
             continue;
         }
-        let valid = Wallet::verify_by_address(Vec::from(v.seed), v.signature, v.address);
-        if valid {
+        if v.verify() {
             for i in 0..32 {
                 result[i] ^= v.seed[i];
             }
@@ -100,7 +310,24 @@ pub fn combine_seed(validators: Vec<Validator>, vdf_seeds: Vec<RandaoSeed>) -> [
     tools::Hasher::hash(Vec::from(result))
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// 以 seed 抽取 size 个验证者组成 RANDAO 委员会，只有委员会成员提交种子
+/// 与验证者的顺序无关，size 为 0 或不小于验证者数时为所有验证者
+pub fn sample_randao_committee(
+    validators: &[Validator],
+    size: usize,
+    seed: [u8; 32],
+) -> Vec<Validator> {
+    let mut sorted = validators.to_vec();
+    sorted.sort_by(|a, b| a.address.cmp(&b.address));
+    if size == 0 || size >= sorted.len() {
+        return sorted;
+    }
+    sorted.shuffle(&mut StdRng::from_seed(seed));
+    sorted.truncate(size);
+    sorted
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Validator {
     pub address: String,
     pub stake: f64,
@@ -130,6 +357,60 @@ impl Validator {
     }
 }
 
+/// 验证者集合的增量更新：删除的地址和新增或变化的验证者
+/// 重复应用同一个增量结果不变（伪造身份共用通道，节点可能收到多次）
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ValidatorSetDiff {
+    pub removed: Vec<String>,
+    pub upserted: Vec<Validator>,
+}
+
+impl ValidatorSetDiff {
+    /// 计算从 old 到 new 的增量，增量无法保持验证者顺序时返回 None（需要发送完整集合）
+    pub fn between(old: &[Validator], new: &[Validator]) -> Option<ValidatorSetDiff> {
+        let new_addresses: HashSet<&str> = new.iter().map(|v| v.address.as_str()).collect();
+        let old_map: HashMap<&str, &Validator> =
+            old.iter().map(|v| (v.address.as_str(), v)).collect();
+        let diff = ValidatorSetDiff {
+            removed: old
+                .iter()
+                .filter(|v| !new_addresses.contains(v.address.as_str()))
+                .map(|v| v.address.clone())
+                .collect(),
+            upserted: new
+                .iter()
+                .filter(|v| old_map.get(v.address.as_str()) != Some(v))
+                .cloned()
+                .collect(),
+        };
+        let mut applied = old.to_vec();
+        diff.apply(&mut applied);
+        if applied == new {
+            Some(diff)
+        } else {
+            None
+        }
+    }
+
+    /// 已存在的验证者原地更新，新的验证者追加到末尾
+    pub fn apply(&self, validators: &mut Vec<Validator>) {
+        validators.retain(|v| !self.removed.contains(&v.address));
+        for validator in self.upserted.iter() {
+            match validators
+                .iter_mut()
+                .find(|v| v.address == validator.address)
+            {
+                Some(v) => *v = validator.clone(),
+                None => validators.push(validator.clone()),
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.upserted.is_empty()
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum ValidatorError {
     JSONError,
@@ -164,16 +445,23 @@ pub struct RandaoSeed {
     pub address: String,
     pub seed: [u8; 32],
     pub signature: String,
+    // 种子所属的时隙，WorldState 只接受当前时隙的种子
+    #[serde(default)]
+    pub epoch: u64,
+    #[serde(default)]
+    pub slot: u64,
 }
 
 impl RandaoSeed {
-    pub fn new(wallet: Wallet) -> Self {
+    pub fn new(wallet: Wallet, epoch: u64, slot: u64) -> Self {
         let seed = RandaoSeed::generate_seed();
-        let signature = wallet.sign(Vec::from(seed));
+        let signature = wallet.sign(RandaoSeed::signed_message(&seed, epoch, slot));
         RandaoSeed {
             address: wallet.address,
             seed,
             signature,
+            epoch,
+            slot,
         }
     }
 
@@ -184,6 +472,22 @@ impl RandaoSeed {
         seed
     }
 
+    /// 签名覆盖种子及其 epoch/slot，防止旧种子被改标到后续时隙
+    fn signed_message(seed: &[u8; 32], epoch: u64, slot: u64) -> Vec<u8> {
+        let mut message = Vec::from(*seed);
+        message.extend_from_slice(&epoch.to_le_bytes());
+        message.extend_from_slice(&slot.to_le_bytes());
+        message
+    }
+
+    pub fn verify(&self) -> bool {
+        Wallet::verify_by_address(
+            RandaoSeed::signed_message(&self.seed, self.epoch, self.slot),
+            self.signature.clone(),
+            self.address.clone(),
+        )
+    }
+
     pub fn from_json(json: Vec<u8>) -> Result<RandaoSeed, ValidatorError> {
         let randao_seed: RandaoSeed = serde_json::from_slice(json.as_slice())?;
         Ok(randao_seed)
@@ -193,3 +497,496 @@ impl RandaoSeed {
         serde_json::to_vec(&self).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine_seed() {
+        let wallet1 = Wallet::new();
+        let wallet2 = Wallet::new();
+        let outsider = Wallet::new();
+        let validators = vec![
+            Validator::new(wallet1.address.clone(), 1.0, 1.0),
+            Validator::new(wallet2.address.clone(), 1.0, 1.0),
+        ];
+        let seed1 = RandaoSeed::new(wallet1, 0, 0);
+        let seed2 = RandaoSeed::new(wallet2, 0, 0);
+        let mut expected = [0u8; 32];
+        for (i, b) in expected.iter_mut().enumerate() {
+            *b = seed1.seed[i] ^ seed2.seed[i];
+        }
+        let expected = tools::Hasher::hash(Vec::from(expected));
+
+        // 非验证者的种子不参与组合
+        let seeds = vec![seed1, seed2, RandaoSeed::new(outsider, 0, 0)];
+        assert_eq!(combine_seed(validators, seeds), expected);
+    }
+
+    #[test]
+    fn test_retagged_seed_rejected() {
+        let wallet = Wallet::new();
+        let validators = vec![Validator::new(wallet.address.clone(), 1.0, 1.0)];
+        let seed = RandaoSeed::new(wallet, 0, 1);
+        assert!(seed.verify());
+
+        // 改标到后续时隙后签名不再有效，不参与组合
+        let mut retagged = seed.clone();
+        retagged.slot = 2;
+        assert!(!retagged.verify());
+        assert_eq!(
+            combine_seed(validators, vec![retagged]),
+            tools::Hasher::hash(vec![0u8; 32])
+        );
+    }
+
+    #[test]
+    fn test_sample_randao_committee() {
+        let validators: Vec<Validator> = (0..10)
+            .map(|i| Validator::new(format!("v{}", i), 1.0, 1.0))
+            .collect();
+        let committee = sample_randao_committee(&validators, 3, [7; 32]);
+        assert_eq!(committee.len(), 3);
+        assert!(committee.iter().all(|v| validators.contains(v)));
+
+        // 相同种子选出相同的委员会，与验证者顺序无关
+        let mut reversed = validators.clone();
+        reversed.reverse();
+        assert_eq!(sample_randao_committee(&reversed, 3, [7; 32]), committee);
+        assert_ne!(sample_randao_committee(&validators, 3, [8; 32]), committee);
+
+        // 大小为 0 或不小于验证者数时为所有验证者
+        assert_eq!(sample_randao_committee(&validators, 0, [7; 32]).len(), 10);
+        assert_eq!(sample_randao_committee(&validators, 20, [7; 32]).len(), 10);
+    }
+
+    #[test]
+    fn test_parse_consensus_switch() {
+        let switch: ConsensusSwitch = "pog@10".parse().unwrap();
+        assert_eq!(
+            switch,
+            ConsensusSwitch {
+                consensus: ConsensusType::POG,
+                epoch: 10
+            }
+        );
+        assert_eq!(switch.to_string(), "pog@10");
+        assert_eq!(
+            "POS@3".parse::<ConsensusSwitch>().unwrap().consensus,
+            ConsensusType::POS
+        );
+        assert!("pog".parse::<ConsensusSwitch>().is_err());
+        assert!("pog@x".parse::<ConsensusSwitch>().is_err());
+        assert!("abc@1".parse::<ConsensusSwitch>().is_err());
+        assert!("pos@0".parse::<ConsensusSwitch>().is_err());
+    }
+
+    #[test]
+    fn test_validator_set_diff() {
+        let v = |address: &str, stake: f64| Validator::new(address.to_string(), stake, 1.0);
+        let old = vec![v("a", 1.0), v("b", 2.0), v("c", 3.0)];
+        let new = vec![v("a", 1.5), v("c", 3.0), v("d", 4.0)];
+        let diff = ValidatorSetDiff::between(&old, &new).unwrap();
+        assert_eq!(diff.removed, vec!["b".to_string()]);
+        assert_eq!(diff.upserted, vec![v("a", 1.5), v("d", 4.0)]);
+
+        // 重复应用结果不变
+        let mut applied = old.clone();
+        diff.apply(&mut applied);
+        diff.apply(&mut applied);
+        assert_eq!(applied, new);
+        assert!(ValidatorSetDiff::between(&new, &new).unwrap().is_empty());
+
+        // 重新注册的验证者移到末尾，增量无法表示
+        let moved = vec![v("b", 2.0), v("c", 3.0), v("a", 1.0)];
+        assert!(ValidatorSetDiff::between(&old, &moved).is_none());
+    }
+
+    #[test]
+    fn test_cumulative_select() {
+        let weights = [0.1, 0.2, 0.0, 0.3];
+        assert_eq!(cumulative_select(&weights, 0.0), Some(0));
+        assert_eq!(cumulative_select(&weights, 0.15), Some(1));
+        assert_eq!(cumulative_select(&weights, 0.35), Some(3));
+        // 浮点误差使 random_value 不小于累加和时，返回最后一个权重为正的验证者
+        assert_eq!(cumulative_select(&weights, 0.6), Some(3));
+        assert_eq!(
+            cumulative_select(&[0.1, 0.2, 0.0], 0.30000000000000004),
+            Some(1)
+        );
+        assert_eq!(cumulative_select(&[0.0, 0.0], 0.0), None);
+    }
+
+    /// stake 大小悬殊时，任意种子都能选出出块者
+    #[test]
+    fn test_select_always_returns_proposer() {
+        let validators: Vec<Validator> = [1e-12, 0.1, 0.2, 0.3, 1e6 / 3.0, 7.0 / 3.0, 0.0]
+            .iter()
+            .map(|stake| Validator::new(Wallet::new().address, *stake, 1.0))
+            .collect();
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let mut pos = pos::PosConsensus::new(1.0);
+        let mut pog = pog::PogConsensus::new(3, 0.5);
+        for i in 0..2000u64 {
+            let mut seed = [0u8; 32];
+            seed[..8].copy_from_slice(&i.to_le_bytes());
+            assert!(pos.select_proposer(&validators, seed, &blockchain).is_ok());
+            assert!(pog.select_proposer(&validators, seed, &blockchain).is_ok());
+        }
+    }
+
+    /// PoS 使用真实 stake，PoW 使用算力，POG 在没有贡献记录时退回归一化的真实 stake
+    #[test]
+    fn test_effective_weights() {
+        let validators = vec![
+            Validator::new("a".to_string(), 3.0, 2.0),
+            Validator::new("b".to_string(), 1.0, 5.0),
+        ];
+        let pos = pos::PosConsensus::new(1.0);
+        let pow = pow::PowConsensus::new(1, 1, std::time::Duration::from_secs(1), 1.0);
+        let pog = pog::PogConsensus::new(3, 0.5);
+        let weights = |consensus: &dyn Consensus| {
+            let weights = consensus.effective_weights(&validators);
+            (weights["a"], weights["b"])
+        };
+        assert_eq!(weights(&pos), (3.0, 1.0));
+        assert_eq!(weights(&pow), (2.0, 5.0));
+        assert_eq!(weights(&pog), (0.75, 0.25));
+    }
+
+    /// stake 全为0时不应 panic，而是在验证者中均匀随机选择
+    #[test]
+    fn test_select_with_zero_stake() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators: Vec<Validator> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|a| Validator::new(a.to_string(), 0.0, 1.0))
+            .collect();
+        let consensus_list: Vec<fn() -> Box<dyn Consensus>> = vec![
+            || Box::new(pos::PosConsensus::new(1.0)),
+            || Box::new(pog::PogConsensus::new(3, 1.0)),
+            || {
+                // block_index 为 0 时 Minotaur 总是选第一个验证者
+                let mut minotaur = minotaur::MinotaurConsensus::new(1.0);
+                minotaur.block_index = 1;
+                Box::new(minotaur)
+            },
+        ];
+        for new_consensus in consensus_list {
+            let mut consensus = new_consensus();
+            let selected: HashSet<String> = (0..64u8)
+                .map(|i| {
+                    consensus
+                        .select_proposer(&validators, [i; 32], &blockchain)
+                        .unwrap()
+                        .address
+                })
+                .collect();
+            assert_eq!(selected.len(), validators.len(), "{}", consensus.name());
+        }
+    }
+
+    /// 验证者顺序不同时，同样的种子选出同一个出块者
+    /// Minotaur 的 next_slot 会启动后台挖矿线程，在 minotaur 的测试中单独验证
+    #[test]
+    fn test_selection_ignores_validator_order() {
+        let validators: Vec<Validator> = (1..=6)
+            .map(|i| Validator::new(Wallet::new().address, i as f64, 1.0))
+            .collect();
+        let mut reversed = validators.clone();
+        reversed.reverse();
+        let mut rotated = validators.clone();
+        rotated.rotate_left(2);
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+
+        let consensus_list: Vec<fn() -> Box<dyn Consensus>> = vec![
+            || Box::new(pos::PosConsensus::new(1.0)),
+            || Box::new(pog::PogConsensus::new(3, 1.0)),
+            // 难度过高挖不出块，走按种子随机选择的分支
+            || {
+                Box::new(pow::PowConsensus::new(
+                    256,
+                    4,
+                    std::time::Duration::from_millis(1),
+                    1.0,
+                ))
+            },
+        ];
+        for new_consensus in consensus_list {
+            for seed in 0..16u8 {
+                let pick = |validators: &[Validator]| {
+                    let mut consensus = new_consensus();
+                    consensus
+                        .select_proposer(validators, [seed; 32], &blockchain)
+                        .unwrap()
+                        .address
+                };
+                let expected = pick(&validators);
+                assert_eq!(pick(&reversed), expected);
+                assert_eq!(pick(&rotated), expected);
+            }
+        }
+    }
+
+    /// 区块补贴按比例分给路径参与者：验证者加到 stake，中继节点由 distribute_network_fees 返回
+    #[test]
+    fn test_relayer_reward_fraction() {
+        use crate::blockchain::block::Body;
+        use crate::blockchain::path::TransactionPaths;
+        use crate::blockchain::transaction::Transaction;
+
+        let wallet = Wallet::new();
+        let relay = Wallet::new();
+        let miner = Wallet::new();
+        // 手续费为0，只分配区块补贴
+        let transaction = Transaction::with_fee("123".to_string(), 32, 0.0, wallet.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(relay.address.clone(), wallet.clone());
+        transaction_paths.add_path(miner.address.clone(), relay.clone());
+        let body = Body::new(
+            vec![transaction],
+            vec![transaction_paths.to_aggregated_signed_paths()],
+        );
+        let genesis = Block::gen_genesis_block();
+        let block = Block::new(1, 0, 1, genesis.header.hash.clone(), body, miner.clone()).unwrap();
+
+        let consensus_list: Vec<fn() -> Box<dyn Consensus>> = vec![
+            || {
+                let mut pos = pos::PosConsensus::new(1.0);
+                pos.set_relayer_reward_fraction(0.4);
+                Box::new(pos)
+            },
+            || {
+                let mut pog = pog::PogConsensus::new(3, 1.0);
+                pog.set_relayer_reward_fraction(0.4);
+                Box::new(pog)
+            },
+            || {
+                let mut pow =
+                    pow::PowConsensus::new(256, 1, std::time::Duration::from_millis(1), 1.0);
+                pow.set_relayer_reward_fraction(0.4);
+                Box::new(pow)
+            },
+            || {
+                let mut minotaur = minotaur::MinotaurConsensus::new(1.0);
+                minotaur.set_relayer_reward_fraction(0.4);
+                Box::new(minotaur)
+            },
+        ];
+        for new_consensus in consensus_list {
+            let consensus = new_consensus();
+            // 中继节点不是验证者
+            let mut validators = vec![
+                Validator::new(wallet.address.clone(), 1.0, 1.0),
+                Validator::new(miner.address.clone(), 1.0, 1.0),
+            ];
+            let relay_rewards = consensus.distribute_network_fees(&block, &validators);
+            consensus.distribute_rewards(&block, &mut validators, HashMap::new());
+
+            // 路径 [wallet, relay, miner]：0.4 的补贴由 wallet 和 relay 平分，矿工得到剩余的 0.6
+            assert_eq!(relay_rewards.len(), 1, "{}", consensus.name());
+            assert!((relay_rewards[&relay.address] - 0.2).abs() < 1e-9);
+            assert!((validators[0].stake - 1.2).abs() < 1e-9);
+            assert!((validators[1].stake - 1.6).abs() < 1e-9);
+        }
+
+        // 比例为0时全部归矿工
+        let pos = pos::PosConsensus::new(1.0);
+        let mut validators = vec![Validator::new(miner.address.clone(), 1.0, 1.0)];
+        assert!(pos.distribute_network_fees(&block, &validators).is_empty());
+        pos.distribute_rewards(&block, &mut validators, HashMap::new());
+        assert!((validators[0].stake - 2.0).abs() < 1e-9);
+    }
+
+    /// 超出 [0, 1] 的比例按边界处理，分出的奖励与剩余奖励之和不变且都不为负
+    #[test]
+    fn test_reward_fraction_clamped() {
+        use crate::blockchain::block::Body;
+        use crate::blockchain::path::TransactionPaths;
+        use crate::blockchain::transaction::Transaction;
+
+        let wallet = Wallet::new();
+        let relay = Wallet::new();
+        let miner = Wallet::new();
+        let transaction = Transaction::with_fee("123".to_string(), 32, 0.0, wallet.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(relay.address.clone(), wallet.clone());
+        transaction_paths.add_path(miner.address.clone(), relay.clone());
+        let body = Body::new(
+            vec![transaction],
+            vec![transaction_paths.to_aggregated_signed_paths()],
+        );
+        let genesis = Block::gen_genesis_block();
+        let block = Block::new(1, 0, 1, genesis.header.hash.clone(), body, miner.clone()).unwrap();
+        let validators: Vec<Validator> = [&wallet, &relay, &miner]
+            .iter()
+            .map(|w| Validator::new(w.address.clone(), 0.0, 1.0))
+            .collect();
+        let paid = |validators: &[Validator]| validators.iter().map(|v| v.stake).sum::<f64>();
+
+        for (fraction, expected) in [
+            (1.0, 0.0),
+            (1.5, 0.0),
+            (0.0, 1.0),
+            (-0.5, 1.0),
+            (f64::NAN, 1.0),
+        ] {
+            let mut subsidized = validators.clone();
+            let remaining = pay_relayer_subsidy(&block, &mut subsidized, 1.0, fraction);
+            assert!((remaining - expected).abs() < 1e-9, "fraction {}", fraction);
+            assert!((paid(&subsidized) + remaining - 1.0).abs() < 1e-9);
+            assert!(subsidized.iter().all(|v| v.stake >= 0.0));
+
+            let mut universal = validators.clone();
+            let remaining = pay_universal_reward(&mut universal, 1.0, fraction);
+            assert!((remaining - expected).abs() < 1e-9, "fraction {}", fraction);
+            assert!((paid(&universal) + remaining - 1.0).abs() < 1e-9);
+            assert!(universal.iter().all(|v| v.stake >= 0.0));
+        }
+    }
+
+    /// 奖励中按比例分出的普惠部分由所有验证者平分，每个区块所有验证者的 stake 都增加
+    #[test]
+    fn test_universal_reward_fraction() {
+        use crate::blockchain::block::Body;
+
+        let miner = Wallet::new();
+        let genesis = Block::gen_genesis_block();
+        let block = Block::new(
+            1,
+            0,
+            1,
+            genesis.header.hash.clone(),
+            Body::new(vec![], vec![]),
+            miner.clone(),
+        )
+        .unwrap();
+
+        let consensus_list: Vec<fn() -> Box<dyn Consensus>> = vec![
+            || {
+                let mut pos = pos::PosConsensus::new(1.0);
+                pos.set_universal_reward_fraction(0.4);
+                Box::new(pos)
+            },
+            || {
+                let mut pow =
+                    pow::PowConsensus::new(256, 1, std::time::Duration::from_millis(1), 1.0);
+                pow.set_universal_reward_fraction(0.4);
+                Box::new(pow)
+            },
+            || {
+                let mut minotaur = minotaur::MinotaurConsensus::new(1.0);
+                minotaur.set_universal_reward_fraction(0.4);
+                Box::new(minotaur)
+            },
+        ];
+        for new_consensus in consensus_list {
+            let consensus = new_consensus();
+            let mut validators = vec![
+                Validator::new(miner.address.clone(), 1.0, 1.0),
+                Validator::new("a".to_string(), 1.0, 1.0),
+                Validator::new("b".to_string(), 1.0, 1.0),
+                Validator::new("c".to_string(), 1.0, 1.0),
+            ];
+            for round in 1..=3 {
+                consensus.distribute_rewards(&block, &mut validators, HashMap::new());
+                // 0.4 由4个验证者平分，矿工另得剩余的 0.6
+                let share = 0.1 * round as f64;
+                assert!(
+                    (validators[0].stake - 1.0 - share - 0.6 * round as f64).abs() < 1e-9,
+                    "{}",
+                    consensus.name()
+                );
+                for validator in validators[1..].iter() {
+                    assert!((validator.stake - 1.0 - share).abs() < 1e-9);
+                }
+            }
+        }
+    }
+
+    /// 小费全部归出块者，基础手续费的分配与没有小费时相同
+    /// PoS、PoW、Minotaur 的基础手续费进入网络费用池，不归出块者
+    #[test]
+    fn test_tip_goes_to_miner() {
+        use crate::blockchain::block::Body;
+        use crate::blockchain::path::TransactionPaths;
+        use crate::blockchain::transaction::Transaction;
+
+        let wallet = Wallet::new();
+        let relay = Wallet::new();
+        let miner = Wallet::new();
+        let gen_block = |tip: f64| {
+            let transaction =
+                Transaction::with_tip("123".to_string(), 32, 1.0, tip, wallet.clone());
+            let mut transaction_paths = TransactionPaths::new(transaction.clone());
+            transaction_paths.add_path(relay.address.clone(), wallet.clone());
+            transaction_paths.add_path(miner.address.clone(), relay.clone());
+            let body = Body::new(
+                vec![transaction],
+                vec![transaction_paths.to_aggregated_signed_paths()],
+            );
+            let genesis = Block::gen_genesis_block();
+            Block::new(1, 0, 1, genesis.header.hash.clone(), body, miner.clone()).unwrap()
+        };
+        let (block, tipped_block) = (gen_block(0.0), gen_block(0.5));
+        assert_eq!(block_tips(&tipped_block), 0.5);
+
+        let consensus_list: Vec<fn() -> Box<dyn Consensus>> = vec![
+            || Box::new(pos::PosConsensus::new(1.0)),
+            || Box::new(pog::PogConsensus::new(3, 1.0)),
+            || {
+                Box::new(pow::PowConsensus::new(
+                    256,
+                    1,
+                    std::time::Duration::from_millis(1),
+                    1.0,
+                ))
+            },
+            || Box::new(minotaur::MinotaurConsensus::new(1.0)),
+        ];
+        for new_consensus in consensus_list {
+            let validators = vec![
+                Validator::new(wallet.address.clone(), 1.0, 1.0),
+                Validator::new(relay.address.clone(), 1.0, 1.0),
+                Validator::new(miner.address.clone(), 1.0, 1.0),
+            ];
+            let mut without_tip = validators.clone();
+            new_consensus().distribute_rewards(&block, &mut without_tip, HashMap::new());
+            let mut with_tip = validators.clone();
+            new_consensus().distribute_rewards(&tipped_block, &mut with_tip, HashMap::new());
+
+            let name = new_consensus().name();
+            assert!(
+                (with_tip[2].stake - without_tip[2].stake - 0.5).abs() < 1e-9,
+                "{}",
+                name
+            );
+            for i in 0..2 {
+                assert!(
+                    (with_tip[i].stake - without_tip[i].stake).abs() < 1e-9,
+                    "{}",
+                    name
+                );
+            }
+
+            // POG 之外，基础手续费进入网络费用池，由路径上的 wallet 和 relay 平分，出块者只得固定奖励
+            if name == "POG" {
+                continue;
+            }
+            assert!((without_tip[2].stake - 2.0).abs() < 1e-9, "{}", name);
+            for validator in &without_tip[..2] {
+                assert!((validator.stake - 1.5).abs() < 1e-9, "{}", name);
+            }
+            // relay 不是验证者时，它的份额由 distribute_network_fees 发放
+            let consensus = new_consensus();
+            let mut without_relay = vec![validators[0].clone(), validators[2].clone()];
+            consensus.distribute_rewards(&block, &mut without_relay, HashMap::new());
+            let relay_fees = consensus.distribute_network_fees(&block, &without_relay);
+            assert_eq!(relay_fees.len(), 1, "{}", name);
+            assert!((relay_fees[&relay.address] - 0.5).abs() < 1e-9, "{}", name);
+            assert!((without_relay[0].stake - 1.5).abs() < 1e-9, "{}", name);
+            assert!((without_relay[1].stake - 2.0).abs() < 1e-9, "{}", name);
+        }
+    }
+}
@@ -1,14 +1,21 @@
 use crate::blockchain::block::Block;
 use crate::blockchain::Blockchain;
-use crate::consensus::{Consensus, Validator, ValidatorError};
-use log::{debug, info};
+use crate::consensus::{
+    block_tips, cumulative_select, pay_relayer_subsidy, relayer_subsidy_for_relays,
+    sort_validators, Consensus, Validator, ValidatorError,
+};
+use log::{debug, error, info, warn};
 use rand::prelude::StdRng;
 use rand::{Rng, SeedableRng};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
 
 pub struct PogConsensus {
     ntd: usize,
     base_reward: f64,
+    // 区块补贴中分给路径参与者的比例
+    relayer_reward_fraction: f64,
     // Temporal smoothing state: Score(n,t) for each node
     score_history: HashMap<String, f64>,
     // Parameters for contribution calculation
@@ -16,6 +23,34 @@ pub struct PogConsensus {
     k_sat: f64,
     k_base: f64,
     omega: f64,
+    // 预热时隙数，预热期间 omega 固定为0（纯 PoS），之后才按计划递增
+    warmup_slots: u64,
+    slots_elapsed: u64,
+    // 每个 epoch 结束时 score_history 乘以的系数，1 表示不衰减
+    epoch_decay: f64,
+    // 每个时隙的网络贡献记录输出（contribution.jsonl），None 表示不输出
+    contribution_writer: Option<Box<dyn Write + Send + Sync>>,
+}
+
+/// contribution.jsonl 中的一条记录：某个时隙各节点归一化后的网络贡献
+/// block_index/epoch/slot 为计算贡献所用路径所在的区块
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ContributionRecord {
+    pub block_index: u64,
+    pub epoch: u64,
+    pub slot: u64,
+    pub omega: f64,
+    pub contribution: HashMap<String, f64>,
+}
+
+/// 读取 contribution.jsonl，跳过无法解析的行
+pub fn read_contribution_records(path: &str) -> std::io::Result<Vec<ContributionRecord>> {
+    let file = std::fs::File::open(path)?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
 }
 
 impl PogConsensus {
@@ -23,11 +58,42 @@ impl PogConsensus {
         PogConsensus {
             ntd: initial_ntd,
             base_reward,
+            relayer_reward_fraction: 0.0,
             score_history: HashMap::new(),
             alpha: 0.5,  // EMA factor: smaller alpha = longer memory
             k_sat: 1.0,  // Saturation scale
             k_base: 1.0, // Saturation base
             omega: 0.0,  // Start with pure PoS (omega=0), gradually increase to 1
+            warmup_slots: 0,
+            slots_elapsed: 0,
+            epoch_decay: 1.0,
+            contribution_writer: None,
+        }
+    }
+
+    /// Record per-slot normalized contribution to the given writer (one JSON per line)
+    pub fn set_contribution_writer<W: Write + Send + Sync + 'static>(&mut self, writer: W) {
+        self.contribution_writer = Some(Box::new(writer));
+    }
+
+    fn write_contribution_record(&mut self, block: &Block, contribution: &HashMap<String, f64>) {
+        let omega = self.current_omega();
+        let writer = match self.contribution_writer.as_mut() {
+            Some(w) => w,
+            None => return,
+        };
+        let record = ContributionRecord {
+            block_index: block.header.index,
+            epoch: block.header.epoch,
+            slot: block.header.slot,
+            omega,
+            contribution: contribution.clone(),
+        };
+        let result = serde_json::to_string(&record)
+            .map_err(std::io::Error::from)
+            .and_then(|line| writeln!(writer, "{}", line));
+        if let Err(e) = result {
+            error!("POG: failed to write contribution record: {}", e);
         }
     }
 
@@ -36,6 +102,28 @@ impl PogConsensus {
         self.omega = omega.max(0.0).min(1.0);
     }
 
+    /// Set the number of warm-up slots during which omega is forced to 0
+    pub fn set_warmup_slots(&mut self, slots: u64) {
+        self.warmup_slots = slots;
+    }
+
+    pub fn set_relayer_reward_fraction(&mut self, fraction: f64) {
+        self.relayer_reward_fraction = fraction;
+    }
+
+    pub fn in_warmup(&self) -> bool {
+        self.slots_elapsed < self.warmup_slots
+    }
+
+    /// omega actually used for virtual stake, 0 during warm-up
+    pub fn current_omega(&self) -> f64 {
+        if self.in_warmup() {
+            0.0
+        } else {
+            self.omega
+        }
+    }
+
     /// Compute position weights: alpha_k(L) = 2(L - k + 1) / (L(L + 1))
     fn compute_position_weight(position: usize, path_length: usize) -> f64 {
         if path_length == 0 || position > path_length || position == 0 {
@@ -44,40 +132,24 @@ impl PogConsensus {
         2.0 * (path_length - position + 1) as f64 / (path_length * (path_length + 1)) as f64
     }
 
-    fn select_internal(
-        &mut self,
-        validators: Vec<Validator>,
+    /// 按当前的贡献历史计算虚拟 stake 并选择出块者，不修改状态
+    /// 贡献历史由 advance_state 每个时隙更新一次，同一时隙重复选择的结果相同
+    pub fn select(
+        &self,
+        validators: &[Validator],
         combines_seeds: [u8; 32],
-        blockchain: Blockchain,
     ) -> Result<Validator, ValidatorError> {
-        let last_block = blockchain.get_last_block();
-        let paths = last_block.get_all_paths();
-
-        // Step 1: Calculate network contribution (Score(n,t)) with temporal smoothing
-        let slot_contribution = self.cal_slot_contribution(&paths, &validators);
-        self.update_score_history(&slot_contribution, &validators);
-
-        debug!(
-            "Score history: {}",
-            serde_json::to_string(&self.score_history)?
-        );
-
-        // Step 2: Calculate normalized stake and contribution
-        let s_real_map: HashMap<String, f64> = validators
-            .iter()
-            .map(|x| (x.address.to_string(), x.stake))
-            .collect();
+        if validators.is_empty() {
+            return Err(ValidatorError::NOValidatorError);
+        }
+        let validators = sort_validators(validators);
 
-        let normalized_stake = self.normalize_map(&s_real_map);
-        let normalized_contribution = self.normalize_map(&self.score_history);
-
-        // Step 3: Calculate virtual stake using hybrid formula
-        let s_virtual_map =
-            self.cal_virtual_stake(&s_real_map, &normalized_stake, &normalized_contribution);
+        // Calculate virtual stake from normalized stake and contribution (Score(n,t))
+        let s_virtual_map = self.virtual_stakes_from(&validators, &self.score_history);
 
         debug!("Virtual stake: {}", serde_json::to_string(&s_virtual_map)?);
 
-        // Step 4: Select proposer probabilistically using virtual stake
+        // Select proposer probabilistically using virtual stake
         let validators_with_virtual_stake: Vec<(String, f64)> = validators
             .iter()
             .map(|x| {
@@ -91,28 +163,72 @@ impl PogConsensus {
         let total_virtual_stake: f64 = validators_with_virtual_stake.iter().map(|(_, vs)| vs).sum();
 
         let mut rng = StdRng::from_seed(combines_seeds);
-        let random_value = if total_virtual_stake < 0.0001 {
-            0.0
-        } else {
-            rng.gen_range(0.0..total_virtual_stake.max(0.0001))
-        };
+        if total_virtual_stake < 0.0001 {
+            // 真实 stake 也全为0时无法按权重选择，改为均匀随机选择
+            let index = rng.gen_range(0..validators.len());
+            warn!(
+                "Total virtual stake is zero, selecting validator {} uniformly",
+                validators[index].address
+            );
+            return Ok(validators[index].clone());
+        }
+        let random_value = rng.gen_range(0.0..total_virtual_stake);
 
-        let mut accumulated_weight = 0.0;
-        for (address, virtual_stake) in validators_with_virtual_stake {
-            accumulated_weight += virtual_stake;
-            if accumulated_weight >= random_value {
-                // Find the original validator to return
-                if let Some(validator) = validators.iter().find(|v| v.address == address) {
-                    info!(
-                        "Proposer {} elected with virtual stake {:.6}",
-                        validator.address, virtual_stake
-                    );
-                    return Ok(validator.clone());
-                }
+        let virtual_stakes: Vec<f64> = validators_with_virtual_stake
+            .iter()
+            .map(|(_, vs)| *vs)
+            .collect();
+        match cumulative_select(&virtual_stakes, random_value) {
+            Some(index) => {
+                let validator = &validators[index];
+                info!(
+                    "Proposer {} elected with virtual stake {:.6}",
+                    validator.address, virtual_stakes[index]
+                );
+                Ok(validator.clone())
             }
+            None => Err(ValidatorError::NOValidatorError),
         }
+    }
 
-        Err(ValidatorError::NOValidatorError)
+    /// 下一个时隙 advance_state 之后选择出块者时使用的虚拟 stake，不修改 score_history
+    /// last_block 为该时隙链上的最新区块，其中的路径计入本时隙的网络贡献
+    pub fn preview_virtual_stakes(
+        &self,
+        validators: &[Validator],
+        last_block: &Block,
+    ) -> HashMap<String, f64> {
+        let slot_contribution = self.cal_slot_contribution(&last_block.get_all_paths(), validators);
+        let score_history = self.next_score_history(&slot_contribution, validators);
+        self.virtual_stakes_from(validators, &score_history)
+    }
+
+    /// 按给定的 score_history 计算虚拟 stake
+    fn virtual_stakes_from(
+        &self,
+        validators: &[Validator],
+        score_history: &HashMap<String, f64>,
+    ) -> HashMap<String, f64> {
+        let s_real_map: HashMap<String, f64> = validators
+            .iter()
+            .map(|x| (x.address.to_string(), x.stake))
+            .collect();
+
+        let normalized_stake = self.normalize_map(&s_real_map);
+        let normalized_contribution = self.normalize_map(score_history);
+
+        // Calculate virtual stake using hybrid formula
+        let s_virtual_map =
+            self.cal_virtual_stake(&s_real_map, &normalized_stake, &normalized_contribution);
+        if s_virtual_map.values().sum::<f64>() < 0.0001 {
+            // 还没有贡献记录且 omega 较高时虚拟 stake 全为0，退回按真实 stake 选择（omega=0），避免跳过该时隙
+            warn!(
+                "Virtual stake is degenerate (omega={:.2}), falling back to real stake",
+                self.current_omega()
+            );
+            return normalized_stake;
+        }
+        s_virtual_map
     }
 
     /// Normalize a map so all values sum to 1
@@ -192,21 +308,22 @@ impl PogConsensus {
         slot_contribution
     }
 
-    /// Update temporal score history using EMA
+    /// Temporal score history after this slot using EMA, without updating self.score_history
     /// Score(n,t) = alpha * C_slot(n,t) + (1 - alpha) * Score(n,t-1)
-    fn update_score_history(
-        &mut self,
+    fn next_score_history(
+        &self,
         slot_contribution: &HashMap<String, f64>,
         validators: &[Validator],
-    ) {
+    ) -> HashMap<String, f64> {
+        let mut score_history = self.score_history.clone();
         for validator in validators {
             let current_slot = slot_contribution.get(&validator.address).unwrap_or(&0.0);
             let previous_score = self.score_history.get(&validator.address).unwrap_or(&0.0);
 
             let new_score = self.alpha * current_slot + (1.0 - self.alpha) * previous_score;
-            self.score_history
-                .insert(validator.address.clone(), new_score);
+            score_history.insert(validator.address.clone(), new_score);
         }
+        score_history
     }
 
     /// Get real stake of a node from validator list
@@ -233,7 +350,8 @@ impl PogConsensus {
                 let hat_s = normalized_stake.get(node).unwrap_or(&0.0);
 
                 // S_v(n,t) = omega * hat_C + (1 - omega) * hat_S_r
-                let s_v = self.omega * hat_c + (1.0 - self.omega) * hat_s;
+                let omega = self.current_omega();
+                let s_v = omega * hat_c + (1.0 - omega) * hat_s;
                 (node.clone(), s_v)
             })
             .collect()
@@ -245,23 +363,90 @@ impl Consensus for PogConsensus {
         "POG"
     }
 
+    /// Set the factor applied to all scores at each epoch end, 1 means no decay
+    fn set_epoch_decay(&mut self, decay: f64) {
+        self.epoch_decay = decay.clamp(0.0, 1.0);
+    }
+
     fn select_proposer(
         &mut self,
         validators: &[Validator],
         combines_seed: [u8; 32],
-        blockchain: &Blockchain,
+        _blockchain: &Blockchain,
     ) -> Result<Validator, ValidatorError> {
-        self.select_internal(validators.to_vec(), combines_seed, blockchain.clone())
+        self.select(validators, combines_seed)
+    }
+
+    /// 把最新区块中的路径计入网络贡献，按 EMA 更新 score_history
+    fn advance_state(&mut self, validators: &[Validator], blockchain: &Blockchain) {
+        let validators = sort_validators(validators);
+        let last_block = blockchain.get_last_block();
+        let slot_contribution =
+            self.cal_slot_contribution(&last_block.get_all_paths(), &validators);
+        self.score_history = self.next_score_history(&slot_contribution, &validators);
+        debug!("Score history: {:?}", self.score_history);
+
+        let normalized_contribution = self.normalize_map(&self.score_history);
+        self.write_contribution_record(&last_block, &normalized_contribution);
+        self.slots_elapsed += 1;
     }
 
     fn on_epoch_end(&mut self, blocks: &[Block]) {
         let paths: Vec<Vec<String>> = blocks.iter().flat_map(|b| b.get_all_paths()).collect();
         self.adjust_ntd(&paths);
-        self.set_omega(self.omega + 0.1);
+        // 衰减贡献历史，让最近的贡献占更大比重
+        if self.epoch_decay < 1.0 {
+            for score in self.score_history.values_mut() {
+                *score *= self.epoch_decay;
+            }
+        }
+        // 预热期间不递增 omega，预热结束后才开始
+        if !self.in_warmup() {
+            self.set_omega(self.omega + 0.1);
+        }
     }
 
     fn state_summary(&self) -> String {
-        format!("pog(ntd={}_omega={:.2})", self.ntd, self.omega)
+        if self.in_warmup() {
+            format!(
+                "pog(ntd={}_omega={:.2}_warmup={}/{})",
+                self.ntd,
+                self.current_omega(),
+                self.slots_elapsed,
+                self.warmup_slots
+            )
+        } else {
+            format!("pog(ntd={}_omega={:.2})", self.ntd, self.omega)
+        }
+    }
+
+    fn contribution(&self) -> HashMap<String, f64> {
+        self.normalize_map(&self.score_history)
+    }
+
+    fn effective_weights(&self, validators: &[Validator]) -> HashMap<String, f64> {
+        self.virtual_stakes_from(&sort_validators(validators), &self.score_history)
+    }
+
+    fn ntd(&self) -> Option<usize> {
+        Some(self.ntd)
+    }
+
+    fn distribute_network_fees(
+        &self,
+        block: &Block,
+        validators: &[Validator],
+    ) -> HashMap<String, f64> {
+        let mut rewards = self.relay_fee_rewards(block, validators);
+        for (address, reward) in relayer_subsidy_for_relays(
+            block,
+            validators,
+            self.base_reward,
+            self.relayer_reward_fraction,
+        ) {
+            *rewards.entry(address).or_insert(0.0) += reward;
+        }
+        rewards
     }
 
     fn distribute_rewards(
@@ -274,9 +459,16 @@ impl Consensus for PogConsensus {
         // 第1层：矿工直接获得交易费的一部分
         // 第2层：剩余费用按网络贡献（虚拟股份）分配给所有验证者
 
-        let block_reward = self.base_reward;
-        // 计算本块总费用
+        // 出块奖励中可分出一部分给路径参与者
+        let block_reward = pay_relayer_subsidy(
+            block,
+            validators,
+            self.base_reward,
+            self.relayer_reward_fraction,
+        );
+        // 计算本块总费用（基础手续费），小费不参与分配，全部归出块者
         let total_fees: f64 = block.body.transactions.iter().map(|tx| tx.fee).sum();
+        let tips = block_tips(block);
 
         // 计算路径统计用于奖励惩罚
         let paths: Vec<Vec<String>> = block.get_all_paths();
@@ -290,30 +482,18 @@ impl Consensus for PogConsensus {
                 .iter_mut()
                 .find(|v| v.address == block.header.miner)
             {
-                validator.stake += block_reward + total_fees;
+                validator.stake += block_reward + total_fees + tips;
                 info!(
                     "POG: Miner {} received reward: {:.6}, new stake: {:.6}",
                     validator.address,
-                    block_reward + total_fees,
+                    block_reward + total_fees + tips,
                     validator.stake
                 );
             }
             return;
         }
 
-        let avg_path_length = paths
-            .iter()
-            .map(|p| p.len().saturating_sub(1) as f64)
-            .sum::<f64>()
-            / paths.len() as f64;
-
-        // 计算惩罚因子：P(B) = (NTD / L_avg)^2，当 L_avg > NTD 时
-        let penalty_factor = if avg_path_length > self.ntd as f64 {
-            let ratio = self.ntd as f64 / avg_path_length;
-            ratio * ratio
-        } else {
-            1.0
-        };
+        let (avg_path_length, penalty_factor) = self.penalty_factor(block);
 
         debug!(
             "POG: rewards distribution - total_fees={:.6}, avg_path_length={:.2}, penalty_factor={:.6}",
@@ -329,8 +509,8 @@ impl Consensus for PogConsensus {
         let virtual_stake_map =
             self.cal_virtual_stake(&s_real_map, &normalized_stake, &normalized_contribution);
 
-        // 第1层：矿工奖励 = 0.5 * total_fees * penalty_factor
-        let miner_share = block_reward + 0.5 * total_fees * penalty_factor;
+        // 第1层：矿工奖励 = 0.5 * total_fees * penalty_factor，另加全部小费
+        let miner_share = block_reward + 0.5 * total_fees * penalty_factor + tips;
 
         // 矿工获得挖矿费用
         if let Some(validator) = validators
@@ -347,7 +527,9 @@ impl Consensus for PogConsensus {
         }
 
         // 第2层：网络费用池 = total_fees * (1 - 0.5 * penalty_factor)
-        let network_pool = total_fees * (1.0 - 0.5 * penalty_factor);
+        // 中继节点的份额由 distribute_network_fees 单独发放，这里先扣除（不含区块补贴部分）
+        let relay_fees: f64 = self.relay_fee_rewards(block, validators).values().sum();
+        let network_pool = total_fees * (1.0 - 0.5 * penalty_factor) - relay_fees;
 
         // 按虚拟股份分配网络费用池
         for validator in validators.iter_mut() {
@@ -369,15 +551,54 @@ impl Consensus for PogConsensus {
 }
 
 impl PogConsensus {
+    /// 中继节点从网络费用池中获得的份额，返回 地址 -> 奖励
+    fn relay_fee_rewards(&self, block: &Block, validators: &[Validator]) -> HashMap<String, f64> {
+        // 网络费用池平均分到每条路径，路径上的非验证者按位置权重获得其中一份
+        let mut rewards: HashMap<String, f64> = HashMap::new();
+        let paths = block.get_all_paths();
+        if paths.is_empty() {
+            return rewards;
+        }
+        let total_fees: f64 = block.body.transactions.iter().map(|tx| tx.fee).sum();
+        let (_, penalty_factor) = self.penalty_factor(block);
+        let pool_per_path = total_fees * (1.0 - 0.5 * penalty_factor) / paths.len() as f64;
+        let validator_set: HashSet<&String> = validators.iter().map(|v| &v.address).collect();
+
+        for path in paths.iter() {
+            if path.is_empty() {
+                continue;
+            }
+            // 去掉最后的矿工节点
+            let path_nodes = &path[..path.len() - 1];
+            for (i, node) in path_nodes.iter().enumerate() {
+                if validator_set.contains(node) {
+                    continue;
+                }
+                let weight = Self::compute_position_weight(i + 1, path_nodes.len());
+                *rewards.entry(node.clone()).or_insert(0.0) += pool_per_path * weight;
+            }
+        }
+        rewards
+    }
+
+    /// 返回 (平均路径长度, 惩罚因子)
+    /// 惩罚因子：P(B) = (NTD / L_avg)^2，当 L_avg > NTD 时
+    fn penalty_factor(&self, block: &Block) -> (f64, f64) {
+        let avg_path_length = block.average_path_length();
+        let penalty_factor = if avg_path_length > self.ntd as f64 {
+            let ratio = self.ntd as f64 / avg_path_length;
+            ratio * ratio
+        } else {
+            1.0
+        };
+        (avg_path_length, penalty_factor)
+    }
+
     fn adjust_ntd(&mut self, paths: &[Vec<String>]) {
         if paths.is_empty() {
             return;
         }
-        let p_ave = paths
-            .iter()
-            .map(|path| path.len().saturating_sub(1))
-            .sum::<usize>() as f64
-            / paths.len() as f64;
+        let p_ave = Block::mean_path_length(paths);
         let target = p_ave.ceil() as usize;
         if self.ntd > target {
             self.ntd -= 1;
@@ -389,10 +610,13 @@ impl PogConsensus {
 
 #[cfg(test)]
 mod tests {
+    use crate::blockchain::block::{Block, Body};
     use crate::blockchain::path::{AggregatedSignedPaths, TransactionPaths};
     use crate::blockchain::transaction::Transaction;
-    use crate::consensus::pog::PogConsensus;
-    use crate::consensus::Validator;
+    use crate::blockchain::Blockchain;
+    use crate::consensus::pog::{read_contribution_records, PogConsensus};
+    use crate::consensus::test_fixtures::{fixed_validators, select_with_fixed_seeds};
+    use crate::consensus::{Consensus, Validator};
     use crate::wallet::Wallet;
     use log::info;
 
@@ -432,7 +656,7 @@ mod tests {
         let slot_contribution = pog.cal_slot_contribution(&paths, &validators);
         info!("Slot contribution (omega=0): {:#?}", slot_contribution);
 
-        pog.update_score_history(&slot_contribution, &validators);
+        pog.score_history = pog.next_score_history(&slot_contribution, &validators);
         info!("Score history: {:#?}", pog.score_history);
 
         let s_real_map: std::collections::HashMap<String, f64> = validators
@@ -457,4 +681,261 @@ mod tests {
         info!("Sum of virtual stakes: {}", sum);
         assert!((sum - 1.0).abs() < 1e-6, "Virtual stakes should sum to 1");
     }
+
+    #[test]
+    fn test_contribution_records() {
+        let path = std::env::temp_dir().join(format!("contribution_{}.jsonl", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let wallet = Wallet::new();
+        let wallet2 = Wallet::new();
+        let miner = Wallet::new();
+        let transaction = Transaction::new("123".to_string(), 32, wallet.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(wallet2.address.clone(), wallet.clone());
+        transaction_paths.add_path(miner.address.clone(), wallet2.clone());
+        let body = Body::new(
+            vec![transaction],
+            vec![transaction_paths.to_aggregated_signed_paths()],
+        );
+        let mut blockchain = Blockchain::new(Block::gen_genesis_block());
+        let block = Block::new(1, 0, 1, blockchain.get_last_hash(), body, miner.clone()).unwrap();
+        blockchain.add_block(block).unwrap();
+        let validators = vec![
+            Validator::new(wallet.address.clone(), 1.0, 1.0),
+            Validator::new(wallet2.address.clone(), 1.0, 1.0),
+            Validator::new(miner.address.clone(), 1.0, 1.0),
+        ];
+
+        let mut pog = PogConsensus::new(3, 1.0);
+        pog.set_contribution_writer(std::fs::File::create(&path).unwrap());
+        pog.advance_state(&validators, &blockchain);
+        pog.advance_state(&validators, &blockchain);
+        drop(pog);
+
+        let records = read_contribution_records(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].block_index, 1);
+        let sum: f64 = records[0].contribution.values().sum();
+        assert!((sum - 1.0).abs() < 1e-6);
+        assert!(records[0].contribution[&wallet.address] > 0.0);
+    }
+
+    /// 预览不修改状态，且与随后选择出块者时使用的虚拟 stake 相同
+    #[test]
+    fn test_preview_virtual_stakes() {
+        let wallet = Wallet::new();
+        let wallet2 = Wallet::new();
+        let miner = Wallet::new();
+        let transaction = Transaction::new("123".to_string(), 32, wallet.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(wallet2.address.clone(), wallet.clone());
+        transaction_paths.add_path(miner.address.clone(), wallet2.clone());
+        let body = Body::new(
+            vec![transaction],
+            vec![transaction_paths.to_aggregated_signed_paths()],
+        );
+        let mut blockchain = Blockchain::new(Block::gen_genesis_block());
+        let block = Block::new(1, 0, 1, blockchain.get_last_hash(), body, miner.clone()).unwrap();
+        blockchain.add_block(block).unwrap();
+        let validators = vec![
+            Validator::new(wallet.address.clone(), 1.0, 1.0),
+            Validator::new(wallet2.address.clone(), 2.0, 1.0),
+            Validator::new(miner.address.clone(), 3.0, 1.0),
+        ];
+
+        let mut pog = PogConsensus::new(3, 1.0);
+        pog.set_omega(0.5);
+        let last_block = blockchain.get_last_block();
+        let preview = pog.preview_virtual_stakes(&validators, &last_block);
+        assert!(pog.contribution().is_empty());
+        assert_eq!(
+            pog.preview_virtual_stakes(&validators, &last_block),
+            preview
+        );
+        let sum: f64 = preview.values().sum();
+        assert!((sum - 1.0).abs() < 1e-6);
+        // 有网络贡献的节点的虚拟 stake 高于真实 stake 占比
+        assert!(preview[&wallet.address] > 1.0 / 6.0);
+
+        pog.advance_state(&validators, &blockchain);
+        let actual = pog.virtual_stakes_from(&validators, &pog.score_history);
+        assert_eq!(actual.len(), preview.len());
+        for (address, stake) in actual {
+            assert!((preview[&address] - stake).abs() < 1e-12);
+        }
+    }
+
+    /// 选择出块者不推进贡献历史，同一时隙重复选择的结果相同
+    #[test]
+    fn test_select_is_pure() {
+        let wallet = Wallet::new();
+        let miner = Wallet::new();
+        let transaction = Transaction::new("123".to_string(), 32, wallet.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(miner.address.clone(), wallet.clone());
+        let body = Body::new(
+            vec![transaction],
+            vec![transaction_paths.to_aggregated_signed_paths()],
+        );
+        let mut blockchain = Blockchain::new(Block::gen_genesis_block());
+        let block = Block::new(1, 0, 1, blockchain.get_last_hash(), body, miner.clone()).unwrap();
+        blockchain.add_block(block).unwrap();
+        let validators: Vec<Validator> = [&wallet, &miner]
+            .iter()
+            .map(|w| Validator::new(w.address.clone(), 1.0, 1.0))
+            .collect();
+
+        let mut pog = PogConsensus::new(3, 1.0);
+        pog.set_omega(0.5);
+        pog.advance_state(&validators, &blockchain);
+        let history = pog.score_history.clone();
+        for seed in 0..16u8 {
+            let first = pog.select(&validators, [seed; 32]).unwrap();
+            let second = pog
+                .select_proposer(&validators, [seed; 32], &blockchain)
+                .unwrap();
+            assert_eq!(first.address, second.address);
+        }
+        assert_eq!(pog.score_history, history);
+        assert_eq!(pog.slots_elapsed, 1);
+    }
+
+    #[test]
+    fn test_epoch_decay() {
+        let wallet = Wallet::new();
+        let miner = Wallet::new();
+        let transaction = Transaction::new("123".to_string(), 32, wallet.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(miner.address.clone(), wallet.clone());
+        let body = Body::new(
+            vec![transaction],
+            vec![transaction_paths.to_aggregated_signed_paths()],
+        );
+        let mut blockchain = Blockchain::new(Block::gen_genesis_block());
+        let block = Block::new(1, 0, 1, blockchain.get_last_hash(), body, miner.clone()).unwrap();
+        blockchain.add_block(block).unwrap();
+        let validators: Vec<Validator> = [&wallet, &miner]
+            .iter()
+            .map(|w| Validator::new(w.address.clone(), 1.0, 1.0))
+            .collect();
+
+        let mut pog = PogConsensus::new(3, 1.0);
+        pog.set_epoch_decay(0.5);
+        pog.advance_state(&validators, &blockchain);
+        let before = pog.score_history[&wallet.address];
+        assert!(before > 0.0);
+        pog.on_epoch_end(&[]);
+        assert!((pog.score_history[&wallet.address] - before * 0.5).abs() < 1e-12);
+        // 归一化后的贡献比例不变
+        assert!((pog.contribution()[&wallet.address] - 1.0).abs() < 1e-12);
+
+        // 默认不衰减
+        let mut pog = PogConsensus::new(3, 1.0);
+        pog.advance_state(&validators, &blockchain);
+        pog.on_epoch_end(&[]);
+        assert_eq!(pog.score_history[&wallet.address], before);
+    }
+
+    #[test]
+    fn test_relay_network_fees() {
+        let wallet = Wallet::new();
+        let relay = Wallet::new();
+        let miner = Wallet::new();
+        let transaction = Transaction::with_fee("123".to_string(), 32, 2.0, wallet.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(relay.address.clone(), wallet.clone());
+        transaction_paths.add_path(miner.address.clone(), relay.clone());
+        let body = Body::new(
+            vec![transaction],
+            vec![transaction_paths.to_aggregated_signed_paths()],
+        );
+        let mut blockchain = Blockchain::new(Block::gen_genesis_block());
+        let block = Block::new(1, 0, 1, blockchain.get_last_hash(), body, miner.clone()).unwrap();
+        blockchain.add_block(block.clone()).unwrap();
+        // 中继节点不是验证者
+        let mut validators = vec![
+            Validator::new(wallet.address.clone(), 1.0, 1.0),
+            Validator::new(miner.address.clone(), 1.0, 1.0),
+        ];
+
+        let mut pog = PogConsensus::new(3, 1.0);
+        let relay_rewards = pog.distribute_network_fees(&block, &validators);
+        assert_eq!(relay_rewards.len(), 1);
+        let relay_reward = relay_rewards[&relay.address];
+        assert!(relay_reward > 0.0);
+
+        // 中继节点的份额从网络费用池中扣除，发放总额不超过 出块奖励 + 手续费
+        pog.distribute_rewards(&block, &mut validators, Default::default());
+        let total_stake: f64 = validators.iter().map(|v| v.stake).sum();
+        assert!(total_stake + relay_reward <= 2.0 + 1.0 + 2.0 + 1e-9);
+
+        for seed in 0..10u8 {
+            let proposer = pog
+                .select_proposer(&validators, [seed; 32], &blockchain)
+                .unwrap();
+            assert_ne!(proposer.address, relay.address);
+        }
+    }
+
+    /// 固定验证者集合和种子时，按虚拟 stake 选出的 proposer 是确定的
+    #[test]
+    fn test_select_proposer_deterministic() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators = fixed_validators();
+        let selected = select_with_fixed_seeds(|seed| {
+            // 每次使用新的实例，避免贡献历史影响结果
+            let mut pog = PogConsensus::new(3, 1.0);
+            pog.select_proposer(&validators, seed, &blockchain)
+                .unwrap()
+                .address
+        });
+        assert_eq!(selected, vec!["c", "d", "d", "c", "b", "d"]);
+    }
+
+    /// omega=1 且还没有任何贡献时，按真实 stake 选择而不是跳过时隙
+    #[test]
+    fn test_zero_contribution_falls_back_to_real_stake() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators = vec![
+            Validator::new("a".to_string(), 0.0, 1.0),
+            Validator::new("b".to_string(), 0.0, 1.0),
+            Validator::new("c".to_string(), 10.0, 1.0),
+        ];
+        let mut pog = PogConsensus::new(3, 1.0);
+        pog.set_omega(1.0);
+        for i in 0..16u8 {
+            let proposer = pog
+                .select_proposer(&validators, [i; 32], &blockchain)
+                .unwrap();
+            assert_eq!(proposer.address, "c");
+        }
+    }
+
+    /// 预热期间 omega 固定为0，不受递增计划影响
+    #[test]
+    fn test_omega_zero_during_warmup() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators = vec![
+            Validator::new("a".to_string(), 1.0, 1.0),
+            Validator::new("b".to_string(), 2.0, 1.0),
+        ];
+        let mut pog = PogConsensus::new(3, 1.0);
+        pog.set_warmup_slots(3);
+        for i in 0..3u8 {
+            assert!(pog.in_warmup());
+            pog.on_epoch_end(&[]);
+            assert_eq!(pog.current_omega(), 0.0);
+            assert!(pog.state_summary().contains("warmup"));
+            pog.advance_state(&validators, &blockchain);
+            pog.select_proposer(&validators, [i; 32], &blockchain)
+                .unwrap();
+        }
+        // 预热结束后才开始递增
+        assert!(!pog.in_warmup());
+        assert_eq!(pog.current_omega(), 0.0);
+        pog.on_epoch_end(&[]);
+        assert!((pog.current_omega() - 0.1).abs() < 1e-9);
+        assert!(!pog.state_summary().contains("warmup"));
+    }
 }
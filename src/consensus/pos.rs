@@ -2,17 +2,35 @@ use std::collections::HashMap;
 
 use crate::blockchain::block::Block;
 use crate::blockchain::Blockchain;
-use crate::consensus::{Consensus, Validator, ValidatorError};
+use crate::consensus::{
+    block_tips, cumulative_select, network_fees_for_relays, pay_base_fee_pool, pay_relayer_subsidy,
+    pay_universal_reward, sort_validators, Consensus, Validator, ValidatorError,
+};
+use log::{debug, info, warn};
 use rand::prelude::StdRng;
 use rand::{Rng, SeedableRng};
 
 pub struct PosConsensus {
     base_reward: f64,
+    relayer_reward_fraction: f64,   // 区块补贴中分给路径参与者的比例
+    universal_reward_fraction: f64, // 奖励中由所有验证者平分的比例
 }
 
 impl PosConsensus {
     pub fn new(base_reward: f64) -> Self {
-        PosConsensus { base_reward }
+        PosConsensus {
+            base_reward,
+            relayer_reward_fraction: 0.0,
+            universal_reward_fraction: 0.0,
+        }
+    }
+
+    pub fn set_relayer_reward_fraction(&mut self, fraction: f64) {
+        self.relayer_reward_fraction = fraction;
+    }
+
+    pub fn set_universal_reward_fraction(&mut self, fraction: f64) {
+        self.universal_reward_fraction = fraction;
     }
 
     fn select(
@@ -25,15 +43,21 @@ impl PosConsensus {
         }
         let total_stake: f64 = validators.iter().map(|v| v.stake).sum();
         let mut rng = StdRng::from_seed(combines_seeds);
+        if total_stake <= 0.0 {
+            // 所有验证者 stake 都为0时，gen_range 会 panic，改为均匀随机选择
+            let index = rng.gen_range(0..validators.len());
+            warn!(
+                "Total stake is zero, selecting validator {} uniformly",
+                validators[index].address
+            );
+            return Ok(validators[index].clone());
+        }
         let random_value = rng.gen_range(0.0..total_stake);
-        let mut accumulated_weight = 0f64;
-        for validator in validators.clone() {
-            accumulated_weight += validator.stake;
-            if accumulated_weight > random_value {
-                return Ok(validator);
-            }
+        let stakes: Vec<f64> = validators.iter().map(|v| v.stake).collect();
+        match cumulative_select(&stakes, random_value) {
+            Some(index) => Ok(validators[index].clone()),
+            None => Err(ValidatorError::NOValidatorError),
         }
-        Err(ValidatorError::NOValidatorError)
     }
 }
 
@@ -48,7 +72,11 @@ impl Consensus for PosConsensus {
         combines_seed: [u8; 32],
         blockchain: &Blockchain,
     ) -> Result<Validator, ValidatorError> {
-        Self::select(validators.to_vec(), combines_seed, blockchain.clone())
+        Self::select(
+            sort_validators(validators),
+            combines_seed,
+            blockchain.clone(),
+        )
     }
 
     fn on_epoch_end(&mut self, _blocks: &[Block]) {}
@@ -57,29 +85,78 @@ impl Consensus for PosConsensus {
         "pos".to_string()
     }
 
+    fn distribute_network_fees(
+        &self,
+        block: &Block,
+        validators: &[Validator],
+    ) -> HashMap<String, f64> {
+        network_fees_for_relays(
+            block,
+            validators,
+            self.base_reward,
+            self.relayer_reward_fraction,
+        )
+    }
+
     fn distribute_rewards(
         &self,
         block: &Block,
         validators: &mut [Validator],
         _nodes_index: HashMap<String, u32>,
     ) {
-        // PoS: 固定奖励 + 交易费用
+        // PoS: 固定奖励 + 小费，基础手续费进入网络费用池，由路径参与者分得
+        let base_reward = pay_relayer_subsidy(
+            block,
+            validators,
+            self.base_reward,
+            self.relayer_reward_fraction,
+        );
+        let burned_fees = pay_base_fee_pool(block, validators);
+        // 奖励中按比例分出的普惠部分由所有验证者平分
+        let miner_reward =
+            pay_universal_reward(validators, base_reward, self.universal_reward_fraction);
+        // 小费全部归出块者
+        let tips = block_tips(block);
         if let Some(validator) = validators
             .iter_mut()
             .find(|v| v.address == block.header.miner)
         {
-            let base_reward = self.base_reward;
-            let tx_fees: f64 = block.body.transactions.iter().map(|tx| tx.fee).sum();
-            let total_reward = base_reward + tx_fees;
-            validator.stake += total_reward;
-            log::info!(
-                "PoS: Miner {} received reward: base={:.6} + fees={:.6} = {:.6}, new stake: {:.6}",
+            validator.stake += miner_reward + tips;
+            info!(
+                "PoS: Miner {} received reward: base={:.6} - universal={:.6} + tips={:.6} = {:.6}, new stake: {:.6}",
                 validator.address,
                 base_reward,
-                tx_fees,
-                total_reward,
+                base_reward - miner_reward,
+                tips,
+                miner_reward + tips,
                 validator.stake
             );
         }
+        if burned_fees > 0.0 {
+            debug!(
+                "PoS: burned {:.6} base fees of block {} without relays",
+                burned_fees, block.header.index
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::test_fixtures::{fixed_validators, select_with_fixed_seeds};
+
+    /// 固定验证者集合和种子时，选出的 proposer 是确定的
+    #[test]
+    fn test_select_proposer_deterministic() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators = fixed_validators();
+        let mut pos = PosConsensus::new(1.0);
+        let selected = select_with_fixed_seeds(|seed| {
+            pos.select_proposer(&validators, seed, &blockchain)
+                .unwrap()
+                .address
+        });
+        assert_eq!(selected, vec!["c", "d", "d", "c", "b", "d"]);
     }
 }
@@ -1,9 +1,13 @@
 use crate::blockchain::block::Block;
 use crate::blockchain::Blockchain;
-use crate::consensus::{Consensus, Validator, ValidatorError};
-use log::{info, warn};
-use rand::Rng;
-use sha2::{Digest, Sha256};
+use crate::consensus::{
+    block_tips, network_fees_for_relays, pay_base_fee_pool, pay_relayer_subsidy,
+    pay_universal_reward, sort_validators, Consensus, Validator, ValidatorError,
+};
+use crate::tools::{HashAlgo, Hasher};
+use log::{debug, info, warn};
+use rand::prelude::StdRng;
+use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -20,6 +24,12 @@ pub struct PowConsensus {
     max_threads: usize,
     slot_duration: Duration,
     base_reward: f64,
+    /// 区块补贴中分给路径参与者的比例
+    relayer_reward_fraction: f64,
+    /// 奖励中由所有验证者平分的比例
+    universal_reward_fraction: f64,
+    /// 工作量计算使用的哈希算法，默认 SHA-256
+    hasher: Hasher,
 }
 
 impl PowConsensus {
@@ -36,9 +46,24 @@ impl PowConsensus {
             max_threads,
             slot_duration,
             base_reward,
+            relayer_reward_fraction: 0.0,
+            universal_reward_fraction: 0.0,
+            hasher: Hasher::with_algo(HashAlgo::Sha256),
         }
     }
 
+    pub fn set_relayer_reward_fraction(&mut self, fraction: f64) {
+        self.relayer_reward_fraction = fraction;
+    }
+
+    pub fn set_universal_reward_fraction(&mut self, fraction: f64) {
+        self.universal_reward_fraction = fraction;
+    }
+
+    pub fn set_hash_algo(&mut self, algo: HashAlgo) {
+        self.hasher = Hasher::with_algo(algo);
+    }
+
     /// 验证工作量证明
     /// 检查 hash 是否满足难度要求（leading zeros）
     fn verify_pow(hash: &[u8], difficulty: usize) -> bool {
@@ -69,11 +94,9 @@ impl PowConsensus {
     #[allow(dead_code)]
     fn mine_pow(data: &[u8], difficulty: usize, max_attempts: u64) -> Option<(u64, Vec<u8>)> {
         for nonce in 0..max_attempts {
-            let mut hasher = Sha256::new();
-            hasher.update(data);
-            hasher.update(nonce.to_le_bytes());
-            let hash = hasher.finalize();
-            let hash_bytes = hash.to_vec();
+            let hash_bytes = Hasher::with_algo(HashAlgo::Sha256)
+                .digest(&[data, &nonce.to_le_bytes()])
+                .to_vec();
 
             if Self::verify_pow(&hash_bytes, difficulty) {
                 return Some((nonce, hash_bytes));
@@ -161,6 +184,7 @@ impl Consensus for PowConsensus {
                 let should_stop_clone = Arc::clone(&should_stop);
                 let difficulty = self.difficulty;
                 let seed = combines_seed;
+                let hasher = self.hasher;
 
                 // 恢复为固定的最大尝试次数，不再通过次数限制算力
                 let max_attempts = 100_000_000u64;
@@ -197,11 +221,9 @@ impl Consensus for PowConsensus {
                             }
                         }
 
-                        let mut hasher = Sha256::new();
-                        hasher.update(&mining_data);
-                        hasher.update(nonce.to_le_bytes());
-                        let hash = hasher.finalize();
-                        let hash_bytes = hash.to_vec();
+                        let hash_bytes = hasher
+                            .digest(&[&mining_data, &nonce.to_le_bytes()])
+                            .to_vec();
 
                         // 验证是否满足难度要求
                         if Self::verify_pow(&hash_bytes, difficulty) {
@@ -274,8 +296,9 @@ impl Consensus for PowConsensus {
                 Ok(validator)
             }
             None => {
-                // 如果在规定时间内没有找到获胜者，随机选择一个验证者并降低难度
-                let mut rng = rand::thread_rng();
+                // 如果在规定时间内没有找到获胜者，按种子随机选择一个验证者并降低难度
+                let validators = sort_validators(validators);
+                let mut rng = StdRng::from_seed(combines_seed);
                 let index = rng.gen_range(0..validators.len());
                 self.difficulty = self.difficulty.saturating_sub(1);
                 warn!(
@@ -292,6 +315,14 @@ impl Consensus for PowConsensus {
         self.adjust_difficulty(blocks);
     }
 
+    /// 出块概率与算力成正比，与 stake 无关
+    fn effective_weights(&self, validators: &[Validator]) -> HashMap<String, f64> {
+        validators
+            .iter()
+            .map(|v| (v.address.clone(), v.hash_power))
+            .collect()
+    }
+
     fn state_summary(&self) -> String {
         format!(
             "pow(difficulty={}_work_amount={:.0})",
@@ -300,24 +331,57 @@ impl Consensus for PowConsensus {
         )
     }
 
+    fn distribute_network_fees(
+        &self,
+        block: &Block,
+        validators: &[Validator],
+    ) -> HashMap<String, f64> {
+        network_fees_for_relays(
+            block,
+            validators,
+            self.base_reward,
+            self.relayer_reward_fraction,
+        )
+    }
+
     fn distribute_rewards(
         &self,
         block: &Block,
         validators: &mut [Validator],
         _nodes_index: HashMap<String, u32>,
     ) {
-        // PoW: 固定奖励 + 交易费用
+        // PoW: 固定奖励 + 小费，基础手续费进入网络费用池，由路径参与者分得
+        let base_reward = pay_relayer_subsidy(
+            block,
+            validators,
+            self.base_reward,
+            self.relayer_reward_fraction,
+        );
+        let burned_fees = pay_base_fee_pool(block, validators);
+        // 奖励中按比例分出的普惠部分由所有验证者平分
+        let miner_reward =
+            pay_universal_reward(validators, base_reward, self.universal_reward_fraction);
+        // 小费全部归出块者
+        let tips = block_tips(block);
         if let Some(validator) = validators
             .iter_mut()
             .find(|v| v.address == block.header.miner)
         {
-            let base_reward = self.base_reward;
-            let tx_fees: f64 = block.body.transactions.iter().map(|tx| tx.fee).sum();
-            let total_reward = base_reward + tx_fees;
-            validator.stake += total_reward;
+            validator.stake += miner_reward + tips;
             info!(
-                "PoW: Miner {} received reward: base={:.6} + fees={:.6} = {:.6}, new stake: {:.6}",
-                validator.address, base_reward, tx_fees, total_reward, validator.stake
+                "PoW: Miner {} received reward: base={:.6} - universal={:.6} + tips={:.6} = {:.6}, new stake: {:.6}",
+                validator.address,
+                base_reward,
+                base_reward - miner_reward,
+                tips,
+                miner_reward + tips,
+                validator.stake
+            );
+        }
+        if burned_fees > 0.0 {
+            debug!(
+                "PoW: burned {:.6} base fees of block {} without relays",
+                burned_fees, block.header.index
             );
         }
     }
@@ -326,6 +390,7 @@ impl Consensus for PowConsensus {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::consensus::test_fixtures::{fixed_validators, select_with_fixed_seeds};
 
     #[test]
     fn test_pow_verification() {
@@ -361,4 +426,20 @@ mod tests {
         // 验证找到的 nonce 确实满足难度要求
         assert!(PowConsensus::verify_pow(&hash, 2));
     }
+
+    /// 无人在时限内找到解时，按种子回退选择，结果也是确定的
+    #[test]
+    fn test_fallback_select_deterministic() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators = fixed_validators();
+        // 难度 256 不可能满足，必然走回退路径
+        let mut pow = PowConsensus::new(256, 4, Duration::from_millis(1), 1.0);
+        let selected = select_with_fixed_seeds(|seed| {
+            pow.select_proposer(&validators, seed, &blockchain)
+                .unwrap()
+                .address
+        });
+        assert_eq!(selected, vec!["b", "b", "d", "b", "a", "a"]);
+        assert_eq!(pow.difficulty, 250);
+    }
 }
@@ -0,0 +1,258 @@
+use crate::blockchain::block::Block;
+use crate::blockchain::Blockchain;
+use crate::consensus::{Consensus, Validator, ValidatorError};
+use log::warn;
+use std::collections::{HashMap, VecDeque};
+
+/// 按给定的出块者序列依次选择出块者（每个时隙一个），不看 stake 和种子
+/// 用于确定性的测试和构造特定的攻击场景；序列用完后由内部共识选择
+/// 奖励分配、NTD 等其余行为都交给内部共识
+pub struct ScriptedConsensus {
+    schedule: VecDeque<String>,
+    inner: Box<dyn Consensus>,
+}
+
+impl ScriptedConsensus {
+    pub fn new(schedule: Vec<String>, inner: Box<dyn Consensus>) -> Self {
+        ScriptedConsensus {
+            schedule: schedule.into(),
+            inner,
+        }
+    }
+}
+
+impl Consensus for ScriptedConsensus {
+    fn name(&self) -> &'static str {
+        "SCRIPTED"
+    }
+
+    /// 序列中的地址不在验证者集合中（未注册或离线）时，该时隙没有出块者
+    fn select_proposer(
+        &mut self,
+        validators: &[Validator],
+        combines_seed: [u8; 32],
+        blockchain: &Blockchain,
+    ) -> Result<Validator, ValidatorError> {
+        let address = match self.schedule.pop_front() {
+            Some(address) => address,
+            None => {
+                return self
+                    .inner
+                    .select_proposer(validators, combines_seed, blockchain)
+            }
+        };
+        match validators.iter().find(|v| v.address == address) {
+            Some(validator) => Ok(validator.clone()),
+            None => {
+                warn!("Scheduled proposer {} is not a validator", address);
+                Err(ValidatorError::NOValidatorError)
+            }
+        }
+    }
+
+    fn advance_state(&mut self, validators: &[Validator], blockchain: &Blockchain) {
+        self.inner.advance_state(validators, blockchain);
+    }
+
+    fn on_epoch_end(&mut self, blocks: &[Block]) {
+        self.inner.on_epoch_end(blocks);
+    }
+
+    fn apply_block_feedback(&mut self, block: &Block) {
+        self.inner.apply_block_feedback(block);
+    }
+
+    fn state_summary(&self) -> String {
+        format!(
+            "scripted(remaining={})+{}",
+            self.schedule.len(),
+            self.inner.state_summary()
+        )
+    }
+
+    fn ntd(&self) -> Option<usize> {
+        self.inner.ntd()
+    }
+
+    fn contribution(&self) -> HashMap<String, f64> {
+        self.inner.contribution()
+    }
+
+    /// 序列未用完时与 select_proposer 一致，只有序列中的下一个地址会被选中
+    fn effective_weights(&self, validators: &[Validator]) -> HashMap<String, f64> {
+        match self.schedule.front() {
+            Some(address) => validators
+                .iter()
+                .map(|v| {
+                    let weight = if v.address == *address { 1.0 } else { 0.0 };
+                    (v.address.clone(), weight)
+                })
+                .collect(),
+            None => self.inner.effective_weights(validators),
+        }
+    }
+
+    fn distribute_rewards(
+        &self,
+        block: &Block,
+        validators: &mut [Validator],
+        nodes_index: HashMap<String, u32>,
+    ) {
+        self.inner
+            .distribute_rewards(block, validators, nodes_index);
+    }
+
+    fn distribute_network_fees(
+        &self,
+        block: &Block,
+        validators: &[Validator],
+    ) -> HashMap<String, f64> {
+        self.inner.distribute_network_fees(block, validators)
+    }
+
+    fn next_slot(&mut self, validators: &[Validator], block_index: u64) {
+        self.inner.next_slot(validators, block_index);
+    }
+
+    fn set_epoch_decay(&mut self, decay: f64) {
+        self.inner.set_epoch_decay(decay);
+    }
+
+    /// 保留剩余的出块者序列，只替换序列用完后使用的共识
+    fn switch_to(mut self: Box<Self>, consensus: Box<dyn Consensus>) -> Box<dyn Consensus> {
+        self.inner = consensus;
+        self
+    }
+}
+
+/// 读取出块者序列文件：每行一个节点编号或地址，忽略空行和 # 开头的注释
+pub fn load_schedule(path: &str) -> Result<Vec<String>, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("read {} failed: {}", path, e))?;
+    Ok(content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect())
+}
+
+/// 把序列中的节点编号解析为地址，地址原样保留
+pub fn resolve_schedule(
+    entries: &[String],
+    nodes_index: &HashMap<String, u32>,
+) -> Result<Vec<String>, String> {
+    entries
+        .iter()
+        .map(|entry| match entry.parse::<u32>() {
+            Ok(index) => nodes_index
+                .iter()
+                .find(|(_, i)| **i == index)
+                .map(|(address, _)| address.clone())
+                .ok_or_else(|| format!("unknown node index in schedule: {}", index)),
+            Err(_) => Ok(entry.clone()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::pog::PogConsensus;
+    use crate::consensus::pos::PosConsensus;
+
+    #[test]
+    fn test_scripted_consensus() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators = vec![
+            Validator::new("a".to_string(), 1.0, 1.0),
+            Validator::new("b".to_string(), 100.0, 1.0),
+        ];
+        let mut scripted = ScriptedConsensus::new(
+            vec!["a".to_string(), "a".to_string(), "x".to_string()],
+            Box::new(PosConsensus::new(1.0)),
+        );
+        let mut select = |seed: u8| {
+            scripted
+                .select_proposer(&validators, [seed; 32], &blockchain)
+                .map(|v| v.address)
+        };
+        assert_eq!(select(0).unwrap(), "a");
+        assert_eq!(select(1).unwrap(), "a");
+        // 不是验证者的地址没有出块者
+        assert!(select(2).is_err());
+        // 序列用完后由内部共识选择，与直接使用内部共识的结果相同
+        let mut pos = PosConsensus::new(1.0);
+        for seed in 3..8 {
+            let expected = pos
+                .select_proposer(&validators, [seed; 32], &blockchain)
+                .unwrap()
+                .address;
+            assert_eq!(select(seed).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_scripted_effective_weights() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators = vec![
+            Validator::new("a".to_string(), 1.0, 1.0),
+            Validator::new("b".to_string(), 3.0, 1.0),
+        ];
+        let mut scripted =
+            ScriptedConsensus::new(vec!["b".to_string()], Box::new(PosConsensus::new(1.0)));
+        let weights = scripted.effective_weights(&validators);
+        assert_eq!((weights["a"], weights["b"]), (0.0, 1.0));
+        scripted
+            .select_proposer(&validators, [0; 32], &blockchain)
+            .unwrap();
+        // 序列用完后使用内部共识的权重
+        assert_eq!(
+            scripted.effective_weights(&validators),
+            PosConsensus::new(1.0).effective_weights(&validators)
+        );
+    }
+
+    /// 切换共识时保留剩余的序列，序列用完后由新的共识选择
+    #[test]
+    fn test_scripted_switch_keeps_schedule() {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let validators = vec![
+            Validator::new("a".to_string(), 1.0, 1.0),
+            Validator::new("b".to_string(), 100.0, 1.0),
+        ];
+        let scripted: Box<dyn Consensus> = Box::new(ScriptedConsensus::new(
+            vec!["a".to_string()],
+            Box::new(PosConsensus::new(1.0)),
+        ));
+        assert_eq!(scripted.ntd(), None);
+        let mut switched = scripted.switch_to(Box::new(PogConsensus::new(0, 1.0)));
+        assert_eq!(switched.name(), "SCRIPTED");
+        assert!(switched.ntd().is_some());
+        assert_eq!(
+            switched
+                .select_proposer(&validators, [0; 32], &blockchain)
+                .unwrap()
+                .address,
+            "a"
+        );
+    }
+
+    #[test]
+    fn test_resolve_schedule() {
+        let nodes_index = HashMap::from([("0xaaa".to_string(), 0), ("0xbbb".to_string(), 1)]);
+        let entries = vec!["1".to_string(), "0xccc".to_string(), "0".to_string()];
+        assert_eq!(
+            resolve_schedule(&entries, &nodes_index).unwrap(),
+            vec!["0xbbb", "0xccc", "0xaaa"]
+        );
+        assert!(resolve_schedule(&["7".to_string()], &nodes_index).is_err());
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "# node 1 proposes twice\n1\n\n 1 \n0xccc\n").unwrap();
+        assert_eq!(
+            load_schedule(file.path().to_str().unwrap()).unwrap(),
+            vec!["1", "1", "0xccc"]
+        );
+    }
+}
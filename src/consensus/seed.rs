@@ -0,0 +1,217 @@
+use crate::blockchain::block::Block;
+use crate::consensus::{combine_seed, RandaoSeed, Validator};
+use crate::tools::Hasher;
+use clap::ValueEnum;
+use log::debug;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+
+/// 出块者选举所用随机种子的来源
+/// 每个时隙调用一次 next_seed，得到的种子交给共识的 select_proposer
+pub trait SeedSource: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn next_seed(
+        &mut self,
+        validators: &[Validator],
+        randao_seeds: Vec<RandaoSeed>,
+        last_block: &Block,
+    ) -> [u8; 32];
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedSourceType {
+    Randao,
+    BlockHash,
+    Fixed,
+}
+
+impl Display for SeedSourceType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            SeedSourceType::Randao => write!(f, "randao"),
+            SeedSourceType::BlockHash => write!(f, "block-hash"),
+            SeedSourceType::Fixed => write!(f, "fixed"),
+        }
+    }
+}
+
+impl SeedSourceType {
+    /// fixed_seed 仅用于 Fixed，作为确定性种子序列的起点
+    pub fn build(&self, fixed_seed: u64) -> Box<dyn SeedSource> {
+        match *self {
+            SeedSourceType::Randao => Box::new(RandaoSeedSource),
+            SeedSourceType::BlockHash => Box::new(BlockHashSeedSource),
+            SeedSourceType::Fixed => Box::new(FixedSeedSource::from_u64(fixed_seed)),
+        }
+    }
+}
+
+/// 默认：验证者提交的 RANDAO 种子异或后哈希
+pub struct RandaoSeedSource;
+
+impl SeedSource for RandaoSeedSource {
+    fn name(&self) -> &'static str {
+        "randao"
+    }
+
+    fn next_seed(
+        &mut self,
+        validators: &[Validator],
+        randao_seeds: Vec<RandaoSeed>,
+        _last_block: &Block,
+    ) -> [u8; 32] {
+        combine_seed(validators.to_vec(), randao_seeds)
+    }
+}
+
+/// 使用上一个区块哈希作为种子，不受 RANDAO 提交情况影响
+pub struct BlockHashSeedSource;
+
+impl SeedSource for BlockHashSeedSource {
+    fn name(&self) -> &'static str {
+        "block-hash"
+    }
+
+    fn next_seed(
+        &mut self,
+        _validators: &[Validator],
+        _randao_seeds: Vec<RandaoSeed>,
+        last_block: &Block,
+    ) -> [u8; 32] {
+        Hasher::hash(last_block.header.hash.as_bytes().to_vec())
+    }
+}
+
+/// 按给定序列依次返回种子，用于测试和确定性重放
+/// 序列用完后以最后一个种子的哈希继续延伸
+pub struct FixedSeedSource {
+    seeds: Vec<[u8; 32]>,
+    next: usize,
+}
+
+impl FixedSeedSource {
+    pub fn new(seeds: Vec<[u8; 32]>) -> Self {
+        FixedSeedSource { seeds, next: 0 }
+    }
+
+    pub fn from_u64(seed: u64) -> Self {
+        FixedSeedSource::new(vec![Hasher::hash(seed.to_le_bytes().to_vec())])
+    }
+}
+
+impl SeedSource for FixedSeedSource {
+    fn name(&self) -> &'static str {
+        "fixed"
+    }
+
+    fn next_seed(
+        &mut self,
+        _validators: &[Validator],
+        _randao_seeds: Vec<RandaoSeed>,
+        _last_block: &Block,
+    ) -> [u8; 32] {
+        if self.next >= self.seeds.len() {
+            let last = self.seeds.last().copied().unwrap_or([0; 32]);
+            self.seeds.push(Hasher::hash(last.to_vec()));
+        }
+        let seed = self.seeds[self.next];
+        self.next += 1;
+        seed
+    }
+}
+
+/// 简单的VDF：对种子顺序哈希 difficulty 次
+/// 计算无法并行，使最后一个提交 RANDAO 种子的验证者无法在截止前预知结果
+pub fn simple_vdf(seed: [u8; 32], difficulty: u64) -> [u8; 32] {
+    let mut result = seed;
+    for _ in 0..difficulty {
+        result = Hasher::hash(result.to_vec());
+    }
+    result
+}
+
+/// 在内部种子来源的结果上再执行一次 VDF
+pub struct VdfSeedSource {
+    inner: Box<dyn SeedSource>,
+    difficulty: u64,
+    pub last_elapsed: Duration, // 最近一次VDF计算耗时
+}
+
+impl VdfSeedSource {
+    pub fn new(inner: Box<dyn SeedSource>, difficulty: u64) -> Self {
+        VdfSeedSource {
+            inner,
+            difficulty,
+            last_elapsed: Duration::ZERO,
+        }
+    }
+}
+
+impl SeedSource for VdfSeedSource {
+    fn name(&self) -> &'static str {
+        "vdf"
+    }
+
+    fn next_seed(
+        &mut self,
+        validators: &[Validator],
+        randao_seeds: Vec<RandaoSeed>,
+        last_block: &Block,
+    ) -> [u8; 32] {
+        let seed = self.inner.next_seed(validators, randao_seeds, last_block);
+        let start = Instant::now();
+        let result = simple_vdf(seed, self.difficulty);
+        self.last_elapsed = start.elapsed();
+        debug!(
+            "VDF({}) over {} seed took {} ms",
+            self.difficulty,
+            self.inner.name(),
+            self.last_elapsed.as_millis()
+        );
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_seed_source() {
+        let block = Block::gen_genesis_block();
+        let mut source = FixedSeedSource::new(vec![[1; 32], [2; 32]]);
+        assert_eq!(source.next_seed(&[], vec![], &block), [1; 32]);
+        assert_eq!(source.next_seed(&[], vec![], &block), [2; 32]);
+        assert_eq!(
+            source.next_seed(&[], vec![], &block),
+            Hasher::hash([2; 32].to_vec())
+        );
+
+        let mut a = FixedSeedSource::from_u64(7);
+        let mut b = FixedSeedSource::from_u64(7);
+        for _ in 0..3 {
+            assert_eq!(
+                a.next_seed(&[], vec![], &block),
+                b.next_seed(&[], vec![], &block)
+            );
+        }
+    }
+
+    #[test]
+    fn test_vdf_seed_source() {
+        let block = Block::gen_genesis_block();
+        assert_eq!(simple_vdf([3; 32], 0), [3; 32]);
+        assert_eq!(
+            simple_vdf([3; 32], 2),
+            Hasher::hash(Hasher::hash([3; 32].to_vec()).to_vec())
+        );
+
+        let inner = Box::new(FixedSeedSource::new(vec![[3; 32]]));
+        let mut source = VdfSeedSource::new(inner, 100);
+        assert_eq!(
+            source.next_seed(&[], vec![], &block),
+            simple_vdf([3; 32], 100)
+        );
+    }
+}
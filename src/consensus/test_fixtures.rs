@@ -0,0 +1,26 @@
+use crate::consensus::Validator;
+
+/// 检查出块者选择是否确定时使用的种子
+pub const FIXED_SEEDS: [[u8; 32]; 6] = [
+    [0u8; 32],
+    [3u8; 32],
+    [9u8; 32],
+    [100u8; 32],
+    [200u8; 32],
+    [255u8; 32],
+];
+
+/// stake 依次为 1~4 的验证者 a~d
+pub fn fixed_validators() -> Vec<Validator> {
+    vec![
+        Validator::new("a".to_string(), 1.0, 1.0),
+        Validator::new("b".to_string(), 2.0, 1.0),
+        Validator::new("c".to_string(), 3.0, 1.0),
+        Validator::new("d".to_string(), 4.0, 1.0),
+    ]
+}
+
+/// 对每个固定种子调用 select，返回依次选出的地址
+pub fn select_with_fixed_seeds(mut select: impl FnMut([u8; 32]) -> String) -> Vec<String> {
+    FIXED_SEEDS.iter().map(|seed| select(*seed)).collect()
+}
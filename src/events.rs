@@ -0,0 +1,140 @@
+use crate::network::message::{Message, MessageType};
+use crate::tools::get_timestamp;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+// 事件日志：记录节点和 WorldState 处理的每条消息，每行一条 JSON 记录
+// 未开启时 record 只读取一次 ENABLED，几乎没有开销
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref EVENT_WRITER: Mutex<Option<LineWriter<File>>> = Mutex::new(None);
+}
+
+/// 消息的接收方
+pub const WORLD_TARGET: &str = "world";
+
+pub fn node_target(index: u32) -> String {
+    format!("node:{}", index)
+}
+
+/// 一条消息事件，data 为消息内容的十六进制编码，重放时还原为原消息
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Event {
+    pub timestamp: u64,
+    pub to: String,
+    pub msg_type: MessageType,
+    pub from: String,
+    pub size: usize,
+    pub data: String,
+}
+
+impl Event {
+    pub fn new(to: String, msg: &Message) -> Self {
+        Event {
+            timestamp: get_timestamp(),
+            to,
+            msg_type: msg.msg_type.clone(),
+            from: msg.from.clone(),
+            size: msg.data.len(),
+            data: hex::encode(&msg.data),
+        }
+    }
+
+    /// 接收方是节点时返回节点编号
+    pub fn node_index(&self) -> Option<u32> {
+        self.to.strip_prefix("node:")?.parse().ok()
+    }
+
+    pub fn to_message(&self) -> Result<Message, hex::FromHexError> {
+        Ok(Message {
+            msg_type: self.msg_type.clone(),
+            data: hex::decode(&self.data)?,
+            from: self.from.clone(),
+        })
+    }
+}
+
+/// 开始记录事件到 path，已有文件会被覆盖
+pub fn start_recording(path: &str) -> std::io::Result<()> {
+    let file = File::create(path)?;
+    *EVENT_WRITER.lock().unwrap() = Some(LineWriter::new(file));
+    ENABLED.store(true, Ordering::Release);
+    Ok(())
+}
+
+pub fn stop_recording() {
+    ENABLED.store(false, Ordering::Release);
+    if let Some(mut writer) = EVENT_WRITER.lock().unwrap().take() {
+        let _ = writer.flush();
+    }
+}
+
+pub fn is_recording() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// 记录 to 处理的一条消息
+pub fn record(to: impl FnOnce() -> String, msg: &Message) {
+    if !is_recording() {
+        return;
+    }
+    let line = match serde_json::to_string(&Event::new(to(), msg)) {
+        Ok(line) => line,
+        Err(_) => return,
+    };
+    if let Some(writer) = EVENT_WRITER.lock().unwrap().as_mut() {
+        let _ = writeln!(writer, "{}", line);
+    }
+}
+
+/// 读取事件日志，跳过无法解析的行
+pub fn load(path: &str) -> std::io::Result<Vec<Event>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut events = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        match serde_json::from_str::<Event>(&line) {
+            Ok(event) => events.push(event),
+            Err(e) => log::warn!("Skip invalid event: {}", e),
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_load() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+
+        // 未开启时不记录；其他测试的节点也可能同时记录，只检查本测试的节点
+        record(|| node_target(9001), &Message::new_generate_block_msg());
+        start_recording(path).unwrap();
+        let msg = Message::new_update_partition_msg(vec!["a".to_string()]);
+        record(|| node_target(9002), &msg);
+        record(|| node_target(9003), &Message::new_generate_block_msg());
+        stop_recording();
+        record(|| node_target(9001), &Message::new_generate_block_msg());
+
+        let events: Vec<Event> = load(path)
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.node_index().is_some_and(|i| i > 9000))
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].node_index(), Some(9002));
+        assert_eq!(events[0].size, msg.data.len());
+        let replayed = events[0].to_message().unwrap();
+        assert_eq!(replayed.data, msg.data);
+        assert!(matches!(replayed.msg_type, MessageType::UpdatePartition));
+        assert!(matches!(events[1].msg_type, MessageType::GenerateBlock));
+    }
+}
@@ -1,5 +1,7 @@
 pub mod blockchain;
 pub mod consensus;
+pub mod events;
+pub mod logger;
 pub mod metrics;
 pub mod network;
 pub mod tools;
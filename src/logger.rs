@@ -0,0 +1,316 @@
+use clap::ValueEnum;
+use dashmap::DashMap;
+use lazy_static::lazy_static;
+use log::kv::{Error as KvError, Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::{json, Map};
+use simplelog::{Config, SharedLogger};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::sync::Mutex;
+
+// 每个节点的日志都以 pog::node::{index} 为 target 输出
+// 便于按节点过滤日志（例如 RUST_LOG=pog::node::3=debug）
+pub const NODE_TARGET_PREFIX: &str = "pog::node::";
+
+// 单个节点的日志级别覆盖，运行时可通过 set_node_log_level 修改
+lazy_static! {
+    static ref NODE_LOG_LEVELS: DashMap<u32, LevelFilter> = DashMap::new();
+}
+
+pub fn node_target(index: u32) -> String {
+    format!("{}{}", NODE_TARGET_PREFIX, index)
+}
+
+/// 从 target 中解析节点编号，非节点日志返回 None
+pub fn parse_node_target(target: &str) -> Option<u32> {
+    target.strip_prefix(NODE_TARGET_PREFIX)?.parse().ok()
+}
+
+/// 运行时调整某个节点的日志级别
+pub fn set_node_log_level(index: u32, level: LevelFilter) {
+    NODE_LOG_LEVELS.insert(index, level);
+    if level > log::max_level() {
+        log::set_max_level(level);
+    }
+}
+
+pub fn clear_node_log_level(index: u32) {
+    NODE_LOG_LEVELS.remove(&index);
+}
+
+pub fn node_log_level(index: u32) -> Option<LevelFilter> {
+    NODE_LOG_LEVELS.get(&index).map(|entry| *entry.value())
+}
+
+/// 解析 RUST_LOG 风格的过滤规则，例如 "info,pog::node::3=debug,pog::consensus=warn"
+/// 不带 target 的级别作为默认级别，无法解析的项被忽略
+pub fn parse_log_filters(spec: &str) -> (Option<LevelFilter>, Vec<(String, LevelFilter)>) {
+    let mut default_level = None;
+    let mut filters = Vec::new();
+    for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        match directive.split_once('=') {
+            Some((target, level)) => {
+                if let Ok(level) = level.trim().parse() {
+                    filters.push((target.trim().to_string(), level));
+                }
+            }
+            None => match directive.parse() {
+                Ok(level) => default_level = Some(level),
+                // 只写 target 表示输出该 target 的所有日志
+                Err(_) => filters.push((directive.to_string(), LevelFilter::Trace)),
+            },
+        }
+    }
+    (default_level, filters)
+}
+
+/// target 是否属于 prefix 模块（相同或以 "prefix::" 开头）
+fn target_matches(target: &str, prefix: &str) -> bool {
+    target
+        .strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// 在全局日志级别之上，按 target 和节点覆盖日志级别的 Logger
+/// inner 负责实际输出，需要配置为不低于任何覆盖级别（一般为 Trace）
+pub struct NodeLevelLogger {
+    inner: Box<dyn Log>,
+    default_level: LevelFilter,
+    target_levels: Vec<(String, LevelFilter)>, // target 前缀 -> 级别，最长匹配优先
+}
+
+impl NodeLevelLogger {
+    pub fn new(inner: Box<dyn Log>, default_level: LevelFilter) -> Self {
+        NodeLevelLogger {
+            inner,
+            default_level,
+            target_levels: Vec::new(),
+        }
+    }
+
+    /// 按 parse_log_filters 的规则设置默认级别和 target 级别
+    pub fn with_filters(mut self, spec: &str) -> Self {
+        let (default_level, target_levels) = parse_log_filters(spec);
+        if let Some(level) = default_level {
+            self.default_level = level;
+        }
+        self.target_levels = target_levels;
+        self
+    }
+
+    /// 节点覆盖级别优先，其次是匹配最长的 target 级别，最后是默认级别
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        if let Some(level) = parse_node_target(target).and_then(node_log_level) {
+            return level;
+        }
+        self.target_levels
+            .iter()
+            .filter(|(prefix, _)| target_matches(target, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default_level)
+    }
+
+    /// 安装为全局 Logger
+    pub fn init(self) -> Result<(), log::SetLoggerError> {
+        let max_level = NODE_LOG_LEVELS
+            .iter()
+            .map(|entry| *entry.value())
+            .chain(self.target_levels.iter().map(|(_, level)| *level))
+            .fold(self.default_level, Ord::max);
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl Log for NodeLevelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl Display for LogFormat {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// 以 NDJSON 格式输出日志，每行一条记录，便于程序解析
+/// 记录格式：{"timestamp","level","target","node","message","fields"}
+/// fields 为日志中的键值对，例如 info!(target: .., slot = 3; "...")
+pub struct JsonLogger {
+    level: LevelFilter,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLogger {
+    pub fn new<W: Write + Send + 'static>(level: LevelFilter, writer: W) -> Box<JsonLogger> {
+        Box::new(JsonLogger {
+            level,
+            writer: Mutex::new(Box::new(writer)),
+        })
+    }
+
+    pub fn format_record(record: &Record) -> String {
+        let mut fields = FieldsVisitor(Map::new());
+        let _ = record.key_values().visit(&mut fields);
+        json!({
+            "timestamp": chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string(),
+            "level": record.level().to_string(),
+            "target": record.target(),
+            "node": parse_node_target(record.target()),
+            "message": record.args().to_string(),
+            "fields": fields.0,
+        })
+        .to_string()
+    }
+}
+
+struct FieldsVisitor(Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for FieldsVisitor {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), KvError> {
+        let value = if let Some(v) = value.to_bool() {
+            json!(v)
+        } else if let Some(v) = value.to_u64() {
+            json!(v)
+        } else if let Some(v) = value.to_i64() {
+            json!(v)
+        } else if let Some(v) = value.to_f64() {
+            json!(v)
+        } else {
+            json!(value.to_string())
+        };
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = JsonLogger::format_record(record);
+            let mut writer = self.writer.lock().unwrap();
+            let _ = writeln!(writer, "{}", line);
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.writer.lock().unwrap().flush();
+    }
+}
+
+impl SharedLogger for JsonLogger {
+    fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    fn config(&self) -> Option<&Config> {
+        None
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NullLogger;
+
+    impl Log for NullLogger {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+        fn log(&self, _: &Record) {}
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_node_level_override() {
+        assert_eq!(parse_node_target(&node_target(7)), Some(7));
+        assert_eq!(parse_node_target("pog::network::world_state"), None);
+
+        let logger = NodeLevelLogger::new(Box::new(NullLogger), LevelFilter::Info);
+        set_node_log_level(1007, LevelFilter::Debug);
+        assert_eq!(logger.level_for(&node_target(1007)), LevelFilter::Debug);
+        assert_eq!(logger.level_for(&node_target(1008)), LevelFilter::Info);
+        assert_eq!(logger.level_for("pog::network"), LevelFilter::Info);
+
+        clear_node_log_level(1007);
+        assert_eq!(logger.level_for(&node_target(1007)), LevelFilter::Info);
+    }
+
+    #[test]
+    fn test_target_filters() {
+        let (default_level, filters) =
+            parse_log_filters("warn, pog::network=info,pog::network::world_state=debug,bad=x");
+        assert_eq!(default_level, Some(LevelFilter::Warn));
+        assert_eq!(filters.len(), 2);
+
+        let logger = NodeLevelLogger::new(Box::new(NullLogger), LevelFilter::Info).with_filters(
+            "warn,pog::network=info,pog::network::world_state=debug,pog::node::2005=trace",
+        );
+        assert_eq!(logger.level_for("pog::consensus::pog"), LevelFilter::Warn);
+        assert_eq!(logger.level_for("pog::network::node"), LevelFilter::Info);
+        assert_eq!(
+            logger.level_for("pog::network::world_state"),
+            LevelFilter::Debug
+        );
+        // 前缀必须在模块边界上匹配
+        assert_eq!(logger.level_for("pog::networking"), LevelFilter::Warn);
+        assert_eq!(logger.level_for(&node_target(2005)), LevelFilter::Trace);
+        assert_eq!(logger.level_for(&node_target(20050)), LevelFilter::Warn);
+
+        // --debug-node 的覆盖优先于过滤规则
+        set_node_log_level(2005, LevelFilter::Debug);
+        assert_eq!(logger.level_for(&node_target(2005)), LevelFilter::Debug);
+        clear_node_log_level(2005);
+    }
+
+    #[test]
+    fn test_json_log_record() {
+        let target = node_target(3);
+        let kvs = [("slot", 5u64)];
+        let record = Record::builder()
+            .level(log::Level::Info)
+            .target(&target)
+            .args(format_args!("block generated"))
+            .key_values(&kvs)
+            .build();
+        let line = JsonLogger::format_record(&record);
+        let v: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(v["level"], "INFO");
+        assert_eq!(v["node"], 3);
+        assert_eq!(v["message"], "block generated");
+        assert_eq!(v["fields"]["slot"], 5);
+    }
+}
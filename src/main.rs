@@ -1,16 +1,55 @@
-use clap::Parser;
-use log::LevelFilter;
-use pog::consensus::ConsensusType;
+use clap::{Parser, Subcommand};
+use log::{info, LevelFilter};
+use pog::blockchain::block::Block;
+use pog::consensus::seed::SeedSourceType;
+use pog::consensus::{ConsensusSwitch, ConsensusType};
+use pog::logger::{self, JsonLogger, LogFormat, NodeLevelLogger};
+use pog::metrics::{self, HashPowerDistribution, MetricsSinkKind};
 use pog::network;
 use pog::network::graph::TopologyType;
+use pog::network::{PrintMode, PropagationStrategy, ProposerMode, SimulationConfig, TxSourceNodes};
+use pog::wallet::Wallet;
 use simplelog::{
-    ColorChoice, CombinedLogger, ConfigBuilder, TermLogger, TerminalMode, WriteLogger,
+    ColorChoice, CombinedLogger, Config, ConfigBuilder, SharedLogger, TermLogger, TerminalMode,
+    WriteLogger,
 };
 use std::fs::File;
 
 #[derive(Parser, Debug)]
 #[clap(version = "1.0", author = "wujian", about = "POG协议模拟")]
-struct Args {
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 运行模拟 (Run the simulation)
+    Run(Box<RunArgs>),
+    /// 生成并打印一个钱包密钥对 (Generate and print a wallet keypair)
+    Genkey,
+    /// 汇总 run 生成的指标 CSV (Summarize metrics CSVs)
+    Analyze {
+        /// 时隙指标文件 metrics_slots_<consensus>.csv
+        #[clap(long, required_unless_present_any = ["epochs", "diff"])]
+        slots: Option<String>,
+
+        /// epoch 指标文件 metrics_epochs_<consensus>.csv
+        #[clap(long)]
+        epochs: Option<String>,
+
+        /// 对比两次运行的 epoch 指标文件 metrics_epochs_<consensus>.csv，按 epoch 对齐，epoch 数不同时截断到较短的一方
+        #[clap(long, num_args = 2, value_names = ["RUN_A", "RUN_B"])]
+        diff: Option<Vec<String>>,
+
+        /// 以 JSON 输出 --diff 的结果
+        #[clap(long, requires = "diff")]
+        json: bool,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct RunArgs {
     /// 节点个数(Node number)
     #[clap(short, long, default_value = "20")]
     node_num: u32,
@@ -24,6 +63,11 @@ struct Args {
     #[clap(short, long, default_value = "0")]
     fake_node_num: u32,
 
+    /// 集中到恶意节点上的总权益和算力比例 (Fraction of total stake and hash power held by sybil nodes)
+    /// 在女巫身份平分权益之前集中，0 表示不调整；only sybil_node_num > 0 usefully
+    #[clap(long, default_value = "0.0")]
+    attacker_stake_fraction: f64,
+
     /// 不稳定节点个数(Unstable node num)
     #[clap(short, long, default_value = "0")]
     unstable_node_num: u32,
@@ -32,10 +76,61 @@ struct Args {
     #[clap(long, default_value = "0.5")]
     offline_probability: f64,
 
+    /// 不稳定节点每次下线持续的epoch数 (Unstable node offline duration in epochs)
+    #[clap(long, default_value = "1")]
+    offline_duration_epochs: u64,
+
+    /// 离线的验证者每个 epoch 扣减的 stake 比例 (Stake decay for validators that were offline during the epoch)
+    /// 按 epoch 统计，0 表示不扣减
+    #[clap(long, default_value = "0.0")]
+    offline_stake_decay: f64,
+
+    /// 单个验证者 stake 占总量的上限，超出部分在每个 epoch 结束时按比例分给其他验证者 (Max stake share per validator)
+    /// 与共识类型无关，1 表示不限制
+    #[clap(long, default_value = "1.0")]
+    max_stake_share: f64,
+
+    /// 验证者集合的大小上限，超出时只保留 stake 最高的 K 个，其余等待 (Max active validators)
+    /// 每个 epoch 结束时重新排名，stake 增加的节点可以进入；0 表示不限制
+    #[clap(long, default_value = "0")]
+    max_validators: usize,
+
+    /// 引导节点个数，从诚实节点中选取，永不下线 (Bootstrap node num, never offline)
+    /// 块同步时节点优先向引导节点请求
+    #[clap(long, default_value = "0")]
+    bootstrap_node_num: u32,
+
+    /// 中继节点个数，只转发交易和区块，不成为验证者 (Relay node num, never a validator)
+    /// 出现在交易路径中时按 POG 网络费用获得奖励
+    #[clap(long, default_value = "0")]
+    relay_node_num: u32,
+
+    /// 诚实节点和不稳定节点中注册为验证者的比例，取值 (0, 1]，其余节点只转发 (Fraction of nodes that register as validators)
+    /// 未注册的节点不会被选为出块者，出现在交易路径中时按 POG 网络费用获得奖励
+    #[clap(long, default_value = "1.0", value_parser = parse_unit_fraction)]
+    validator_fraction: f64,
+
+    /// 块同步时最多回滚的区块数 (Max reorg depth during block sync)
+    #[clap(long, default_value = "3")]
+    max_reorg_depth: u64,
+
     /// 每秒交易个数（泊松分布）(Number of transactions per second)
     #[clap(short, long, default_value = "10")]
     trans_num: u32,
 
+    /// 每条消息最多合并的交易数，1表示每笔交易单独发送 (Max transactions per message)
+    #[clap(long, default_value = "1")]
+    tx_batch_size: usize,
+
+    /// 交易生成器的间隔（毫秒），每个间隔的泊松均值按每秒交易数缩放 (Transaction generator tick in ms)
+    #[clap(long, default_value = "1000")]
+    tx_interval_ms: u64,
+
+    /// 每个间隔最多发送的交易数，超出的推迟到之后的间隔 (Max transactions sent per tick)
+    /// 0 表示不限制
+    #[clap(long, default_value = "0")]
+    tx_burst_cap: usize,
+
     /// 时隙持续时间（秒）(Slot duration in seconds)
     #[clap(long, default_value = "3")]
     slot_duration: u64,
@@ -56,20 +151,84 @@ struct Args {
     #[arg(short, long, default_value_t = ConsensusType::POG)]
     consensus: ConsensusType,
 
+    /// scripted 共识的出块者序列文件，每行一个节点编号或地址 (Proposer schedule file for scripted consensus)
+    #[clap(long)]
+    schedule: Option<String>,
+
+    /// 出块者序列用完后使用的共识 (Consensus used after the schedule is exhausted)
+    #[arg(long, default_value_t = ConsensusType::POS)]
+    schedule_fallback: ConsensusType,
+
+    /// 在指定 epoch 开始时切换共识，格式为 <type>@<epoch>，可重复指定 (Switch consensus at an epoch)
+    /// 例如 --consensus pos --consensus-switch pog@10
+    #[clap(long = "consensus-switch")]
+    consensus_switches: Vec<ConsensusSwitch>,
+
     ///拓扑结构 (Topology)
     #[arg(long, default_value_t = TopologyType::BA)]
     topology: TopologyType,
 
+    /// ER 拓扑的连边概率，取值 (0, 1] (Edge probability of the ER topology)
+    #[clap(long, default_value = "0.2", value_parser = parse_unit_fraction)]
+    er_probability: f64,
+
+    /// 地理区域数，节点按编号轮流分配到各区域 (Number of geographic regions)
+    /// 大于1时邻居之间的广播带有区域延迟，并将区域分配写入 regions.json；1表示不模拟延迟
+    #[clap(long, default_value = "1")]
+    regions: u32,
+
+    /// 同一区域内的延迟（毫秒）(Intra-region latency in ms)
+    #[clap(long, default_value = "10")]
+    intra_region_latency_ms: u64,
+
+    /// 跨区域的延迟（毫秒）(Inter-region latency in ms)
+    #[clap(long, default_value = "100")]
+    inter_region_latency_ms: u64,
+
+    /// 区域延迟矩阵文件，JSON 格式的 regions x regions 毫秒矩阵 (Region latency matrix file)
+    /// 设置后代替 intra/inter 默认模型
+    #[clap(long)]
+    region_latency_file: Option<String>,
+
+    /// 网络分区实验：在该 epoch 将网络分成两个互不连通的分区 (Epoch to partition the network)
+    /// 不设置表示不分区
+    #[clap(long)]
+    partition_at_epoch: Option<u64>,
+
+    /// 网络分区实验：在该 epoch 恢复连通，并记录分叉深度和重新一致的时间 (Epoch to heal the partition)
+    /// 不设置表示一直不恢复
+    #[clap(long)]
+    partition_heal_epoch: Option<u64>,
+
+    /// 第一个分区的节点比例，按节点编号划分 (Fraction of nodes in the first partition)
+    #[clap(long, default_value = "0.5")]
+    partition_fraction: f64,
+
     /// 初始Gini指数 (Initial Gini coefficient for stake distribution)
     /// 0 = 完全平等，1 = 完全不平等
     #[clap(short, long, default_value = "0.0")]
     gini: f64,
 
-    /// 交易手续费 (Transaction fee)
-    /// 每笔交易的手续费，设置为0表示禁用手续费
+    /// 节点算力分布 (Hash power distribution, used by POW and MINOTAUR)
+    /// stake: 与权益相同；uniform: 均等；skewed: 按 --hash-power-gini 生成，与权益独立
+    #[arg(long, default_value_t = HashPowerDistribution::Stake)]
+    hash_power_distribution: HashPowerDistribution,
+
+    /// skewed 算力分布的目标Gini指数 (Target Gini coefficient for skewed hash power)
+    #[clap(long, default_value = "0.5")]
+    hash_power_gini: f64,
+
+    /// 交易基础手续费 (Transaction base fee)
+    /// 每笔交易的基础手续费，按共识的费用机制分配，设置为0表示禁用手续费
     #[clap(long, default_value = "0.0")]
     transaction_fee: f64,
 
+    /// 交易小费 (Transaction priority fee / tip)
+    /// 在基础手续费之外支付，全部归出块者，出块时按小费从高到低打包
+    /// 每笔交易的小费在 0 到该值之间随机，使不同交易的小费不同
+    #[clap(long, default_value = "0.0")]
+    transaction_tip: f64,
+
     /// 图拓扑生成种子 (Graph topology generation seed)
     /// 用于固定网络拓扑结构，便于可重复实验
     #[clap(long, default_value = "888")]
@@ -79,6 +238,24 @@ struct Args {
     #[clap(long, default_value = "1.0")]
     base_reward: f64,
 
+    /// 固定奖励中分给路径参与者（除矿工外）的比例 (Fraction of the base reward paid to path participants)
+    /// 适用于所有共识，按路径平分后由路径上的节点平分，0 表示全部归矿工
+    #[clap(long, default_value = "0.0")]
+    relayer_reward_fraction: f64,
+
+    /// 出块奖励（补贴和手续费）中由所有验证者平分的比例 (Fraction of block rewards shared by all validators)
+    /// 适用于 POS、POW 和 MINOTAUR，作为与 POG 对比的去中心化基线；0 表示全部归矿工
+    #[clap(long, default_value = "0.0")]
+    universal_reward_fraction: f64,
+
+    /// POG 预热时隙数，期间 omega 固定为0（纯PoS）(POG warm-up slots with omega forced to 0)
+    #[clap(long, default_value = "0")]
+    pog_warmup_slots: u64,
+
+    /// POG 每个 epoch 结束时贡献历史乘以的系数，1 表示不衰减 (POG score decay factor per epoch)
+    #[clap(long, default_value = "1.0")]
+    pog_epoch_decay: f64,
+
     /// 每个区块最大交易数量 (Max transactions per block)
     #[clap(long, default_value = "200")]
     max_tx_per_block: usize,
@@ -88,57 +265,331 @@ struct Args {
     /// 设置为0表示使用随机地址(0 means random).
     #[clap(long, default_value = "8")]
     wallet_seed: u64,
+
+    /// 出块者选举的随机种子来源 (Seed source for proposer selection)
+    /// randao: 验证者RANDAO种子; block-hash: 上一区块哈希; fixed: 由graph_seed派生的确定性序列
+    #[arg(long, default_value_t = SeedSourceType::Randao)]
+    seed_source: SeedSourceType,
+
+    /// VDF难度，对选举种子顺序哈希的次数，0表示不启用 (VDF difficulty, 0 = disabled)
+    /// 用于防止最后提交RANDAO种子的验证者操纵结果
+    #[clap(long, default_value = "0")]
+    vdf_difficulty: u64,
+
+    /// 每个时隙开始后接受 RANDAO 种子的时间占时隙的比例，之后到达的种子被忽略 (Seed reveal window as a fraction of the slot)
+    /// 例如 0.5 表示只接受前半个时隙内到达的种子；1 表示整个时隙
+    #[clap(long, default_value = "1.0")]
+    seed_reveal_window: f64,
+
+    /// 每个时隙由上一个种子抽取的 RANDAO 委员会大小，只有委员会成员提交种子 (RANDAO committee size)
+    /// 0 表示所有验证者都提交
+    #[clap(long, default_value = "0")]
+    randao_committee_size: usize,
+
+    /// 交易缓存过期时隙数，超过后未被打包的交易从内存池移除 (Transaction expiry in slots)
+    /// 设置为0表示永不过期
+    #[clap(long, default_value = "0")]
+    tx_expiry_slots: u64,
+
+    /// 每个节点交易缓存的最大交易数，满时淘汰小费和手续费最低的交易，费用相同时淘汰最早收到的 (Per-node transaction cache size)
+    /// 不设置表示与 max_tx_per_block 相同
+    #[clap(long)]
+    tx_cache_size: Option<usize>,
+
+    /// 只由指定节点发起交易，其余节点只转发 (Nodes that originate transactions)
+    /// 节点编号列表如 "0,3,5"，或节点比例如 "0.2"；不设置表示所有节点
+    #[clap(long)]
+    tx_source_nodes: Option<TxSourceNodes>,
+
+    /// 收到区块时抽样验证路径签名的百分比 (Percent of block paths to verify, 0-100)
+    /// 完整验证很消耗CPU，0表示不验证
+    #[clap(long, default_value = "0.0")]
+    block_verify_sample: f64,
+
+    /// 出块时抽样验证自己打包的路径签名的百分比 (Percent of own paths verified when proposing, 0-100)
+    /// 出块者信任自己的内存池时可以降低以节省出块开销，无效路径只能由收到区块的节点发现
+    #[clap(long, default_value = "100.0")]
+    proposer_verify_sample: f64,
+
+    /// 每个节点每个时隙最多自己发起的交易数，超出的被丢弃，0 表示不限速 (Per-node origination rate limit per slot)
+    #[clap(long, default_value = "0")]
+    max_tx_per_slot: usize,
+
+    /// 在 metrics_slots 中记录每个时隙选择出块者使用的种子 (Record the per-slot proposer seed in slot metrics)
+    #[clap(long)]
+    record_seeds: bool,
+
+    /// 把最终确定（之后至少有 max-reorg-depth 个区块）的交易传播路径（含每跳时间和上链时的 NTD）写入 paths_export.jsonl (Export finalized transaction propagation paths to paths_export.jsonl)
+    #[clap(long)]
+    export_paths: bool,
+
+    /// 把 POG 每个时隙各节点归一化后的网络贡献写入 contribution.jsonl (Export per-slot POG contribution to contribution.jsonl)
+    #[clap(long)]
+    export_contribution: bool,
+
+    /// 指标输出格式，逗号分隔可同时输出多种 (Metrics sinks: csv, jsonl, null)
+    #[arg(long, value_delimiter = ',', default_value = "csv")]
+    metrics_sink: Vec<MetricsSinkKind>,
+
+    /// 出块者的确定方式 (How the proposer is determined)
+    /// central: WorldState 选出后通知出块者; local: 节点自行计算（仅 POS/POG）
+    #[arg(long, default_value_t = ProposerMode::Central)]
+    proposer_mode: ProposerMode,
+
+    /// 交易的传播方式，区块总是发给所有邻居 (Transaction propagation strategy)
+    /// flood: 转发给所有邻居; push-pull: 先公告哈希，邻居再请求; random-walk: 只转发给一个随机邻居
+    #[arg(long, default_value_t = PropagationStrategy::Flood)]
+    propagation_strategy: PropagationStrategy,
+
+    /// 区块先向邻居公告哈希，邻居没有该区块时再请求完整区块（inv/getdata），减少重复传输 (Announce block hashes before sending)
+    #[clap(long)]
+    announce_blocks: bool,
+
+    /// 记录节点和 WorldState 处理的每条消息到 JSONL 文件 (Record every processed message)
+    /// 用于复现共识分歧，可用 --replay-events 重放
+    #[clap(long)]
+    record_events: Option<String>,
+
+    /// 重放事件日志：各节点按原顺序重新处理记录的消息，不运行 WorldState (Replay an event log)
+    /// 需要与记录时相同的节点数量、wallet_seed 和 --genesis-file
+    #[clap(long)]
+    replay_events: Option<String>,
+
+    /// 节点状态目录：每个 epoch 把各节点的钱包、余额、区块链和内存池保存到 node_{index}.json，验证者 stake 表保存到 validators.json (Node state directory)
+    /// 目录中已有状态文件时从中恢复节点，需要与保存时相同的 --genesis-file
+    #[clap(long)]
+    node_state_dir: Option<String>,
+
+    /// 定期输出的间隔秒数，0 表示不输出 (Printer interval in seconds)
+    #[clap(long, default_value = "10")]
+    print_interval: u64,
+
+    /// 定期输出的内容 (What the printer outputs)
+    /// dump: 随机节点把整条链写入文件; status: 一行状态（高度、最新哈希、验证者数、共识状态/NTD）
+    #[arg(long, default_value_t = PrintMode::Dump)]
+    print_mode: PrintMode,
+
+    /// 创世块文件 (Genesis block file)
+    /// 文件存在时从中加载创世块；不存在时由 wallet_seed 生成确定性的创世块并写入该文件
+    /// 不设置表示每次运行使用随机创世块
+    #[clap(long)]
+    genesis_file: Option<String>,
+
+    /// 确定性创世块的初始发行量 (Initial supply of the generated genesis block)
+    #[clap(long, default_value = "50")]
+    genesis_supply: i64,
+
+    /// 运行时长（秒），到时后停止模拟；0表示一直运行 (Run duration in seconds, 0 = forever)
+    #[clap(long, default_value = "0")]
+    run_seconds: u64,
+
+    /// 运行的时隙数，到达后停止模拟；0表示不按时隙数停止，与 --run-seconds 同时设置时先到者为准 (Number of slots to run, 0 = no limit)
+    #[clap(long, default_value = "0")]
+    run_slots: u64,
+
+    /// 单独输出某些节点的Debug日志 (Enable debug logs for the given node index)
+    /// 可重复指定，例如 --debug-node 3 --debug-node 7；其他 target 的级别可用 RUST_LOG 设置
+    #[clap(long)]
+    debug_node: Vec<u32>,
+
+    /// 日志文件格式 (Log file format for output.log)
+    /// json 为每行一条 JSON 记录，便于程序解析
+    #[arg(long, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     //args
-    let args = Args::parse();
+    match Cli::parse().command {
+        Command::Run(args) => run(*args).await,
+        Command::Genkey => {
+            TermLogger::init(
+                LevelFilter::Info,
+                Config::default(),
+                TerminalMode::Mixed,
+                ColorChoice::Auto,
+            )?;
+            Wallet::new().print();
+            Ok(())
+        }
+        Command::Analyze {
+            slots,
+            epochs,
+            diff,
+            json,
+        } => {
+            if let Some(file) = slots {
+                let summary = metrics::summarize_slots_csv(&read_metrics_file(&file)?)?;
+                println!("== {} ==\n{}", file, summary);
+            }
+            if let Some(file) = epochs {
+                let summary = metrics::summarize_epochs_csv(&read_metrics_file(&file)?)?;
+                println!("== {} ==\n{}", file, summary);
+            }
+            if let Some(files) = diff {
+                let diff = metrics::MetricsDiff::from_epochs_csv(
+                    &read_metrics_file(&files[0])?,
+                    &read_metrics_file(&files[1])?,
+                )?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&diff)?);
+                } else {
+                    println!("== {} vs {} ==\n{}", files[0], files[1], diff);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn read_metrics_file(file: &str) -> Result<String, String> {
+    std::fs::read_to_string(file).map_err(|e| format!("failed to read {}: {}", file, e))
+}
 
+/// 解析取值范围为 (0, 1] 的比例参数
+fn parse_unit_fraction(value: &str) -> Result<f64, String> {
+    let fraction: f64 = value
+        .parse()
+        .map_err(|_| format!("{} is not a number", value))?;
+    if fraction > 0.0 && fraction <= 1.0 {
+        Ok(fraction)
+    } else {
+        Err(format!("{} is not in (0, 1]", fraction))
+    }
+}
+
+async fn run(args: RunArgs) -> Result<(), Box<dyn std::error::Error>> {
     //log setting
-    init_logger()?;
-
-    network::start_network(
-        args.node_num,
-        args.sybil_node_num,
-        args.fake_node_num,
-        args.unstable_node_num,
-        args.offline_probability,
-        args.trans_num,
-        args.slot_duration,
-        args.slot_per_epoch,
-        args.pow_difficulty,
-        args.pow_max_threads,
-        args.consensus,
-        args.topology,
-        args.gini,
-        args.transaction_fee,
-        args.graph_seed,
-        args.base_reward,
-        args.max_tx_per_block,
-        args.wallet_seed,
-    )
+    init_logger(&args.debug_node, args.log_format)?;
+    let genesis_block = load_genesis(&args)?;
+
+    network::run_simulation(SimulationConfig {
+        node_num: args.node_num,
+        sybil_node_num: args.sybil_node_num,
+        fake_node_num: args.fake_node_num,
+        attacker_stake_fraction: args.attacker_stake_fraction,
+        unstable_node_num: args.unstable_node_num,
+        offline_probability: args.offline_probability,
+        offline_duration_epochs: args.offline_duration_epochs,
+        offline_stake_decay: args.offline_stake_decay,
+        max_stake_share: args.max_stake_share,
+        max_validators: args.max_validators,
+        bootstrap_node_num: args.bootstrap_node_num,
+        relay_node_num: args.relay_node_num,
+        validator_fraction: args.validator_fraction,
+        max_reorg_depth: args.max_reorg_depth,
+        trans_num_per_second: args.trans_num,
+        tx_batch_size: args.tx_batch_size,
+        tx_interval_ms: args.tx_interval_ms,
+        tx_burst_cap: args.tx_burst_cap,
+        slot_duration: args.slot_duration,
+        slot_per_epoch: args.slot_per_epoch,
+        pow_difficulty: args.pow_difficulty,
+        pow_max_threads: args.pow_max_threads,
+        consensus: args.consensus,
+        schedule: args.schedule,
+        schedule_fallback: args.schedule_fallback,
+        consensus_switches: args.consensus_switches,
+        topology: args.topology,
+        er_probability: args.er_probability,
+        regions: args.regions,
+        intra_region_latency_ms: args.intra_region_latency_ms,
+        inter_region_latency_ms: args.inter_region_latency_ms,
+        region_latency_file: args.region_latency_file,
+        partition_at_epoch: args.partition_at_epoch,
+        partition_heal_epoch: args.partition_heal_epoch,
+        partition_fraction: args.partition_fraction,
+        gini: args.gini,
+        hash_power_distribution: args.hash_power_distribution,
+        hash_power_gini: args.hash_power_gini,
+        transaction_fee: args.transaction_fee,
+        transaction_tip: args.transaction_tip,
+        graph_seed: args.graph_seed,
+        base_reward: args.base_reward,
+        relayer_reward_fraction: args.relayer_reward_fraction,
+        universal_reward_fraction: args.universal_reward_fraction,
+        pog_warmup_slots: args.pog_warmup_slots,
+        pog_epoch_decay: args.pog_epoch_decay,
+        max_tx_per_block: args.max_tx_per_block,
+        wallet_seed: args.wallet_seed,
+        genesis_block,
+        seed_source: args.seed_source,
+        vdf_difficulty: args.vdf_difficulty,
+        seed_reveal_window: args.seed_reveal_window,
+        randao_committee_size: args.randao_committee_size,
+        node_state_dir: args.node_state_dir,
+        tx_expiry_slots: args.tx_expiry_slots,
+        tx_cache_size: args.tx_cache_size,
+        tx_source_nodes: args.tx_source_nodes,
+        block_verify_sample: args.block_verify_sample,
+        proposer_verify_sample: args.proposer_verify_sample,
+        max_tx_per_slot: args.max_tx_per_slot,
+        record_seeds: args.record_seeds,
+        export_paths: args.export_paths,
+        export_contribution: args.export_contribution,
+        metrics_sinks: args.metrics_sink,
+        proposer_mode: args.proposer_mode,
+        propagation_strategy: args.propagation_strategy,
+        announce_blocks: args.announce_blocks,
+        record_events: args.record_events,
+        replay_events: args.replay_events,
+        print_interval: args.print_interval,
+        print_mode: args.print_mode,
+        run_seconds: args.run_seconds,
+        run_slots: args.run_slots,
+    })
     .await;
     Ok(())
 }
 
-pub fn init_logger() -> Result<(), Box<dyn std::error::Error>> {
+fn load_genesis(args: &RunArgs) -> Result<Block, Box<dyn std::error::Error>> {
+    let path = match &args.genesis_file {
+        Some(path) => path,
+        None => return Ok(Block::gen_genesis_block()),
+    };
+    if std::path::Path::new(path).exists() {
+        let block = Block::load_genesis_block(path)
+            .map_err(|e| format!("failed to load genesis file {}: {}", path, e))?;
+        info!("Load genesis block from {}", path);
+        Ok(block)
+    } else {
+        let block = Block::gen_genesis_block_with(args.wallet_seed, args.genesis_supply);
+        std::fs::write(path, block.to_json())?;
+        info!("Write genesis block to {}", path);
+        Ok(block)
+    }
+}
+
+pub fn init_logger(
+    debug_nodes: &[u32],
+    log_format: LogFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
     let config = ConfigBuilder::new()
         .set_time_format_str("%Y-%m-%d %H:%M:%S")
         .build();
-    CombinedLogger::init(vec![
+    for index in debug_nodes {
+        logger::set_node_log_level(*index, LevelFilter::Debug);
+    }
+    // 级别过滤由 NodeLevelLogger 负责，内部 Logger 不再过滤
+    let log_file = File::create("output.log").unwrap();
+    let file_logger: Box<dyn SharedLogger> = match log_format {
+        LogFormat::Text => WriteLogger::new(LevelFilter::Trace, config.clone(), log_file),
+        LogFormat::Json => JsonLogger::new(LevelFilter::Trace, log_file),
+    };
+    let inner = CombinedLogger::new(vec![
         TermLogger::new(
-            LevelFilter::Info,
-            config.clone(),
+            LevelFilter::Trace,
+            config,
             TerminalMode::Mixed,
             ColorChoice::Auto,
         ),
-        WriteLogger::new(
-            LevelFilter::Info,
-            config,
-            File::create("output.log").unwrap(),
-        ),
-    ])
-    .unwrap();
+        file_logger,
+    ]);
+    // RUST_LOG 可按 target 设置级别，例如 RUST_LOG=info,pog::node::3=debug
+    let filters = std::env::var("RUST_LOG").unwrap_or_default();
+    NodeLevelLogger::new(inner, LevelFilter::Info)
+        .with_filters(&filters)
+        .init()?;
     Ok(())
 }
@@ -1,4 +1,13 @@
+use crate::blockchain::block::Block;
+use crate::blockchain::Blockchain;
+use clap::ValueEnum;
+use petgraph::Graph;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
 
 /// 每个槽的指标
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -19,6 +28,11 @@ pub struct SlotMetrics {
     pub tx_packing_delay_stats: TxPackingDelayStats, // 交易打包延迟统计
     pub block_production_success: usize, // 成功出块数
     pub block_production_failed: usize, // 失败出块数
+    pub offline_slots_saved_estimate: f64, // 跳过离线验证者累计避免的空时隙（按离线 stake 占比估计）
+    pub seed: Option<String>, // 选择出块者使用的组合种子（hex），开启 --record-seeds 时记录
+    // 选择出块者时各验证者实际使用的权重（地址 -> 权重），只输出到 JSONL
+    #[serde(default)]
+    pub effective_weights: BTreeMap<String, f64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -35,16 +49,22 @@ pub struct TxPackingDelayStats {
 }
 
 impl SlotMetrics {
-    pub fn to_csv_header() -> String {
-        "epoch,slot,miner,proposer_stake,timestamp,block_hash,tx_count,throughput,avg_path_length,\
+    /// record_seed 为 true 时在末尾增加 seed 列
+    pub fn to_csv_header(record_seed: bool) -> String {
+        let header = "epoch,slot,miner,proposer_stake,timestamp,block_hash,tx_count,throughput,avg_path_length,\
          min_path_length,max_path_length,median_path_length,stake_concentration,\
-         gini_coefficient,consensus_type,consensus_state,avg_tx_delay_ms,block_production_success,block_production_failed"
-            .to_string()
+         gini_coefficient,consensus_type,consensus_state,avg_tx_delay_ms,block_production_success,block_production_failed,\
+         offline_slots_saved_estimate";
+        if record_seed {
+            format!("{},seed", header)
+        } else {
+            header.to_string()
+        }
     }
 
     pub fn to_csv_row(&self) -> String {
-        format!(
-            "{},{},{},{:.6},{},{},{},{:.2},{:.2},{},{},{},{:.6},{:.6},{},{},{:.2},{},{}",
+        let row = format!(
+            "{},{},{},{:.6},{},{},{},{:.2},{:.2},{},{},{},{:.6},{:.6},{},{},{:.2},{},{},{:.2}",
             self.epoch,
             self.slot,
             self.miner,
@@ -64,6 +84,796 @@ impl SlotMetrics {
             self.tx_packing_delay_stats.avg_delay_ms,
             self.block_production_success,
             self.block_production_failed,
+            self.offline_slots_saved_estimate,
+        );
+        match &self.seed {
+            Some(seed) => format!("{},{}", row, seed),
+            None => row,
+        }
+    }
+}
+
+/// 每个epoch的指标
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EpochMetrics {
+    pub epoch: u64,
+    pub timestamp: u64,
+    pub consensus_type: String,
+    pub consensus_state: String,
+    pub degree_contribution_correlation: f64, // 节点度与网络贡献的Pearson相关系数
+    pub contribution_cv: f64,                 // 网络贡献的变异系数，越低说明参与越广泛
+    pub hash_power_gini: f64,                 // 验证者算力的Gini系数
+    pub decayed_stake: f64,                   // 因错过出块累计扣减的 stake
+    pub tx_generated: usize,                  // 本 epoch 生成的交易数
+    pub tx_included: usize,                   // 本 epoch 上链的交易数
+    pub inclusion_rate: f64,                  // 累计上链率
+    pub validator_count: usize,               // epoch 结束时的验证者数量
+    pub validators_added: usize,              // 相比上个 epoch 新加入的验证者数
+    pub validators_removed: usize,            // 相比上个 epoch 退出的验证者数
+    pub empty_slots: u64,                     // 没有产生区块的时隙数（未选出出块者或出块者错过）
+    #[serde(default)]
+    pub throughput: f64, // 本 epoch 区块中的交易数除以 epoch 时长（tx/s）
+    #[serde(default)]
+    pub gini_coefficient: f64, // epoch 结束时验证者 stake 的 Gini 系数
+    #[serde(default)]
+    pub stake_concentration: f64, // epoch 结束时 stake 的 Herfindahl index
+    #[serde(default)]
+    pub proposer_entropy: f64, // 本 epoch 出块者分布的香农熵（bit）
+    // 本 epoch 上链交易的路径长度分布 (长度, 交易数)，按长度升序，只输出到 JSONL
+    #[serde(default)]
+    pub path_length_histogram: Vec<(usize, usize)>,
+}
+
+impl EpochMetrics {
+    pub fn to_csv_header() -> String {
+        "epoch,timestamp,consensus_type,consensus_state,degree_contribution_correlation,\
+         contribution_cv,hash_power_gini,decayed_stake,tx_generated,tx_included,inclusion_rate,\
+         validator_count,validators_added,validators_removed,empty_slots,throughput,\
+         gini_coefficient,stake_concentration,proposer_entropy"
+            .to_string()
+    }
+
+    pub fn to_csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{:.6},{:.6},{:.6},{:.6},{},{},{:.6},{},{},{},{},{:.6},{:.6},{:.6},{:.6}",
+            self.epoch,
+            self.timestamp,
+            self.consensus_type,
+            self.consensus_state,
+            self.degree_contribution_correlation,
+            self.contribution_cv,
+            self.hash_power_gini,
+            self.decayed_stake,
+            self.tx_generated,
+            self.tx_included,
+            self.inclusion_rate,
+            self.validator_count,
+            self.validators_added,
+            self.validators_removed,
+            self.empty_slots,
+            self.throughput,
+            self.gini_coefficient,
+            self.stake_concentration,
+            self.proposer_entropy,
+        )
+    }
+}
+
+/// 指标的输出目标，WorldState 把收集到的每条指标依次交给所有 sink
+pub trait MetricsSink: Send + Sync {
+    fn record_slot(&mut self, metrics: &SlotMetrics);
+    fn record_epoch(&mut self, metrics: &EpochMetrics);
+}
+
+/// 指标输出格式
+/// csv: metrics_slots_{consensus}.csv 和 metrics_epochs_{consensus}.csv
+/// jsonl: 同名的 .jsonl 文件，每行一条 JSON 记录
+/// null: 不输出
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsSinkKind {
+    Csv,
+    Jsonl,
+    Null,
+}
+
+impl MetricsSinkKind {
+    pub fn build(&self, consensus_name: &str) -> Box<dyn MetricsSink> {
+        match self {
+            MetricsSinkKind::Csv => Box::new(CsvSink::new(consensus_name)),
+            MetricsSinkKind::Jsonl => Box::new(JsonlSink::new(consensus_name)),
+            MetricsSinkKind::Null => Box::new(NullSink),
+        }
+    }
+}
+
+impl Display for MetricsSinkKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            MetricsSinkKind::Csv => write!(f, "csv"),
+            MetricsSinkKind::Jsonl => write!(f, "jsonl"),
+            MetricsSinkKind::Null => write!(f, "null"),
+        }
+    }
+}
+
+/// 删除旧文件后以追加方式打开，打开失败时返回 None，之后的指标不再输出
+fn create_metrics_file(path: &str) -> Option<File> {
+    let _ = std::fs::remove_file(path);
+    OpenOptions::new().create(true).append(true).open(path).ok()
+}
+
+/// 文件为空时先写入表头
+fn append_line(file: &mut Option<File>, header: impl FnOnce() -> Option<String>, line: &str) {
+    if let Some(file) = file {
+        if file.metadata().map(|m| m.len()).unwrap_or(0) == 0 {
+            if let Some(header) = header() {
+                let _ = writeln!(file, "{}", header);
+            }
+        }
+        let _ = writeln!(file, "{}", line);
+        let _ = file.flush();
+    }
+}
+
+/// CSV 格式的指标文件，时隙指标带有种子时表头增加 seed 列
+pub struct CsvSink {
+    slots_file: Option<File>,
+    epochs_file: Option<File>,
+}
+
+impl CsvSink {
+    pub fn new(consensus_name: &str) -> Self {
+        CsvSink {
+            slots_file: create_metrics_file(&format!("metrics_slots_{}.csv", consensus_name)),
+            epochs_file: create_metrics_file(&format!("metrics_epochs_{}.csv", consensus_name)),
+        }
+    }
+}
+
+impl MetricsSink for CsvSink {
+    fn record_slot(&mut self, metrics: &SlotMetrics) {
+        let header = || Some(SlotMetrics::to_csv_header(metrics.seed.is_some()));
+        append_line(&mut self.slots_file, header, &metrics.to_csv_row());
+    }
+
+    fn record_epoch(&mut self, metrics: &EpochMetrics) {
+        let header = || Some(EpochMetrics::to_csv_header());
+        append_line(&mut self.epochs_file, header, &metrics.to_csv_row());
+    }
+}
+
+/// JSON Lines 格式的指标文件，保留 CSV 中展开的嵌套字段
+pub struct JsonlSink {
+    slots_file: Option<File>,
+    epochs_file: Option<File>,
+}
+
+impl JsonlSink {
+    pub fn new(consensus_name: &str) -> Self {
+        JsonlSink {
+            slots_file: create_metrics_file(&format!("metrics_slots_{}.jsonl", consensus_name)),
+            epochs_file: create_metrics_file(&format!("metrics_epochs_{}.jsonl", consensus_name)),
+        }
+    }
+}
+
+impl MetricsSink for JsonlSink {
+    fn record_slot(&mut self, metrics: &SlotMetrics) {
+        if let Ok(line) = serde_json::to_string(metrics) {
+            append_line(&mut self.slots_file, || None, &line);
+        }
+    }
+
+    fn record_epoch(&mut self, metrics: &EpochMetrics) {
+        if let Ok(line) = serde_json::to_string(metrics) {
+            append_line(&mut self.epochs_file, || None, &line);
+        }
+    }
+}
+
+/// 丢弃所有指标
+pub struct NullSink;
+
+impl MetricsSink for NullSink {
+    fn record_slot(&mut self, _metrics: &SlotMetrics) {}
+
+    fn record_epoch(&mut self, _metrics: &EpochMetrics) {}
+}
+
+/// 一笔上链交易的传播路径，paths_export.jsonl 的一行
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PathRecord {
+    pub tx_hash: String,
+    pub block_index: u64,
+    pub epoch: u64,
+    pub slot: u64,
+    pub miner: String,
+    pub created_at: u64,          // 交易创建时间（秒）
+    pub path: Vec<String>,        // 发起者到出块者依次经过的地址
+    pub hop_timestamps: Vec<u64>, // path[1..] 每一跳的到达时间（毫秒），旧格式的区块为空
+    pub ntd: Option<usize>,       // 上链时 POG 的 NTD，其他共识为 None
+}
+
+impl PathRecord {
+    pub fn from_block(block: &Block, ntd: Option<usize>) -> Vec<PathRecord> {
+        block
+            .body
+            .transactions
+            .iter()
+            .zip(block.body.paths.iter())
+            .map(|(transaction, paths)| PathRecord {
+                tx_hash: transaction.hash.clone(),
+                block_index: block.header.index,
+                epoch: block.header.epoch,
+                slot: block.header.slot,
+                miner: block.header.miner.clone(),
+                created_at: transaction.timestamp,
+                path: paths.paths.clone(),
+                hop_timestamps: paths.hop_timestamps.clone(),
+                ntd,
+            })
+            .collect()
+    }
+}
+
+/// 把 WorldState 链上最终确定的区块中每笔交易的传播路径写入 paths_export.jsonl，用于离线分析
+/// 区块之后至少有 depth 个区块时视为最终确定，此前被同步替换掉的区块不会导出
+pub struct PathExport {
+    file: Option<File>,
+    depth: u64,                                 // 最终确定所需的后续区块数
+    exported: u64,                              // 已导出到的区块高度，创世块不导出
+    ntd: HashMap<String, (u64, Option<usize>)>, // 尚未最终确定的区块哈希 -> (高度, 接受时的 NTD)
+}
+
+impl PathExport {
+    pub const FILE_NAME: &'static str = "paths_export.jsonl";
+
+    pub fn new(depth: u64) -> Self {
+        PathExport {
+            file: create_metrics_file(Self::FILE_NAME),
+            depth,
+            exported: 0,
+            ntd: HashMap::new(),
+        }
+    }
+
+    /// 记录区块被接受时的 NTD，区块最终确定后才导出
+    pub fn record_block(&mut self, block: &Block, ntd: Option<usize>) {
+        self.ntd
+            .insert(block.header.hash.clone(), (block.header.index, ntd));
+    }
+
+    /// 新近最终确定的区块中的路径，没有记录接受时 NTD 的区块（例如同步来的区块）使用 current_ntd
+    pub fn finalized_records(
+        &mut self,
+        blockchain: &Blockchain,
+        current_ntd: Option<usize>,
+    ) -> Vec<PathRecord> {
+        let final_height = blockchain.get_last_index().saturating_sub(self.depth);
+        let mut records = Vec::new();
+        while self.exported < final_height {
+            self.exported += 1;
+            let block = &blockchain.blocks()[self.exported as usize];
+            let ntd = match self.ntd.get(&block.header.hash) {
+                Some((_, ntd)) => *ntd,
+                None => current_ntd,
+            };
+            records.extend(PathRecord::from_block(block, ntd));
+        }
+        let exported = self.exported;
+        self.ntd.retain(|_, (index, _)| *index > exported);
+        records
+    }
+
+    /// 写入新近最终确定的区块中的路径，返回写入的记录数
+    pub fn finalize(&mut self, blockchain: &Blockchain, current_ntd: Option<usize>) -> usize {
+        let records = self.finalized_records(blockchain, current_ntd);
+        for record in records.iter() {
+            if let Ok(line) = serde_json::to_string(record) {
+                append_line(&mut self.file, || None, &line);
+            }
+        }
+        records.len()
+    }
+}
+
+/// 两个 epoch 之间验证者集合的变化，返回 (新加入数, 退出数)
+pub fn validator_set_churn(
+    previous: &HashSet<String>,
+    current: &HashSet<String>,
+) -> (usize, usize) {
+    let added = current.difference(previous).count();
+    let removed = previous.difference(current).count();
+    (added, removed)
+}
+
+/// 交易上链率统计：节点报告生成的交易，WorldState 接受的区块中出现后视为上链
+/// 上链率低说明交易传播或内存池存在问题
+/// 交易按报告时 WorldState 已运行的时隙数记录，超过一定时隙仍未上链的交易不再跟踪
+#[derive(Debug, Clone, Default)]
+pub struct InclusionTracker {
+    pending: HashMap<String, u64>, // 尚未上链的交易哈希 -> 报告生成的时隙
+    included_txs: HashMap<String, u64>, // 已上链的交易哈希 -> 报告生成的时隙，区块被回滚时恢复为未上链
+    pub generated: usize,               // 累计生成的交易数
+    pub included: usize,                // 累计上链的交易数
+    pub expired: usize,                 // 超时不再跟踪的未上链交易数
+    epoch_generated: usize,
+    epoch_included: usize,
+}
+
+impl InclusionTracker {
+    pub fn record_generated(&mut self, tx_hash: String, slot: u64) {
+        if self.included_txs.contains_key(&tx_hash) {
+            return;
+        }
+        if self.pending.insert(tx_hash, slot).is_none() {
+            self.generated += 1;
+            self.epoch_generated += 1;
+        }
+    }
+
+    /// 区块中出现的已报告交易计为上链，同一交易只计一次
+    pub fn record_block(&mut self, block: &Block) {
+        for transaction in block.body.transactions.iter() {
+            if let Some(slot) = self.pending.remove(&transaction.hash) {
+                self.included_txs.insert(transaction.hash.clone(), slot);
+                self.included += 1;
+                self.epoch_included += 1;
+            }
+        }
+    }
+
+    /// 区块被回滚，其中计为上链的交易恢复为未上链
+    pub fn revert_block(&mut self, block: &Block) {
+        for transaction in block.body.transactions.iter() {
+            if let Some(slot) = self.included_txs.remove(&transaction.hash) {
+                self.pending.insert(transaction.hash.clone(), slot);
+                self.included -= 1;
+                self.epoch_included = self.epoch_included.saturating_sub(1);
+            }
+        }
+    }
+
+    /// 不再跟踪 slot 之前生成的交易：未上链的计为超时，已上链的不再支持回滚，返回超时的交易数
+    pub fn expire_before(&mut self, slot: u64) -> usize {
+        let before = self.pending.len();
+        self.pending.retain(|_, s| *s >= slot);
+        self.included_txs.retain(|_, s| *s >= slot);
+        let expired = before - self.pending.len();
+        self.expired += expired;
+        expired
+    }
+
+    /// 累计上链率，没有生成交易时为1
+    pub fn inclusion_rate(&self) -> f64 {
+        if self.generated == 0 {
+            return 1.0;
+        }
+        self.included as f64 / self.generated as f64
+    }
+
+    /// 在 slot 之前生成但仍未上链的交易数，包括超时不再跟踪的交易
+    pub fn pending_before(&self, slot: u64) -> usize {
+        self.expired + self.pending.values().filter(|s| **s < slot).count()
+    }
+
+    /// 返回本 epoch 生成和上链的交易数，并清零 epoch 计数
+    pub fn end_epoch(&mut self) -> (usize, usize) {
+        let counts = (self.epoch_generated, self.epoch_included);
+        self.epoch_generated = 0;
+        self.epoch_included = 0;
+        counts
+    }
+}
+
+/// metrics_slots CSV 的汇总指标
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotsSummary {
+    pub slots: usize,
+    pub skipped_rows: usize, // 无法解析而跳过的行
+    pub mean_throughput: f64,
+    pub median_throughput: f64,
+    pub final_gini: f64,
+    pub final_stake_concentration: f64,
+    pub proposer_entropy: f64,  // 出块者分布的香农熵（bit）
+    pub proposer_gini: f64,     // 各出块者出块数的Gini系数
+    pub path_length_trend: f64, // 平均路径长度对全局时隙（跨 epoch 连续编号）的线性回归斜率
+}
+
+impl Display for SlotsSummary {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "slots: {} (skipped {})", self.slots, self.skipped_rows)?;
+        writeln!(
+            f,
+            "throughput: mean {:.2} tx/s, median {:.2} tx/s",
+            self.mean_throughput, self.median_throughput
+        )?;
+        writeln!(f, "final gini: {:.6}", self.final_gini)?;
+        writeln!(
+            f,
+            "final stake concentration: {:.6}",
+            self.final_stake_concentration
+        )?;
+        writeln!(
+            f,
+            "proposer entropy: {:.4} bits, proposer gini: {:.6}",
+            self.proposer_entropy, self.proposer_gini
+        )?;
+        write!(
+            f,
+            "avg path length trend: {:+.6} per slot",
+            self.path_length_trend
+        )
+    }
+}
+
+/// metrics_epochs CSV 的汇总指标
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochsSummary {
+    pub epochs: usize,
+    pub skipped_rows: usize,
+    pub mean_degree_contribution_correlation: f64,
+    pub final_contribution_cv: f64,
+    pub final_hash_power_gini: f64,
+    pub final_decayed_stake: f64,
+    pub final_validator_count: usize,
+    pub validator_count_trend: f64, // 验证者数量随 epoch 变化的斜率
+}
+
+impl Display for EpochsSummary {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "epochs: {} (skipped {})", self.epochs, self.skipped_rows)?;
+        writeln!(
+            f,
+            "mean degree-contribution correlation: {:.6}",
+            self.mean_degree_contribution_correlation
+        )?;
+        writeln!(
+            f,
+            "final contribution cv: {:.6}",
+            self.final_contribution_cv
+        )?;
+        writeln!(
+            f,
+            "final hash power gini: {:.6}",
+            self.final_hash_power_gini
+        )?;
+        writeln!(f, "final decayed stake: {:.6}", self.final_decayed_stake)?;
+        write!(
+            f,
+            "final validator count: {} (trend {:+.4}/epoch)",
+            self.final_validator_count, self.validator_count_trend
+        )
+    }
+}
+
+/// 按表头解析 CSV，返回每行中 columns 对应的字段；字段缺失的行计入跳过数
+fn read_csv_columns<'a>(
+    content: &'a str,
+    columns: &[&str],
+) -> Result<(Vec<Vec<&'a str>>, usize), String> {
+    let mut lines = content.lines();
+    let header: Vec<&str> = lines.next().ok_or("empty csv")?.split(',').collect();
+    let positions = columns
+        .iter()
+        .map(|name| {
+            header
+                .iter()
+                .position(|c| c == name)
+                .ok_or(format!("missing column {}", name))
+        })
+        .collect::<Result<Vec<usize>, String>>()?;
+
+    let mut rows = Vec::new();
+    let mut skipped = 0;
+    for line in lines.filter(|l| !l.trim().is_empty()) {
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() != header.len() {
+            skipped += 1;
+            continue;
+        }
+        rows.push(positions.iter().map(|p| fields[*p]).collect());
+    }
+    Ok((rows, skipped))
+}
+
+/// 将字段解析为数值，任一字段无法解析时返回 None
+fn parse_fields(fields: &[&str]) -> Option<Vec<f64>> {
+    fields.iter().map(|f| f.parse::<f64>().ok()).collect()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+/// 最小二乘拟合 y = a + b * x 的斜率 b
+pub fn linear_slope(xs: &[f64], ys: &[f64]) -> f64 {
+    let n = xs.len().min(ys.len());
+    if n < 2 {
+        return 0.0;
+    }
+    let mean_x = mean(&xs[..n]);
+    let mean_y = mean(&ys[..n]);
+    let mut cov = 0.0;
+    let mut var_x = 0.0;
+    for i in 0..n {
+        cov += (xs[i] - mean_x) * (ys[i] - mean_y);
+        var_x += (xs[i] - mean_x) * (xs[i] - mean_x);
+    }
+    if var_x == 0.0 {
+        return 0.0;
+    }
+    cov / var_x
+}
+
+/// 按次数计算的香农熵（bit），所有次数集中在一个取值时为0
+pub fn shannon_entropy(counts: &[f64]) -> f64 {
+    let total: f64 = counts.iter().sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    counts
+        .iter()
+        .filter(|c| **c > 0.0)
+        .map(|c| {
+            let p = c / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// 按 SlotMetrics::to_csv_header 的列名解析 metrics_slots CSV 并汇总
+/// 列数不符或数值无法解析的行被跳过并计数
+/// 路径长度趋势按全局时隙 epoch * slot_per_epoch + slot 回归，slot_per_epoch 取文件中最大的 slot + 1
+pub fn summarize_slots_csv(content: &str) -> Result<SlotsSummary, String> {
+    let (rows, mut skipped_rows) = read_csv_columns(
+        content,
+        &[
+            "epoch",
+            "slot",
+            "throughput",
+            "gini_coefficient",
+            "stake_concentration",
+            "avg_path_length",
+            "miner",
+        ],
+    )?;
+
+    let mut epoch_slots = Vec::new();
+    let mut throughputs = Vec::new();
+    let mut path_lengths = Vec::new();
+    let mut proposer_counts: HashMap<&str, f64> = HashMap::new();
+    let mut final_gini = 0.0;
+    let mut final_stake_concentration = 0.0;
+    for row in rows {
+        let Some(values) = parse_fields(&row[..6]) else {
+            skipped_rows += 1;
+            continue;
+        };
+        epoch_slots.push((values[0], values[1]));
+        throughputs.push(values[2]);
+        final_gini = values[3];
+        final_stake_concentration = values[4];
+        path_lengths.push(values[5]);
+        *proposer_counts.entry(row[6]).or_insert(0.0) += 1.0;
+    }
+    let slot_per_epoch = epoch_slots.iter().map(|(_, s)| *s).fold(0.0, f64::max) + 1.0;
+    let slots: Vec<f64> = epoch_slots
+        .iter()
+        .map(|(epoch, slot)| epoch * slot_per_epoch + slot)
+        .collect();
+    let counts: Vec<f64> = proposer_counts.values().cloned().collect();
+    Ok(SlotsSummary {
+        slots: throughputs.len(),
+        skipped_rows,
+        mean_throughput: mean(&throughputs),
+        median_throughput: median(&throughputs),
+        final_gini,
+        final_stake_concentration,
+        proposer_entropy: shannon_entropy(&counts),
+        proposer_gini: calculate_gini(&counts),
+        path_length_trend: linear_slope(&slots, &path_lengths),
+    })
+}
+
+/// 按 EpochMetrics::to_csv_header 的列名解析 metrics_epochs CSV 并汇总
+pub fn summarize_epochs_csv(content: &str) -> Result<EpochsSummary, String> {
+    let (rows, mut skipped_rows) = read_csv_columns(
+        content,
+        &[
+            "degree_contribution_correlation",
+            "contribution_cv",
+            "hash_power_gini",
+            "decayed_stake",
+            "validator_count",
+        ],
+    )?;
+
+    let mut correlations = Vec::new();
+    let mut validator_counts = Vec::new();
+    let mut last = vec![0.0; 5];
+    for row in rows {
+        let Some(values) = parse_fields(&row) else {
+            skipped_rows += 1;
+            continue;
+        };
+        correlations.push(values[0]);
+        validator_counts.push(values[4]);
+        last = values;
+    }
+    let epochs: Vec<f64> = (0..validator_counts.len()).map(|i| i as f64).collect();
+    Ok(EpochsSummary {
+        epochs: correlations.len(),
+        skipped_rows,
+        mean_degree_contribution_correlation: mean(&correlations),
+        final_contribution_cv: last[1],
+        final_hash_power_gini: last[2],
+        final_decayed_stake: last[3],
+        final_validator_count: last[4] as usize,
+        validator_count_trend: linear_slope(&epochs, &validator_counts),
+    })
+}
+
+/// 一次运行中某个 epoch 的指标，取自 metrics_epochs CSV
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct EpochStats {
+    pub throughput: f64,
+    pub gini: f64,                // epoch 结束时的 stake Gini 系数
+    pub stake_concentration: f64, // epoch 结束时的 Herfindahl index
+    pub proposer_entropy: f64,    // 本 epoch 出块者分布的香农熵（bit）
+}
+
+/// 按 EpochMetrics::to_csv_header 的列名解析 metrics_epochs CSV，返回 epoch -> 指标和跳过的行数
+/// 同一个 epoch 出现多次时使用最后一行
+pub fn epochs_csv_by_epoch(content: &str) -> Result<(BTreeMap<u64, EpochStats>, usize), String> {
+    let (rows, mut skipped_rows) = read_csv_columns(
+        content,
+        &[
+            "epoch",
+            "throughput",
+            "gini_coefficient",
+            "stake_concentration",
+            "proposer_entropy",
+        ],
+    )?;
+    let mut epochs = BTreeMap::new();
+    for row in rows {
+        let (Ok(epoch), Some(values)) = (row[0].parse::<u64>(), parse_fields(&row[1..])) else {
+            skipped_rows += 1;
+            continue;
+        };
+        epochs.insert(
+            epoch,
+            EpochStats {
+                throughput: values[0],
+                gini: values[1],
+                stake_concentration: values[2],
+                proposer_entropy: values[3],
+            },
+        );
+    }
+    Ok((epochs, skipped_rows))
+}
+
+/// 两次运行同一个 epoch 的对比，delta 为 b - a
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EpochComparison {
+    pub epoch: u64,
+    pub a: EpochStats,
+    pub b: EpochStats,
+    pub delta_throughput: f64,
+    pub delta_gini: f64,
+    pub delta_stake_concentration: f64,
+    pub delta_proposer_entropy: f64,
+}
+
+/// 两次运行的 epoch 指标按 epoch 对齐后的差异
+/// epoch 数不同时截断到较短的一方：只对比不超过两次运行中较早结束的最后一个 epoch
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct MetricsDiff {
+    pub epochs_a: usize,
+    pub epochs_b: usize,
+    pub skipped_rows: usize,
+    pub epochs: Vec<EpochComparison>,
+}
+
+impl MetricsDiff {
+    pub fn from_epochs_csv(a: &str, b: &str) -> Result<Self, String> {
+        let (stats_a, skipped_a) = epochs_csv_by_epoch(a)?;
+        let (stats_b, skipped_b) = epochs_csv_by_epoch(b)?;
+        let last_epoch = match (stats_a.keys().last(), stats_b.keys().last()) {
+            (Some(last_a), Some(last_b)) => *last_a.min(last_b),
+            _ => 0,
+        };
+        let epochs = stats_a
+            .range(..=last_epoch)
+            .filter_map(|(epoch, a)| {
+                let b = stats_b.get(epoch)?;
+                Some(EpochComparison {
+                    epoch: *epoch,
+                    a: *a,
+                    b: *b,
+                    delta_throughput: b.throughput - a.throughput,
+                    delta_gini: b.gini - a.gini,
+                    delta_stake_concentration: b.stake_concentration - a.stake_concentration,
+                    delta_proposer_entropy: b.proposer_entropy - a.proposer_entropy,
+                })
+            })
+            .collect();
+        Ok(MetricsDiff {
+            epochs_a: stats_a.len(),
+            epochs_b: stats_b.len(),
+            skipped_rows: skipped_a + skipped_b,
+            epochs,
+        })
+    }
+}
+
+impl Display for MetricsDiff {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "epochs: a {}, b {}, compared {} (skipped {} rows)",
+            self.epochs_a,
+            self.epochs_b,
+            self.epochs.len(),
+            self.skipped_rows
+        )?;
+        writeln!(
+            f,
+            "{:>6} | {:>28} | {:>24} | {:>24} | {:>24}",
+            "epoch",
+            "throughput a/b/delta",
+            "gini a/b/delta",
+            "stake hhi a/b/delta",
+            "proposer entropy a/b/delta"
+        )?;
+        for e in self.epochs.iter() {
+            writeln!(
+                f,
+                "{:>6} | {:>8.2} {:>8.2} {:>+9.2} | {:>7.4} {:>7.4} {:>+8.4} | {:>7.4} {:>7.4} {:>+8.4} | {:>7.4} {:>7.4} {:>+8.4}",
+                e.epoch,
+                e.a.throughput,
+                e.b.throughput,
+                e.delta_throughput,
+                e.a.gini,
+                e.b.gini,
+                e.delta_gini,
+                e.a.stake_concentration,
+                e.b.stake_concentration,
+                e.delta_stake_concentration,
+                e.a.proposer_entropy,
+                e.b.proposer_entropy,
+                e.delta_proposer_entropy,
+            )?;
+        }
+        let deltas = |delta: fn(&EpochComparison) -> f64| -> Vec<f64> {
+            self.epochs.iter().map(delta).collect()
+        };
+        write!(
+            f,
+            "mean delta: throughput {:+.2}, gini {:+.4}, stake hhi {:+.4}, proposer entropy {:+.4}",
+            mean(&deltas(|e| e.delta_throughput)),
+            mean(&deltas(|e| e.delta_gini)),
+            mean(&deltas(|e| e.delta_stake_concentration)),
+            mean(&deltas(|e| e.delta_proposer_entropy)),
         )
     }
 }
@@ -108,8 +918,75 @@ pub fn calculate_stake_concentration(stakes: &[f64]) -> f64 {
     shares.iter().map(|s| s * s).sum()
 }
 
-/// 计算路径长度统计
-pub fn calculate_path_stats(paths: Vec<Vec<String>>) -> PathStats {
+/// 将总权益的 fraction 集中到 attackers（节点编号）上，由攻击者平分
+/// 其余节点按原比例分配剩下的权益，总权益不变
+pub fn concentrate_stake(stakes: &[f64], attackers: &[usize], fraction: f64) -> Vec<f64> {
+    let total: f64 = stakes.iter().sum();
+    let attacker_num = attackers.iter().filter(|i| **i < stakes.len()).count();
+    if attacker_num == 0 || total <= 0.0 {
+        return stakes.to_vec();
+    }
+    let others_total: f64 = stakes
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !attackers.contains(i))
+        .map(|(_, s)| s)
+        .sum();
+    let attacker_stake = total * fraction / attacker_num as f64;
+    let others_scale = if others_total > 0.0 {
+        total * (1.0 - fraction) / others_total
+    } else {
+        0.0
+    };
+    stakes
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            if attackers.contains(&i) {
+                attacker_stake
+            } else {
+                s * others_scale
+            }
+        })
+        .collect()
+}
+
+/// 攻击者（主节点及其女巫身份）的 stake 份额和出块份额
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AttackerReport {
+    pub stake_fraction: f64, // 攻击者初始 stake 占总 stake 的比例
+    pub proposer_share: f64, // 攻击者出块占所有区块（不含创世区块）的比例
+    pub sybil_advantage: f64,
+}
+
+impl AttackerReport {
+    /// miners 为各区块的出块者，attackers 为攻击者的全部地址
+    pub fn new(stake_fraction: f64, miners: &[String], attackers: &HashSet<String>) -> Self {
+        let proposer_share = if miners.is_empty() {
+            0.0
+        } else {
+            miners.iter().filter(|m| attackers.contains(*m)).count() as f64 / miners.len() as f64
+        };
+        AttackerReport {
+            stake_fraction,
+            proposer_share,
+            sybil_advantage: sybil_advantage(proposer_share, stake_fraction),
+        }
+    }
+}
+
+/// 女巫优势：攻击者出块份额与其 stake 份额之比
+/// 1 表示出块机会与权益相称，大于 1 说明攻击者获得了超出权益的出块机会
+pub fn sybil_advantage(proposer_share: f64, stake_fraction: f64) -> f64 {
+    if stake_fraction <= 0.0 {
+        return 0.0;
+    }
+    proposer_share / stake_fraction
+}
+
+/// 计算区块的路径长度统计，路径长度按 Block::path_length 的定义（不含发送者，即转发跳数）
+pub fn calculate_path_stats(block: &Block) -> PathStats {
+    let paths = block.get_all_paths();
     if paths.is_empty() {
         return PathStats {
             avg_length: 0.0,
@@ -119,10 +996,10 @@ pub fn calculate_path_stats(paths: Vec<Vec<String>>) -> PathStats {
         };
     }
 
-    let lengths: Vec<usize> = paths.iter().map(|p| p.len()).collect();
+    let lengths: Vec<usize> = paths.iter().map(|p| Block::path_length(p)).collect();
     let min_length = *lengths.iter().min().unwrap_or(&0);
     let max_length = *lengths.iter().max().unwrap_or(&0);
-    let avg_length = lengths.iter().sum::<usize>() as f64 / lengths.len() as f64;
+    let avg_length = block.average_path_length();
 
     let mut sorted_lengths = lengths.clone();
     sorted_lengths.sort_unstable();
@@ -136,6 +1013,15 @@ pub fn calculate_path_stats(paths: Vec<Vec<String>>) -> PathStats {
     }
 }
 
+/// 路径长度分布：(长度, 路径数)，按长度升序，路径长度按 Block::path_length 的定义
+pub fn path_length_histogram(paths: &[Vec<String>]) -> Vec<(usize, usize)> {
+    let mut histogram = BTreeMap::new();
+    for path in paths {
+        *histogram.entry(Block::path_length(path)).or_insert(0) += 1;
+    }
+    histogram.into_iter().collect()
+}
+
 /// 计算Gini系数 (Gini coefficient)
 /// 用于衡量财富/权益分布的不平等程度
 /// 0 = 完全平等, 1 = 完全不平等
@@ -238,3 +1124,556 @@ pub fn generate_stake_by_gini(node_num: u32, target_gini: f64, gini_seed: u64) -
 
     stakes
 }
+
+/// 节点算力的分布方式
+/// stake: 算力与权益相同
+/// uniform: 所有节点算力相等
+/// skewed: 按 --hash-power-gini 生成，与权益分布相互独立
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashPowerDistribution {
+    Stake,
+    Uniform,
+    Skewed,
+}
+
+impl Display for HashPowerDistribution {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            HashPowerDistribution::Stake => write!(f, "stake"),
+            HashPowerDistribution::Uniform => write!(f, "uniform"),
+            HashPowerDistribution::Skewed => write!(f, "skewed"),
+        }
+    }
+}
+
+/// 生成各节点的算力，返回长度与stakes相同、平均每个节点1单位的算力数组
+///
+/// # 参数
+/// * `distribution`: 算力分布方式
+/// * `stakes`: 各节点的权益，stake 分布时直接作为算力
+/// * `target_gini`: skewed 分布的目标Gini系数
+/// * `seed`: 随机种子，skewed 分布使用与权益不同的打乱顺序
+pub fn generate_hash_power(
+    distribution: HashPowerDistribution,
+    stakes: &[f64],
+    target_gini: f64,
+    seed: u64,
+) -> Vec<f64> {
+    match distribution {
+        HashPowerDistribution::Stake => stakes.to_vec(),
+        HashPowerDistribution::Uniform => vec![1.0; stakes.len()],
+        HashPowerDistribution::Skewed => {
+            generate_stake_by_gini(stakes.len() as u32, target_gini, seed.wrapping_add(1))
+        }
+    }
+}
+
+/// 计算变异系数 (Coefficient of variation)：标准差 / 均值
+/// 均值为0时返回0
+pub fn coefficient_of_variation(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    if mean == 0.0 {
+        return 0.0;
+    }
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    variance.sqrt() / mean
+}
+
+/// 计算Pearson相关系数，任一序列方差为0时返回0
+pub fn pearson_correlation(xs: &[f64], ys: &[f64]) -> f64 {
+    let n = xs.len().min(ys.len());
+    if n < 2 {
+        return 0.0;
+    }
+    let mean_x = xs[..n].iter().sum::<f64>() / n as f64;
+    let mean_y = ys[..n].iter().sum::<f64>() / n as f64;
+    let mut cov = 0.0;
+    let mut var_x = 0.0;
+    let mut var_y = 0.0;
+    for i in 0..n {
+        let dx = xs[i] - mean_x;
+        let dy = ys[i] - mean_y;
+        cov += dx * dy;
+        var_x += dx * dx;
+        var_y += dy * dy;
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        return 0.0;
+    }
+    cov / (var_x.sqrt() * var_y.sqrt())
+}
+
+/// 计算节点度与网络贡献的Pearson相关系数
+/// 图中没有贡献记录的节点按0计算
+pub fn degree_contribution_correlation(
+    graph: &Graph<String, ()>,
+    contribution: &HashMap<String, f64>,
+) -> f64 {
+    let (degrees, contributions): (Vec<f64>, Vec<f64>) = graph
+        .node_indices()
+        .map(|i| {
+            let degree = graph.neighbors_undirected(i).collect::<HashSet<_>>().len();
+            let c = *contribution.get(&graph[i]).unwrap_or(&0.0);
+            (degree as f64, c)
+        })
+        .unzip();
+    pearson_correlation(&degrees, &contributions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_metrics_seed_column() {
+        let mut metrics = SlotMetrics {
+            epoch: 1,
+            slot: 2,
+            miner: "miner".to_string(),
+            proposer_stake: 1.0,
+            timestamp: 0,
+            block_hash: "hash".to_string(),
+            tx_count: 0,
+            throughput: 0.0,
+            path_stats: PathStats::default(),
+            stake_concentration: 0.0,
+            gini_coefficient: 0.0,
+            consensus_type: "POS".to_string(),
+            consensus_state: "pos".to_string(),
+            tx_packing_delay_stats: TxPackingDelayStats::default(),
+            block_production_success: 1,
+            block_production_failed: 0,
+            offline_slots_saved_estimate: 0.0,
+            seed: None,
+            effective_weights: BTreeMap::new(),
+        };
+        let columns = |s: &str| s.split(',').count();
+        assert_eq!(
+            columns(&SlotMetrics::to_csv_header(false)),
+            columns(&metrics.to_csv_row())
+        );
+
+        metrics.seed = Some(hex::encode([7u8; 32]));
+        assert!(SlotMetrics::to_csv_header(true).ends_with(",seed"));
+        assert_eq!(
+            columns(&SlotMetrics::to_csv_header(true)),
+            columns(&metrics.to_csv_row())
+        );
+        assert!(metrics.to_csv_row().ends_with(&"07".repeat(32)));
+    }
+
+    #[test]
+    fn test_summarize_slots_csv() {
+        // 每个 epoch 两个时隙，n 为全局时隙编号
+        let metrics = |n: u64, miner: &str, throughput: f64, avg_length: f64| SlotMetrics {
+            epoch: n / 2,
+            slot: n % 2,
+            miner: miner.to_string(),
+            proposer_stake: 1.0,
+            timestamp: 0,
+            block_hash: "hash".to_string(),
+            tx_count: 0,
+            throughput,
+            path_stats: PathStats {
+                avg_length,
+                ..PathStats::default()
+            },
+            stake_concentration: 0.5,
+            gini_coefficient: 0.1 * n as f64,
+            consensus_type: "POS".to_string(),
+            consensus_state: "pos".to_string(),
+            tx_packing_delay_stats: TxPackingDelayStats::default(),
+            block_production_success: 1,
+            block_production_failed: 0,
+            offline_slots_saved_estimate: 0.0,
+            seed: None,
+            effective_weights: BTreeMap::new(),
+        };
+        let csv = [
+            SlotMetrics::to_csv_header(false),
+            metrics(0, "a", 2.0, 1.0).to_csv_row(),
+            "garbage".to_string(),
+            metrics(1, "b", 4.0, 2.0).to_csv_row(),
+            metrics(2, "a", 9.0, 3.0).to_csv_row().replace("9.00", "x"),
+            metrics(3, "c", 6.0, 4.0).to_csv_row(),
+        ]
+        .join("\n");
+        let summary = summarize_slots_csv(&csv).unwrap();
+        assert_eq!(summary.slots, 3);
+        assert_eq!(summary.skipped_rows, 2);
+        assert!((summary.mean_throughput - 4.0).abs() < 1e-9);
+        assert!((summary.median_throughput - 4.0).abs() < 1e-9);
+        assert!((summary.final_gini - 0.3).abs() < 1e-9);
+        assert!((summary.final_stake_concentration - 0.5).abs() < 1e-9);
+        // a、b、c 各出块一次
+        assert!((summary.proposer_entropy - 3f64.log2()).abs() < 1e-9);
+        assert_eq!(summary.proposer_gini, 0.0);
+        assert!((summary.path_length_trend - 1.0).abs() < 1e-9);
+
+        assert!(summarize_slots_csv("").is_err());
+        assert!(summarize_slots_csv("epoch,slot\n0,1").is_err());
+    }
+
+    #[test]
+    fn test_metrics_diff() {
+        let metrics = |epoch: u64, throughput: f64, proposer_entropy: f64| EpochMetrics {
+            epoch,
+            timestamp: 0,
+            consensus_type: "POS".to_string(),
+            consensus_state: "pos".to_string(),
+            degree_contribution_correlation: 0.0,
+            contribution_cv: 0.0,
+            hash_power_gini: 0.0,
+            decayed_stake: 0.0,
+            tx_generated: 0,
+            tx_included: 0,
+            inclusion_rate: 1.0,
+            validator_count: 4,
+            validators_added: 0,
+            validators_removed: 0,
+            empty_slots: 0,
+            throughput,
+            gini_coefficient: 0.1 * epoch as f64,
+            stake_concentration: 0.25,
+            proposer_entropy,
+            path_length_histogram: vec![],
+        };
+        let csv = |rows: Vec<EpochMetrics>| {
+            let mut lines = vec![EpochMetrics::to_csv_header()];
+            lines.extend(rows.iter().map(|m| m.to_csv_row()));
+            lines.join("\n")
+        };
+        // a 运行 3 个 epoch，b 运行 2 个 epoch，截断到 b 的 epoch 0~1
+        let a = csv(vec![
+            metrics(0, 2.0, 1.0),
+            metrics(1, 1.0, 0.5),
+            metrics(2, 1.0, 0.5),
+        ]);
+        let mut b = csv(vec![metrics(0, 5.0, 0.0), metrics(1, 1.5, 0.5)]);
+        b.push_str("\nx,1,2");
+        let diff = MetricsDiff::from_epochs_csv(&a, &b).unwrap();
+        assert_eq!((diff.epochs_a, diff.epochs_b), (3, 2));
+        assert_eq!(diff.skipped_rows, 1);
+        assert_eq!(
+            diff.epochs.iter().map(|e| e.epoch).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        let epoch0 = &diff.epochs[0];
+        assert!((epoch0.delta_throughput - 3.0).abs() < 1e-9);
+        assert!((epoch0.delta_proposer_entropy + 1.0).abs() < 1e-9);
+        let epoch1 = &diff.epochs[1];
+        assert!((epoch1.a.gini - 0.1).abs() < 1e-9);
+        assert!((epoch1.delta_throughput - 0.5).abs() < 1e-9);
+        assert_eq!(epoch1.delta_gini, 0.0);
+        assert_eq!(epoch1.delta_stake_concentration, 0.0);
+        assert!(diff.to_string().contains("compared 2"));
+        assert!(serde_json::to_string(&diff)
+            .unwrap()
+            .contains("delta_throughput"));
+
+        assert!(MetricsDiff::from_epochs_csv(&a, "epoch,slot\n0,1").is_err());
+    }
+
+    #[test]
+    fn test_path_length_histogram() {
+        assert!(path_length_histogram(&[]).is_empty());
+        let path = |hops: usize| -> Vec<String> { (0..=hops).map(|i| i.to_string()).collect() };
+        let paths = vec![path(3), path(1), path(3), path(0), path(1), path(3)];
+        assert_eq!(path_length_histogram(&paths), vec![(0, 1), (1, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn test_summarize_epochs_csv() {
+        let metrics = |epoch: u64, correlation: f64| EpochMetrics {
+            epoch,
+            timestamp: 0,
+            consensus_type: "POG".to_string(),
+            consensus_state: "pog".to_string(),
+            degree_contribution_correlation: correlation,
+            contribution_cv: 0.1 * epoch as f64,
+            hash_power_gini: 0.2,
+            decayed_stake: 1.5,
+            tx_generated: 0,
+            tx_included: 0,
+            inclusion_rate: 1.0,
+            validator_count: 4 + epoch as usize,
+            validators_added: 1,
+            validators_removed: 0,
+            empty_slots: 0,
+            throughput: 0.0,
+            gini_coefficient: 0.0,
+            stake_concentration: 0.0,
+            proposer_entropy: 0.0,
+            path_length_histogram: vec![(1, 2)],
+        };
+        let csv = [
+            EpochMetrics::to_csv_header(),
+            metrics(0, 0.2).to_csv_row(),
+            metrics(1, 0.4).to_csv_row(),
+            "1,2,3".to_string(),
+        ]
+        .join("\n");
+        let summary = summarize_epochs_csv(&csv).unwrap();
+        assert_eq!(summary.epochs, 2);
+        assert_eq!(summary.skipped_rows, 1);
+        assert!((summary.mean_degree_contribution_correlation - 0.3).abs() < 1e-9);
+        assert!((summary.final_contribution_cv - 0.1).abs() < 1e-9);
+        assert!((summary.final_hash_power_gini - 0.2).abs() < 1e-9);
+        assert!((summary.final_decayed_stake - 1.5).abs() < 1e-9);
+        assert_eq!(summary.final_validator_count, 5);
+        assert!((summary.validator_count_trend - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_degree_contribution_correlation() {
+        assert!((pearson_correlation(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0]) - 1.0).abs() < 1e-9);
+        assert!((pearson_correlation(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]) + 1.0).abs() < 1e-9);
+        // 方差为0或样本不足时为0
+        assert_eq!(pearson_correlation(&[1.0, 1.0, 1.0], &[1.0, 2.0, 3.0]), 0.0);
+        assert_eq!(pearson_correlation(&[1.0], &[2.0]), 0.0);
+
+        // 星形图：中心节点度为3，叶子节点度为1，重复的边不重复计算度
+        let mut graph = Graph::<String, ()>::new();
+        let nodes: Vec<_> = ["hub", "a", "b", "c"]
+            .iter()
+            .map(|name| graph.add_node(name.to_string()))
+            .collect();
+        for leaf in &nodes[1..] {
+            graph.add_edge(nodes[0], *leaf, ());
+        }
+        graph.add_edge(nodes[1], nodes[0], ());
+        let contribution = HashMap::from([("hub".to_string(), 0.7), ("a".to_string(), 0.1)]);
+        let correlation = degree_contribution_correlation(&graph, &contribution);
+        assert!(correlation > 0.9, "{}", correlation);
+        // 贡献集中在叶子节点时为负相关
+        let contribution = HashMap::from([("a".to_string(), 0.5), ("b".to_string(), 0.5)]);
+        assert!(degree_contribution_correlation(&graph, &contribution) < 0.0);
+    }
+
+    #[test]
+    fn test_coefficient_of_variation() {
+        assert_eq!(coefficient_of_variation(&[]), 0.0);
+        assert_eq!(coefficient_of_variation(&[0.0, 0.0]), 0.0);
+        // 贡献完全均匀时为0
+        assert_eq!(coefficient_of_variation(&[0.25; 4]), 0.0);
+        // 均值 2，总体标准差 1
+        assert!((coefficient_of_variation(&[1.0, 3.0]) - 0.5).abs() < 1e-9);
+        // 集中在一个节点时变异系数更高
+        assert!(
+            coefficient_of_variation(&[1.0, 0.0, 0.0, 0.0])
+                > coefficient_of_variation(&[0.4, 0.2, 0.2, 0.2])
+        );
+    }
+
+    #[test]
+    fn test_validator_set_churn() {
+        let set = |addresses: &[&str]| -> HashSet<String> {
+            addresses.iter().map(|a| a.to_string()).collect()
+        };
+        assert_eq!(validator_set_churn(&set(&[]), &set(&["a", "b"])), (2, 0));
+        assert_eq!(
+            validator_set_churn(&set(&["a", "b", "c"]), &set(&["b", "c", "d"])),
+            (1, 1)
+        );
+        assert_eq!(validator_set_churn(&set(&["a"]), &set(&["a"])), (0, 0));
+    }
+
+    #[test]
+    fn test_inclusion_tracker() {
+        use crate::blockchain::transaction::Transaction;
+        use crate::wallet::Wallet;
+
+        let wallet = Wallet::new();
+        let txs: Vec<Transaction> = (0..3)
+            .map(|i| Transaction::with_timestamp("to".to_string(), i, 0.0, 0, wallet.clone()))
+            .collect();
+        let mut tracker = InclusionTracker::default();
+        assert_eq!(tracker.inclusion_rate(), 1.0);
+        for (i, tx) in txs.iter().enumerate() {
+            tracker.record_generated(tx.hash.clone(), i as u64);
+        }
+        tracker.record_generated(txs[0].hash.clone(), 0);
+        assert_eq!(tracker.generated, 3);
+
+        let mut block = Block::gen_genesis_block();
+        block.body.transactions = vec![txs[0].clone(), txs[2].clone()];
+        tracker.record_block(&block);
+        tracker.record_block(&block);
+        assert_eq!(tracker.included, 2);
+        assert!((tracker.inclusion_rate() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(tracker.pending_before(1), 0);
+        assert_eq!(tracker.pending_before(2), 1);
+
+        assert_eq!(tracker.end_epoch(), (3, 2));
+        assert_eq!(tracker.end_epoch(), (0, 0));
+
+        // 区块被回滚后交易恢复为未上链，重新上链时只计一次
+        tracker.revert_block(&block);
+        assert_eq!(tracker.included, 0);
+        assert_eq!(tracker.pending_before(3), 3);
+        tracker.record_generated(txs[0].hash.clone(), 0);
+        assert_eq!(tracker.generated, 3);
+        tracker.record_block(&block);
+        assert_eq!(tracker.included, 2);
+
+        // 时隙 2 之前生成的交易都已上链，只有时隙 1 生成的交易超时
+        assert_eq!(tracker.expire_before(2), 1);
+        assert_eq!(tracker.expired, 1);
+        assert_eq!(tracker.pending_before(3), 1);
+        assert!((tracker.inclusion_rate() - 2.0 / 3.0).abs() < 1e-9);
+        // 回滚时只有仍在跟踪的 txs[2] 恢复为未上链
+        tracker.revert_block(&block);
+        assert_eq!(tracker.included, 1);
+    }
+
+    #[test]
+    fn test_generate_hash_power() {
+        let stakes = generate_stake_by_gini(50, 0.3, 8);
+        assert_eq!(
+            generate_hash_power(HashPowerDistribution::Stake, &stakes, 0.8, 8),
+            stakes
+        );
+        let uniform = generate_hash_power(HashPowerDistribution::Uniform, &stakes, 0.8, 8);
+        assert_eq!(calculate_gini(&uniform), 0.0);
+
+        let skewed = generate_hash_power(HashPowerDistribution::Skewed, &stakes, 0.8, 8);
+        assert_eq!(skewed.len(), 50);
+        assert!((calculate_gini(&skewed) - 0.8).abs() < 0.05);
+        assert!((skewed.iter().sum::<f64>() - 50.0).abs() < 1e-6);
+        // 与权益分布使用不同的打乱顺序
+        let stakes = generate_stake_by_gini(50, 0.8, 8);
+        assert_ne!(skewed, stakes);
+    }
+
+    #[test]
+    fn test_concentrate_stake() {
+        let stakes = vec![1.0, 2.0, 3.0, 4.0];
+        let concentrated = concentrate_stake(&stakes, &[2, 3], 0.6);
+        assert!((concentrated.iter().sum::<f64>() - 10.0).abs() < 1e-9);
+        assert!((concentrated[2] - 3.0).abs() < 1e-9);
+        assert!((concentrated[3] - 3.0).abs() < 1e-9);
+        // 其余节点保持原来的比例
+        assert!((concentrated[1] / concentrated[0] - 2.0).abs() < 1e-9);
+        // 没有攻击者时不变
+        assert_eq!(concentrate_stake(&stakes, &[], 0.6), stakes);
+    }
+
+    #[test]
+    fn test_attacker_report() {
+        let miners: Vec<String> = ["a", "s1", "b", "s2"]
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        let attackers: HashSet<String> = ["s1", "s2", "s3"].iter().map(|a| a.to_string()).collect();
+        let report = AttackerReport::new(0.25, &miners, &attackers);
+        assert_eq!(report.proposer_share, 0.5);
+        assert_eq!(report.sybil_advantage, 2.0);
+        assert_eq!(
+            AttackerReport::new(0.0, &miners, &attackers).sybil_advantage,
+            0.0
+        );
+        assert_eq!(
+            AttackerReport::new(0.5, &[], &attackers).proposer_share,
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_path_record_from_block() {
+        use crate::blockchain::block::Body;
+        use crate::blockchain::path::TransactionPaths;
+        use crate::blockchain::transaction::Transaction;
+        use crate::wallet::Wallet;
+
+        let sender = Wallet::new();
+        let relay = Wallet::new();
+        let miner = Wallet::new();
+        let transaction = Transaction::new(relay.address.clone(), 1, sender.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(relay.address.clone(), sender.clone());
+        transaction_paths.add_path(miner.address.clone(), relay);
+        let body = Body::new(
+            vec![transaction.clone()],
+            vec![transaction_paths.to_aggregated_signed_paths()],
+        );
+        let block = Block::new(3, 1, 2, String::new(), body, miner.clone()).unwrap();
+
+        let records = PathRecord::from_block(&block, Some(4));
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.tx_hash, transaction.hash);
+        assert_eq!((record.block_index, record.epoch, record.slot), (3, 1, 2));
+        assert_eq!(record.path.len(), 3);
+        assert_eq!(record.path[0], sender.address);
+        assert_eq!(record.path[2], miner.address);
+        assert_eq!(record.hop_timestamps.len(), 2);
+        assert!(record.hop_timestamps[0] <= record.hop_timestamps[1]);
+        assert!(record.hop_timestamps[0] >= record.created_at * 1000);
+        assert_eq!(record.ntd, Some(4));
+    }
+
+    /// 区块有 depth 个后续区块后才导出，同步来的区块使用当前 NTD，被替换的区块不导出
+    #[test]
+    fn test_path_export_finalized_records() {
+        use crate::blockchain::block::Body;
+        use crate::blockchain::path::TransactionPaths;
+        use crate::blockchain::transaction::Transaction;
+        use crate::wallet::Wallet;
+
+        let miner = Wallet::new();
+        let next_block = |blockchain: &Blockchain| {
+            let sender = Wallet::new();
+            let transaction = Transaction::new(miner.address.clone(), 1, sender.clone());
+            let mut transaction_paths = TransactionPaths::new(transaction.clone());
+            transaction_paths.add_path(miner.address.clone(), sender);
+            let body = Body::new(
+                vec![transaction],
+                vec![transaction_paths.to_aggregated_signed_paths()],
+            );
+            let index = blockchain.get_last_index() + 1;
+            Block::new(
+                index,
+                0,
+                index,
+                blockchain.get_last_hash(),
+                body,
+                miner.clone(),
+            )
+            .unwrap()
+        };
+
+        let mut export = PathExport {
+            file: None,
+            depth: 2,
+            exported: 0,
+            ntd: HashMap::new(),
+        };
+        let mut blockchain = Blockchain::new(Block::gen_genesis_block());
+        for _ in 0..2 {
+            let block = next_block(&blockchain);
+            export.record_block(&block, Some(1));
+            blockchain.add_block(block).unwrap();
+        }
+        assert!(export.finalized_records(&blockchain, Some(9)).is_empty());
+
+        // 高度 2 的区块被替换，替换后的区块没有接受时的 NTD
+        let replaced = blockchain.get_last_block();
+        blockchain.truncate_to(2);
+        for _ in 0..3 {
+            let block = next_block(&blockchain);
+            blockchain.add_block(block).unwrap();
+        }
+        let records = export.finalized_records(&blockchain, Some(9));
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[0].block_index, records[0].ntd), (1, Some(1)));
+        assert_eq!((records[1].block_index, records[1].ntd), (2, Some(9)));
+        assert_ne!(records[1].tx_hash, replaced.body.transactions[0].hash);
+        assert!(export.ntd.is_empty());
+
+        assert!(export.finalized_records(&blockchain, Some(9)).is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::blockchain::Blockchain;
+use crate::network::partition::common_prefix_len;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+
+/// 单个节点的链与 WorldState 的链的比较结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeConsistency {
+    pub index: u32,
+    pub height: u64, // 区块数量（含创世区块）
+    pub tip: String,
+    pub common_prefix: usize, // 与 WorldState 的链一致的最长前缀区块数（含创世区块）
+    pub diverged_at: Option<u64>, // 第一个不同区块的高度，只是落后或领先时为 None
+    pub consistent: bool,     // 最新区块与 WorldState 相同
+}
+
+/// 模拟结束时 WorldState 的链（上帝视角）与每个节点的链的比较
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConsistencyReport {
+    pub world_height: u64,
+    pub world_tip: String,
+    pub consistent_nodes: usize,
+    pub nodes: Vec<NodeConsistency>, // 按节点编号排序
+}
+
+impl ConsistencyReport {
+    pub fn new(world: &Blockchain, nodes: &HashMap<u32, Blockchain>) -> Self {
+        let world_hashes = block_hashes(world);
+        let mut reports: Vec<NodeConsistency> = nodes
+            .iter()
+            .map(|(index, blockchain)| {
+                let hashes = block_hashes(blockchain);
+                let common_prefix = common_prefix_len(&[world_hashes.clone(), hashes.clone()]);
+                let diverged_at = if common_prefix < world_hashes.len().min(hashes.len()) {
+                    Some(blockchain.blocks()[common_prefix].header.index)
+                } else {
+                    None
+                };
+                NodeConsistency {
+                    index: *index,
+                    height: blockchain.height(),
+                    tip: blockchain.get_last_hash(),
+                    common_prefix,
+                    diverged_at,
+                    consistent: hashes == world_hashes,
+                }
+            })
+            .collect();
+        reports.sort_by_key(|r| r.index);
+        ConsistencyReport {
+            world_height: world.height(),
+            world_tip: world.get_last_hash(),
+            consistent_nodes: reports.iter().filter(|r| r.consistent).count(),
+            nodes: reports,
+        }
+    }
+
+    /// 与 WorldState 的链出现不同区块的节点
+    pub fn diverged(&self) -> impl Iterator<Item = &NodeConsistency> {
+        self.nodes.iter().filter(|r| r.diverged_at.is_some())
+    }
+
+    pub fn write(&self, path: &str) -> std::io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
+
+fn block_hashes(blockchain: &Blockchain) -> Vec<String> {
+    blockchain
+        .blocks()
+        .iter()
+        .map(|b| b.header.hash.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::block::{Block, Body};
+    use crate::wallet::Wallet;
+
+    fn extend(blockchain: &mut Blockchain, wallet: &Wallet, slot: u64) {
+        let last = blockchain.get_last_block();
+        let block = Block::new(
+            last.header.index + 1,
+            0,
+            slot,
+            last.header.hash.clone(),
+            Body::new(vec![], vec![]),
+            wallet.clone(),
+        )
+        .unwrap();
+        blockchain.add_block(block).unwrap();
+    }
+
+    #[test]
+    fn test_consistency_report() {
+        let wallet = Wallet::new();
+        let mut world = Blockchain::new(Block::gen_genesis_block());
+        extend(&mut world, &wallet, 1);
+        extend(&mut world, &wallet, 2);
+
+        // 节点0与 WorldState 一致，节点1落后一个区块，节点2在高度2分叉
+        let same = world.clone();
+        let mut behind = world.clone();
+        behind.pop_block();
+        let mut forked = behind.clone();
+        extend(&mut forked, &wallet, 3);
+        let nodes = HashMap::from([(0, same), (1, behind), (2, forked)]);
+
+        let report = ConsistencyReport::new(&world, &nodes);
+        assert_eq!(report.world_height, 3);
+        assert_eq!(report.consistent_nodes, 1);
+        let prefixes: Vec<usize> = report.nodes.iter().map(|r| r.common_prefix).collect();
+        assert_eq!(prefixes, vec![3, 2, 2]);
+        let diverged: Vec<(u32, Option<u64>)> = report
+            .diverged()
+            .map(|r| (r.index, r.diverged_at))
+            .collect();
+        assert_eq!(diverged, vec![(2, Some(2))]);
+        assert!(!report.nodes[1].consistent);
+        assert_eq!(report.nodes[1].diverged_at, None);
+    }
+}
@@ -116,6 +116,16 @@ impl BANetwork {
 }
 
 //Erdős–Rényi(ER)拓扑
+pub const DEFAULT_ER_PROBABILITY: f64 = 0.2;
+
+/// ER 图的连通阈值 ln(n)/n，连边概率低于该值时图很可能不连通
+pub fn er_connectivity_threshold(node_num: usize) -> f64 {
+    if node_num < 2 {
+        return 0.0;
+    }
+    (node_num as f64).ln() / node_num as f64
+}
+
 pub fn random_er_graph(nodes_address: Vec<String>, probability: f64) -> Graph<String, ()> {
     let mut graph = Graph::<String, ()>::new();
     let mut rng = rand::thread_rng();
@@ -186,7 +196,9 @@ pub fn print_graph(graph: &Graph<String, ()>) {
 
 #[cfg(test)]
 mod tests {
-    use crate::network::graph::{print_graph, BANetwork};
+    use crate::network::graph::{
+        er_connectivity_threshold, print_graph, random_er_graph, BANetwork,
+    };
     use log::info;
     use petgraph::dot::{Config, Dot};
     use petgraph::graph::NodeIndex;
@@ -324,4 +336,15 @@ mod tests {
             info!("Edge: {} -> {}", graph[source], graph[target]);
         }
     }
+
+    #[test]
+    fn test_er_probability() {
+        assert_eq!(er_connectivity_threshold(1), 0.0);
+        assert!((er_connectivity_threshold(20) - 20f64.ln() / 20.0).abs() < 1e-12);
+        // 节点越多，保持连通所需的连边概率越低
+        assert!(er_connectivity_threshold(200) < er_connectivity_threshold(20));
+
+        let nodes_address: Vec<String> = (0..10).map(|i| format!("addr{}", i)).collect();
+        assert_eq!(random_er_graph(nodes_address, 1.0).edge_count(), 45);
+    }
 }
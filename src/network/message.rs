@@ -1,7 +1,8 @@
 use crate::blockchain::block::Block;
 use crate::blockchain::path::TransactionPaths;
-use crate::consensus::{RandaoSeed, Validator};
-use crate::network::world_state::SlotManager;
+use crate::consensus::{ConsensusType, RandaoSeed, Validator, ValidatorSetDiff};
+use crate::network::node::MempoolReport;
+use crate::network::world_state::{ExpectedProposer, SlotManager};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fmt::{Display, Formatter};
@@ -22,6 +23,23 @@ impl Message {
         }
     }
 
+    /// 区块公告：只携带区块哈希，邻居没有该区块时再请求
+    pub fn new_announce_block_msg(hash: String, from: String) -> Message {
+        Message {
+            msg_type: MessageType::AnnounceBlock,
+            data: hash.into_bytes(),
+            from,
+        }
+    }
+
+    pub fn new_request_block_msg(hash: String, from: String) -> Message {
+        Message {
+            msg_type: MessageType::RequestBlock,
+            data: hash.into_bytes(),
+            from,
+        }
+    }
+
     pub fn new_transaction_paths_msg(transaction_paths: TransactionPaths, from: String) -> Message {
         Message {
             msg_type: MessageType::SendTransactionPaths,
@@ -30,6 +48,35 @@ impl Message {
         }
     }
 
+    /// 多笔交易合并为一条消息，减少通道消息数
+    pub fn new_transaction_paths_batch_msg(batch: Vec<TransactionPaths>, from: String) -> Message {
+        Message {
+            msg_type: MessageType::SendTransactionPathsBatch,
+            data: serde_json::to_vec(&batch).unwrap_or_default(),
+            from,
+        }
+    }
+
+    /// push-pull 传播：只公告交易哈希和本节点持有的路径长度，邻居再请求没有的交易
+    pub fn new_announce_transactions_msg(
+        announcements: Vec<(String, usize)>,
+        from: String,
+    ) -> Message {
+        Message {
+            msg_type: MessageType::AnnounceTransactions,
+            data: serde_json::to_vec(&announcements).unwrap_or_default(),
+            from,
+        }
+    }
+
+    pub fn new_request_transactions_msg(hashes: Vec<String>, from: String) -> Message {
+        Message {
+            msg_type: MessageType::RequestTransactions,
+            data: serde_json::to_vec(&hashes).unwrap_or_default(),
+            from,
+        }
+    }
+
     pub fn new_generate_block_msg() -> Message {
         Message {
             msg_type: MessageType::GenerateBlock,
@@ -46,10 +93,19 @@ impl Message {
         }
     }
 
-    pub fn new_send_randao_seed_msg() -> Message {
+    pub fn new_generate_transaction_paths_batch_msg(tos: Vec<String>) -> Message {
+        Message {
+            msg_type: MessageType::GenerateTransactionPathsBatch,
+            data: serde_json::to_vec(&tos).unwrap_or_default(),
+            from: "".to_string(),
+        }
+    }
+
+    /// 通知节点为给定的时隙发送 RANDAO 种子，data 为 (epoch, slot)
+    pub fn new_send_randao_seed_msg(epoch: u64, slot: u64) -> Message {
         Message {
             msg_type: MessageType::SendRandaoSeed,
-            data: vec![],
+            data: serde_json::to_vec(&(epoch, slot)).unwrap_or_default(),
             from: "".to_string(),
         }
     }
@@ -86,6 +142,79 @@ impl Message {
         }
     }
 
+    pub fn new_update_proposer_msg(proposer: &ExpectedProposer) -> Message {
+        Message {
+            msg_type: MessageType::UpdateProposer,
+            data: serde_json::to_vec(proposer).unwrap_or_default(),
+            from: "".to_string(),
+        }
+    }
+
+    pub fn new_update_validator_set_msg(validators: &[Validator]) -> Message {
+        Message {
+            msg_type: MessageType::UpdateValidatorSet,
+            data: serde_json::to_vec(validators).unwrap_or_default(),
+            from: "".to_string(),
+        }
+    }
+
+    pub fn new_update_validator_set_diff_msg(diff: &ValidatorSetDiff) -> Message {
+        Message {
+            msg_type: MessageType::UpdateValidatorSetDiff,
+            data: serde_json::to_vec(diff).unwrap_or_default(),
+            from: "".to_string(),
+        }
+    }
+
+    pub fn new_update_partition_msg(addresses: Vec<String>) -> Message {
+        Message {
+            msg_type: MessageType::UpdatePartition,
+            data: serde_json::to_vec(&addresses).unwrap_or_default(),
+            from: "".to_string(),
+        }
+    }
+
+    /// 共识切换后通知节点新的共识类型
+    pub fn new_switch_consensus_msg(consensus: ConsensusType) -> Message {
+        Message {
+            msg_type: MessageType::SwitchConsensus,
+            data: serde_json::to_vec(&consensus).unwrap_or_default(),
+            from: "".to_string(),
+        }
+    }
+
+    pub fn new_query_mempool_msg() -> Message {
+        Message {
+            msg_type: MessageType::QueryMempool,
+            data: vec![],
+            from: "".to_string(),
+        }
+    }
+
+    pub fn new_request_mempool_health_msg() -> Message {
+        Message {
+            msg_type: MessageType::RequestMempoolHealth,
+            data: vec![],
+            from: "".to_string(),
+        }
+    }
+
+    pub fn new_response_mempool_health_msg(report: &MempoolReport, from: String) -> Message {
+        Message {
+            msg_type: MessageType::ResponseMempoolHealth,
+            data: serde_json::to_vec(report).unwrap_or_default(),
+            from,
+        }
+    }
+
+    pub fn new_query_confirmations_msg(tx_hash: String) -> Message {
+        Message {
+            msg_type: MessageType::QueryConfirmations,
+            data: tx_hash.into_bytes(),
+            from: "".to_string(),
+        }
+    }
+
     pub fn new_print_blockchain_msg() -> Message {
         Message {
             msg_type: MessageType::PrintBlockchain,
@@ -102,6 +231,14 @@ impl Message {
         }
     }
 
+    pub fn new_block_sync_timeout_msg(seq: u64) -> Message {
+        Message {
+            msg_type: MessageType::BlockSyncTimeout,
+            data: seq.to_le_bytes().to_vec(),
+            from: "".to_string(),
+        }
+    }
+
     pub fn new_response_block_sync_msg(blocks: Vec<Block>, from: String) -> Message {
         let blocks_json = serde_json::to_string(&blocks).unwrap_or_default();
         Message {
@@ -143,14 +280,40 @@ impl Message {
             from: "".to_string(),
         }
     }
+
+    pub fn new_transaction_generated_msg(tx_hash: String) -> Message {
+        Message {
+            msg_type: MessageType::TransactionGenerated,
+            data: tx_hash.into_bytes(),
+            from: "".to_string(),
+        }
+    }
+
+    pub fn new_node_online_status_msg(address: String, online: bool) -> Message {
+        let payload = serde_json::json!({
+            "address": address,
+            "online": online
+        });
+        Message {
+            msg_type: MessageType::NodeOnlineStatus,
+            data: payload.to_string().into_bytes(),
+            from: "".to_string(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum MessageType {
     SendBlock,
+    AnnounceBlock, // 区块公告：只携带区块哈希
+    RequestBlock,  // 按哈希请求公告过的区块
     SendTransactionPaths,
+    SendTransactionPathsBatch, // 一条消息携带多笔交易路径
+    AnnounceTransactions,      // push-pull 传播：公告交易哈希
+    RequestTransactions,       // push-pull 传播：请求公告过的交易
     GenerateBlock,
     GenerateTransactionPaths,
+    GenerateTransactionPathsBatch, // 一次生成多笔交易
     SendRandaoSeed,
     ReceiveRandaoSeed,
     BecomeValidator,
@@ -159,9 +322,21 @@ pub enum MessageType {
     PrintBlockchain,
     RequestBlockSync,
     ResponseBlockSync,
-    UpdateValidatorStake,  // Node 通知 WorldState 更新 Validator 的 stake
-    UpdateNodeBalance,     // WorldState 通知 Node 更新其 balance
-    BlockProductionFailed, // Node 报告出块失败事件
+    UpdateValidatorStake,   // Node 通知 WorldState 更新 Validator 的 stake
+    UpdateNodeBalance,      // WorldState 通知 Node 更新其 balance
+    BlockProductionFailed,  // Node 报告出块失败事件
+    BlockSyncTimeout,       // Node 自己的块同步请求超时
+    UpdateValidatorSet,     // WorldState 通知 Node 当前的验证者集合
+    UpdateValidatorSetDiff, // WorldState 通知 Node 验证者集合的增量
+    UpdatePartition,        // 网络分区实验：断开给定地址的邻居，地址为空时恢复
+    SwitchConsensus,        // WorldState 在 epoch 开始时切换共识后通知 Node 新的共识类型
+    QueryMempool,           // 让节点输出交易缓存的交易数和路径长度分布
+    QueryConfirmations,     // 让节点输出给定交易的确认数
+    RequestMempoolHealth,   // 让节点向 WorldState 上报内存池的交易数和丢弃计数
+    ResponseMempoolHealth,  // Node 对 RequestMempoolHealth 的响应
+    NodeOnlineStatus,       // Node 向 WorldState 报告上线/下线
+    TransactionGenerated,   // Node 向 WorldState 报告生成的交易哈希，用于统计上链率
+    UpdateProposer,         // WorldState 通知 Node 本时隙选出的出块者
 }
 
 impl Display for MessageType {
@@ -173,6 +348,21 @@ impl Display for MessageType {
             MessageType::SendTransactionPaths => {
                 write!(f, "SendTransactionPaths")
             }
+            MessageType::SendTransactionPathsBatch => {
+                write!(f, "SendTransactionPathsBatch")
+            }
+            MessageType::AnnounceBlock => {
+                write!(f, "AnnounceBlock")
+            }
+            MessageType::RequestBlock => {
+                write!(f, "RequestBlock")
+            }
+            MessageType::AnnounceTransactions => {
+                write!(f, "AnnounceTransactions")
+            }
+            MessageType::RequestTransactions => {
+                write!(f, "RequestTransactions")
+            }
             MessageType::GenerateBlock => {
                 write!(f, "GenerateBlock")
             }
@@ -196,6 +386,9 @@ impl Display for MessageType {
             MessageType::GenerateTransactionPaths => {
                 write!(f, "GenerateTransactionPaths")
             }
+            MessageType::GenerateTransactionPathsBatch => {
+                write!(f, "GenerateTransactionPathsBatch")
+            }
 
             MessageType::PrintBlockchain => {
                 write!(f, "PrintBlockchain")
@@ -215,6 +408,42 @@ impl Display for MessageType {
             MessageType::BlockProductionFailed => {
                 write!(f, "BlockProductionFailed")
             }
+            MessageType::BlockSyncTimeout => {
+                write!(f, "BlockSyncTimeout")
+            }
+            MessageType::UpdateValidatorSet => {
+                write!(f, "UpdateValidatorSet")
+            }
+            MessageType::UpdateValidatorSetDiff => {
+                write!(f, "UpdateValidatorSetDiff")
+            }
+            MessageType::UpdatePartition => {
+                write!(f, "UpdatePartition")
+            }
+            MessageType::SwitchConsensus => {
+                write!(f, "SwitchConsensus")
+            }
+            MessageType::QueryMempool => {
+                write!(f, "QueryMempool")
+            }
+            MessageType::QueryConfirmations => {
+                write!(f, "QueryConfirmations")
+            }
+            MessageType::RequestMempoolHealth => {
+                write!(f, "RequestMempoolHealth")
+            }
+            MessageType::ResponseMempoolHealth => {
+                write!(f, "ResponseMempoolHealth")
+            }
+            MessageType::NodeOnlineStatus => {
+                write!(f, "NodeOnlineStatus")
+            }
+            MessageType::TransactionGenerated => {
+                write!(f, "TransactionGenerated")
+            }
+            MessageType::UpdateProposer => {
+                write!(f, "UpdateProposer")
+            }
         }
     }
 }
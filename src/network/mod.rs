@@ -1,53 +1,420 @@
 use crate::blockchain::block::Block;
 use crate::blockchain::Blockchain;
-use crate::consensus::ConsensusType;
+use crate::consensus::pog::PogConsensus;
+use crate::consensus::pos::PosConsensus;
+use crate::consensus::scripted;
+use crate::consensus::seed::{SeedSourceType, VdfSeedSource};
+use crate::consensus::{Consensus, ConsensusSwitch, ConsensusType, Validator};
+use crate::events;
+use crate::metrics::{
+    AttackerReport, HashPowerDistribution, InclusionTracker, MetricsSinkKind, PathExport,
+};
+use crate::network::consistency::ConsistencyReport;
 use crate::network::graph::TopologyType;
-use crate::network::message::Message;
-use crate::network::node::{Neighbor, Node, NodeType};
+use crate::network::message::{Message, MessageType};
+use crate::network::node::{
+    MempoolHealth, MempoolReport, MessageCounts, MessageStats, Neighbor, Node, NodeType,
+};
+use crate::network::partition::{PartitionDriver, PartitionReport};
+use crate::network::region::RegionLatency;
 use crate::network::world_state::WorldState;
+use crate::wallet::KeyRegistry;
+use clap::ValueEnum;
 use futures::future::join_all;
-use log::{debug, info};
+use log::{debug, error, info, warn};
+use petgraph::Graph;
 use rand::prelude::*;
 use rand::thread_rng;
 use rand_distr::{Distribution, Poisson};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::RwLock;
 use tokio::time;
 
+pub mod consistency;
 pub mod graph;
 pub mod message;
 pub mod node;
+pub mod partition;
+pub mod region;
+#[cfg(test)]
+pub(crate) mod test_fixtures;
 pub mod world_state;
 
-pub async fn start_network(
-    node_num: u32,
-    sybil_node_num: u32,
-    fake_node_num: u32,
-    unstable_node_num: u32,
-    offline_probability: f64,
+/// 模拟参数，与命令行参数一一对应，默认值与命令行默认值一致
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    pub node_num: u32,
+    pub sybil_node_num: u32,
+    pub fake_node_num: u32,
+    pub attacker_stake_fraction: f64, // 集中到恶意节点上的总 stake 比例，0 表示不调整
+    pub unstable_node_num: u32,
+    pub offline_probability: f64,
+    pub offline_duration_epochs: u64,
+    pub offline_stake_decay: f64, // 离线的验证者每个 epoch 扣减的 stake 比例
+    pub max_stake_share: f64,     // 单个验证者 stake 占总量的上限，1 表示不限制
+    pub max_validators: usize,    // 验证者集合的大小上限，按 stake 排名准入，0 表示不限制
+    pub bootstrap_node_num: u32,
+    pub relay_node_num: u32,
+    pub validator_fraction: f64, // 注册为验证者的节点比例 (0, 1]（不含女巫和中继节点），其余节点只转发
+    pub max_reorg_depth: u64,
+    pub trans_num_per_second: u32,
+    pub tx_batch_size: usize, // 每条消息最多携带的交易数
+    pub tx_interval_ms: u64,  // 交易生成器的间隔（毫秒）
+    pub tx_burst_cap: usize,  // 每个间隔最多发送的交易数，0 表示不限制
+    pub slot_duration: u64,
+    pub slot_per_epoch: u64,
+    pub pow_difficulty: usize,
+    pub pow_max_threads: usize,
+    pub consensus: ConsensusType,
+    pub schedule: Option<String>, // scripted 共识的出块者序列文件
+    pub schedule_fallback: ConsensusType, // 出块者序列用完后使用的共识
+    pub consensus_switches: Vec<ConsensusSwitch>, // 在指定 epoch 开始时切换共识
+    pub topology: TopologyType,
+    pub er_probability: f64, // ER 拓扑的连边概率
+    pub regions: u32,        // 地理区域数，1 表示不模拟延迟
+    pub intra_region_latency_ms: u64,
+    pub inter_region_latency_ms: u64,
+    pub region_latency_file: Option<String>, // 区域延迟矩阵文件，代替 intra/inter 默认模型
+    pub partition_at_epoch: Option<u64>,     // 网络分区开始的 epoch，None 表示不分区
+    pub partition_heal_epoch: Option<u64>,   // 网络分区恢复的 epoch，None 表示不恢复
+    pub partition_fraction: f64,             // 第一个分区的节点比例
+    pub gini: f64,
+    pub hash_power_distribution: HashPowerDistribution,
+    pub hash_power_gini: f64, // skewed 算力分布的目标Gini系数
+    pub transaction_fee: f64,
+    pub transaction_tip: f64, // 交易小费上限，每笔交易的小费在 0 到该值之间随机
+    pub graph_seed: u64,
+    pub base_reward: f64,
+    pub relayer_reward_fraction: f64, // 区块补贴中分给路径参与者的比例，适用于所有共识
+    pub universal_reward_fraction: f64, // POS/POW/MINOTAUR 奖励中由所有验证者平分的比例
+    pub pog_warmup_slots: u64,
+    pub pog_epoch_decay: f64, // POG 每个 epoch 结束时贡献历史乘以的系数，1 表示不衰减
+    pub max_tx_per_block: usize,
+    pub wallet_seed: u64,
+    pub genesis_block: Block,
+    pub seed_source: SeedSourceType,
+    pub vdf_difficulty: u64,
+    pub seed_reveal_window: f64, // 每个时隙开始后接受 RANDAO 种子的时间占时隙的比例，1 表示整个时隙
+    pub randao_committee_size: usize, // 每个时隙提交 RANDAO 种子的验证者数，0 表示所有验证者
+    pub node_state_dir: Option<String>, // 每个节点的状态文件目录，None 表示不保存
+    pub tx_expiry_slots: u64,
+    pub tx_cache_size: Option<usize>, // 交易缓存容量，None 表示等于 max_tx_per_block
+    pub tx_source_nodes: Option<TxSourceNodes>,
+    pub block_verify_sample: f64,
+    pub proposer_verify_sample: f64, // 出块时抽样验证路径的百分比
+    pub max_tx_per_slot: usize,      // 每个节点每时隙最多发起的交易数，0 表示不限速
+    pub record_seeds: bool,          // 时隙指标中记录选择出块者的种子
+    pub export_paths: bool,          // 导出最终确定的交易传播路径到 paths_export.jsonl
+    pub export_contribution: bool,   // 导出 POG 每个时隙的网络贡献到 contribution.jsonl
+    pub metrics_sinks: Vec<MetricsSinkKind>, // 指标输出格式，可同时输出多种
+    pub proposer_mode: ProposerMode,
+    pub propagation_strategy: PropagationStrategy, // 交易的传播方式
+    pub announce_blocks: bool,                     // 区块先公告哈希，邻居请求时才发送完整区块
+    pub record_events: Option<String>,             // 记录处理的每条消息的事件日志文件
+    pub replay_events: Option<String>,             // 重放的事件日志文件，设置后不运行正常的模拟
+    pub print_interval: u64,                       // Printer 的输出间隔（秒），0 表示不输出
+    pub print_mode: PrintMode,
+    pub run_seconds: u64, // 运行时长（秒），0 表示一直运行
+    pub run_slots: u64,   // 运行的时隙数，0 表示不按时隙数停止
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        SimulationConfig {
+            node_num: 20,
+            sybil_node_num: 0,
+            fake_node_num: 0,
+            attacker_stake_fraction: 0.0,
+            unstable_node_num: 0,
+            offline_probability: 0.5,
+            offline_duration_epochs: 1,
+            offline_stake_decay: 0.0,
+            max_stake_share: 1.0,
+            max_validators: 0,
+            bootstrap_node_num: 0,
+            relay_node_num: 0,
+            validator_fraction: 1.0,
+            max_reorg_depth: node::DEFAULT_MAX_REORG_DEPTH,
+            trans_num_per_second: 10,
+            tx_batch_size: 1,
+            tx_interval_ms: 1000,
+            tx_burst_cap: 0,
+            slot_duration: 3,
+            slot_per_epoch: 5,
+            pow_difficulty: 20,
+            pow_max_threads: 2,
+            consensus: ConsensusType::POG,
+            schedule: None,
+            schedule_fallback: ConsensusType::POS,
+            consensus_switches: Vec::new(),
+            topology: TopologyType::BA,
+            er_probability: graph::DEFAULT_ER_PROBABILITY,
+            regions: 1,
+            intra_region_latency_ms: 10,
+            inter_region_latency_ms: 100,
+            region_latency_file: None,
+            partition_at_epoch: None,
+            partition_heal_epoch: None,
+            partition_fraction: 0.5,
+            gini: 0.0,
+            hash_power_distribution: HashPowerDistribution::Stake,
+            hash_power_gini: 0.5,
+            transaction_fee: 0.0,
+            transaction_tip: 0.0,
+            graph_seed: 888,
+            base_reward: 1.0,
+            relayer_reward_fraction: 0.0,
+            universal_reward_fraction: 0.0,
+            pog_warmup_slots: 0,
+            pog_epoch_decay: 1.0,
+            max_tx_per_block: 200,
+            wallet_seed: 8,
+            genesis_block: Block::gen_genesis_block(),
+            seed_source: SeedSourceType::Randao,
+            vdf_difficulty: 0,
+            seed_reveal_window: 1.0,
+            randao_committee_size: 0,
+            node_state_dir: None,
+            tx_expiry_slots: 0,
+            tx_cache_size: None,
+            tx_source_nodes: None,
+            block_verify_sample: 0.0,
+            proposer_verify_sample: 100.0,
+            max_tx_per_slot: 0,
+            record_seeds: false,
+            export_paths: false,
+            export_contribution: false,
+            metrics_sinks: vec![MetricsSinkKind::Csv],
+            proposer_mode: ProposerMode::Central,
+            propagation_strategy: PropagationStrategy::Flood,
+            announce_blocks: false,
+            record_events: None,
+            replay_events: None,
+            print_interval: 10,
+            print_mode: PrintMode::Dump,
+            run_seconds: 0,
+            run_slots: 0,
+        }
+    }
+}
+
+/// 模拟结束时各节点的区块链和验证者的 stake
+#[derive(Debug, Clone, Default)]
+pub struct SimulationResult {
+    pub blockchains: HashMap<u32, Blockchain>,
+    pub stakes: HashMap<String, f64>,
+    pub partition: Option<PartitionReport>, // 网络分区实验的结果
+    pub attacker: Option<AttackerReport>,   // 存在女巫节点时，攻击者的出块份额
+    pub inclusion: InclusionTracker,        // WorldState 统计的交易上链情况
+    pub consistency: ConsistencyReport,     // 结束时 WorldState 的链与各节点的链的比较
+    pub key_registry: KeyRegistry,          // 本次模拟的节点登记的 BLS 公钥
+    pub messages: MessageCounts,            // 节点之间发出的交易和区块消息数
+}
+
+/// 不限运行时长时，链上交易布隆过滤器按这么多个 epoch 的交易量预留容量，超出后自动扩容
+const TX_FILTER_EPOCHS: u64 = 100;
+
+/// 按配置的交易量估计运行期间上链的交易数，作为链上交易布隆过滤器的容量
+/// 每个时隙上链的交易不超过生成的交易数和 max_tx_per_block
+fn tx_filter_capacity(
     trans_num_per_second: u32,
+    max_tx_per_block: usize,
     slot_duration: u64,
     slot_per_epoch: u64,
-    pow_difficulty: usize,
-    pow_max_threads: usize,
-    consensus: ConsensusType,
-    topology: TopologyType,
-    gini: f64,
-    transaction_fee: f64,
-    graph_seed: u64,
-    base_reward: f64,
-    max_tx_per_block: usize,
-    wallet_seed: u64,
-) {
+    run_seconds: u64,
+    run_slots: u64,
+) -> usize {
+    let per_slot =
+        (trans_num_per_second as u64 * slot_duration.max(1)).min(max_tx_per_block as u64);
+    let slots = if run_slots > 0 {
+        run_slots
+    } else if run_seconds > 0 {
+        run_seconds.div_ceil(slot_duration.max(1))
+    } else {
+        slot_per_epoch.max(1) * TX_FILTER_EPOCHS
+    };
+    per_slot.saturating_mul(slots).max(1) as usize
+}
+
+/// 以给定参数运行一次模拟
+/// run_seconds 或 run_slots 大于 0 时运行指定时长或时隙数后停止所有任务并返回结果，先到者为准
+/// 两者都为 0 时一直运行
+pub async fn run_simulation(config: SimulationConfig) -> SimulationResult {
+    let SimulationConfig {
+        node_num,
+        sybil_node_num,
+        fake_node_num,
+        attacker_stake_fraction,
+        unstable_node_num,
+        offline_probability,
+        offline_duration_epochs,
+        offline_stake_decay,
+        max_stake_share,
+        max_validators,
+        bootstrap_node_num,
+        relay_node_num,
+        validator_fraction,
+        max_reorg_depth,
+        trans_num_per_second,
+        tx_batch_size,
+        tx_interval_ms,
+        tx_burst_cap,
+        slot_duration,
+        slot_per_epoch,
+        pow_difficulty,
+        pow_max_threads,
+        consensus,
+        schedule,
+        schedule_fallback,
+        consensus_switches,
+        topology,
+        er_probability,
+        regions,
+        intra_region_latency_ms,
+        inter_region_latency_ms,
+        region_latency_file,
+        partition_at_epoch,
+        partition_heal_epoch,
+        partition_fraction,
+        gini,
+        hash_power_distribution,
+        hash_power_gini,
+        transaction_fee,
+        transaction_tip,
+        graph_seed,
+        base_reward,
+        relayer_reward_fraction,
+        universal_reward_fraction,
+        pog_warmup_slots,
+        pog_epoch_decay,
+        max_tx_per_block,
+        wallet_seed,
+        genesis_block,
+        seed_source,
+        vdf_difficulty,
+        seed_reveal_window,
+        randao_committee_size,
+        node_state_dir,
+        tx_expiry_slots,
+        tx_cache_size,
+        tx_source_nodes,
+        block_verify_sample,
+        proposer_verify_sample,
+        max_tx_per_slot,
+        record_seeds,
+        export_paths,
+        export_contribution,
+        metrics_sinks,
+        proposer_mode,
+        propagation_strategy,
+        announce_blocks,
+        record_events,
+        replay_events,
+        print_interval,
+        print_mode,
+        run_seconds,
+        run_slots,
+    } = config;
     info!("Consensus Type is {}", consensus);
+    // scripted 共识先按出块者序列出块，WorldState 和节点都按 schedule_fallback 运行
+    let consensus_name = consensus.to_string();
+    let (consensus, proposer_schedule) = if consensus == ConsensusType::SCRIPTED {
+        let entries = match &schedule {
+            Some(path) => match scripted::load_schedule(path) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    error!("Load proposer schedule failed: {}", e);
+                    return SimulationResult::default();
+                }
+            },
+            None => {
+                warn!("Scripted consensus without schedule, always use the fallback consensus");
+                vec![]
+            }
+        };
+        let fallback = match schedule_fallback {
+            ConsensusType::POW | ConsensusType::SCRIPTED => {
+                warn!(
+                    "Schedule fallback {} is not supported, fall back to pos",
+                    schedule_fallback
+                );
+                ConsensusType::POS
+            }
+            fallback => fallback,
+        };
+        info!(
+            "Proposer schedule of {} slots, then {}",
+            entries.len(),
+            fallback
+        );
+        (fallback, Some(entries))
+    } else {
+        if schedule.is_some() {
+            warn!("Proposer schedule is only used by scripted consensus, ignored");
+        }
+        (consensus, None)
+    };
+    // 节点按启动时的共识挖矿和计时，不支持与 PoW 互相切换
+    let consensus_switches: Vec<ConsensusSwitch> = if consensus == ConsensusType::POW {
+        if !consensus_switches.is_empty() {
+            warn!("Consensus switch is not supported by pow, ignored");
+        }
+        vec![]
+    } else {
+        consensus_switches
+            .into_iter()
+            .filter(|switch| match switch.consensus {
+                ConsensusType::POW | ConsensusType::SCRIPTED => {
+                    warn!("Consensus switch {} is not supported, ignored", switch);
+                    false
+                }
+                _ => true,
+            })
+            .collect()
+    };
+    for switch in consensus_switches.iter() {
+        info!(
+            "Switch consensus to {} at epoch {}",
+            switch.consensus, switch.epoch
+        );
+    }
+    if let Some(path) = &record_events {
+        match events::start_recording(path) {
+            Ok(()) => info!("Record events to {}", path),
+            Err(e) => error!("Record events to {} failed: {}", path, e),
+        }
+    }
 
     //1. new blockchain
-    let genesis_block = Block::gen_genesis_block();
     let bc = Blockchain::new(genesis_block.clone());
     info!("Generate genesis block");
 
     //2. world state
+    let relayer_reward_fraction = if (0.0..=1.0).contains(&relayer_reward_fraction) {
+        relayer_reward_fraction
+    } else {
+        warn!(
+            "Relayer reward fraction {} is out of [0, 1], fall back to 0",
+            relayer_reward_fraction
+        );
+        0.0
+    };
+    let pog_epoch_decay = if (0.0..=1.0).contains(&pog_epoch_decay) {
+        pog_epoch_decay
+    } else {
+        warn!(
+            "POG epoch decay {} is out of [0, 1], fall back to 1",
+            pog_epoch_decay
+        );
+        1.0
+    };
     let (mut world, world_sender, world_receiver) = WorldState::new(
         genesis_block,
         consensus,
@@ -57,11 +424,99 @@ pub async fn start_network(
         pow_difficulty,
         pow_max_threads,
         base_reward,
+        relayer_reward_fraction,
+        universal_reward_fraction,
+        pog_warmup_slots,
+        export_contribution,
+    );
+    world.set_pog_epoch_decay(pog_epoch_decay);
+    let mut source = seed_source.build(graph_seed);
+    if vdf_difficulty > 0 {
+        source = Box::new(VdfSeedSource::new(source, vdf_difficulty));
+    }
+    world.set_seed_source(source);
+    world.set_randao_committee_size(randao_committee_size);
+    if seed_reveal_window > 0.0 && seed_reveal_window <= 1.0 {
+        world.set_seed_reveal_window(seed_reveal_window);
+    } else {
+        warn!(
+            "Seed reveal window {} is out of (0, 1], fall back to 1",
+            seed_reveal_window
+        );
+    }
+    world.set_record_seeds(record_seeds);
+    if export_paths {
+        // 超过最大重组深度的区块不会再被替换，视为最终确定
+        world.set_path_export(PathExport::new(max_reorg_depth));
+    }
+    for kind in &metrics_sinks {
+        world.add_metrics_sink(kind.build(&consensus_name));
+    }
+    if (0.0..=1.0).contains(&offline_stake_decay) {
+        world.set_offline_stake_decay(offline_stake_decay);
+    } else {
+        warn!(
+            "Offline stake decay {} is out of [0, 1], fall back to 0",
+            offline_stake_decay
+        );
+    }
+    if max_stake_share > 0.0 && max_stake_share <= 1.0 {
+        world.set_max_stake_share(max_stake_share);
+    } else {
+        warn!(
+            "Max stake share {} is out of (0, 1], fall back to 1",
+            max_stake_share
+        );
+    }
+    if max_validators > 0 {
+        world.set_max_validators(max_validators);
+        info!(
+            "Max validators[{}]: registrations beyond the cap wait by stake rank",
+            max_validators
+        );
+    }
+    let proposer_mode = if proposer_mode == ProposerMode::Local
+        && (proposer_schedule.is_some()
+            || !consensus_switches.is_empty()
+            || new_local_consensus(consensus, pog_warmup_slots, pog_epoch_decay).is_none())
+    {
+        warn!(
+            "Proposer mode local is not supported by {}, fall back to central",
+            consensus_name
+        );
+        ProposerMode::Central
+    } else {
+        proposer_mode
+    };
+    world.set_proposer_mode(proposer_mode);
+    world.set_consensus_switches(consensus_switches);
+    info!(
+        "Generate world state, seed source[{}], vdf difficulty[{}]",
+        seed_source, vdf_difficulty
     );
-    info!("Generate world state");
 
     //3. nodes
-    let total_nodes = node_num + sybil_node_num + unstable_node_num;
+    let total_nodes = node_num + sybil_node_num + unstable_node_num + relay_node_num;
+    // 引导节点从诚实节点中选取（前 bootstrap_node_num 个）
+    let bootstrap_node_num = if bootstrap_node_num > node_num {
+        warn!(
+            "Bootstrap node num {} exceeds honest node num {}, clamped",
+            bootstrap_node_num, node_num
+        );
+        node_num
+    } else {
+        bootstrap_node_num
+    };
+
+    let tx_cache_size = tx_cache_size.unwrap_or(max_tx_per_block);
+    let tx_filter_capacity = tx_filter_capacity(
+        trans_num_per_second,
+        max_tx_per_block,
+        slot_duration,
+        slot_per_epoch,
+        run_seconds,
+        run_slots,
+    );
 
     // Generate stake distribution based on gini with wallet_seed for shuffling
     let stake_values = if gini > 0.0 {
@@ -70,10 +525,65 @@ pub async fn start_network(
         // Default: equal stakes
         vec![1.0; total_nodes as usize]
     };
+    let hash_power_values = crate::metrics::generate_hash_power(
+        hash_power_distribution,
+        &stake_values,
+        hash_power_gini,
+        wallet_seed,
+    );
+    // 恶意节点（带女巫身份）的编号，集中 stake 和算力发生在女巫身份平分 stake 之前
+    let attacker_indices: Vec<usize> =
+        (node_num as usize..(node_num + sybil_node_num) as usize).collect();
+    let (stake_values, hash_power_values) = if attacker_stake_fraction == 0.0 {
+        (stake_values, hash_power_values)
+    } else if !(0.0..1.0).contains(&attacker_stake_fraction) {
+        warn!(
+            "Attacker stake fraction {} is out of [0, 1), ignored",
+            attacker_stake_fraction
+        );
+        (stake_values, hash_power_values)
+    } else if sybil_node_num == 0 {
+        warn!("Attacker stake fraction requires sybil nodes, ignored");
+        (stake_values, hash_power_values)
+    } else {
+        info!(
+            "Concentrate {:.2} of total stake and hash power on {} sybil nodes",
+            attacker_stake_fraction, sybil_node_num
+        );
+        (
+            crate::metrics::concentrate_stake(
+                &stake_values,
+                &attacker_indices,
+                attacker_stake_fraction,
+            ),
+            crate::metrics::concentrate_stake(
+                &hash_power_values,
+                &attacker_indices,
+                attacker_stake_fraction,
+            ),
+        )
+    };
+    let total_stake: f64 = stake_values.iter().sum();
+    let attacker_stake_fraction = if total_stake > 0.0 {
+        attacker_indices
+            .iter()
+            .filter_map(|i| stake_values.get(*i))
+            .sum::<f64>()
+            / total_stake
+    } else {
+        0.0
+    };
+    info!(
+        "Hash power distribution[{}], Gini: {:.6}",
+        hash_power_distribution,
+        crate::metrics::calculate_gini(&hash_power_values)
+    );
 
+    // 每次模拟使用独立的公钥注册表，同一进程内并发运行的模拟互不影响
+    let key_registry = KeyRegistry::new();
     let mut node_map: HashMap<String, Node> = (0..total_nodes)
         .map(|i| {
-            let hash_power = stake_values.get(i as usize).cloned().unwrap_or(1.0);
+            let hash_power = hash_power_values.get(i as usize).cloned().unwrap_or(1.0);
             if i < node_num {
                 // Honest nodes
                 let mut node = Node::new(
@@ -85,9 +595,21 @@ pub async fn start_network(
                     max_tx_per_block,
                     consensus,
                     wallet_seed,
+                    &key_registry,
                 );
+                if i < bootstrap_node_num {
+                    node.set_node_type(NodeType::Bootstrap);
+                }
                 node.set_transaction_fee(transaction_fee);
+                node.set_transaction_tip(transaction_tip);
                 node.set_hash_power(hash_power);
+                node.set_tx_expiry_slots(tx_expiry_slots);
+                node.set_tx_cache_size(tx_cache_size);
+                node.set_tx_filter_capacity(tx_filter_capacity);
+                node.set_block_verify_sample(block_verify_sample);
+                node.set_proposer_verify_sample(proposer_verify_sample);
+                node.set_max_tx_per_slot(max_tx_per_slot);
+                node.set_max_reorg_depth(max_reorg_depth);
                 node.simple_print();
                 (node.get_address(), node)
             } else if i < node_num + sybil_node_num {
@@ -102,12 +624,21 @@ pub async fn start_network(
                     max_tx_per_block,
                     consensus,
                     wallet_seed,
+                    &key_registry,
                 );
                 node.set_transaction_fee(transaction_fee);
+                node.set_transaction_tip(transaction_tip);
                 node.set_hash_power(hash_power);
+                node.set_tx_expiry_slots(tx_expiry_slots);
+                node.set_tx_cache_size(tx_cache_size);
+                node.set_tx_filter_capacity(tx_filter_capacity);
+                node.set_block_verify_sample(block_verify_sample);
+                node.set_proposer_verify_sample(proposer_verify_sample);
+                node.set_max_tx_per_slot(max_tx_per_slot);
+                node.set_max_reorg_depth(max_reorg_depth);
                 node.simple_print();
                 (node.get_address(), node)
-            } else {
+            } else if i < node_num + sybil_node_num + unstable_node_num {
                 // Unstable nodes
                 let mut node = Node::new(
                     i,
@@ -118,17 +649,153 @@ pub async fn start_network(
                     max_tx_per_block,
                     consensus,
                     wallet_seed,
+                    &key_registry,
                 );
-                node.set_node_type(NodeType::Unstable);
-                node.set_offline_probability(offline_probability);
+                node.set_node_type(NodeType::unstable(
+                    offline_probability,
+                    offline_duration_epochs,
+                ));
                 node.set_transaction_fee(transaction_fee);
+                node.set_transaction_tip(transaction_tip);
                 node.set_hash_power(hash_power);
+                node.set_tx_expiry_slots(tx_expiry_slots);
+                node.set_tx_cache_size(tx_cache_size);
+                node.set_tx_filter_capacity(tx_filter_capacity);
+                node.set_block_verify_sample(block_verify_sample);
+                node.set_proposer_verify_sample(proposer_verify_sample);
+                node.set_max_tx_per_slot(max_tx_per_slot);
+                node.set_max_reorg_depth(max_reorg_depth);
+                node.simple_print();
+                (node.get_address(), node)
+            } else {
+                // Relay nodes
+                let mut node = Node::new(
+                    i,
+                    0,
+                    0,
+                    bc.clone(),
+                    world_sender.clone(),
+                    max_tx_per_block,
+                    consensus,
+                    wallet_seed,
+                    &key_registry,
+                );
+                node.set_node_type(NodeType::Relay);
+                node.set_transaction_fee(transaction_fee);
+                node.set_transaction_tip(transaction_tip);
+                node.set_tx_expiry_slots(tx_expiry_slots);
+                node.set_tx_cache_size(tx_cache_size);
+                node.set_tx_filter_capacity(tx_filter_capacity);
+                node.set_block_verify_sample(block_verify_sample);
+                node.set_proposer_verify_sample(proposer_verify_sample);
+                node.set_max_tx_per_slot(max_tx_per_slot);
+                node.set_max_reorg_depth(max_reorg_depth);
                 node.simple_print();
                 (node.get_address(), node)
             }
         })
         .collect();
 
+    // 只有部分节点注册为验证者，女巫节点和中继节点不参与选择
+    if validator_fraction < 1.0 {
+        let candidates: Vec<u32> = (0..node_num)
+            .chain(node_num + sybil_node_num..node_num + sybil_node_num + unstable_node_num)
+            .collect();
+        let selected = select_validators(&candidates, validator_fraction, wallet_seed);
+        for node in node_map.values_mut() {
+            if candidates.contains(&node.index) && !selected.contains(&node.index) {
+                node.set_validator(false);
+            }
+        }
+        info!(
+            "Validator fraction[{}]: {} of {} nodes register as validators",
+            validator_fraction,
+            selected.len(),
+            candidates.len()
+        );
+    }
+
+    // 从状态目录恢复上次中断时保存的节点，恢复后的地址是保存时的钱包地址
+    let mut restored_stakes = HashMap::new();
+    if let Some(dir) = &node_state_dir {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            error!("Create node state dir {} failed: {}", dir, e);
+            return SimulationResult::default();
+        }
+        let mut restored = 0;
+        let mut nodes = HashMap::new();
+        for (_, mut node) in node_map.drain() {
+            let path = format!("{}/node_{}.json", dir, node.index);
+            if std::path::Path::new(&path).exists() {
+                match node.load_state(&path).await {
+                    Ok(()) => restored += 1,
+                    Err(e) => warn!("Load node state {} failed: {}", path, e),
+                }
+            }
+            node.set_state_path(path);
+            nodes.insert(node.get_address(), node);
+        }
+        node_map = nodes;
+        let mut longest: Option<Blockchain> = None;
+        for node in node_map.values() {
+            let blockchain = node.blockchain.read().await;
+            if longest
+                .as_ref()
+                .is_none_or(|l| blockchain.height() > l.height())
+            {
+                longest = Some(blockchain.clone());
+            }
+        }
+        if let Some(blockchain) = longest.filter(|b| b.height() > 1) {
+            world.resume_chain(blockchain).await;
+        }
+        // 验证者 stake 表随 epoch 变化（离线衰减、上限再分配），恢复后覆盖初始 stake
+        let validators_path = format!("{}/validators.json", dir);
+        if std::path::Path::new(&validators_path).exists() {
+            match world.load_validators(&validators_path).await {
+                Ok(stakes) => restored_stakes = stakes,
+                Err(e) => warn!("Load validators {} failed: {}", validators_path, e),
+            }
+        }
+        world.set_state_path(validators_path);
+        info!(
+            "Node state dir[{}]: restored {} of {} nodes, {} validator stakes",
+            dir,
+            restored,
+            node_map.len(),
+            restored_stakes.len()
+        );
+    }
+
+    // 所有节点共享消息计数，用于比较不同传播方式的消息数
+    let message_stats = Arc::new(MessageStats::default());
+    for node in node_map.values_mut() {
+        node.set_propagation(propagation_strategy, message_stats.clone());
+        node.set_announce_blocks(announce_blocks);
+    }
+    if propagation_strategy != PropagationStrategy::Flood || announce_blocks {
+        info!(
+            "Propagation strategy[{}], announce blocks[{}]",
+            propagation_strategy, announce_blocks
+        );
+    }
+
+    if let Some(path) = replay_events {
+        return replay(node_map, &path, world_receiver, key_registry).await;
+    }
+
+    if proposer_mode == ProposerMode::Local {
+        for node in node_map.values_mut() {
+            if let Some(local) = new_local_consensus(consensus, pog_warmup_slots, pog_epoch_decay) {
+                node.set_local_proposer(local);
+            }
+        }
+        info!(
+            "Proposer mode[{}], nodes compute the proposer themselves",
+            proposer_mode
+        );
+    }
+
     let nodes_sender: HashMap<String, Sender<Message>> = node_map
         .iter()
         .map(|(address, node)| (address.clone(), node.sender.clone()))
@@ -139,56 +806,98 @@ pub async fn start_network(
         .map(|(address, node)| (address.clone(), node.index))
         .collect();
     world.nodes_index = nodes_index.clone();
+    if let Some(entries) = proposer_schedule {
+        match scripted::resolve_schedule(&entries, &nodes_index) {
+            Ok(schedule) => world.set_proposer_schedule(schedule),
+            Err(e) => {
+                error!("Invalid proposer schedule: {}", e);
+                return SimulationResult::default();
+            }
+        }
+    }
+
+    let bootstrap_addresses: HashSet<String> = node_map
+        .iter()
+        .filter(|(_, node)| matches!(node.node_type, NodeType::Bootstrap))
+        .map(|(address, _)| address.clone())
+        .collect();
+
+    // 攻击者的全部地址：恶意节点及其女巫身份
+    let attacker_addresses: HashSet<String> = node_map
+        .values()
+        .filter(|node| matches!(node.node_type, NodeType::Sybil { .. }))
+        .flat_map(|node| {
+            std::iter::once(node.get_address()).chain(
+                node.sybil_identities()
+                    .iter()
+                    .map(|sybil| sybil.get_address()),
+            )
+        })
+        .collect();
 
     let nodes_address: Vec<String> = node_map.keys().cloned().collect();
     // nodes_address.sort();
     info!(
-        "Generate {} honest nodes, {} sybil nodes, {} unstable nodes",
-        node_num, sybil_node_num, unstable_node_num
+        "Generate {} honest nodes ({} bootstrap), {} sybil nodes, {} unstable nodes, {} relay nodes",
+        node_num, bootstrap_node_num, sybil_node_num, unstable_node_num, relay_node_num
     );
 
     //4. gen the network graph
     let graph = match topology {
-        TopologyType::ER => graph::random_er_graph(nodes_address.clone(), 0.2),
+        TopologyType::ER => {
+            let threshold = graph::er_connectivity_threshold(nodes_address.len());
+            if er_probability < threshold {
+                warn!(
+                    "ER probability {} is below ln(n)/n = {:.4} for {} nodes, the graph is likely disconnected",
+                    er_probability,
+                    threshold,
+                    nodes_address.len()
+                );
+            }
+            graph::random_er_graph(nodes_address.clone(), er_probability)
+        }
         TopologyType::BA => graph::random_graph_with_ba_network(nodes_address.clone(), graph_seed),
     };
     info!("Generate network graph[{}]", topology);
+    world.set_graph(graph.clone());
     tokio::time::sleep(Duration::from_secs(3)).await;
 
-    //deal the node neighborhoods
-    for edge in graph.edge_indices() {
-        let (source, target) = graph.edge_endpoints(edge).unwrap();
-        let from = graph[source].clone();
-        let to = graph[target].clone();
-        {
-            let node_from = node_map.get_mut(&from).unwrap();
-            if node_from
-                .neighbors
-                .iter()
-                .find(|&x| x.address.clone() == to)
-                .is_none()
-            {
-                node_from.neighbors.push(Neighbor::new(
-                    *nodes_index.get(&to).unwrap(),
-                    to.clone(),
-                    nodes_sender.get(&to).unwrap().clone(),
-                ));
+    //geographic regions
+    let region_latency = if regions > 1 {
+        let regions = regions as usize;
+        let model = match &region_latency_file {
+            Some(path) => RegionLatency::from_file(path, regions).unwrap_or_else(|e| {
+                warn!("Load region latency failed: {}, using the default model", e);
+                RegionLatency::uniform(regions, intra_region_latency_ms, inter_region_latency_ms)
+            }),
+            None => {
+                RegionLatency::uniform(regions, intra_region_latency_ms, inter_region_latency_ms)
             }
+        };
+        let node_regions = region::assign_regions(&nodes_index, regions);
+        if let Err(e) = region::write_regions(&node_regions, &nodes_index, "regions.json") {
+            error!("Write regions.json failed: {}", e);
         }
-        {
-            let node_to = node_map.get_mut(&to).unwrap();
-            if node_to
-                .neighbors
-                .iter()
-                .find(|&x| x.address.clone() == from)
-                .is_none()
-            {
-                node_to.neighbors.push(Neighbor::new(
-                    *nodes_index.get(&from).unwrap(),
-                    from.clone(),
-                    nodes_sender.get(&from).unwrap().clone(),
-                ));
+        info!("Assign nodes to {} regions", regions);
+        Some((model, node_regions))
+    } else {
+        None
+    };
+
+    //deal the node neighborhoods
+    for (address, neighbor_addresses) in neighbor_lists(&graph) {
+        let node = node_map.get_mut(&address).unwrap();
+        for to in neighbor_addresses {
+            let mut neighbor = Neighbor::new(
+                *nodes_index.get(&to).unwrap(),
+                to.clone(),
+                nodes_sender.get(&to).unwrap().clone(),
+            );
+            neighbor.is_bootstrap = bootstrap_addresses.contains(&to);
+            if let Some((model, node_regions)) = &region_latency {
+                neighbor.latency = model.latency(node_regions[&address], node_regions[&to]);
             }
+            node.neighbors.push(neighbor);
         }
     }
 
@@ -197,9 +906,9 @@ pub async fn start_network(
     node_map
         .iter()
         .for_each(|(_address, node)| match node.node_type {
-            NodeType::Sybil => {
+            NodeType::Sybil { .. } => {
                 // sybil的消息,由主节点控制
-                node.sybil_nodes.iter().for_each(|sybil| {
+                node.sybil_identities().iter().for_each(|sybil| {
                     world
                         .nodes_sender
                         .insert(sybil.get_address(), node.sender.clone());
@@ -208,8 +917,90 @@ pub async fn start_network(
             _ => {}
         });
 
+    // 只有指定的节点发起交易，其余节点只负责转发
+    let tx_nodes_sender: HashMap<String, Sender<Message>> = match &tx_source_nodes {
+        Some(tx_source_nodes) => match tx_source_nodes.resolve(&nodes_index) {
+            Ok(sources) => {
+                info!(
+                    "Transaction source nodes[{}]: {} nodes",
+                    tx_source_nodes,
+                    sources.len()
+                );
+                nodes_sender
+                    .iter()
+                    .filter(|(address, _)| sources.contains(*address))
+                    .map(|(address, sender)| (address.clone(), sender.clone()))
+                    .collect()
+            }
+            Err(e) => {
+                error!("Invalid tx source nodes: {}", e);
+                return SimulationResult::default();
+            }
+        },
+        None => nodes_sender.clone(),
+    };
+
+    // 模拟结束时读取各节点的区块链和验证者 stake
+    let node_blockchains: Vec<(u32, Arc<RwLock<Blockchain>>)> = node_map
+        .values()
+        .map(|node| (node.index, node.blockchain.clone()))
+        .collect();
+    let nodes_health: HashMap<String, Arc<MempoolHealth>> = node_map
+        .iter()
+        .map(|(address, node)| (address.clone(), node.mempool_health.clone()))
+        .collect();
+    world.set_key_registry(
+        key_registry.clone(),
+        node_map
+            .iter()
+            .map(|(address, node)| (address.clone(), node.transaction_paths_cache.clone()))
+            .collect(),
+    );
+    let validators = world.validators.clone();
+    let consensus_state = world.consensus_state.clone();
+    let inclusion = world.inclusion.clone();
+    let mempool_reports = world.mempool_reports.clone();
+    let world_blockchain = world.blockchain.clone();
+
+    // 网络分区实验
+    let partition_driver = match partition_at_epoch {
+        Some(at_epoch) => {
+            let heal_epoch = match partition_heal_epoch {
+                Some(heal_epoch) if heal_epoch <= at_epoch => {
+                    warn!(
+                        "Partition heal epoch {} is not after partition epoch {}, never heal",
+                        heal_epoch, at_epoch
+                    );
+                    None
+                }
+                heal_epoch => heal_epoch,
+            };
+            let partition_fraction = if partition_fraction > 0.0 && partition_fraction < 1.0 {
+                partition_fraction
+            } else {
+                warn!(
+                    "Partition fraction {} is not in (0, 1), using 0.5",
+                    partition_fraction
+                );
+                0.5
+            };
+            Some(PartitionDriver::new(
+                at_epoch,
+                heal_epoch,
+                partition::split(&nodes_index, partition_fraction),
+                nodes_sender.clone(),
+                node_blockchains.iter().map(|(_, bc)| bc.clone()).collect(),
+                world.current_slot.clone(),
+                slot_per_epoch,
+            ))
+        }
+        None => None,
+    };
+    let partition_report = partition_driver.as_ref().map(|driver| driver.report());
+
     //start the world and all node
     let mut tasks = vec![];
+    let mut slots_run = world.slots_run();
     let t = tokio::spawn(async move {
         world.run(world_receiver).await;
         info!("World state running");
@@ -217,15 +1008,8 @@ pub async fn start_network(
     tasks.push(t);
 
     //become validator
-    // Create address -> stake mapping using node.index to match hash_power assignment
-    let mut stake_map: HashMap<String, f64> = HashMap::new();
-    for (address, node) in node_map.iter() {
-        let stake = stake_values
-            .get(node.index as usize)
-            .cloned()
-            .unwrap_or(1.0);
-        stake_map.insert(address.clone(), stake);
-    }
+    let mut stake_map = stake_assignment(&nodes_index, &stake_values);
+    stake_map.extend(restored_stakes);
 
     // Convert to JSON and send to all nodes
     let stake_json = serde_json::to_vec(&stake_map).unwrap_or_default();
@@ -246,35 +1030,464 @@ pub async fn start_network(
     }
 
     let mut tg = TransactionGenerator::new(
-        nodes_sender.clone(),
+        tx_nodes_sender,
         nodes_address.clone(),
-        Duration::from_secs(1),
+        Duration::from_millis(tx_interval_ms.max(1)),
         trans_num_per_second,
     );
+    tg.set_batch_size(tx_batch_size);
+    tg.set_burst_cap(tx_burst_cap);
+    tg.set_mempool_health(nodes_health);
 
     let t = tokio::spawn(async move {
         info!(
-            "Transaction Generator running, {} tx/s",
-            trans_num_per_second
+            "Transaction Generator running, {} tx/s, every {}ms",
+            trans_num_per_second, tx_interval_ms
         );
         tg.run().await;
     });
     tasks.push(t);
 
-    let mut printer = Printer::new(nodes_sender.clone(), Duration::from_secs(10));
-    let t = tokio::spawn(async move {
-        printer.run().await;
-    });
-    tasks.push(t);
+    if let Some(mut driver) = partition_driver {
+        let t = tokio::spawn(async move {
+            driver.run().await;
+        });
+        tasks.push(t);
+    }
+
+    // 打印间隔为0时不输出
+    if print_interval > 0 {
+        let mut printer = Printer::new(
+            nodes_sender.clone(),
+            Duration::from_secs(print_interval),
+            print_mode,
+            node_blockchains.clone(),
+            mempool_reports,
+            validators.clone(),
+            consensus_state,
+        );
+        let t = tokio::spawn(async move {
+            printer.run().await;
+        });
+        tasks.push(t);
+    }
+
+    if run_seconds == 0 && run_slots == 0 {
+        let _ = join_all(tasks).await;
+        return SimulationResult::default();
+    }
+    tokio::select! {
+        _ = time::sleep(Duration::from_secs(run_seconds)), if run_seconds > 0 => {}
+        _ = slots_run.wait_for(|n| *n >= run_slots), if run_slots > 0 => {}
+    }
+    for task in tasks.iter() {
+        task.abort();
+    }
+    let _ = join_all(tasks).await;
+    events::stop_recording();
+    info!("Simulation finished after {} slots", *slots_run.borrow());
+
+    let mut blockchains = HashMap::new();
+    for (index, blockchain) in node_blockchains {
+        blockchains.insert(index, blockchain.read().await.clone());
+    }
+    let stakes = validators
+        .read()
+        .await
+        .iter()
+        .map(|v| (v.address.clone(), v.stake))
+        .collect();
+    let partition = match partition_report {
+        Some(report) => report.lock().await.clone(),
+        None => None,
+    };
+    let attacker = if attacker_addresses.is_empty() {
+        None
+    } else {
+        // 以最长链统计出块者，不含创世区块
+        let miners: Vec<String> = blockchains
+            .values()
+            .max_by_key(|bc| bc.len())
+            .map(|bc| {
+                bc.blocks()
+                    .iter()
+                    .skip(1)
+                    .map(|b| b.header.miner.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let report = AttackerReport::new(attacker_stake_fraction, &miners, &attacker_addresses);
+        info!(
+            "Attacker stake fraction {:.4}, proposer share {:.4}, sybil advantage {:.4}",
+            report.stake_fraction, report.proposer_share, report.sybil_advantage
+        );
+        Some(report)
+    };
+    let inclusion = inclusion.read().await.clone();
+    info!(
+        "Transactions generated {}, included {}, expired {}, inclusion rate {:.4}",
+        inclusion.generated,
+        inclusion.included,
+        inclusion.expired,
+        inclusion.inclusion_rate()
+    );
+    let consistency = ConsistencyReport::new(&*world_blockchain.read().await, &blockchains);
+    info!(
+        "WorldState height {}, {}/{} nodes consistent",
+        consistency.world_height,
+        consistency.consistent_nodes,
+        consistency.nodes.len()
+    );
+    for node in consistency.diverged() {
+        warn!(
+            "Node[{}] diverged from WorldState at height {:?}, common prefix {}",
+            node.index, node.diverged_at, node.common_prefix
+        );
+    }
+    if let Err(e) = consistency.write("final_consistency_report.json") {
+        error!("Failed to write consistency report: {}", e);
+    }
+    let messages = message_stats.counts();
+    info!(
+        "Propagation[{}]: {} messages, transactions {}, announcements {}, requests {}, blocks {}",
+        propagation_strategy,
+        messages.total(),
+        messages.transactions,
+        messages.announcements,
+        messages.requests,
+        messages.blocks
+    );
+    info!(
+        "Block propagation[{}]: announcements {}, requests {}, {} bytes",
+        if announce_blocks { "announce" } else { "flood" },
+        messages.block_announcements,
+        messages.block_requests,
+        messages.block_bytes
+    );
+    SimulationResult {
+        blockchains,
+        stakes,
+        partition,
+        attacker,
+        inclusion,
+        consistency,
+        key_registry,
+        messages,
+    }
+}
+
+/// 重放事件日志：把每个节点记录的消息按原顺序重新发给编号相同的节点
+/// 节点之间不连接，WorldState 不运行，节点只处理日志中的消息
+/// 出块的时间戳不同会产出不同的区块，GenerateBlock 按日志中该节点随后广播的自己产出的区块还原，因此各节点的链与记录时一致
+/// WorldState 的事件只用于分析，不重放
+async fn replay(
+    node_map: HashMap<String, Node>,
+    path: &str,
+    mut world_receiver: Receiver<Message>,
+    key_registry: KeyRegistry,
+) -> SimulationResult {
+    let events = match events::load(path) {
+        Ok(events) => events,
+        Err(e) => {
+            error!("Load events from {} failed: {}", path, e);
+            return SimulationResult::default();
+        }
+    };
+    let node_senders: HashMap<u32, Sender<Message>> = node_map
+        .values()
+        .map(|node| (node.index, node.sender.clone()))
+        .collect();
+    let node_blockchains: Vec<(u32, Arc<RwLock<Blockchain>>)> = node_map
+        .values()
+        .map(|node| (node.index, node.blockchain.clone()))
+        .collect();
+    // 每个节点出块使用的地址，女巫节点也以伪造身份出块
+    let miner_addresses: HashMap<u32, (String, HashSet<String>)> = node_map
+        .values()
+        .map(|node| {
+            let mut addresses: HashSet<String> = node
+                .sybil_identities()
+                .iter()
+                .map(|identity| identity.wallet.address.clone())
+                .collect();
+            addresses.insert(node.get_address());
+            (node.index, (node.get_address(), addresses))
+        })
+        .collect();
 
+    let mut tasks = vec![];
+    // 节点发给 WorldState 的消息直接丢弃
+    tasks.push(tokio::spawn(async move {
+        while world_receiver.recv().await.is_some() {}
+    }));
+    for (_, mut node) in node_map {
+        tasks.push(tokio::spawn(async move {
+            node.run().await;
+        }));
+    }
+
+    let mut replayed = 0;
+    for (position, event) in events.iter().enumerate() {
+        let index = match event.node_index() {
+            Some(index) => index,
+            None => continue,
+        };
+        let sender = match node_senders.get(&index) {
+            Some(sender) => sender,
+            None => {
+                warn!("Replay: unknown Node[{}], event skipped", index);
+                continue;
+            }
+        };
+        let msg = if matches!(event.msg_type, MessageType::GenerateBlock) {
+            let (address, miners) = &miner_addresses[&index];
+            match produced_block(&events[position + 1..], address, miners) {
+                Some(block) => Message::new_block_msg(block, address.clone()),
+                None => {
+                    debug!(
+                        "Replay: Node[{}] produced no block for event {}",
+                        index, position
+                    );
+                    continue;
+                }
+            }
+        } else {
+            match event.to_message() {
+                Ok(msg) => msg,
+                Err(e) => {
+                    warn!("Replay: invalid event data for Node[{}]: {}", index, e);
+                    continue;
+                }
+            }
+        };
+        if sender.send(msg).await.is_ok() {
+            replayed += 1;
+        }
+    }
+
+    // 等待所有节点处理完队列中的消息
+    while node_senders
+        .values()
+        .any(|sender| sender.capacity() < sender.max_capacity())
+    {
+        time::sleep(Duration::from_millis(50)).await;
+    }
+    time::sleep(Duration::from_millis(100)).await;
+    for task in tasks.iter() {
+        task.abort();
+    }
     let _ = join_all(tasks).await;
+    info!(
+        "Replayed {} of {} events from {}",
+        replayed,
+        events.len(),
+        path
+    );
+
+    let mut blockchains = HashMap::new();
+    for (index, blockchain) in node_blockchains {
+        let blockchain = blockchain.read().await.clone();
+        info!(
+            "Replay Node[{}]: height {}, last hash {}",
+            index,
+            blockchain.get_last_index(),
+            blockchain.get_last_hash()
+        );
+        blockchains.insert(index, blockchain);
+    }
+    SimulationResult {
+        blockchains,
+        key_registry,
+        ..Default::default()
+    }
+}
+
+/// 出块者的确定方式
+/// central: WorldState 选出出块者后通知其出块
+/// local: 每个节点根据收到的验证者集合和种子自行计算是否轮到自己出块（仅支持 POS 和 POG）
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProposerMode {
+    Central,
+    Local,
+}
+
+impl Display for ProposerMode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            ProposerMode::Central => {
+                write!(f, "central")
+            }
+            ProposerMode::Local => {
+                write!(f, "local")
+            }
+        }
+    }
+}
+
+/// 交易的传播方式，区块总是发给所有邻居
+/// flood: 转发给所有邻居; push-pull: 先向邻居公告交易哈希，邻居只请求需要的交易;
+/// random-walk: 只转发给一个随机邻居
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropagationStrategy {
+    Flood,
+    PushPull,
+    RandomWalk,
+}
+
+impl Display for PropagationStrategy {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            PropagationStrategy::Flood => {
+                write!(f, "flood")
+            }
+            PropagationStrategy::PushPull => {
+                write!(f, "push-pull")
+            }
+            PropagationStrategy::RandomWalk => {
+                write!(f, "random-walk")
+            }
+        }
+    }
+}
+
+/// 本地计算出块者时节点使用的共识实例，奖励由 WorldState 分配，这里不需要 base_reward
+/// POW 和 MINOTAUR 的选择依赖挖矿过程，不支持本地计算，返回 None
+fn new_local_consensus(
+    consensus: ConsensusType,
+    pog_warmup_slots: u64,
+    pog_epoch_decay: f64,
+) -> Option<Box<dyn Consensus>> {
+    match consensus {
+        ConsensusType::POS => Some(Box::new(PosConsensus::new(0.0))),
+        ConsensusType::POG => {
+            let mut pog = PogConsensus::new(0, 0.0);
+            pog.set_warmup_slots(pog_warmup_slots);
+            pog.set_epoch_decay(pog_epoch_decay);
+            Some(Box::new(pog))
+        }
+        ConsensusType::POW | ConsensusType::MINOTAUR | ConsensusType::SCRIPTED => None,
+    }
+}
+
+/// 日志中节点收到 GenerateBlock 之后广播的第一个自己产出的区块，即这次出块的结果
+fn produced_block(
+    events: &[events::Event],
+    address: &str,
+    miners: &HashSet<String>,
+) -> Option<Block> {
+    events
+        .iter()
+        .filter(|e| matches!(e.msg_type, MessageType::SendBlock) && e.from == address)
+        .filter_map(|e| Block::from_json(e.to_message().ok()?.data).ok())
+        .find(|block| miners.contains(&block.header.miner))
+}
+
+/// 由网络拓扑得到每个节点的邻居地址，按边的顺序排列并去重
+/// 使用 HashSet 去重，避免每条边都线性扫描已有邻居
+fn neighbor_lists(graph: &Graph<String, ()>) -> HashMap<String, Vec<String>> {
+    let mut neighbors: HashMap<String, Vec<String>> = HashMap::new();
+    let mut seen: HashMap<String, HashSet<String>> = HashMap::new();
+    for edge in graph.edge_indices() {
+        let (source, target) = graph.edge_endpoints(edge).unwrap();
+        let from = &graph[source];
+        let to = &graph[target];
+        for (a, b) in [(from, to), (to, from)] {
+            if seen.entry(a.clone()).or_default().insert(b.clone()) {
+                neighbors.entry(a.clone()).or_default().push(b.clone());
+            }
+        }
+    }
+    neighbors
+}
+
+/// 发起交易的节点：节点编号列表（如 "0,3,5"）或节点比例（如 "0.2"）
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxSourceNodes {
+    Indices(Vec<u32>),
+    Fraction(f64),
+}
+
+impl FromStr for TxSourceNodes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.contains('.') {
+            let fraction: f64 = s.parse().map_err(|_| format!("invalid fraction: {}", s))?;
+            if fraction <= 0.0 || fraction > 1.0 {
+                return Err(format!("fraction must be in (0, 1]: {}", s));
+            }
+            return Ok(TxSourceNodes::Fraction(fraction));
+        }
+        let indices = s
+            .split(',')
+            .map(|x| {
+                x.trim()
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid node index: {}", x))
+            })
+            .collect::<Result<Vec<u32>, String>>()?;
+        Ok(TxSourceNodes::Indices(indices))
+    }
+}
+
+impl Display for TxSourceNodes {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            TxSourceNodes::Indices(indices) => {
+                let indices: Vec<String> = indices.iter().map(|i| i.to_string()).collect();
+                write!(f, "{}", indices.join(","))
+            }
+            TxSourceNodes::Fraction(fraction) => write!(f, "{}", fraction),
+        }
+    }
+}
+
+impl TxSourceNodes {
+    /// 解析为节点地址集合，比例方式按节点编号取前 ceil(fraction * n) 个
+    pub fn resolve(&self, nodes_index: &HashMap<String, u32>) -> Result<HashSet<String>, String> {
+        match self {
+            TxSourceNodes::Indices(indices) => {
+                let missing: Vec<&u32> = indices
+                    .iter()
+                    .filter(|i| !nodes_index.values().any(|x| x == *i))
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(format!("node index {:?} not found", missing));
+                }
+                Ok(nodes_index
+                    .iter()
+                    .filter(|(_, i)| indices.contains(i))
+                    .map(|(address, _)| address.clone())
+                    .collect())
+            }
+            TxSourceNodes::Fraction(fraction) => {
+                let count = (fraction * nodes_index.len() as f64).ceil() as usize;
+                let mut nodes: Vec<(&String, &u32)> = nodes_index.iter().collect();
+                nodes.sort_by_key(|(_, i)| **i);
+                Ok(nodes
+                    .into_iter()
+                    .take(count)
+                    .map(|(address, _)| address.clone())
+                    .collect())
+            }
+        }
+    }
 }
 
+/// 节点通道剩余容量低于 1/BACKPRESSURE_RATIO 时，不再向其发送交易
+const BACKPRESSURE_RATIO: usize = 10;
+
 struct TransactionGenerator {
     nodes_sender: HashMap<String, Sender<Message>>,
     nodes_address: Vec<String>,
     time_interval: Duration,
-    trans_num_per_interval: u32,
+    trans_num_per_second: u32,
+    dropped_due_to_backpressure: u64, // 因节点通道拥塞而丢弃的交易数
+    batch_size: usize,                // 每条消息最多携带的交易数，1 表示不合并
+    burst_cap: usize,                 // 每个间隔最多发送的交易数，0 表示不限制
+    deferred: usize,                  // 超过 burst_cap 而推迟到之后间隔发送的交易数
+    nodes_health: HashMap<String, Arc<MempoolHealth>>, // 各节点的内存池计数，记录没有送达该节点的交易
 }
 
 impl TransactionGenerator {
@@ -282,61 +1495,223 @@ impl TransactionGenerator {
         nodes_sender: HashMap<String, Sender<Message>>,
         nodes_address: Vec<String>,
         time_interval: Duration,
-        trans_num_per_interval: u32,
+        trans_num_per_second: u32,
     ) -> TransactionGenerator {
         TransactionGenerator {
             nodes_sender,
             nodes_address,
             time_interval,
-            trans_num_per_interval,
+            trans_num_per_second,
+            dropped_due_to_backpressure: 0,
+            batch_size: 1,
+            burst_cap: 0,
+            deferred: 0,
+            nodes_health: HashMap::new(),
         }
     }
 
+    fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    fn set_burst_cap(&mut self, burst_cap: usize) {
+        self.burst_cap = burst_cap;
+    }
+
+    fn set_mempool_health(&mut self, nodes_health: HashMap<String, Arc<MempoolHealth>>) {
+        self.nodes_health = nodes_health;
+    }
+
+    /// 记录因节点通道拥塞没有送达的交易，同时计入该节点的内存池计数
+    fn record_backpressure(&mut self, node: &str, n: u64) {
+        self.dropped_due_to_backpressure += n;
+        if let Some(health) = self.nodes_health.get(node) {
+            MempoolHealth::add(&health.dropped_by_backpressure, n);
+        }
+    }
+
+    /// 每个间隔的泊松均值，按间隔长度缩放每秒交易数
+    fn lambda(&self) -> f64 {
+        self.trans_num_per_second as f64 * self.time_interval.as_secs_f64()
+    }
+
+    /// 本间隔新生成的交易与之前推迟的交易合并，返回本间隔发送的数量，超过 burst_cap 的部分继续推迟
+    fn take_burst(&mut self, generated: usize) -> usize {
+        let total = self.deferred + generated;
+        let send = if self.burst_cap == 0 {
+            total
+        } else {
+            total.min(self.burst_cap)
+        };
+        self.deferred = total - send;
+        send
+    }
+
+    /// 向随机节点发送交易生成请求，不等待拥塞的通道，返回成功发送的数量
+    fn send_transactions(&mut self, num_messages: usize) -> usize {
+        if self.batch_size > 1 {
+            return self.send_transaction_batches(num_messages);
+        }
+        let mut sent = 0;
+        for _ in 0..num_messages {
+            let node = self
+                .nodes_sender
+                .iter()
+                .choose(&mut thread_rng())
+                .map(|(address, sender)| (address.clone(), sender.clone()));
+
+            if let Some((address, sender)) = node {
+                let to = self
+                    .nodes_address
+                    .iter()
+                    .filter(|x| **x != address)
+                    .choose(&mut rand::thread_rng())
+                    .unwrap();
+                if sender.capacity() * BACKPRESSURE_RATIO < sender.max_capacity() {
+                    self.record_backpressure(&address, 1);
+                    continue;
+                }
+                match sender.try_send(Message::new_generate_transaction_path_msg(to.clone())) {
+                    Ok(_) => sent += 1,
+                    Err(TrySendError::Full(_)) => self.record_backpressure(&address, 1),
+                    Err(TrySendError::Closed(_)) => {
+                        error!("Transaction generator: node channel closed");
+                    }
+                }
+            }
+        }
+        sent
+    }
+
+    /// 按节点合并交易生成请求，每个节点每 batch_size 笔交易发送一条消息
+    fn send_transaction_batches(&mut self, num_transactions: usize) -> usize {
+        let mut requests: HashMap<String, Vec<String>> = HashMap::new();
+        for _ in 0..num_transactions {
+            let node = match self.nodes_sender.keys().choose(&mut thread_rng()) {
+                Some(node) => node.clone(),
+                None => break,
+            };
+            let to = self
+                .nodes_address
+                .iter()
+                .filter(|x| **x != node)
+                .choose(&mut rand::thread_rng())
+                .unwrap();
+            requests.entry(node).or_default().push(to.clone());
+        }
+
+        let mut sent = 0;
+        for (node, tos) in requests {
+            let sender = self.nodes_sender[&node].clone();
+            for chunk in tos.chunks(self.batch_size) {
+                if sender.capacity() * BACKPRESSURE_RATIO < sender.max_capacity() {
+                    self.record_backpressure(&node, chunk.len() as u64);
+                    continue;
+                }
+                match sender.try_send(Message::new_generate_transaction_paths_batch_msg(
+                    chunk.to_vec(),
+                )) {
+                    Ok(_) => sent += chunk.len(),
+                    Err(TrySendError::Full(_)) => {
+                        self.record_backpressure(&node, chunk.len() as u64)
+                    }
+                    Err(TrySendError::Closed(_)) => {
+                        error!("Transaction generator: node channel closed");
+                    }
+                }
+            }
+        }
+        sent
+    }
+
     async fn run(&mut self) {
         let mut interval = time::interval(self.time_interval);
 
         loop {
             interval.tick().await;
             // 泊松分布生成器
-            let poisson = Poisson::new(self.trans_num_per_interval as f64).unwrap();
+            let lambda = self.lambda();
+            let generated = match Poisson::new(lambda) {
+                Ok(poisson) => poisson.sample(&mut thread_rng()) as usize,
+                Err(_) => 0,
+            };
+            // 获取本间隔发送的消息数
+            let num_messages = self.take_burst(generated);
 
-            // 获取每秒生成的消息数
-            let num_messages: usize = poisson.sample(&mut thread_rng()) as usize;
-
-            for _ in 0..num_messages {
-                let node = self.nodes_sender.iter().choose(&mut thread_rng());
-
-                if let Some(node) = node {
-                    let to = self
-                        .nodes_address
-                        .iter()
-                        .filter(|x| **x != node.0.clone())
-                        .choose(&mut rand::thread_rng())
-                        .unwrap();
-                    node.1
-                        .send(Message::new_generate_transaction_path_msg(to.clone()))
-                        .await
-                        .unwrap();
-                }
+            let dropped_before = self.dropped_due_to_backpressure;
+            let sent = self.send_transactions(num_messages);
+            if self.dropped_due_to_backpressure > dropped_before {
+                warn!(
+                    "Transaction generator: dropped {} transactions due to backpressure (total {})",
+                    self.dropped_due_to_backpressure - dropped_before,
+                    self.dropped_due_to_backpressure
+                );
             }
             info!(
-                "[{}]Transactions generated (λ={})",
-                num_messages, self.trans_num_per_interval
+                "[{}]Transactions generated (λ={:.2} per {}ms, deferred {})",
+                sent,
+                lambda,
+                self.time_interval.as_millis(),
+                self.deferred
             );
         }
     }
 }
 
+/// Printer 定期输出的内容
+/// dump: 让随机一个节点把整条链写入文件
+/// status: 输出随机一个节点的高度、最新区块哈希、验证者数和共识状态（POG 包含 NTD），开销小，适合长时间运行
+///         同时输出一行内存池状况：抽样节点的交易数和累计丢弃的交易数
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintMode {
+    Dump,
+    Status,
+}
+
+impl Display for PrintMode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            PrintMode::Dump => {
+                write!(f, "dump")
+            }
+            PrintMode::Status => {
+                write!(f, "status")
+            }
+        }
+    }
+}
+
+/// status 模式下统计内存池交易数时抽样的节点数
+const MEMPOOL_SAMPLE_SIZE: usize = 5;
+
 struct Printer {
     nodes_sender: HashMap<String, Sender<Message>>,
     interval: Duration,
+    mode: PrintMode,
+    node_blockchains: Vec<(u32, Arc<RwLock<Blockchain>>)>,
+    mempool_reports: Arc<RwLock<HashMap<String, MempoolReport>>>, // WorldState 收到的节点内存池状况
+    validators: Arc<RwLock<Vec<Validator>>>,
+    consensus_state: Arc<RwLock<String>>,
 }
 
 impl Printer {
-    fn new(nodes_sender: HashMap<String, Sender<Message>>, interval: Duration) -> Printer {
+    fn new(
+        nodes_sender: HashMap<String, Sender<Message>>,
+        interval: Duration,
+        mode: PrintMode,
+        node_blockchains: Vec<(u32, Arc<RwLock<Blockchain>>)>,
+        mempool_reports: Arc<RwLock<HashMap<String, MempoolReport>>>,
+        validators: Arc<RwLock<Vec<Validator>>>,
+        consensus_state: Arc<RwLock<String>>,
+    ) -> Printer {
         Printer {
             nodes_sender,
             interval,
+            mode,
+            node_blockchains,
+            mempool_reports,
+            validators,
+            consensus_state,
         }
     }
 
@@ -345,22 +1720,112 @@ impl Printer {
         loop {
             interval.tick().await;
 
-            let node = self.nodes_sender.iter().choose(&mut rand::thread_rng());
-            node.unwrap()
-                .1
-                .send(Message::new_print_blockchain_msg())
-                .await
-                .unwrap();
+            match self.mode {
+                PrintMode::Dump => {
+                    let node = self.nodes_sender.iter().choose(&mut rand::thread_rng());
+                    let sender = node.unwrap().1;
+                    sender
+                        .send(Message::new_print_blockchain_msg())
+                        .await
+                        .unwrap();
+                    // 同时输出该节点交易缓存的路径长度分布
+                    sender.send(Message::new_query_mempool_msg()).await.unwrap();
+                }
+                PrintMode::Status => {
+                    // 内存池状况来自节点对上一次查询的响应
+                    info!("{}", self.status_line().await);
+                    info!("{}", self.mempool_line().await);
+                    for sender in self.nodes_sender.values() {
+                        if let Err(e) = sender.try_send(Message::new_request_mempool_health_msg()) {
+                            debug!("Printer: request mempool health failed: {}", e);
+                        }
+                    }
+                }
+            }
         }
     }
+
+    /// 随机一个节点的状态：高度、最新区块哈希、验证者数和共识状态
+    async fn status_line(&self) -> String {
+        let (index, blockchain) = match self.node_blockchains.choose(&mut rand::thread_rng()) {
+            Some(node) => node,
+            None => return "Status: no nodes".to_string(),
+        };
+        let (height, tip_hash) = {
+            let blockchain = blockchain.read().await;
+            (blockchain.height(), blockchain.get_last_hash())
+        };
+        format!(
+            "Status: Node[{}] height {}, tip {}, validators {}, consensus[{}]",
+            index,
+            height,
+            &tip_hash[..8.min(tip_hash.len())],
+            self.validators.read().await.len(),
+            self.consensus_state.read().await
+        )
+    }
+
+    /// 内存池状况：抽样节点的平均交易数，以及所有节点累计过期、淘汰、超出限速、余额不足和通道拥塞丢弃的交易数
+    async fn mempool_line(&self) -> String {
+        let reports = self.mempool_reports.read().await;
+        if reports.is_empty() {
+            return "Mempool: no reports".to_string();
+        }
+        let sample = reports
+            .values()
+            .choose_multiple(&mut rand::thread_rng(), MEMPOOL_SAMPLE_SIZE);
+        let sizes: Vec<usize> = sample.iter().map(|report| report.mempool_size).collect();
+        let total =
+            |counter: fn(&MempoolReport) -> u64| -> u64 { reports.values().map(counter).sum() };
+        format!(
+            "Mempool: avg {:.1} txs over {} nodes, expired {}, evicted {}, rate limited {}, insufficient balance {}, dropped by backpressure {}",
+            sizes.iter().sum::<usize>() as f64 / sizes.len() as f64,
+            sizes.len(),
+            total(|r| r.expired),
+            total(|r| r.evicted),
+            total(|r| r.rate_limited),
+            total(|r| r.insufficient_balance),
+            total(|r| r.dropped_by_backpressure),
+        )
+    }
+}
+
+/// 地址 -> stake 的映射：节点 i 总是得到 stake_values[i]，与 HashMap 的遍历顺序无关，与算力的分配方式一致
+/// wallet_seed 不为 0 时节点 i 的地址由 (wallet_seed, i) 确定，因此相同的种子在多次运行中得到相同的映射
+pub fn stake_assignment(
+    nodes_index: &HashMap<String, u32>,
+    stake_values: &[f64],
+) -> HashMap<String, f64> {
+    nodes_index
+        .iter()
+        .map(|(address, index)| {
+            let stake = stake_values.get(*index as usize).cloned().unwrap_or(1.0);
+            (address.clone(), stake)
+        })
+        .collect()
+}
+
+/// 从候选节点中按比例选出注册为验证者的节点，相同的种子总是选出相同的节点
+/// 至少选出一个节点，保证总有出块者
+pub fn select_validators(candidates: &[u32], fraction: f64, seed: u64) -> HashSet<u32> {
+    if candidates.is_empty() {
+        return HashSet::new();
+    }
+    let count = ((candidates.len() as f64 * fraction.clamp(0.0, 1.0)).round() as usize)
+        .clamp(1, candidates.len());
+    let mut shuffled = candidates.to_vec();
+    shuffled.shuffle(&mut StdRng::seed_from_u64(seed));
+    shuffled.into_iter().take(count).collect()
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use log::info;
     use rand::prelude::Distribution;
     use rand::thread_rng;
     use rand_distr::Poisson;
+    use std::io::Write;
     use std::time::Duration;
 
     #[tokio::test]
@@ -394,4 +1859,299 @@ mod tests {
             }
         }
     }
+
+    #[tokio::test]
+    async fn test_transaction_generator_backpressure() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(20);
+        let mut nodes_sender = HashMap::new();
+        nodes_sender.insert("a".to_string(), sender);
+        let mut tg = TransactionGenerator::new(
+            nodes_sender,
+            vec!["a".to_string(), "b".to_string()],
+            Duration::from_secs(1),
+            10,
+        );
+
+        let health = Arc::new(MempoolHealth::default());
+        tg.set_mempool_health(HashMap::from([("a".to_string(), health.clone())]));
+
+        // 通道剩余容量低于 1/10 后停止发送，而不是阻塞，没有送达的交易计入节点的内存池计数
+        let sent = tg.send_transactions(30);
+        assert_eq!(sent, 19);
+        assert_eq!(tg.dropped_due_to_backpressure, 11);
+        assert_eq!(health.report(0).dropped_by_backpressure, 11);
+
+        while receiver.try_recv().is_ok() {}
+        assert_eq!(tg.send_transactions(5), 5);
+        assert_eq!(tg.dropped_due_to_backpressure, 11);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_generator_batch() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(20);
+        let mut nodes_sender = HashMap::new();
+        nodes_sender.insert("a".to_string(), sender);
+        let mut tg = TransactionGenerator::new(
+            nodes_sender,
+            vec!["a".to_string(), "b".to_string()],
+            Duration::from_secs(1),
+            10,
+        );
+        tg.set_batch_size(10);
+
+        // 25 笔交易合并为 3 条消息
+        assert_eq!(tg.send_transactions(25), 25);
+        let mut sizes = vec![];
+        while let Ok(msg) = receiver.try_recv() {
+            assert!(matches!(
+                msg.msg_type,
+                message::MessageType::GenerateTransactionPathsBatch
+            ));
+            let tos: Vec<String> = serde_json::from_slice(&msg.data).unwrap();
+            assert!(tos.iter().all(|to| to == "b"));
+            sizes.push(tos.len());
+        }
+        assert_eq!(sizes, vec![10, 10, 5]);
+    }
+
+    #[test]
+    fn test_transaction_generator_burst_cap() {
+        let mut tg =
+            TransactionGenerator::new(HashMap::new(), vec![], Duration::from_millis(250), 10);
+        // λ 按间隔缩放
+        assert!((tg.lambda() - 2.5).abs() < 1e-9);
+        assert_eq!(tg.take_burst(7), 7);
+
+        tg.set_burst_cap(3);
+        assert_eq!(tg.take_burst(7), 3);
+        assert_eq!(tg.deferred, 4);
+        assert_eq!(tg.take_burst(1), 3);
+        assert_eq!(tg.take_burst(0), 2);
+        assert_eq!(tg.deferred, 0);
+    }
+
+    /// 重放 GenerateBlock 时按日志还原出块者广播的区块，出块者和收到区块的节点的链与记录时一致
+    #[tokio::test]
+    async fn test_replay_events() {
+        let genesis_block = Block::gen_genesis_block();
+        let new_node = |index| {
+            let (world_sender, world_receiver) = tokio::sync::mpsc::channel(64);
+            let node = Node::new(
+                index,
+                0,
+                0,
+                Blockchain::new(genesis_block.clone()),
+                world_sender,
+                1000,
+                ConsensusType::POS,
+                8,
+                &KeyRegistry::thread_default(),
+            );
+            (node, world_receiver)
+        };
+        let (miner, _world_receiver) = new_node(0);
+        let block = miner.generate_block(0, 1).await.unwrap();
+        let address = miner.get_address();
+        let recorded = [
+            (0, Message::new_generate_block_msg()),
+            (1, Message::new_block_msg(block.clone(), address.clone())),
+        ];
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        for (index, msg) in recorded.iter() {
+            let event = events::Event::new(events::node_target(*index), msg);
+            writeln!(file, "{}", serde_json::to_string(&event).unwrap()).unwrap();
+        }
+        let path = file.path().to_str().unwrap();
+
+        let (node0, world_receiver) = new_node(0);
+        let (node1, _world_receiver1) = new_node(1);
+        let node_map = HashMap::from([(node0.get_address(), node0), (node1.get_address(), node1)]);
+        let result = replay(node_map, path, world_receiver, KeyRegistry::new()).await;
+        for index in [0, 1] {
+            let blockchain = &result.blockchains[&index];
+            assert_eq!(blockchain.get_last_index(), 1);
+            assert_eq!(blockchain.get_last_hash(), block.header.hash);
+        }
+    }
+
+    /// 与原先逐条边线性查找去重的结果一致
+    #[test]
+    fn test_neighbor_lists_match_linear_dedup() {
+        let nodes_address: Vec<String> = (0..200).map(|i| format!("addr{}", i)).collect();
+        for graph in [
+            graph::random_graph_with_ba_network(nodes_address.clone(), 888),
+            graph::random_er_graph(nodes_address.clone(), 0.2),
+        ] {
+            let mut expected: HashMap<String, Vec<String>> = HashMap::new();
+            for edge in graph.edge_indices() {
+                let (source, target) = graph.edge_endpoints(edge).unwrap();
+                let from = graph[source].clone();
+                let to = graph[target].clone();
+                for (a, b) in [(&from, &to), (&to, &from)] {
+                    let list = expected.entry(a.clone()).or_default();
+                    if !list.contains(b) {
+                        list.push(b.clone());
+                    }
+                }
+            }
+            assert_eq!(neighbor_lists(&graph), expected);
+        }
+    }
+
+    #[test]
+    fn test_select_validators() {
+        let candidates: Vec<u32> = (0..10).chain(20..30).collect();
+        let selected = select_validators(&candidates, 0.25, 8);
+        assert_eq!(selected.len(), 5);
+        assert!(selected.iter().all(|i| candidates.contains(i)));
+        assert_eq!(selected, select_validators(&candidates, 0.25, 8));
+
+        assert_eq!(select_validators(&candidates, 1.0, 8).len(), 20);
+        // 至少保留一个验证者
+        assert_eq!(select_validators(&candidates, 0.0, 8).len(), 1);
+        assert!(select_validators(&[], 0.5, 8).is_empty());
+    }
+
+    #[test]
+    fn test_stake_assignment_reproducible() {
+        let (world_sender, _world_receiver) = tokio::sync::mpsc::channel(8);
+        let assign = || {
+            let nodes_index: HashMap<String, u32> = (0..6)
+                .map(|i| {
+                    let node = Node::new(
+                        i,
+                        0,
+                        0,
+                        Blockchain::new(Block::gen_genesis_block()),
+                        world_sender.clone(),
+                        200,
+                        ConsensusType::POS,
+                        8,
+                        &KeyRegistry::thread_default(),
+                    );
+                    (node.get_address(), i)
+                })
+                .collect();
+            let stake_values = crate::metrics::generate_stake_by_gini(6, 0.5, 8);
+            (nodes_index, stake_values)
+        };
+
+        let (nodes_index, stake_values) = assign();
+        let stake_map = stake_assignment(&nodes_index, &stake_values);
+        for (address, index) in nodes_index.iter() {
+            assert_eq!(stake_map[address], stake_values[*index as usize]);
+        }
+        // 相同的 wallet_seed 在另一次运行中得到相同的 地址 -> stake 映射
+        let (nodes_index2, stake_values2) = assign();
+        assert_eq!(stake_map, stake_assignment(&nodes_index2, &stake_values2));
+    }
+
+    #[test]
+    fn test_tx_source_nodes() {
+        let nodes_index: HashMap<String, u32> =
+            (0..10).map(|i| (format!("addr{}", i), i)).collect();
+
+        let sources: TxSourceNodes = "1, 3".parse().unwrap();
+        assert_eq!(sources, TxSourceNodes::Indices(vec![1, 3]));
+        let resolved = sources.resolve(&nodes_index).unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert!(resolved.contains("addr1") && resolved.contains("addr3"));
+
+        let sources: TxSourceNodes = "0.25".parse().unwrap();
+        let resolved = sources.resolve(&nodes_index).unwrap();
+        assert_eq!(resolved.len(), 3);
+        assert!(resolved.contains("addr0") && resolved.contains("addr2"));
+
+        assert!("1.5".parse::<TxSourceNodes>().is_err());
+        assert!("a,b".parse::<TxSourceNodes>().is_err());
+        let missing: TxSourceNodes = "2,12".parse().unwrap();
+        assert!(missing.resolve(&nodes_index).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_printer_status_line() {
+        let genesis = Block::gen_genesis_block();
+        let blockchain = Arc::new(RwLock::new(Blockchain::new(genesis.clone())));
+        let validators = Arc::new(RwLock::new(vec![
+            Validator::new("a".to_string(), 1.0, 1.0),
+            Validator::new("b".to_string(), 1.0, 1.0),
+        ]));
+        let consensus_state = Arc::new(RwLock::new("pog(ntd=3_omega=0.10)".to_string()));
+        let printer = Printer::new(
+            HashMap::new(),
+            Duration::from_secs(1),
+            PrintMode::Status,
+            vec![(7, blockchain)],
+            Arc::new(RwLock::new(HashMap::new())),
+            validators,
+            consensus_state,
+        );
+        let line = printer.status_line().await;
+        assert_eq!(
+            line,
+            format!(
+                "Status: Node[7] height 1, tip {}, validators 2, consensus[pog(ntd=3_omega=0.10)]",
+                &genesis.header.hash[..8]
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_printer_mempool_line() {
+        let reports = Arc::new(RwLock::new(HashMap::new()));
+        let printer = Printer::new(
+            HashMap::new(),
+            Duration::from_secs(1),
+            PrintMode::Status,
+            vec![],
+            reports.clone(),
+            Arc::new(RwLock::new(vec![])),
+            Arc::new(RwLock::new(String::new())),
+        );
+        assert_eq!(printer.mempool_line().await, "Mempool: no reports");
+
+        reports.write().await.extend([
+            (
+                "a".to_string(),
+                MempoolReport {
+                    mempool_size: 3,
+                    expired: 3,
+                    insufficient_balance: 1,
+                    dropped_by_backpressure: 6,
+                    ..Default::default()
+                },
+            ),
+            (
+                "b".to_string(),
+                MempoolReport {
+                    mempool_size: 1,
+                    expired: 2,
+                    evicted: 4,
+                    rate_limited: 5,
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(
+            printer.mempool_line().await,
+            "Mempool: avg 2.0 txs over 2 nodes, expired 5, evicted 4, rate limited 5, insufficient balance 1, dropped by backpressure 6"
+        );
+    }
+
+    /// 布隆过滤器容量按运行期间上链的交易数估计，每个时隙不超过 max_tx_per_block
+    #[test]
+    fn test_tx_filter_capacity() {
+        // 10 tx/s、3 秒一个时隙，运行 20 个时隙
+        assert_eq!(tx_filter_capacity(10, 200, 3, 5, 0, 20), 600);
+        // 按运行秒数换算时隙数
+        assert_eq!(tx_filter_capacity(10, 200, 3, 5, 61, 0), 30 * 21);
+        // 每个时隙最多上链 max_tx_per_block 笔
+        assert_eq!(tx_filter_capacity(1000, 200, 3, 5, 0, 20), 200 * 20);
+        // 不限时长时按 TX_FILTER_EPOCHS 个 epoch 估计
+        assert_eq!(
+            tx_filter_capacity(10, 200, 3, 5, 0, 0),
+            30 * 5 * TX_FILTER_EPOCHS as usize
+        );
+        assert_eq!(tx_filter_capacity(0, 200, 3, 5, 0, 20), 1);
+    }
 }
@@ -2,18 +2,36 @@ use crate::blockchain::block::{Block, BlockError, Body};
 use crate::blockchain::path::{AggregatedSignedPaths, TransactionPaths};
 use crate::blockchain::transaction::Transaction;
 use crate::blockchain::{BlockChainError, Blockchain};
-use crate::consensus::{ConsensusType, RandaoSeed, Validator};
+use crate::consensus::{Consensus, ConsensusType, RandaoSeed, Validator, ValidatorSetDiff};
+use crate::events;
+use crate::logger;
 use crate::network::message::{Message, MessageType};
-use crate::network::world_state::SlotManager;
-use crate::wallet::Wallet;
+use crate::network::world_state::{ExpectedProposer, SlotManager};
+use crate::network::PropagationStrategy;
+use crate::tools::bloom::BloomFilter;
+use crate::wallet::{KeyRegistry, Wallet};
 use log::{debug, error, info, warn};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use serde_json;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::RwLock;
+use tokio::task::JoinSet;
+
+/// 邻居可靠度的EMA平滑系数
+const RELIABILITY_ALPHA: f64 = 0.3;
+
+/// 块同步时默认最多回滚的区块数
+pub const DEFAULT_MAX_REORG_DEPTH: u64 = 3;
 
 ///通过Tokio的mpsc通道与其他节点交互
 ///负责出块、发送交易、发送seed
@@ -28,35 +46,516 @@ pub struct Node {
     pub neighbors: Vec<Neighbor>,
     pub world_state_sender: Sender<Message>,
     pub transaction_paths_cache: Arc<RwLock<HashMap<String, TransactionPaths>>>,
+    pub transaction_received_slot: HashMap<String, u64>, // 交易进入缓存时的时隙计数
+    transaction_cache_order: BTreeMap<EvictionKey, String>, // 缓存满时按小费、手续费从低到高、先收到先淘汰
+    transaction_cache_seq: u64,                             // 交易进入缓存的序号
+    pub slot_count: u64,                                    // 收到的 UpdateSlot 次数
+    pub tx_expiry_slots: u64,                               // 交易缓存过期时隙数，0表示不过期
     pub node_type: NodeType,
-    pub sybil_nodes: Vec<Node>,
     pub is_online: bool,
     pub offline_until_epoch: Option<u64>,
-    pub offline_probability: f64,
+    pub max_reorg_depth: u64, // 块同步时最多回滚的区块数
     pub sync_in_progress: bool,
-    pub transaction_fee: f64,     // 交易手续费
-    pub balance: f64,             // 账户余额
-    pub max_tx_per_block: usize,  // 每个区块最大交易数量
-    pub consensus: ConsensusType, // 共识算法类型
-    pub max_mempool_size: usize,  // 内存池最大容量
-    pub hash_power: f64,          // 节点算力
+    pub sync_target: Option<String>,        // 当前块同步请求的邻居
+    pub sync_candidates: Vec<String>,       // 超时后依次尝试的备选邻居
+    pub sync_request_seq: u64,              // 块同步请求序号，用于忽略过期的超时
+    pub sync_timeout: Duration,             // 块同步请求超时时间
+    pub transaction_fee: f64,               // 交易基础手续费
+    pub transaction_tip: f64,               // 交易小费上限，每笔交易随机取 0 到该值
+    pub balance: f64,                       // 账户余额
+    pub max_tx_per_block: usize,            // 每个区块最大交易数量
+    pub consensus: ConsensusType,           // 共识算法类型
+    pub max_mempool_size: usize,            // 内存池最大容量
+    pub hash_power: f64,                    // 节点算力
+    pub log_target: String,                 // 日志 target：pog::node::{index}
+    pub block_verify_sample: f64,           // 收到区块时抽样验证路径的比例（0~1），0表示不验证
+    pub proposer_verify_sample: f64, // 出块时抽样验证自己打包的路径的比例（0~1），1表示全部验证
+    verify_rng: StdRng,              // 路径抽样验证使用的节点本地随机数
+    pub tx_rate_limiter: TxRateLimiter, // 每时隙自己发起交易数的限速
+    pub mempool_health: Arc<MempoolHealth>, // 内存池丢弃交易的计数，Printer 直接读取
+    key_registry: KeyRegistry,       // 验证路径签名时查找 BLS 公钥的注册表
+    is_validator: bool,              // 收到 BecomeValidator 时是否注册为验证者，中继节点总是不注册
+    chain_cache: ChainCache,         // 最新区块和链上交易的缓存，读取时不占用区块链的锁
+    seen_transactions: SeenTransactions, // 进入过内存池的交易，判断新交易时不占用内存池的锁
+    propagation: PropagationStrategy, // 交易的传播方式
+    pub message_stats: Arc<MessageStats>, // 发出的交易和区块消息数
+    announce_blocks: bool,           // 先公告区块哈希，邻居请求时才发送完整区块
+    recent_blocks: RecentBlocks,     // 公告过的区块，响应邻居的区块请求
+    pub validators: Vec<Validator>,  // WorldState 发送的验证者集合
+    // 本地计算出块者使用的共识实例，None 表示由 WorldState 通知出块
+    local_consensus: Option<Box<dyn Consensus>>,
+    local_proposer_slot: Option<(u64, u64)>, // 最近一次本地计算出块者的 (epoch, slot)
+    partitioned_neighbors: Vec<Neighbor>,    // 网络分区期间断开的邻居，恢复时放回
+    state_path: Option<String>,              // 每个 epoch 保存节点状态的文件，None 表示不保存
+    expected_proposers: HashMap<(u64, u64), Vec<String>>, // WorldState 通知的每个 (epoch, slot) 的出块者
+    last_dump: Option<PathBuf>, // 本节点上次收到 PrintBlockchain 时写入的链文件
 }
 
+/// 节点类型，带有该类型行为的参数
 #[derive(Clone)]
 pub enum NodeType {
     Honest,
-    Selfish,
-    Sybil,
-    Unstable, // 会随机下线的节点
+    // 自私节点，按 drop_probability 不转发收到的交易
+    Selfish {
+        drop_probability: f64,
+    },
+    // 恶意节点，带有多个伪造身份
+    Sybil {
+        identities: Vec<SybilIdentity>,
+    },
+    // 会随机下线的节点：每个 epoch 按 offline_probability 下线 offline_duration_epochs 个 epoch
+    Unstable {
+        offline_probability: f64,
+        offline_duration_epochs: u64,
+    },
+    Bootstrap, // 引导节点，永不下线，始终响应块同步请求
+    Relay,     // 中继节点，只转发交易和区块，不成为验证者
+}
+
+impl NodeType {
+    /// 自私节点，drop_probability 限制在 [0, 1]
+    pub fn selfish(drop_probability: f64) -> Self {
+        NodeType::Selfish {
+            drop_probability: drop_probability.clamp(0.0, 1.0),
+        }
+    }
+
+    /// 不稳定节点，offline_probability 限制在 [0, 1]，每次下线至少持续 1 个 epoch
+    pub fn unstable(offline_probability: f64, offline_duration_epochs: u64) -> Self {
+        NodeType::Unstable {
+            offline_probability: offline_probability.clamp(0.0, 1.0),
+            offline_duration_epochs: offline_duration_epochs.max(1),
+        }
+    }
 }
 
 impl Display for NodeType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match *self {
+        match self {
             NodeType::Honest => write!(f, "Honest"),
-            NodeType::Selfish => write!(f, "Selfish"),
-            NodeType::Sybil => write!(f, "Sybil"),
-            NodeType::Unstable => write!(f, "Unstable"),
+            NodeType::Selfish { drop_probability } => {
+                write!(f, "Selfish(drop={:.2})", drop_probability)
+            }
+            NodeType::Sybil { identities } => write!(f, "Sybil(identities={})", identities.len()),
+            NodeType::Unstable {
+                offline_probability,
+                offline_duration_epochs,
+            } => write!(
+                f,
+                "Unstable(offline={:.2},duration={} epochs)",
+                offline_probability, offline_duration_epochs
+            ),
+            NodeType::Bootstrap => write!(f, "Bootstrap"),
+            NodeType::Relay => write!(f, "Relay"),
+        }
+    }
+}
+
+/// 恶意节点伪造的身份，只有钱包没有独立的通道
+/// 伪造身份的消息都经由主节点的通道收发
+#[derive(Clone)]
+pub struct SybilIdentity {
+    pub index: u32,
+    pub wallet: Wallet,
+    pub hash_power: f64,
+}
+
+impl SybilIdentity {
+    pub fn new(index: u32, wallet: Wallet) -> Self {
+        SybilIdentity {
+            index,
+            wallet,
+            hash_power: 1.0,
+        }
+    }
+
+    pub fn get_address(&self) -> String {
+        self.wallet.address.clone()
+    }
+}
+
+/// 交易缓存（内存池）的统计，路径长度为交易经过的跳数
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MempoolStats {
+    pub tx_count: usize,
+    pub path_lengths: BTreeMap<usize, usize>, // 路径长度 -> 交易数
+}
+
+impl MempoolStats {
+    pub fn avg_path_length(&self) -> f64 {
+        if self.tx_count == 0 {
+            return 0.0;
+        }
+        let total: usize = self
+            .path_lengths
+            .iter()
+            .map(|(length, count)| length * count)
+            .sum();
+        total as f64 / self.tx_count as f64
+    }
+}
+
+/// 节点持久化到磁盘的状态：钱包私钥、余额、区块链和内存池
+#[derive(Serialize, Deserialize)]
+struct NodeState {
+    secret_key: String,
+    balance: f64,
+    blocks: Vec<Block>,
+    mempool: Vec<TransactionPaths>, // 按进入内存池的顺序
+}
+
+/// 内存池丢弃交易的累计计数，节点和交易生成器运行时更新，节点收到 RequestMempoolHealth 时上报
+#[derive(Debug, Default)]
+pub struct MempoolHealth {
+    pub expired: AtomicU64,                 // 过期移除的交易数
+    pub evicted: AtomicU64,                 // 内存池满时淘汰或拒收的交易数
+    pub rate_limited: AtomicU64,            // 超出发起限速被丢弃的交易数
+    pub insufficient_balance: AtomicU64,    // 余额不足未能发起的交易数
+    pub dropped_by_backpressure: AtomicU64, // 节点通道拥塞时交易生成器没有送达的交易数
+}
+
+impl MempoolHealth {
+    pub(crate) fn add(counter: &AtomicU64, n: u64) {
+        counter.fetch_add(n, AtomicOrdering::Relaxed);
+    }
+
+    /// 当前计数和内存池交易数的快照
+    pub fn report(&self, mempool_size: usize) -> MempoolReport {
+        MempoolReport {
+            mempool_size,
+            expired: self.expired.load(AtomicOrdering::Relaxed),
+            evicted: self.evicted.load(AtomicOrdering::Relaxed),
+            rate_limited: self.rate_limited.load(AtomicOrdering::Relaxed),
+            insufficient_balance: self.insufficient_balance.load(AtomicOrdering::Relaxed),
+            dropped_by_backpressure: self.dropped_by_backpressure.load(AtomicOrdering::Relaxed),
+        }
+    }
+}
+
+/// 节点对 RequestMempoolHealth 的响应，由 WorldState 保存，Printer 的 status 模式汇总输出
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MempoolReport {
+    pub mempool_size: usize,
+    pub expired: u64,
+    pub evicted: u64,
+    pub rate_limited: u64,
+    pub insufficient_balance: u64,
+    pub dropped_by_backpressure: u64,
+}
+
+/// 区块公告模式下保留的最近区块数，邻居请求时从中查找
+const RECENT_BLOCKS_CAPACITY: usize = 64;
+
+/// 区块公告（inv/getdata）模式下最近广播过的区块和已请求的区块哈希，超出容量时淘汰最早的区块
+#[derive(Default)]
+struct RecentBlocks {
+    blocks: HashMap<String, Block>,
+    order: VecDeque<String>,
+    requested: HashSet<String>,
+}
+
+impl RecentBlocks {
+    fn insert(&mut self, block: &Block) {
+        let hash = &block.header.hash;
+        self.requested.remove(hash);
+        if self.blocks.insert(hash.clone(), block.clone()).is_some() {
+            return;
+        }
+        self.order.push_back(hash.clone());
+        while self.order.len() > RECENT_BLOCKS_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.blocks.remove(&oldest);
+            }
+        }
+    }
+
+    fn get(&self, hash: &str) -> Option<&Block> {
+        self.blocks.get(hash)
+    }
+
+    /// 收到公告时是否需要请求：没有收到过且没有请求过，同一区块只向第一个公告者请求
+    fn should_request(&mut self, hash: &str) -> bool {
+        if self.blocks.contains_key(hash) || self.requested.contains(hash) {
+            return false;
+        }
+        // 请求后没有收到的区块由块同步补齐，这里只需限制集合大小
+        if self.requested.len() >= RECENT_BLOCKS_CAPACITY {
+            self.requested.clear();
+        }
+        self.requested.insert(hash.to_string());
+        true
+    }
+}
+
+/// 节点发出的交易和区块消息数，用于比较不同传播方式的消息复杂度，可在多个节点间共享
+#[derive(Debug, Default)]
+pub struct MessageStats {
+    pub transactions: AtomicU64,        // 携带交易路径的消息数
+    pub announcements: AtomicU64,       // push-pull 的交易公告数
+    pub requests: AtomicU64,            // push-pull 的交易请求数
+    pub blocks: AtomicU64,              // 携带完整区块的消息数
+    pub block_announcements: AtomicU64, // 区块公告数
+    pub block_requests: AtomicU64,      // 区块请求数
+    pub block_bytes: AtomicU64, // 区块相关消息的字节数：完整区块按 Block::bytes，公告和请求按哈希长度
+}
+
+impl MessageStats {
+    fn add(counter: &AtomicU64, n: usize) {
+        counter.fetch_add(n as u64, AtomicOrdering::Relaxed);
+    }
+
+    pub fn counts(&self) -> MessageCounts {
+        MessageCounts {
+            transactions: self.transactions.load(AtomicOrdering::Relaxed),
+            announcements: self.announcements.load(AtomicOrdering::Relaxed),
+            requests: self.requests.load(AtomicOrdering::Relaxed),
+            blocks: self.blocks.load(AtomicOrdering::Relaxed),
+            block_announcements: self.block_announcements.load(AtomicOrdering::Relaxed),
+            block_requests: self.block_requests.load(AtomicOrdering::Relaxed),
+            block_bytes: self.block_bytes.load(AtomicOrdering::Relaxed),
+        }
+    }
+}
+
+/// MessageStats 某一时刻的读数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MessageCounts {
+    pub transactions: u64,
+    pub announcements: u64,
+    pub requests: u64,
+    pub blocks: u64,
+    pub block_announcements: u64,
+    pub block_requests: u64,
+    pub block_bytes: u64,
+}
+
+impl MessageCounts {
+    /// 消息总数，不含字节数
+    pub fn total(&self) -> u64 {
+        self.transactions
+            + self.announcements
+            + self.requests
+            + self.blocks
+            + self.block_announcements
+            + self.block_requests
+    }
+}
+
+/// 节点自己发起交易的令牌桶限速，每个时隙补满 capacity 个令牌
+/// capacity 为 0 表示不限速；超出的发起请求被丢弃并计数
+#[derive(Debug, Clone, Default)]
+pub struct TxRateLimiter {
+    capacity: usize,
+    tokens: usize,
+    pub dropped: u64, // 因超出限速被丢弃的发起请求数
+}
+
+impl TxRateLimiter {
+    pub fn new(capacity: usize) -> Self {
+        TxRateLimiter {
+            capacity,
+            tokens: capacity,
+            dropped: 0,
+        }
+    }
+
+    /// 新时隙开始时补满令牌
+    pub fn refill(&mut self) {
+        self.tokens = self.capacity;
+    }
+
+    /// 取出一个令牌，令牌用完时返回 false 并计入 dropped
+    pub fn try_acquire(&mut self) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        if self.tokens == 0 {
+            self.dropped += 1;
+            return false;
+        }
+        self.tokens -= 1;
+        true
+    }
+}
+
+/// 打包顺序：小费从高到低，小费相同时按基础手续费从高到低
+fn packing_order(a: &TransactionPaths, b: &TransactionPaths) -> std::cmp::Ordering {
+    let (a, b) = (&a.transaction, &b.transaction);
+    b.tip
+        .partial_cmp(&a.tip)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then(
+            b.fee
+                .partial_cmp(&a.fee)
+                .unwrap_or(std::cmp::Ordering::Equal),
+        )
+}
+
+/// 内存池的淘汰顺序，与打包顺序相反：小费低的先淘汰，小费相同时手续费低的先淘汰，都相同时先收到的先淘汰
+#[derive(Debug, Clone, Copy)]
+struct EvictionKey {
+    tip: f64,
+    fee: f64,
+    seq: u64,
+}
+
+impl EvictionKey {
+    fn new(transaction: &Transaction, seq: u64) -> Self {
+        EvictionKey {
+            tip: transaction.tip,
+            fee: transaction.fee,
+            seq,
+        }
+    }
+
+    /// 只比较费用，不比较收到的先后
+    fn cmp_fee(&self, other: &Self) -> Ordering {
+        self.tip
+            .total_cmp(&other.tip)
+            .then(self.fee.total_cmp(&other.fee))
+    }
+}
+
+impl PartialEq for EvictionKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for EvictionKey {}
+
+impl PartialOrd for EvictionKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EvictionKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_fee(other).then(self.seq.cmp(&other.seq))
+    }
+}
+
+/// 链上交易布隆过滤器的默认容量，模拟中按配置的交易量设置
+pub const DEFAULT_TX_FILTER_CAPACITY: usize = 20_000;
+
+/// 区块链热点查询的缓存，读取时不需要等待区块链的 RwLock
+/// RwLock 中的区块链仍是唯一可信来源，每次写入区块链后在持有写锁时更新缓存
+struct ChainCache {
+    last_index: AtomicU64,
+    last_hash: std::sync::RwLock<String>,
+    // 链上交易哈希，只会误报不会漏报；回滚掉的交易不会移除，只会增加误报
+    tx_filter: std::sync::RwLock<ChainTxFilter>,
+}
+
+/// 链上交易的布隆过滤器，插入数超过容量时按两倍容量重建，误报率不会随链增长而上升
+struct ChainTxFilter {
+    filter: BloomFilter,
+    capacity: usize,
+    inserted: usize,
+}
+
+impl ChainTxFilter {
+    fn new(capacity: usize) -> Self {
+        ChainTxFilter {
+            filter: BloomFilter::with_capacity(capacity, 0.01),
+            capacity,
+            inserted: 0,
+        }
+    }
+
+    fn insert_blocks(&mut self, blocks: &[Block]) {
+        for tx in blocks.iter().flat_map(|b| b.body.transactions.iter()) {
+            self.filter.insert(&tx.hash);
+            self.inserted += 1;
+        }
+    }
+}
+
+impl ChainCache {
+    fn new(blockchain: &Blockchain, tx_filter_capacity: usize) -> Self {
+        let cache = ChainCache {
+            last_index: AtomicU64::new(0),
+            last_hash: std::sync::RwLock::new(String::new()),
+            tx_filter: std::sync::RwLock::new(ChainTxFilter::new(tx_filter_capacity.max(1))),
+        };
+        cache.update(blockchain, blockchain.blocks());
+        cache
+    }
+
+    /// 区块链写入后更新，new_blocks 为可能新加入的区块（多传只会增加误报）
+    fn update(&self, blockchain: &Blockchain, new_blocks: &[Block]) {
+        {
+            let mut tx_filter = self.tx_filter.write().unwrap();
+            tx_filter.insert_blocks(new_blocks);
+            if tx_filter.inserted > tx_filter.capacity {
+                *tx_filter = ChainTxFilter::new(tx_filter.capacity.saturating_mul(2));
+                tx_filter.insert_blocks(blockchain.blocks());
+            }
+        }
+        *self.last_hash.write().unwrap() = blockchain.get_last_hash();
+        self.last_index
+            .store(blockchain.get_last_index(), AtomicOrdering::Release);
+    }
+
+    fn tx_filter_capacity(&self) -> usize {
+        self.tx_filter.read().unwrap().capacity
+    }
+
+    fn last_index(&self) -> u64 {
+        self.last_index.load(AtomicOrdering::Acquire)
+    }
+
+    fn last_hash(&self) -> String {
+        self.last_hash.read().unwrap().clone()
+    }
+
+    /// 返回 false 时交易一定不在链上，返回 true 时需要查询区块链确认
+    fn may_contain_transaction(&self, hash: &str) -> bool {
+        self.tx_filter.read().unwrap().filter.might_contain(hash)
+    }
+}
+
+/// 已收到交易布隆过滤器的最小容量
+const SEEN_TX_FILTER_MIN_CAPACITY: usize = 1024;
+
+/// 进入过内存池的交易哈希，只会误报不会漏报，返回 false 时交易一定是新交易，无需获取内存池的锁
+/// 布隆过滤器不能删除元素，插入数达到容量时按当前内存池重建，保证内存池中的交易都能查到
+struct SeenTransactions {
+    filter: BloomFilter,
+    capacity: usize,
+    inserted: usize,
+}
+
+impl SeenTransactions {
+    /// 容量为内存池容量的两倍，重建后最多占用一半
+    fn new(mempool_size: usize) -> Self {
+        let capacity = mempool_size
+            .saturating_mul(2)
+            .max(SEEN_TX_FILTER_MIN_CAPACITY);
+        SeenTransactions {
+            filter: BloomFilter::with_capacity(capacity, 0.01),
+            capacity,
+            inserted: 0,
+        }
+    }
+
+    fn might_contain(&self, hash: &str) -> bool {
+        self.filter.might_contain(hash)
+    }
+
+    /// 插入新进入内存池的交易，mempool 为插入后的内存池
+    fn insert(&mut self, hash: &str, mempool: &HashMap<String, TransactionPaths>) {
+        if self.inserted < self.capacity {
+            self.filter.insert(hash);
+            self.inserted += 1;
+            return;
+        }
+        self.filter = BloomFilter::with_capacity(self.capacity, 0.01);
+        self.inserted = 0;
+        for hash in mempool.keys() {
+            self.filter.insert(hash);
+            self.inserted += 1;
         }
     }
 }
@@ -66,6 +565,42 @@ pub struct Neighbor {
     pub index: u32,
     pub address: String,
     pub sender: Sender<Message>,
+    pub is_bootstrap: bool, // 是否为引导节点，块同步时优先请求
+    pub reliability: f64,   // 块同步响应的可靠度（0~1），越高越优先请求
+    pub latency: Duration,  // 广播到该邻居的网络延迟，由所在区域决定
+}
+
+/// 单次广播中同时进行的发送数上限
+const MAX_CONCURRENT_SENDS: usize = 16;
+
+/// 在一个后台任务中完成一次广播，而不是每个邻居一个任务
+/// 同时等待的发送不超过 MAX_CONCURRENT_SENDS，避免邻居很多的节点产生大量任务
+/// 按延迟从小到大发送，每条消息在广播开始后经过对应邻居的延迟才发出
+/// 发送失败时以 log_target 记录日志
+fn spawn_broadcast(log_target: String, mut sends: Vec<(Sender<Message>, Message, Duration)>) {
+    if sends.is_empty() {
+        return;
+    }
+    sends.sort_by_key(|(_, _, latency)| *latency);
+    tokio::spawn(async move {
+        let start = tokio::time::Instant::now();
+        let mut join_set = JoinSet::new();
+        for (sender, msg, latency) in sends {
+            if !latency.is_zero() {
+                tokio::time::sleep_until(start + latency).await;
+            }
+            if join_set.len() >= MAX_CONCURRENT_SENDS {
+                join_set.join_next().await;
+            }
+            let log_target = log_target.clone();
+            join_set.spawn(async move {
+                if let Err(e) = sender.send(msg).await {
+                    error!(target: &log_target, "Broadcast to neighbor failed: {}", e);
+                }
+            });
+        }
+        while join_set.join_next().await.is_some() {}
+    });
 }
 
 impl Node {
@@ -78,11 +613,12 @@ impl Node {
         max_tx_per_block: usize,
         consensus: ConsensusType,
         wallet_seed: u64,
+        key_registry: &KeyRegistry,
     ) -> Self {
         let wallet = if wallet_seed == 0 {
-            Wallet::new()
+            Wallet::new_in(key_registry)
         } else {
-            Wallet::new_deterministic(wallet_seed, index)
+            Wallet::new_deterministic_in(wallet_seed, index, key_registry)
         };
         let (sender, receiver) = tokio::sync::mpsc::channel(4096);
         Node {
@@ -90,24 +626,54 @@ impl Node {
             epoch,
             slot,
             wallet,
+            chain_cache: ChainCache::new(&blockchain, DEFAULT_TX_FILTER_CAPACITY),
             blockchain: Arc::new(RwLock::new(blockchain)),
             sender,
             receiver,
             transaction_paths_cache: Arc::new(RwLock::new(HashMap::new())),
+            transaction_received_slot: HashMap::new(),
+            transaction_cache_order: BTreeMap::new(),
+            transaction_cache_seq: 0,
+            slot_count: 0,
+            tx_expiry_slots: 0,
             neighbors: Vec::new(),
             world_state_sender,
             node_type: NodeType::Honest,
-            sybil_nodes: Vec::new(),
             is_online: true,
             offline_until_epoch: None,
-            offline_probability: 0.1,
+            max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
             sync_in_progress: false,
+            sync_target: None,
+            sync_candidates: Vec::new(),
+            sync_request_seq: 0,
+            sync_timeout: Duration::from_secs(2),
             transaction_fee: 0.0,
+            transaction_tip: 0.0,
             balance: 0.0,
             max_tx_per_block,
             consensus,
             max_mempool_size: max_tx_per_block,
+            seen_transactions: SeenTransactions::new(max_tx_per_block),
+            propagation: PropagationStrategy::Flood,
+            message_stats: Arc::new(MessageStats::default()),
+            announce_blocks: false,
+            recent_blocks: RecentBlocks::default(),
             hash_power: 1.0,
+            log_target: logger::node_target(index),
+            block_verify_sample: 0.0,
+            proposer_verify_sample: 1.0,
+            verify_rng: StdRng::from_entropy(),
+            tx_rate_limiter: TxRateLimiter::default(),
+            mempool_health: Arc::new(MempoolHealth::default()),
+            key_registry: key_registry.clone(),
+            is_validator: true,
+            validators: Vec::new(),
+            local_consensus: None,
+            local_proposer_slot: None,
+            partitioned_neighbors: Vec::new(),
+            state_path: None,
+            expected_proposers: HashMap::new(),
+            last_dump: None,
         }
     }
 
@@ -127,24 +693,54 @@ impl Node {
             epoch,
             slot,
             wallet,
+            chain_cache: ChainCache::new(&blockchain, DEFAULT_TX_FILTER_CAPACITY),
             blockchain: Arc::new(RwLock::new(blockchain)),
             sender,
             receiver,
             transaction_paths_cache: Arc::new(RwLock::new(HashMap::new())),
+            transaction_received_slot: HashMap::new(),
+            transaction_cache_order: BTreeMap::new(),
+            transaction_cache_seq: 0,
+            slot_count: 0,
+            tx_expiry_slots: 0,
             neighbors: Vec::new(),
             world_state_sender,
             node_type: NodeType::Honest,
-            sybil_nodes: Vec::new(),
             is_online: true,
             offline_until_epoch: None,
-            offline_probability: 0.1,
+            max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
             sync_in_progress: false,
+            sync_target: None,
+            sync_candidates: Vec::new(),
+            sync_request_seq: 0,
+            sync_timeout: Duration::from_secs(2),
             transaction_fee: 0.0,
+            transaction_tip: 0.0,
             balance: 0.0,
             max_tx_per_block,
             consensus,
             max_mempool_size: max_tx_per_block,
+            seen_transactions: SeenTransactions::new(max_tx_per_block),
+            propagation: PropagationStrategy::Flood,
+            message_stats: Arc::new(MessageStats::default()),
+            announce_blocks: false,
+            recent_blocks: RecentBlocks::default(),
             hash_power: 1.0,
+            log_target: logger::node_target(index),
+            block_verify_sample: 0.0,
+            proposer_verify_sample: 1.0,
+            verify_rng: StdRng::from_entropy(),
+            tx_rate_limiter: TxRateLimiter::default(),
+            mempool_health: Arc::new(MempoolHealth::default()),
+            key_registry: KeyRegistry::thread_default(),
+            is_validator: true,
+            validators: Vec::new(),
+            local_consensus: None,
+            local_proposer_slot: None,
+            partitioned_neighbors: Vec::new(),
+            state_path: None,
+            expected_proposers: HashMap::new(),
+            last_dump: None,
         }
     }
 
@@ -158,51 +754,74 @@ impl Node {
         max_tx_per_block: usize,
         consensus: ConsensusType,
         wallet_seed: u64,
+        key_registry: &KeyRegistry,
     ) -> Self {
-        let mut sybil_nodes: Vec<Node> = Vec::new();
-        for i in 0..fake_node_num {
-            let mut n = Node::new(
-                index * 1000 + i as u32,
-                epoch,
-                slot,
-                blockchain.clone(),
-                world_state_sender.clone(),
-                max_tx_per_block,
-                consensus,
-                wallet_seed,
-            );
-            n.set_node_type(NodeType::Sybil);
-            sybil_nodes.push(n);
-        }
         let wallet = if wallet_seed == 0 {
-            Wallet::new()
+            Wallet::new_in(key_registry)
         } else {
-            Wallet::new_deterministic(wallet_seed, index)
+            Wallet::new_deterministic_in(wallet_seed, index, key_registry)
         };
+        // 伪造身份的钱包由主节点钱包派生，相同的 wallet_seed 得到相同的伪造身份
+        let identities: Vec<SybilIdentity> = (0..fake_node_num)
+            .map(|i| {
+                let wallet = wallet.derive_child_in(i as u32, key_registry);
+                SybilIdentity::new(index * 1000 + i as u32, wallet)
+            })
+            .collect();
         let (sender, receiver) = tokio::sync::mpsc::channel(4096);
         Node {
             index,
             epoch,
             slot,
             wallet,
+            chain_cache: ChainCache::new(&blockchain, DEFAULT_TX_FILTER_CAPACITY),
             blockchain: Arc::new(RwLock::new(blockchain)),
             sender,
             receiver,
             transaction_paths_cache: Arc::new(RwLock::new(HashMap::new())),
+            transaction_received_slot: HashMap::new(),
+            transaction_cache_order: BTreeMap::new(),
+            transaction_cache_seq: 0,
+            slot_count: 0,
+            tx_expiry_slots: 0,
             neighbors: Vec::new(),
             world_state_sender,
-            node_type: NodeType::Sybil,
-            sybil_nodes,
+            node_type: NodeType::Sybil { identities },
             is_online: true,
             offline_until_epoch: None,
-            offline_probability: 0.1,
+            max_reorg_depth: DEFAULT_MAX_REORG_DEPTH,
             sync_in_progress: false,
+            sync_target: None,
+            sync_candidates: Vec::new(),
+            sync_request_seq: 0,
+            sync_timeout: Duration::from_secs(2),
             transaction_fee: 0.0,
+            transaction_tip: 0.0,
             balance: 0.0,
             max_tx_per_block,
             consensus,
             max_mempool_size: max_tx_per_block,
+            seen_transactions: SeenTransactions::new(max_tx_per_block),
+            propagation: PropagationStrategy::Flood,
+            message_stats: Arc::new(MessageStats::default()),
+            announce_blocks: false,
+            recent_blocks: RecentBlocks::default(),
             hash_power: 1.0,
+            log_target: logger::node_target(index),
+            block_verify_sample: 0.0,
+            proposer_verify_sample: 1.0,
+            verify_rng: StdRng::from_entropy(),
+            tx_rate_limiter: TxRateLimiter::default(),
+            mempool_health: Arc::new(MempoolHealth::default()),
+            key_registry: key_registry.clone(),
+            is_validator: true,
+            validators: Vec::new(),
+            local_consensus: None,
+            local_proposer_slot: None,
+            partitioned_neighbors: Vec::new(),
+            state_path: None,
+            expected_proposers: HashMap::new(),
+            last_dump: None,
         }
     }
 
@@ -210,14 +829,270 @@ impl Node {
         self.node_type = node_type;
     }
 
-    pub fn set_offline_probability(&mut self, probability: f64) {
-        self.offline_probability = probability.clamp(0.0, 1.0);
+    /// 恶意节点的伪造身份，其他类型的节点没有伪造身份
+    pub fn sybil_identities(&self) -> &[SybilIdentity] {
+        match &self.node_type {
+            NodeType::Sybil { identities } => identities,
+            _ => &[],
+        }
+    }
+
+    pub fn set_max_reorg_depth(&mut self, depth: u64) {
+        self.max_reorg_depth = depth;
     }
 
     pub fn set_hash_power(&mut self, hash_power: f64) {
         self.hash_power = hash_power;
     }
 
+    /// 设置抽样验证比例，参数为百分比（0~100）
+    pub fn set_block_verify_sample(&mut self, percent: f64) {
+        self.block_verify_sample = (percent / 100.0).clamp(0.0, 1.0);
+    }
+
+    /// 设置出块时路径验证的抽样比例，参数为百分比（0~100）
+    pub fn set_proposer_verify_sample(&mut self, percent: f64) {
+        self.proposer_verify_sample = (percent / 100.0).clamp(0.0, 1.0);
+    }
+
+    /// 验证路径签名使用的 BLS 公钥注册表，默认为创建节点时登记钱包的注册表
+    pub fn set_key_registry(&mut self, registry: KeyRegistry) {
+        self.key_registry = registry;
+    }
+
+    /// 未被选为验证者的节点只转发交易和区块，在 POG 下仍可获得网络费用
+    pub fn set_validator(&mut self, is_validator: bool) {
+        self.is_validator = is_validator;
+    }
+
+    /// 每个时隙最多自己发起的交易数，0 表示不限速
+    pub fn set_max_tx_per_slot(&mut self, max_tx_per_slot: usize) {
+        self.tx_rate_limiter = TxRateLimiter::new(max_tx_per_slot);
+    }
+
+    pub fn set_tx_expiry_slots(&mut self, tx_expiry_slots: u64) {
+        self.tx_expiry_slots = tx_expiry_slots;
+    }
+
+    /// 使用给定的共识实例在本地计算出块者，不再等待 WorldState 通知
+    pub fn set_local_proposer(&mut self, consensus: Box<dyn Consensus>) {
+        self.local_consensus = Some(consensus);
+    }
+
+    /// 交易缓存（内存池）容量，满时淘汰最早收到的交易，需在收到交易前设置
+    pub fn set_tx_cache_size(&mut self, size: usize) {
+        self.max_mempool_size = size;
+        self.seen_transactions = SeenTransactions::new(size);
+    }
+
+    /// 链上交易布隆过滤器的容量，按预计上链的交易数设置，超出后自动扩容，需在节点启动前设置
+    /// 区块链正被占用时无法重建过滤器，保留原来的容量并记录日志
+    pub fn set_tx_filter_capacity(&mut self, capacity: usize) {
+        match self.blockchain.try_read() {
+            Ok(blockchain) => self.chain_cache = ChainCache::new(&blockchain, capacity),
+            Err(_) => warn!(
+                target: &self.log_target,
+                "Node[{}] blockchain is locked, skip resizing the tx filter to {}",
+                self.index, capacity
+            ),
+        }
+    }
+
+    /// 每个 epoch 开始时把节点状态保存到 path，用于中断后恢复长时间运行的模拟
+    pub fn set_state_path(&mut self, path: String) {
+        self.state_path = Some(path);
+    }
+
+    /// 保存钱包、余额、区块链和内存池，先写临时文件再重命名，避免中途崩溃留下不完整的文件
+    pub async fn save_state(&self, path: &str) -> std::io::Result<()> {
+        let blocks = self.blockchain.read().await.blocks().to_vec();
+        let mempool = {
+            let cache = self.transaction_paths_cache.read().await;
+            self.transaction_cache_order
+                .values()
+                .filter_map(|hash| cache.get(hash).cloned())
+                .collect()
+        };
+        let state = NodeState {
+            secret_key: self.wallet.secret_key_string(),
+            balance: self.balance,
+            blocks,
+            mempool,
+        };
+        let tmp_path = format!("{}.tmp", path);
+        tokio::fs::write(&tmp_path, serde_json::to_vec(&state)?).await?;
+        tokio::fs::rename(&tmp_path, path).await
+    }
+
+    /// 从 save_state 保存的文件恢复节点状态，需在节点启动前调用
+    /// 创世区块与当前链不同的文件属于其他模拟，拒绝加载
+    pub async fn load_state(&mut self, path: &str) -> std::io::Result<()> {
+        let invalid = |e: String| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+        let state: NodeState = serde_json::from_slice(&tokio::fs::read(path).await?)?;
+        let wallet =
+            Wallet::from_secret_key_string(state.secret_key).map_err(|e| invalid(e.to_string()))?;
+        let (genesis, blocks) = state
+            .blocks
+            .split_first()
+            .ok_or_else(|| invalid("empty blockchain".to_string()))?;
+        {
+            let mut blockchain = self.blockchain.write().await;
+            if genesis.header.hash != blockchain.blocks()[0].header.hash {
+                return Err(invalid(format!("genesis mismatch in {}", path)));
+            }
+            let mut restored = Blockchain::new(genesis.clone());
+            restored.extend_unchecked(blocks);
+            self.chain_cache = ChainCache::new(&restored, self.chain_cache.tx_filter_capacity());
+            *blockchain = restored;
+        }
+
+        self.key_registry.register(&wallet);
+        self.wallet = wallet;
+        self.balance = state.balance;
+        self.transaction_paths_cache.write().await.clear();
+        self.transaction_cache_order.clear();
+        self.transaction_received_slot.clear();
+        self.seen_transactions = SeenTransactions::new(self.max_mempool_size);
+        for transaction_paths in &state.mempool {
+            self.cache_transaction_paths(transaction_paths).await;
+        }
+        Ok(())
+    }
+
+    /// 交易的传播方式，message_stats 统计发出的消息数，可在多个节点间共享
+    pub fn set_propagation(
+        &mut self,
+        propagation: PropagationStrategy,
+        message_stats: Arc<MessageStats>,
+    ) {
+        self.propagation = propagation;
+        self.message_stats = message_stats;
+    }
+
+    /// 开启后区块先公告哈希（inv/getdata），邻居请求时才发送完整区块
+    pub fn set_announce_blocks(&mut self, announce_blocks: bool) {
+        self.announce_blocks = announce_blocks;
+    }
+
+    /// 当前缓存的交易数及其路径长度分布，用于观察交易打包前经过的路径长短
+    pub async fn mempool_stats(&self) -> MempoolStats {
+        let cache = self.transaction_paths_cache.read().await;
+        let mut path_lengths = BTreeMap::new();
+        for transaction_paths in cache.values() {
+            *path_lengths
+                .entry(transaction_paths.paths.len())
+                .or_insert(0) += 1;
+        }
+        MempoolStats {
+            tx_count: cache.len(),
+            path_lengths,
+        }
+    }
+
+    /// 交易在本节点链上的确认数，交易不在链上时返回 None
+    pub async fn confirmations(&self, tx_hash: &str) -> Option<u64> {
+        self.blockchain.read().await.confirmations(tx_hash)
+    }
+
+    /// 不稳定节点在时隙更新时检查上下线
+    /// 离线期满后恢复在线并请求块同步；epoch 变化时按概率下线 offline_duration_epochs 个 epoch
+    async fn update_online_status(&mut self, old_epoch: u64) {
+        // 检查是否刚从离线恢复
+        if !self.is_online
+            && self.offline_until_epoch.is_some()
+            && self.epoch >= self.offline_until_epoch.unwrap()
+        {
+            // 离线期间公钥可能已被 WorldState 清理，重新注册
+            self.key_registry.register(&self.wallet);
+            // 即将恢复在线，向邻居请求块同步（引导节点及可靠度高的邻居优先）
+            if !self.neighbors.is_empty() {
+                self.request_block_sync().await;
+            }
+
+            self.is_online = true;
+            self.offline_until_epoch = None;
+            self.report_online_status().await;
+            warn!(
+                target: &self.log_target,
+                "Node[{}] is back online at epoch {}",
+                self.index, self.epoch
+            );
+        }
+
+        let (offline_probability, offline_duration_epochs) = match self.node_type {
+            NodeType::Unstable {
+                offline_probability,
+                offline_duration_epochs,
+            } => (offline_probability, offline_duration_epochs),
+            _ => return,
+        };
+        // 仅在 epoch 变化且节点仍在线时，才考虑随机下线
+        if self.is_online && self.epoch != old_epoch && (self.offline_until_epoch.is_none()) {
+            // 根据配置的概率下线 offline_duration_epochs 个epoch
+            if rand::thread_rng().gen_bool(offline_probability) {
+                let until_epoch = self.epoch + offline_duration_epochs;
+                self.is_online = false;
+                self.offline_until_epoch = Some(until_epoch);
+                self.report_online_status().await;
+                warn!(
+                    target: &self.log_target,
+                    "Node[{}] goes offline at epoch {} until epoch {}",
+                    self.index, self.epoch, until_epoch
+                );
+            }
+        }
+    }
+
+    /// 向 WorldState 报告当前的在线状态，离线的验证者不会被选为出块者
+    async fn report_online_status(&self) {
+        let msg = Message::new_node_online_status_msg(self.wallet.address.clone(), self.is_online);
+        if let Err(e) = self.world_state_sender.send(msg).await {
+            error!(
+                target: &self.log_target,
+                "Node[{}] failed to report online status: {}", self.index, e
+            );
+        }
+    }
+
+    /// 记录交易首次进入缓存的时隙，更新路径不会重置
+    fn track_transaction_slot(&mut self, tx_hash: &str) {
+        self.transaction_received_slot
+            .entry(tx_hash.to_string())
+            .or_insert(self.slot_count);
+    }
+
+    /// 移除缓存超过 tx_expiry_slots 个时隙仍未被打包的交易
+    async fn expire_transactions(&mut self) {
+        let mut transaction_paths_cache = self.transaction_paths_cache.write().await;
+        // 已打包或已移除的交易不再跟踪
+        self.transaction_received_slot
+            .retain(|hash, _| transaction_paths_cache.contains_key(hash));
+        if self.tx_expiry_slots == 0 {
+            return;
+        }
+        let slot_count = self.slot_count;
+        let tx_expiry_slots = self.tx_expiry_slots;
+        let expired: Vec<String> = self
+            .transaction_received_slot
+            .iter()
+            .filter(|(_, received)| slot_count - **received > tx_expiry_slots)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        for hash in &expired {
+            transaction_paths_cache.remove(hash);
+            self.transaction_received_slot.remove(hash);
+        }
+        MempoolHealth::add(&self.mempool_health.expired, expired.len() as u64);
+        if !expired.is_empty() {
+            debug!(
+                target: &self.log_target,
+                "Node[{}] dropped {} expired transactions",
+                self.index,
+                expired.len()
+            );
+        }
+    }
+
     pub async fn create_block_template(&self, epoch: u64, slot: u64) -> Result<Block, BlockError> {
         let transaction_paths_to_pack = {
             let transaction_paths_cache = self.transaction_paths_cache.read().await;
@@ -230,13 +1105,8 @@ impl Node {
                 .cloned()
                 .collect();
 
-            // 2. 按手续费从高到低排序
-            valid_paths.sort_by(|a, b| {
-                b.transaction
-                    .fee
-                    .partial_cmp(&a.transaction.fee)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            });
+            // 2. 按小费、基础手续费从高到低排序
+            valid_paths.sort_by(packing_order);
 
             // 3. 截取前 max_tx_per_block 个
             let pack_count = std::cmp::min(valid_paths.len(), self.max_tx_per_block);
@@ -253,20 +1123,19 @@ impl Node {
             paths.push(x.to_aggregated_signed_paths());
         }
 
-        // 获取需要的信息后再释放读锁
-        let blockchain = self.blockchain.read().await;
-        let last_index = blockchain.get_last_index();
-        let last_hash = blockchain.get_last_hash();
-        drop(blockchain);
+        let last_index = self.chain_cache.last_index();
+        let last_hash = self.chain_cache.last_hash();
 
         let body = Body::new(transactions, paths);
-        let new_block = Block::new(
+        let new_block = Block::new_with_path_sample(
             last_index + 1,
             epoch,
             slot,
             last_hash,
             body,
             self.wallet.clone(),
+            self.proposer_verify_sample,
+            &self.key_registry,
         )?;
 
         Ok(new_block)
@@ -284,13 +1153,8 @@ impl Node {
                 .cloned()
                 .collect();
 
-            // 2. 按手续费从高到低排序
-            valid_paths.sort_by(|a, b| {
-                b.transaction
-                    .fee
-                    .partial_cmp(&a.transaction.fee)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            });
+            // 2. 按小费、基础手续费从高到低排序
+            valid_paths.sort_by(packing_order);
 
             // 3. 截取前 max_tx_per_block 个
             let pack_count = std::cmp::min(valid_paths.len(), self.max_tx_per_block);
@@ -323,26 +1187,25 @@ impl Node {
 
         let body = Body::new(transactions, paths);
         let new_block = {
-            Block::new(
+            Block::new_with_path_sample(
                 last_index + 1,
                 epoch,
                 slot,
                 last_hash,
                 body,
                 self.wallet.clone(),
+                self.proposer_verify_sample,
+                &self.key_registry,
             )?
         };
         {
-            if let Err(e) = self
-                .blockchain
-                .clone()
-                .write()
-                .await
-                .add_block(new_block.clone())
-            {
-                error!("Node[{}] error :{}", self.index, e);
+            let mut blockchain = self.blockchain.write().await;
+            if let Err(e) = blockchain.add_block(new_block.clone()) {
+                error!(target: &self.log_target, "Node[{}] error :{}", self.index, e);
                 return Err(BlockError::InvalidBlock);
             };
+            self.chain_cache
+                .update(&blockchain, std::slice::from_ref(&new_block));
         }
 
         Ok(new_block)
@@ -362,6 +1225,7 @@ impl Node {
 
     pub fn simple_print(&self) {
         info!(
+            target: &self.log_target,
             "node[{}],node_type[{}],node_address:{}",
             self.index,
             self.node_type,
@@ -373,6 +1237,10 @@ impl Node {
         self.transaction_fee = fee;
     }
 
+    pub fn set_transaction_tip(&mut self, tip: f64) {
+        self.transaction_tip = tip;
+    }
+
     pub fn set_balance(&mut self, balance: f64) {
         self.balance = balance;
     }
@@ -391,20 +1259,650 @@ impl Node {
         }
     }
 
-    pub async fn run(&mut self) {
-        while let Some(msg) = self.receiver.recv().await {
-            // 离线逻辑：如果节点离线，跳过大多数消息处理
-            // 但 UpdateSlot 消息用于恢复在线逻辑，需要处理
-            if !self.is_online && !matches!(msg.msg_type, MessageType::UpdateSlot) {
-                debug!(
-                    "Node[{}] is offline, skipping message[{}]",
-                    self.index, msg.msg_type
-                );
-                match msg.msg_type {
-                    MessageType::GenerateBlock => {
-                        warn!(
-                            "Node[{}] missed block generation due to being offline at slot {}",
-                            self.index, self.slot
+    /// 块同步的请求顺序：引导节点优先，其次按可靠度从高到低
+    pub fn sync_targets(&self) -> Vec<Neighbor> {
+        let mut targets = self.neighbors.clone();
+        targets.sort_by(|a, b| {
+            b.is_bootstrap.cmp(&a.is_bootstrap).then(
+                b.reliability
+                    .partial_cmp(&a.reliability)
+                    .unwrap_or(Ordering::Equal),
+            )
+        });
+        targets
+    }
+
+    /// 发起块同步：每次只请求一个邻居，超时或响应无用时再请求下一个
+    pub async fn request_block_sync(&mut self) {
+        self.sync_candidates = self
+            .sync_targets()
+            .iter()
+            .map(|n| n.address.clone())
+            .collect();
+        self.sync_in_progress = true;
+        self.send_next_sync_request().await;
+    }
+
+    async fn send_next_sync_request(&mut self) {
+        if self.sync_candidates.is_empty() {
+            warn!(
+                target: &self.log_target,
+                "Node[{}] block sync failed: no more neighbors to request",
+                self.index
+            );
+            self.finish_block_sync();
+            return;
+        }
+        let address = self.sync_candidates.remove(0);
+        let neighbor = match self.neighbors.iter().find(|n| n.address == address) {
+            Some(n) => n.clone(),
+            None => {
+                self.finish_block_sync();
+                return;
+            }
+        };
+        // 多请求 max_reorg_depth 个区块，以便在分叉时找到共同祖先
+        let last_block_index = self
+            .chain_cache
+            .last_index()
+            .saturating_sub(self.max_reorg_depth);
+        self.sync_request_seq += 1;
+        self.sync_target = Some(address);
+        debug!(
+            target: &self.log_target,
+            "Node[{}] requests block sync from Node[{}] (reliability {:.2}), last block index: {}",
+            self.index, neighbor.index, neighbor.reliability, last_block_index
+        );
+
+        let self_address = self.get_address();
+        tokio::spawn(async move {
+            neighbor
+                .sender
+                .send(Message::new_request_block_sync_msg(
+                    last_block_index,
+                    self_address,
+                ))
+                .await
+                .unwrap();
+        });
+
+        // 超时后通知自己切换到下一个邻居
+        let self_sender = self.sender.clone();
+        let seq = self.sync_request_seq;
+        let timeout = self.sync_timeout;
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            let _ = self_sender
+                .send(Message::new_block_sync_timeout_msg(seq))
+                .await;
+        });
+    }
+
+    fn finish_block_sync(&mut self) {
+        self.sync_in_progress = false;
+        self.sync_target = None;
+        self.sync_candidates.clear();
+    }
+
+    fn update_neighbor_reliability(&mut self, address: &str, useful: bool) {
+        if let Some(neighbor) = self.neighbors.iter_mut().find(|n| n.address == address) {
+            let outcome = if useful { 1.0 } else { 0.0 };
+            neighbor.reliability =
+                (1.0 - RELIABILITY_ALPHA) * neighbor.reliability + RELIABILITY_ALPHA * outcome;
+        }
+    }
+
+    /// 处理块同步响应的结果：更新邻居可靠度，成功则结束同步，否则请求下一个邻居
+    async fn on_block_sync_response(&mut self, responder: &str, useful: bool) {
+        self.update_neighbor_reliability(responder, useful);
+        if !self.sync_in_progress {
+            return;
+        }
+        if useful {
+            self.finish_block_sync();
+        } else if self.sync_target.as_deref() == Some(responder) {
+            self.send_next_sync_request().await;
+        }
+    }
+
+    /// 收到交易路径：过滤已上链的交易和路径不更短的重复交易，存入内存池
+    /// 返回 true 表示需要继续广播
+    async fn accept_transaction_paths(
+        &mut self,
+        transaction_paths: &TransactionPaths,
+        msg_type: &MessageType,
+    ) -> bool {
+        // if !transaction_paths.verify_last(self.wallet.address.clone()) {
+        //     error!(target: &self.log_target, "Node[{}] invalid transaction paths", self.index);
+        //     return false;
+        // }
+        // 布隆过滤器判断不在链上时无需获取区块链的读锁
+        if self
+            .chain_cache
+            .may_contain_transaction(&transaction_paths.transaction.hash)
+        {
+            let bc = self.blockchain.read().await;
+            if bc.exist_transaction(transaction_paths.transaction.hash.clone()) {
+                debug!(
+                    target: &self.log_target,
+                    "Node[{}] received transaction[{}] already in blockchain",
+                    self.index, transaction_paths.transaction.hash
+                );
+                return false;
+            }
+        }
+        //判断交易是否已经收到了,判断交易的paths是否最短 (O(1)查找)
+        // 布隆过滤器判断没收到过时无需获取内存池的读锁
+        if self
+            .seen_transactions
+            .might_contain(&transaction_paths.transaction.hash)
+        {
+            let transactions_cache = self.transaction_paths_cache.read().await;
+            let tx_hash = &transaction_paths.transaction.hash;
+
+            if let Some(cached_tx) = transactions_cache.get(tx_hash) {
+                if self.consensus == ConsensusType::POG {
+                    // POG: 只有当缓存的路径长度更短或相等时才跳过
+                    if cached_tx.paths.len() <= transaction_paths.paths.len() {
+                        return false;
+                    }
+                } else {
+                    // 其他共识: 只要收到过就跳过
+                    return false;
+                }
+            }
+        }
+        debug!(
+            target: &self.log_target,
+            "Node[{}] received msg[{}]: transaction hash[{}],path[{}]",
+            self.short_address_with_index(),
+            msg_type,
+            transaction_paths.transaction.hash,
+            transaction_paths.to_paths_string(),
+        );
+        //收到交易，存储
+        self.cache_transaction_paths(transaction_paths).await
+    }
+
+    /// 存入内存池，内存池已满时淘汰小费和手续费最低的交易，费用相同时淘汰最早收到的交易
+    /// 新交易的费用比内存池中所有交易都低、或容量为0时丢弃并返回 false
+    async fn cache_transaction_paths(&mut self, transaction_paths: &TransactionPaths) -> bool {
+        let mut transactions_cache = self.transaction_paths_cache.write().await;
+        let tx_hash = transaction_paths.transaction.hash.clone();
+
+        if !transactions_cache.contains_key(&tx_hash) {
+            let key = EvictionKey::new(&transaction_paths.transaction, self.transaction_cache_seq);
+            // 已打包或过期的交易会留在淘汰队列中，淘汰时跳过
+            while transactions_cache.len() >= self.max_mempool_size {
+                let lowest = self
+                    .transaction_cache_order
+                    .first_key_value()
+                    .map(|(k, hash)| (*k, hash.clone()));
+                match lowest {
+                    Some((_, hash)) if !transactions_cache.contains_key(&hash) => {
+                        self.transaction_cache_order.pop_first();
+                    }
+                    Some((lowest, hash)) if lowest.cmp_fee(&key) != Ordering::Greater => {
+                        self.transaction_cache_order.pop_first();
+                        transactions_cache.remove(&hash);
+                        self.transaction_received_slot.remove(&hash);
+                        MempoolHealth::add(&self.mempool_health.evicted, 1);
+                        debug!(
+                            target: &self.log_target,
+                            "Node[{}] mempool full, evicting transaction[{}]",
+                            self.index, hash
+                        );
+                    }
+                    _ => {
+                        MempoolHealth::add(&self.mempool_health.evicted, 1);
+                        debug!(
+                            target: &self.log_target,
+                            "Node[{}] mempool full, dropping transaction[{}]",
+                            self.index, tx_hash
+                        );
+                        return false;
+                    }
+                }
+            }
+            self.transaction_cache_seq += 1;
+            self.transaction_cache_order.insert(key, tx_hash.clone());
+            // 队列中失效的交易过多时整理一次，避免无限增长
+            if self.transaction_cache_order.len() > 2 * self.max_mempool_size.max(1) {
+                self.transaction_cache_order
+                    .retain(|_, hash| transactions_cache.contains_key(hash) || *hash == tx_hash);
+            }
+        }
+
+        //插入或更新交易
+        if transactions_cache
+            .insert(tx_hash.clone(), transaction_paths.clone())
+            .is_none()
+        {
+            self.seen_transactions.insert(&tx_hash, &transactions_cache);
+        }
+        drop(transactions_cache);
+        self.track_transaction_slot(&tx_hash);
+        true
+    }
+
+    /// 生成一笔发往 to 的交易并存入内存池，超出限速、余额不足或内存池已满时返回 None
+    async fn generate_transaction_paths(
+        &mut self,
+        to: String,
+        msg_type: &MessageType,
+    ) -> Option<TransactionPaths> {
+        if !self.tx_rate_limiter.try_acquire() {
+            MempoolHealth::add(&self.mempool_health.rate_limited, 1);
+            debug!(
+                target: &self.log_target,
+                "Node[{}] transaction origination rate limited, dropped {}",
+                self.index, self.tx_rate_limiter.dropped
+            );
+            return None;
+        }
+
+        // 各笔交易的小费不同，出块者按小费排序打包
+        let tip = if self.transaction_tip > 0.0 {
+            rand::thread_rng().gen_range(0.0..=self.transaction_tip)
+        } else {
+            0.0
+        };
+        // 检查余额是否充足
+        let total_fee = self.transaction_fee + tip;
+        if !self.deduct_balance(total_fee) {
+            MempoolHealth::add(&self.mempool_health.insufficient_balance, 1);
+            warn!(
+                target: &self.log_target,
+                "Node[{}] insufficient balance: {} < {}",
+                self.index, self.balance, total_fee
+            );
+            return None;
+        }
+
+        // 扣除余额后，同步到 Validator 的 stake
+        self.world_state_sender
+            .send(Message::new_update_validator_stake_msg(
+                self.wallet.address.clone(),
+                self.balance,
+            ))
+            .await
+            .unwrap();
+
+        let transaction =
+            Transaction::with_tip(to, 0, self.transaction_fee, tip, self.wallet.clone());
+        let transaction_paths = TransactionPaths::new(transaction);
+        debug!(
+            target: &self.log_target,
+            "Node[{}] received msg[{}]: transaction hash[{}],path[{}]",
+            self.short_address_with_index(),
+            msg_type,
+            transaction_paths.transaction.hash,
+            transaction_paths.to_paths_string()
+        );
+        //缓存交易
+        if !self.cache_transaction_paths(&transaction_paths).await {
+            return None;
+        }
+        let msg =
+            Message::new_transaction_generated_msg(transaction_paths.transaction.hash.clone());
+        if let Err(e) = self.world_state_sender.send(msg).await {
+            error!(
+                target: &self.log_target,
+                "Node[{}] failed to report generated transaction: {}", self.index, e
+            );
+        }
+        Some(transaction_paths)
+    }
+
+    /// 保存 WorldState 发送的完整验证者集合，或在已有集合上应用增量
+    pub fn update_validator_set(&mut self, msg: &Message) {
+        let result = match msg.msg_type {
+            MessageType::UpdateValidatorSet => serde_json::from_slice(&msg.data)
+                .map(|validators: Vec<Validator>| self.validators = validators),
+            MessageType::UpdateValidatorSetDiff => serde_json::from_slice(&msg.data)
+                .map(|diff: ValidatorSetDiff| diff.apply(&mut self.validators)),
+            _ => return,
+        };
+        if let Err(e) = result {
+            error!(target: &self.log_target, "Node[{}] error: {}", self.index, e);
+        }
+    }
+
+    /// 记录 WorldState 通知的出块者，只保留当前和上一个 epoch
+    /// 本地计算出块者时忽略 WorldState 的通知，避免两种选择结果不一致
+    pub fn update_expected_proposer(&mut self, proposer: ExpectedProposer) {
+        if self.local_consensus.is_some() {
+            debug!(
+                target: &self.log_target,
+                "Node[{}] ignore UpdateProposer in local proposer mode", self.index
+            );
+            return;
+        }
+        let epoch = proposer.epoch;
+        self.expected_proposers
+            .insert((proposer.epoch, proposer.slot), proposer.addresses);
+        self.expected_proposers.retain(|(e, _), _| e + 1 >= epoch);
+    }
+
+    /// 区块的出块者是否为该时隙选出的出块者，没有收到该时隙的通知时不做判断
+    pub fn is_expected_proposer(&self, block: &Block) -> bool {
+        match self
+            .expected_proposers
+            .get(&(block.header.epoch, block.header.slot))
+        {
+            Some(addresses) => addresses.contains(&block.header.miner),
+            None => true,
+        }
+    }
+
+    /// WorldState 切换共识后按新的共识处理交易路径（如 POG 接受更短的路径）
+    pub fn switch_consensus(&mut self, consensus: ConsensusType) {
+        if self.consensus == consensus {
+            return;
+        }
+        info!(
+            target: &self.log_target,
+            "Node[{}] switch consensus from {} to {}", self.index, self.consensus, consensus
+        );
+        self.consensus = consensus;
+    }
+
+    /// 网络分区：断开给定地址的邻居，之后的广播和块同步都不会发给它们
+    /// 地址为空时恢复所有断开的邻居
+    pub fn update_partition(&mut self, addresses: &[String]) {
+        if addresses.is_empty() {
+            self.neighbors.append(&mut self.partitioned_neighbors);
+            info!(
+                target: &self.log_target,
+                "Node[{}] partition healed, {} neighbors",
+                self.index,
+                self.neighbors.len()
+            );
+            return;
+        }
+        let cut: HashSet<&String> = addresses.iter().collect();
+        let (partitioned, kept): (Vec<Neighbor>, Vec<Neighbor>) = self
+            .neighbors
+            .drain(..)
+            .partition(|n| cut.contains(&n.address));
+        self.neighbors = kept;
+        self.partitioned_neighbors.extend(partitioned);
+        info!(
+            target: &self.log_target,
+            "Node[{}] partitioned, {} neighbors cut, {} neighbors left",
+            self.index,
+            self.partitioned_neighbors.len(),
+            self.neighbors.len()
+        );
+    }
+
+    /// 本地计算出块者：用收到的验证者集合、时隙种子和本地区块链选出出块者
+    /// 选中自己（或自己控制的伪造身份）时给自己发送出块消息
+    async fn compute_local_proposer(&mut self, old_epoch: u64, seed: [u8; 32]) {
+        let consensus = match self.local_consensus.as_mut() {
+            Some(consensus) => consensus,
+            None => return,
+        };
+        // 伪造身份共用同一个通道，同一时隙会收到多次 UpdateSlot，只计算一次
+        let current = (self.epoch, self.slot);
+        if self.local_proposer_slot == Some(current) {
+            return;
+        }
+        self.local_proposer_slot = Some(current);
+
+        let blockchain = self.blockchain.read().await;
+        if self.epoch != old_epoch {
+            consensus.on_epoch_end(&blockchain.get_last_epoch_block());
+        }
+        consensus.next_slot(&self.validators, blockchain.get_last_index());
+        consensus.advance_state(&self.validators, &blockchain);
+        let proposer = match consensus.select_proposer(&self.validators, seed, &blockchain) {
+            Ok(proposer) => proposer,
+            Err(e) => {
+                warn!(
+                    target: &self.log_target,
+                    "Node[{}] local proposer selection failed: {}",
+                    self.index, e
+                );
+                return;
+            }
+        };
+        drop(blockchain);
+
+        let is_self = proposer.address == self.wallet.address
+            || self
+                .sybil_identities()
+                .iter()
+                .any(|s| s.get_address() == proposer.address);
+        if !is_self {
+            return;
+        }
+        debug!(
+            target: &self.log_target,
+            "Node[{}] is the local proposer at epoch {} slot {}",
+            self.index, self.epoch, self.slot
+        );
+        let self_sender = self.sender.clone();
+        tokio::spawn(async move {
+            let _ = self_sender.send(Message::new_generate_block_msg()).await;
+        });
+    }
+
+    /// 自私节点按 drop_probability 不转发交易
+    fn selfish_drop(&self) -> bool {
+        match self.node_type {
+            NodeType::Selfish { drop_probability } => rand::thread_rng().gen_bool(drop_probability),
+            _ => false,
+        }
+    }
+
+    /// 将一批交易按传播方式发给除 from 以外的邻居
+    fn broadcast_transaction_paths(&self, batch: Vec<TransactionPaths>, from: &str) {
+        if batch.is_empty() {
+            return;
+        }
+        let targets: Vec<&Neighbor> = self
+            .neighbors
+            .iter()
+            .filter(|neighbor| neighbor.address != from)
+            .collect();
+        match self.propagation {
+            PropagationStrategy::Flood => self.send_transaction_paths(batch, &targets),
+            PropagationStrategy::PushPull => self.announce_transactions(&batch, &targets),
+            PropagationStrategy::RandomWalk => {
+                if let Some(target) = targets.choose(&mut rand::thread_rng()) {
+                    self.send_transaction_paths(batch, &[*target]);
+                }
+            }
+        }
+    }
+
+    /// 将一批交易发给 targets，每个邻居只发送一条消息
+    /// Sybil 节点先伪造经过假身份的路径，再由最后一个假身份签名发给邻居
+    fn send_transaction_paths(&self, mut batch: Vec<TransactionPaths>, targets: &[&Neighbor]) {
+        if batch.is_empty() {
+            return;
+        }
+        let mut wallet = self.wallet.clone();
+        if let NodeType::Sybil { identities } = &self.node_type {
+            for transaction_paths in batch.iter_mut() {
+                wallet = self.wallet.clone();
+                identities.iter().for_each(|s| {
+                    transaction_paths.add_path(s.get_address(), wallet.clone());
+                    wallet = s.wallet.clone();
+                });
+            }
+        }
+        let mut sends = Vec::with_capacity(targets.len());
+        for neighbor_sender in targets {
+            let mut new_batch: Vec<TransactionPaths> = batch
+                .iter()
+                .map(|transaction_paths| {
+                    let mut new_trans_paths = transaction_paths.clone();
+                    new_trans_paths.add_path(neighbor_sender.address.clone(), wallet.clone());
+                    debug!(
+                        target: &self.log_target,
+                        "Node[{}] send transaction[{}] paths[{}] to Node[{}]",
+                        self.short_address_with_index(),
+                        new_trans_paths.transaction.hash,
+                        new_trans_paths.to_paths_string(),
+                        neighbor_sender.short_address_with_index()
+                    );
+                    new_trans_paths
+                })
+                .collect();
+            let self_address = self.get_address();
+            let msg = if new_batch.len() == 1 {
+                Message::new_transaction_paths_msg(new_batch.pop().unwrap(), self_address)
+            } else {
+                Message::new_transaction_paths_batch_msg(new_batch, self_address)
+            };
+            sends.push((neighbor_sender.sender.clone(), msg, neighbor_sender.latency));
+        }
+        MessageStats::add(&self.message_stats.transactions, sends.len());
+        spawn_broadcast(self.log_target.clone(), sends);
+    }
+
+    /// push-pull 传播：向 targets 公告交易哈希和本节点持有的路径长度
+    fn announce_transactions(&self, batch: &[TransactionPaths], targets: &[&Neighbor]) {
+        let announcements: Vec<(String, usize)> = batch
+            .iter()
+            .map(|transaction_paths| {
+                (
+                    transaction_paths.transaction.hash.clone(),
+                    transaction_paths.paths.len(),
+                )
+            })
+            .collect();
+        let msg = Message::new_announce_transactions_msg(announcements, self.get_address());
+        let sends: Vec<(Sender<Message>, Message, Duration)> = targets
+            .iter()
+            .map(|neighbor| (neighbor.sender.clone(), msg.clone(), neighbor.latency))
+            .collect();
+        MessageStats::add(&self.message_stats.announcements, sends.len());
+        spawn_broadcast(self.log_target.clone(), sends);
+    }
+
+    /// push-pull 收到交易公告时需要请求的交易：没有收到过且不在链上的交易，
+    /// POG 下还包括经公告者转发后路径更短的交易
+    async fn wanted_transactions(&self, announcements: &[(String, usize)]) -> Vec<String> {
+        let candidates: Vec<&String> = {
+            let cache = self.transaction_paths_cache.read().await;
+            announcements
+                .iter()
+                .filter(|(hash, path_len)| {
+                    if !self.seen_transactions.might_contain(hash) {
+                        return true;
+                    }
+                    match cache.get(hash) {
+                        Some(cached) => {
+                            self.consensus == ConsensusType::POG
+                                && cached.paths.len() > path_len + 1
+                        }
+                        None => true,
+                    }
+                })
+                .map(|(hash, _)| hash)
+                .collect()
+        };
+        let mut wanted = Vec::with_capacity(candidates.len());
+        for hash in candidates {
+            if self.chain_cache.may_contain_transaction(hash)
+                && self.blockchain.read().await.exist_transaction(hash.clone())
+            {
+                continue;
+            }
+            wanted.push(hash.clone());
+        }
+        wanted
+    }
+
+    /// 将区块广播给除 from 以外的邻居，区块只序列化一次
+    /// 区块公告模式下只向邻居发送区块哈希，并保留区块等待邻居请求
+    fn broadcast_block(&mut self, block: &Block, from: &str) {
+        if self.announce_blocks {
+            self.announce_block(block, from);
+            return;
+        }
+        let msg = Message::new_block_msg(block.clone(), self.get_address());
+        let sends = self
+            .neighbors
+            .iter()
+            .filter(|neighbor| neighbor.address != from)
+            .map(|neighbor| {
+                debug!(
+                    target: &self.log_target,
+                    "Node[{}] send block to Node[{}]",
+                    self.index, neighbor.index
+                );
+                (neighbor.sender.clone(), msg.clone(), neighbor.latency)
+            })
+            .collect::<Vec<_>>();
+        MessageStats::add(&self.message_stats.blocks, sends.len());
+        MessageStats::add(
+            &self.message_stats.block_bytes,
+            sends.len() * block.bytes() as usize,
+        );
+        spawn_broadcast(self.log_target.clone(), sends);
+    }
+
+    fn announce_block(&mut self, block: &Block, from: &str) {
+        self.recent_blocks.insert(block);
+        let msg = Message::new_announce_block_msg(block.header.hash.clone(), self.get_address());
+        let sends: Vec<(Sender<Message>, Message, Duration)> = self
+            .neighbors
+            .iter()
+            .filter(|neighbor| neighbor.address != from)
+            .map(|neighbor| (neighbor.sender.clone(), msg.clone(), neighbor.latency))
+            .collect();
+        MessageStats::add(&self.message_stats.block_announcements, sends.len());
+        MessageStats::add(
+            &self.message_stats.block_bytes,
+            sends.len() * block.header.hash.len(),
+        );
+        spawn_broadcast(self.log_target.clone(), sends);
+    }
+
+    /// 向 address 对应的邻居发送一条消息，不是邻居时忽略
+    fn send_to_neighbor(&self, address: &str, msg: Message) -> bool {
+        match self.neighbors.iter().find(|n| n.address == address) {
+            Some(neighbor) => {
+                spawn_broadcast(
+                    self.log_target.clone(),
+                    vec![(neighbor.sender.clone(), msg, neighbor.latency)],
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn run(&mut self) {
+        while let Some(msg) = self.receiver.recv().await {
+            events::record(|| events::node_target(self.index), &msg);
+            // 离线逻辑：如果节点离线，跳过大多数消息处理
+            // 但 UpdateSlot 消息用于恢复在线逻辑，需要处理；验证者集合的增量、网络分区和共识切换不能漏掉，也需要处理
+            if !self.is_online
+                && !matches!(
+                    msg.msg_type,
+                    MessageType::UpdateSlot
+                        | MessageType::UpdateValidatorSet
+                        | MessageType::UpdateValidatorSetDiff
+                        | MessageType::UpdatePartition
+                        | MessageType::SwitchConsensus
+                )
+            {
+                debug!(
+                    target: &self.log_target,
+                    "Node[{}] is offline, skipping message[{}]",
+                    self.index, msg.msg_type
+                );
+                match msg.msg_type {
+                    MessageType::GenerateBlock => {
+                        warn!(
+                            target: &self.log_target,
+                            "Node[{}] missed block generation due to being offline at slot {}",
+                            self.index, self.slot
                         );
                         // 报告出块失败事件到 world_state
                         let world_state_sender = self.world_state_sender.clone();
@@ -431,58 +1929,104 @@ impl Node {
                     let block = match Block::from_json(msg.data) {
                         Ok(b) => b,
                         Err(e) => {
-                            error!("Node[{}] error: {}", self.index, e);
+                            error!(target: &self.log_target, "Node[{}] error: {}", self.index, e);
                             continue;
                         }
                     };
                     debug!(
+                        target: &self.log_target,
                         "Node[{}] received msg[{}]: block hash[{}]",
                         self.index, msg.msg_type, block.header.hash
                     );
+                    if !self.is_expected_proposer(&block) {
+                        warn!(
+                            target: &self.log_target,
+                            "Node[{}] rejected block[{}]: miner {} is not the proposer of epoch[{}] slot[{}]",
+                            self.index,
+                            block.header.hash,
+                            block.header.miner,
+                            block.header.epoch,
+                            block.header.slot
+                        );
+                        continue;
+                    }
+                    if self.block_verify_sample > 0.0
+                        && !block.verify_paths_sample(
+                            self.block_verify_sample,
+                            &mut self.verify_rng,
+                            &self.key_registry,
+                        )
+                    {
+                        warn!(
+                            target: &self.log_target,
+                            "Node[{}] rejected block[{}]: sampled path verification failed",
+                            self.index, block.header.hash
+                        );
+                        continue;
+                    }
                     {
                         //添加到自己的区块链
                         let mut blockchain = self.blockchain.write().await;
                         if let Err(e) = blockchain.add_block(block.clone()) {
                             match e {
                                 BlockChainError::DuplicateBlocksReceived => {
-                                    debug!("Node[{}] add block error: {}", self.index, e);
+                                    debug!(
+                                        target: &self.log_target,
+                                        "Node[{}] add block error: {}",
+                                        self.index, e
+                                    );
                                 }
                                 BlockChainError::IndexTooSmall => {
-                                    debug!("Node[{}] add block error: {}", self.index, e);
+                                    debug!(
+                                        target: &self.log_target,
+                                        "Node[{}] add block error: {}",
+                                        self.index, e
+                                    );
                                 }
                                 BlockChainError::TransactionExists => {
-                                    debug!("Node[{}] add block error: {}", self.index, e);
+                                    debug!(
+                                        target: &self.log_target,
+                                        "Node[{}] add block error: {}",
+                                        self.index, e
+                                    );
                                 }
                                 BlockChainError::ParentHashMismatch => {
-                                    warn!("Node[{}] error: {}, trying Block Sync", self.index, e);
+                                    warn!(
+                                        target: &self.log_target,
+                                        "Node[{}] error: {}, trying Block Sync",
+                                        self.index, e
+                                    );
                                     // 先释放写锁，再向邻居请求块同步（避免死锁）
                                     let last_block_index = blockchain.get_last_index();
                                     drop(blockchain);
 
-                                    if !self.neighbors.is_empty() {
-                                        self.sync_in_progress = true;
-                                        for neighbor in self.neighbors.clone() {
-                                            let self_address = self.get_address();
-                                            tokio::spawn(async move {
-                                                neighbor
-                                                    .sender
-                                                    .send(Message::new_request_block_sync_msg(
-                                                        last_block_index,
-                                                        self_address,
-                                                    ))
-                                                    .await
-                                                    .unwrap();
-                                            });
-                                        }
+                                    if !self.sync_in_progress {
+                                        self.request_block_sync().await;
+                                    } else {
+                                        debug!(
+                                            target: &self.log_target,
+                                            "Node[{}] block sync already in progress, last block index: {}",
+                                            self.index, last_block_index
+                                        );
                                     }
                                 }
                                 _ => {
-                                    error!("Node[{}] add block error: {}", self.index, e);
+                                    error!(
+                                        target: &self.log_target,
+                                        "Node[{}] add block error: {}",
+                                        self.index, e
+                                    );
                                 }
                             }
                             continue;
                         }
-                        debug!("Node[{}] add block successfully", self.index);
+                        self.chain_cache
+                            .update(&blockchain, std::slice::from_ref(&block));
+                        debug!(
+                            target: &self.log_target,
+                            "Node[{}] add block successfully",
+                            self.index
+                        );
                     }
                     {
                         //清除交易缓存
@@ -499,166 +2043,116 @@ impl Node {
                         }
                     }
                     //广播到其他邻居
-                    for neighbor_sender in self.neighbors.clone() {
-                        if msg.from == neighbor_sender.address {
-                            continue;
-                        }
-                        let block = block.clone();
-                        debug!(
-                            "Node[{}] send block to Node[{}]",
-                            self.index, neighbor_sender.index
-                        );
-                        let self_address = self.get_address();
-                        tokio::spawn(async move {
-                            neighbor_sender
-                                .sender
-                                .send(Message::new_block_msg(block, self_address))
-                                .await
-                                .unwrap();
-                        });
-                    }
+                    self.broadcast_block(&block, &msg.from);
                 }
                 MessageType::SendTransactionPaths => {
-                    let mut transaction_paths = match TransactionPaths::from_json(msg.data) {
+                    let transaction_paths = match TransactionPaths::from_json(msg.data) {
                         Ok(t) => t,
                         Err(e) => {
-                            error!("Node[{}] error: {}", self.index, e);
+                            error!(target: &self.log_target, "Node[{}] error: {}", self.index, e);
                             continue;
                         }
                     };
-
-                    // if !transaction_paths.verify_last(self.wallet.address.clone()) {
-                    //     error!("Node[{}] invalid transaction paths", self.index);
-                    //     continue;
-                    // }
+                    if self
+                        .accept_transaction_paths(&transaction_paths, &msg.msg_type)
+                        .await
+                        && !self.selfish_drop()
                     {
-                        let bc = self.blockchain.read().await;
-                        if bc.exist_transaction(transaction_paths.transaction.hash.clone()) {
-                            debug!(
-                                "Node[{}] received transaction[{}] already in blockchain",
-                                self.index, transaction_paths.transaction.hash
-                            );
+                        self.broadcast_transaction_paths(vec![transaction_paths], &msg.from);
+                    }
+                }
+                MessageType::SendTransactionPathsBatch => {
+                    let batch: Vec<TransactionPaths> = match serde_json::from_slice(&msg.data) {
+                        Ok(batch) => batch,
+                        Err(e) => {
+                            error!(target: &self.log_target, "Node[{}] error: {}", self.index, e);
                             continue;
                         }
-                    }
-                    //判断交易是否已经收到了,判断交易的paths是否最短 (O(1)查找)
-                    {
-                        let transactions_cache = self.transaction_paths_cache.read().await;
-                        let tx_hash = &transaction_paths.transaction.hash;
-
-                        if let Some(cached_tx) = transactions_cache.get(tx_hash) {
-                            if self.consensus == ConsensusType::POG {
-                                // POG: 只有当缓存的路径长度更短或相等时才跳过
-                                if cached_tx.paths.len() <= transaction_paths.paths.len() {
-                                    continue;
-                                }
-                            } else {
-                                // 其他共识: 只要收到过就跳过
-                                continue;
-                            }
+                    };
+                    let mut accepted = Vec::with_capacity(batch.len());
+                    for transaction_paths in batch {
+                        if self
+                            .accept_transaction_paths(&transaction_paths, &msg.msg_type)
+                            .await
+                            && !self.selfish_drop()
+                        {
+                            accepted.push(transaction_paths);
                         }
                     }
-                    debug!(
-                        "Node[{}] received msg[{}]: transaction hash[{}],path[{}]",
-                        self.short_address_with_index(),
-                        msg.msg_type,
-                        transaction_paths.transaction.hash,
-                        transaction_paths.to_paths_string(),
-                    );
-                    //收到交易，存储
+                    self.broadcast_transaction_paths(accepted, &msg.from);
+                }
+                MessageType::AnnounceBlock => {
+                    let hash = String::from_utf8_lossy(&msg.data).to_string();
+                    if hash == self.chain_cache.last_hash()
+                        || !self.recent_blocks.should_request(&hash)
                     {
-                        let mut transactions_cache = self.transaction_paths_cache.write().await;
-                        let tx_hash = transaction_paths.transaction.hash.clone();
-
-                        // 检查内存池是否已满
-                        if transactions_cache.len() >= self.max_mempool_size {
-                            // 如果内存池满了，且这是一个新交易，则丢弃
-                            if !transactions_cache.contains_key(&tx_hash) {
-                                debug!(
-                                    "Node[{}] mempool full, dropping transaction[{}]",
-                                    self.index, tx_hash
-                                );
-                                continue;
-                            }
-                        }
-
-                        //插入或更新交易
-                        transactions_cache.insert(tx_hash, transaction_paths.clone());
+                        continue;
                     }
-
-                    match self.node_type {
-                        NodeType::Selfish => {
-                            // drop propagation
-                            let mut rng = rand::thread_rng();
-                            let random_bool: bool = rng.gen_bool(0.5);
-                            if random_bool {
-                                continue;
-                            }
+                    let request = Message::new_request_block_msg(hash.clone(), self.get_address());
+                    if self.send_to_neighbor(&msg.from, request) {
+                        MessageStats::add(&self.message_stats.block_requests, 1);
+                        MessageStats::add(&self.message_stats.block_bytes, hash.len());
+                    }
+                }
+                MessageType::RequestBlock => {
+                    let hash = String::from_utf8_lossy(&msg.data).to_string();
+                    let block = match self.recent_blocks.get(&hash) {
+                        Some(block) => block.clone(),
+                        None => {
+                            debug!(
+                                target: &self.log_target,
+                                "Node[{}] requested block[{}] not found",
+                                self.index, hash
+                            );
+                            continue;
                         }
-                        NodeType::Sybil => {
-                            //Sybil,伪造路径,再广播
-                            let mut wallet = self.wallet.clone();
-                            self.sybil_nodes.iter().for_each(|s| {
-                                transaction_paths.add_path(s.get_address(), wallet.clone());
-                                wallet = s.wallet.clone();
-                            });
-                            for neighbor_sender in self.neighbors.clone() {
-                                if msg.from == neighbor_sender.address {
-                                    continue;
-                                }
-                                let mut new_trans_paths = transaction_paths.clone();
-                                new_trans_paths
-                                    .add_path(neighbor_sender.address.clone(), wallet.clone());
-                                debug!(
-                                    "Sybil Node[{}] send transaction[{}] paths[{}] to Node[{}]",
-                                    self.short_address_with_index(),
-                                    new_trans_paths.transaction.hash,
-                                    new_trans_paths.to_paths_string(),
-                                    neighbor_sender.short_address_with_index()
-                                );
-                                let self_address = self.get_address();
-                                tokio::spawn(async move {
-                                    neighbor_sender
-                                        .sender
-                                        .send(Message::new_transaction_paths_msg(
-                                            new_trans_paths,
-                                            self_address,
-                                        ))
-                                        .await
-                                        .unwrap();
-                                });
-                            }
+                    };
+                    let bytes = block.bytes() as usize;
+                    if self.send_to_neighbor(
+                        &msg.from,
+                        Message::new_block_msg(block, self.get_address()),
+                    ) {
+                        MessageStats::add(&self.message_stats.blocks, 1);
+                        MessageStats::add(&self.message_stats.block_bytes, bytes);
+                    }
+                }
+                MessageType::AnnounceTransactions => {
+                    let announcements: Vec<(String, usize)> = match serde_json::from_slice(
+                        &msg.data,
+                    ) {
+                        Ok(announcements) => announcements,
+                        Err(e) => {
+                            error!(target: &self.log_target, "Node[{}] error: {}", self.index, e);
                             continue;
                         }
-                        _ => {}
+                    };
+                    let wanted = self.wanted_transactions(&announcements).await;
+                    if wanted.is_empty() {
+                        continue;
                     }
-
-                    //并广播到邻居
-                    for neighbor_sender in self.neighbors.clone() {
-                        if msg.from == neighbor_sender.address {
+                    let request = Message::new_request_transactions_msg(wanted, self.get_address());
+                    if self.send_to_neighbor(&msg.from, request) {
+                        MessageStats::add(&self.message_stats.requests, 1);
+                    }
+                }
+                MessageType::RequestTransactions => {
+                    let hashes: Vec<String> = match serde_json::from_slice(&msg.data) {
+                        Ok(hashes) => hashes,
+                        Err(e) => {
+                            error!(target: &self.log_target, "Node[{}] error: {}", self.index, e);
                             continue;
                         }
-                        let mut new_trans_paths = transaction_paths.clone();
-                        new_trans_paths
-                            .add_path(neighbor_sender.address.clone(), self.wallet.clone());
-                        debug!(
-                            "Node[{}] send transaction[{}] paths[{}] to Node[{}]",
-                            self.short_address_with_index(),
-                            new_trans_paths.transaction.hash,
-                            new_trans_paths.to_paths_string(),
-                            neighbor_sender.short_address_with_index()
-                        );
-                        let self_address = self.get_address();
-                        tokio::spawn(async move {
-                            neighbor_sender
-                                .sender
-                                .send(Message::new_transaction_paths_msg(
-                                    new_trans_paths,
-                                    self_address,
-                                ))
-                                .await
-                                .unwrap();
-                        });
+                    };
+                    // 公告后已打包或过期的交易不再发送
+                    let batch: Vec<TransactionPaths> = {
+                        let cache = self.transaction_paths_cache.read().await;
+                        hashes
+                            .iter()
+                            .filter_map(|hash| cache.get(hash).cloned())
+                            .collect()
+                    };
+                    if let Some(neighbor) = self.neighbors.iter().find(|n| n.address == msg.from) {
+                        self.send_transaction_paths(batch, &[neighbor]);
                     }
                 }
 
@@ -666,6 +2160,7 @@ impl Node {
                     // 同步过程中不能出块
                     if self.sync_in_progress {
                         warn!(
+                            target: &self.log_target,
                             "Node[{}] skipping block generation due to sync in progress at slot {}",
                             self.index, self.slot
                         );
@@ -686,6 +2181,7 @@ impl Node {
                         Ok(b) => b,
                         Err(e) => {
                             error!(
+                                target: &self.log_target,
                                 "Node[{}] generate block failed: {} at slot {}",
                                 self.index, e, self.slot
                             );
@@ -694,28 +2190,27 @@ impl Node {
                         }
                     };
                     info!(
+                        target: &self.log_target,
                         "Node[{}] is the miner: block hash[{}]",
                         self.index, block.header.hash
                     );
                     block.simple_print();
                     let during = block.header.timestamp - last_block_time;
                     info!(
+                        target: &self.log_target,
                         "Current {:.2}TX/s",
                         block.body.transactions.len() as f64 / during as f64
                     );
 
-                    //广播区块
-                    for neighbor_sender in self.neighbors.clone() {
-                        let block = block.clone();
-                        let self_address = self.get_address();
-                        tokio::spawn(async move {
-                            neighbor_sender
-                                .sender
-                                .send(Message::new_block_msg(block, self_address))
-                                .await
-                                .unwrap();
-                        });
+                    // 自己产出的区块记录为收到的区块，重放时代替 GenerateBlock 还原同样的链
+                    if events::is_recording() {
+                        events::record(
+                            || events::node_target(self.index),
+                            &Message::new_block_msg(block.clone(), self.get_address()),
+                        );
                     }
+                    //广播区块
+                    self.broadcast_block(&block, "");
                     //告诉下worldState
                     let world_state_sender = self.world_state_sender.clone();
                     let self_address = self.get_address();
@@ -731,132 +2226,53 @@ impl Node {
                         Ok(to) => to,
                         Err(e) => {
                             error!(
+                                target: &self.log_target,
                                 "Node[{}] generate transaction paths failed:{}",
                                 self.index, e
                             );
                             continue;
                         }
                     };
-
-                    // 检查余额是否充足
-                    if !self.deduct_balance(self.transaction_fee) {
-                        warn!(
-                            "Node[{}] insufficient balance: {} < {}",
-                            self.index, self.balance, self.transaction_fee
-                        );
-                        continue;
-                    }
-
-                    // 扣除余额后，同步到 Validator 的 stake
-                    self.world_state_sender
-                        .send(Message::new_update_validator_stake_msg(
-                            self.wallet.address.clone(),
-                            self.balance,
-                        ))
-                        .await
-                        .unwrap();
-
-                    let transaction =
-                        Transaction::with_fee(to, 0, self.transaction_fee, self.wallet.clone());
-                    let mut transaction_paths = TransactionPaths::new(transaction);
-                    debug!(
-                        "Node[{}] received msg[{}]: transaction hash[{}],path[{}]",
-                        self.short_address_with_index(),
-                        msg.msg_type,
-                        transaction_paths.transaction.hash,
-                        transaction_paths.to_paths_string()
-                    );
-                    //缓存交易
+                    if let Some(transaction_paths) =
+                        self.generate_transaction_paths(to, &msg.msg_type).await
                     {
-                        let mut transactions_cache = self.transaction_paths_cache.write().await;
-                        let tx_hash = transaction_paths.transaction.hash.clone();
-
-                        // 检查内存池是否已满
-                        if transactions_cache.len() >= self.max_mempool_size {
-                            // 如果内存池满了，且这是一个新交易，则丢弃
-                            if !transactions_cache.contains_key(&tx_hash) {
-                                debug!(
-                                    "Node[{}] mempool full, dropping generated transaction[{}]",
-                                    self.index, tx_hash
-                                );
-                                continue;
-                            }
-                        }
-
-                        transactions_cache.insert(tx_hash, transaction_paths.clone());
+                        self.broadcast_transaction_paths(vec![transaction_paths], &msg.from);
                     }
-                    match self.node_type {
-                        NodeType::Sybil => {
-                            //Sybil,伪造路径,再广播
-                            let mut wallet = self.wallet.clone();
-                            self.sybil_nodes.iter().for_each(|s| {
-                                transaction_paths.add_path(s.get_address(), wallet.clone());
-                                wallet = s.wallet.clone();
-                            });
-                            for neighbor_sender in self.neighbors.clone() {
-                                if msg.from == neighbor_sender.address {
-                                    continue;
-                                }
-                                let mut new_trans_paths = transaction_paths.clone();
-                                new_trans_paths
-                                    .add_path(neighbor_sender.address.clone(), wallet.clone());
-                                debug!(
-                                    "Sybil Node[{}] send transaction[{}] paths[{}] to Node[{}]",
-                                    self.short_address_with_index(),
-                                    new_trans_paths.transaction.hash,
-                                    new_trans_paths.to_paths_string(),
-                                    neighbor_sender.short_address_with_index()
-                                );
-                                let self_address = self.get_address();
-                                tokio::spawn(async move {
-                                    neighbor_sender
-                                        .sender
-                                        .send(Message::new_transaction_paths_msg(
-                                            new_trans_paths,
-                                            self_address,
-                                        ))
-                                        .await
-                                        .unwrap();
-                                });
-                            }
+                }
+                MessageType::GenerateTransactionPathsBatch => {
+                    let tos: Vec<String> = match serde_json::from_slice(&msg.data) {
+                        Ok(tos) => tos,
+                        Err(e) => {
+                            error!(
+                                target: &self.log_target,
+                                "Node[{}] generate transaction paths failed:{}",
+                                self.index, e
+                            );
                             continue;
                         }
-                        _ => {}
-                    }
-                    //广播交易
-                    for neighbor_sender in self.neighbors.clone() {
-                        let mut new_trans_paths = transaction_paths.clone();
-                        new_trans_paths
-                            .add_path(neighbor_sender.address.clone(), self.wallet.clone());
-                        debug!(
-                            "Node[{}] send transaction[{}] paths[{}] to Node[{}]",
-                            self.short_address_with_index(),
-                            new_trans_paths.transaction.hash,
-                            new_trans_paths.to_paths_string(),
-                            neighbor_sender.short_address_with_index()
-                        );
-                        let self_address = self.get_address();
-                        tokio::spawn(async move {
-                            neighbor_sender
-                                .sender
-                                .send(Message::new_transaction_paths_msg(
-                                    new_trans_paths,
-                                    self_address,
-                                ))
-                                .await
-                                .unwrap();
-                        });
+                    };
+                    let mut batch = Vec::with_capacity(tos.len());
+                    for to in tos {
+                        if let Some(transaction_paths) =
+                            self.generate_transaction_paths(to, &msg.msg_type).await
+                        {
+                            batch.push(transaction_paths);
+                        }
                     }
+                    self.broadcast_transaction_paths(batch, &msg.from);
                 }
                 MessageType::SendRandaoSeed => {
-                    let seed = RandaoSeed::generate_seed();
-                    let signature = self.wallet.sign(Vec::from(seed));
-                    let randao_seed = RandaoSeed {
-                        address: self.wallet.address.clone(),
-                        seed,
-                        signature,
+                    let (epoch, slot) = match serde_json::from_slice::<(u64, u64)>(&msg.data) {
+                        Ok(target) => target,
+                        Err(e) => {
+                            error!(target: &self.log_target, "Node[{}] error: {}", self.index, e);
+                            continue;
+                        }
                     };
+                    let randao_seed = RandaoSeed::new(self.wallet.clone(), epoch, slot);
+                    let seed = randao_seed.seed;
                     debug!(
+                        target: &self.log_target,
                         "Node[{}] received msg[{}]: seed[{:?}]",
                         self.index, msg.msg_type, seed
                     );
@@ -866,7 +2282,11 @@ impl Node {
                         .unwrap();
                 }
                 MessageType::BecomeValidator => {
-                    debug!("Node[{}] received msg[{}]", self.index, msg.msg_type);
+                    debug!(
+                        target: &self.log_target,
+                        "Node[{}] received msg[{}]",
+                        self.index, msg.msg_type
+                    );
 
                     // Try to parse stake_map from JSON data
                     let stake_map: std::collections::HashMap<String, f64> =
@@ -884,10 +2304,18 @@ impl Node {
                     self.set_balance(my_stake);
 
                     info!(
+                        target: &self.log_target,
                         "Node[{}] with address[{}] becomes validator with stake {} and pow power {}",
                         self.index, self.wallet.address, my_stake, self.hash_power
                     );
-                    match self.node_type {
+                    match &self.node_type {
+                        _ if !self.is_validator => {
+                            debug!(
+                                target: &self.log_target,
+                                "Node[{}] is not selected as validator, only relays",
+                                self.index
+                            );
+                        }
                         NodeType::Honest => {
                             self.world_state_sender
                                 .send(Message::new_receive_become_validator_msg(Validator::new(
@@ -898,7 +2326,7 @@ impl Node {
                                 .await
                                 .unwrap();
                         }
-                        NodeType::Selfish => {
+                        NodeType::Selfish { .. } => {
                             self.world_state_sender
                                 .send(Message::new_receive_become_validator_msg(Validator::new(
                                     self.wallet.address.clone(),
@@ -908,7 +2336,7 @@ impl Node {
                                 .await
                                 .unwrap();
                         }
-                        NodeType::Unstable => {
+                        NodeType::Unstable { .. } | NodeType::Bootstrap => {
                             self.world_state_sender
                                 .send(Message::new_receive_become_validator_msg(Validator::new(
                                     self.wallet.address.clone(),
@@ -918,9 +2346,18 @@ impl Node {
                                 .await
                                 .unwrap();
                         }
-                        NodeType::Sybil => {
+                        NodeType::Relay => {
+                            // 中继节点不注册为验证者，因此不会被选为出块者
+                            // 网络费用由 WorldState 通过 UpdateNodeBalance 同步
+                            debug!(
+                                target: &self.log_target,
+                                "Node[{}] is a relay node, skip becoming validator",
+                                self.index
+                            );
+                        }
+                        NodeType::Sybil { identities } => {
                             // For malicious nodes with sybil, divide stake among all sybil identities
-                            let sybil_num = self.sybil_nodes.len();
+                            let sybil_num = identities.len();
                             let stake = my_stake / (sybil_num + 1) as f64;
 
                             self.world_state_sender
@@ -931,7 +2368,7 @@ impl Node {
                                 )))
                                 .await
                                 .unwrap();
-                            for sybil in self.sybil_nodes.iter() {
+                            for sybil in identities.iter() {
                                 // 处理 sybil
                                 self.world_state_sender
                                     .send(Message::new_receive_become_validator_msg(
@@ -943,7 +2380,11 @@ impl Node {
                                     ))
                                     .await
                                     .unwrap();
-                                info!("Node[{}] become validator->fake node", sybil.index);
+                                info!(
+                                    target: &self.log_target,
+                                    "Node[{}] become validator->fake node",
+                                    sybil.index
+                                );
                             }
                         }
                     }
@@ -962,91 +2403,155 @@ impl Node {
                             msg.data[7],
                         ]);
                         self.set_balance(new_balance);
-                        debug!("Node[{}] updated balance to {}", self.index, new_balance);
+                        debug!(
+                            target: &self.log_target,
+                            "Node[{}] updated balance to {}",
+                            self.index, new_balance
+                        );
                     }
                 }
                 MessageType::UpdateSlot => {
                     let slot = match SlotManager::from_json(msg.data) {
                         Ok(t) => t,
                         Err(e) => {
-                            error!("Node[{}] error: {}", self.index, e);
+                            error!(target: &self.log_target, "Node[{}] error: {}", self.index, e);
                             continue;
                         }
                     };
-                    debug!("Node[{}] received msg[{}]", self.index, msg.msg_type);
+                    debug!(
+                        target: &self.log_target,
+                        "Node[{}] received msg[{}]",
+                        self.index, msg.msg_type
+                    );
 
                     let old_epoch = self.epoch;
                     self.slot = slot.current_slot;
                     self.epoch = slot.current_epoch;
+                    self.slot_count += 1;
+                    self.tx_rate_limiter.refill();
+                    self.expire_transactions().await;
 
                     // 恢复在线时向邻居请求块同步（仅对不稳定节点）
-                    if matches!(self.node_type, NodeType::Unstable) {
-                        // 检查是否刚从离线恢复
-                        if !self.is_online
-                            && self.offline_until_epoch.is_some()
-                            && self.epoch >= self.offline_until_epoch.unwrap()
-                        {
-                            // 即将恢复在线，准备同步
-                            let last_block_index =
-                                { self.blockchain.read().await.blocks.len() as u64 - 1 };
-
-                            // 向所有邻居发送块同步请求，确保至少有一个在线的邻居能响应
-                            if !self.neighbors.is_empty() {
-                                for neighbor in self.neighbors.clone() {
-                                    let self_address = self.get_address();
-                                    tokio::spawn(async move {
-                                        debug!(
-                                            "Node[{}] requests block sync from Node[{}], last block index: {}",
-                                            self_address, neighbor.address, last_block_index
-                                        );
-                                        neighbor
-                                            .sender
-                                            .send(Message::new_request_block_sync_msg(
-                                                last_block_index,
-                                                self_address,
-                                            ))
-                                            .await
-                                            .unwrap();
-                                    });
-                                }
-                            }
-
-                            self.is_online = true;
-                            self.offline_until_epoch = None;
-                            warn!(
-                                "Node[{}] is back online at epoch {}",
-                                self.index, self.epoch
-                            );
-                        }
-
-                        // 仅在 epoch 变化且节点仍在线时，才考虑随机下线
-                        if self.is_online
-                            && self.epoch != old_epoch
-                            && (self.offline_until_epoch.is_none())
-                        {
-                            use rand::Rng;
-                            let mut rng = rand::thread_rng();
-                            // 根据配置的概率下线一个epoch
-                            if rng.gen_bool(self.offline_probability) {
-                                self.is_online = false;
-                                self.offline_until_epoch = Some(self.epoch + 1);
-                                warn!(
-                                    "Node[{}] goes offline at epoch {} until epoch {}",
-                                    self.index,
-                                    self.epoch,
-                                    self.epoch + 1
+                    if matches!(self.node_type, NodeType::Unstable { .. }) {
+                        self.update_online_status(old_epoch).await;
+                    }
+                    self.compute_local_proposer(old_epoch, slot.next_seed).await;
+                    if self.epoch != old_epoch {
+                        if let Some(path) = &self.state_path {
+                            if let Err(e) = self.save_state(path).await {
+                                error!(
+                                    target: &self.log_target,
+                                    "Node[{}] save state to {} failed: {}",
+                                    self.index, path, e
                                 );
                             }
                         }
                     }
                 }
+                MessageType::UpdateValidatorSet | MessageType::UpdateValidatorSetDiff => {
+                    self.update_validator_set(&msg);
+                }
+                MessageType::UpdateProposer => {
+                    match serde_json::from_slice::<ExpectedProposer>(&msg.data) {
+                        Ok(proposer) => self.update_expected_proposer(proposer),
+                        Err(e) => {
+                            error!(target: &self.log_target, "Node[{}] error: {}", self.index, e);
+                        }
+                    }
+                }
+                MessageType::UpdatePartition => {
+                    match serde_json::from_slice::<Vec<String>>(&msg.data) {
+                        Ok(addresses) => self.update_partition(&addresses),
+                        Err(e) => {
+                            error!(target: &self.log_target, "Node[{}] error: {}", self.index, e);
+                        }
+                    }
+                }
+                MessageType::SwitchConsensus => {
+                    match serde_json::from_slice::<ConsensusType>(&msg.data) {
+                        Ok(consensus) => self.switch_consensus(consensus),
+                        Err(e) => {
+                            error!(target: &self.log_target, "Node[{}] error: {}", self.index, e);
+                        }
+                    }
+                }
+                MessageType::QueryMempool => {
+                    let stats = self.mempool_stats().await;
+                    info!(
+                        target: &self.log_target,
+                        "Node[{}] mempool: {} txs, avg path length {:.2}, path lengths {:?}",
+                        self.index,
+                        stats.tx_count,
+                        stats.avg_path_length(),
+                        stats.path_lengths
+                    );
+                }
+                MessageType::RequestMempoolHealth => {
+                    let mempool_size = self.transaction_paths_cache.read().await.len();
+                    let report = self.mempool_health.report(mempool_size);
+                    let msg = Message::new_response_mempool_health_msg(&report, self.get_address());
+                    if let Err(e) = self.world_state_sender.send(msg).await {
+                        error!(
+                            target: &self.log_target,
+                            "Node[{}] error: send mempool health failed {:?}", self.index, e
+                        );
+                    }
+                }
+                MessageType::QueryConfirmations => {
+                    let tx_hash = String::from_utf8_lossy(&msg.data).to_string();
+                    match self.confirmations(&tx_hash).await {
+                        Some(confirmations) => info!(
+                            target: &self.log_target,
+                            "Node[{}] transaction {} has {} confirmations",
+                            self.index, tx_hash, confirmations
+                        ),
+                        None => info!(
+                            target: &self.log_target,
+                            "Node[{}] transaction {} is not on chain",
+                            self.index, tx_hash
+                        ),
+                    }
+                }
                 MessageType::PrintBlockchain => {
-                    debug!("Node[{}] received msg[{}]", self.index, msg.msg_type);
-                    self.blockchain.read().await.write_to_file_all_json().await;
+                    debug!(
+                        target: &self.log_target,
+                        "Node[{}] received msg[{}]",
+                        self.index, msg.msg_type
+                    );
+                    let result = self
+                        .blockchain
+                        .read()
+                        .await
+                        .write_to_file_all_json(Path::new("."), self.index)
+                        .await;
+                    match result {
+                        Ok(path) => {
+                            debug!(
+                                target: &self.log_target,
+                                "Node[{}] write blockchain to {}",
+                                self.index,
+                                path.display()
+                            );
+                            // 末端变化后删除本节点上次写的文件，只保留各节点当前末端的输出
+                            // 文件名包含节点编号，不会删除其他节点的输出
+                            if let Some(last) = self.last_dump.replace(path.clone()) {
+                                if last != path {
+                                    let _ = tokio::fs::remove_file(last).await;
+                                }
+                            }
+                        }
+                        Err(e) => error!(
+                            target: &self.log_target,
+                            "Node[{}] error writing blockchain file: {}",
+                            self.index, e
+                        ),
+                    }
                 }
                 MessageType::RequestBlockSync => {
-                    if self.sync_in_progress {
+                    // 引导节点始终响应块同步请求
+                    if self.sync_in_progress && !matches!(self.node_type, NodeType::Bootstrap) {
                         debug!(
+                            target: &self.log_target,
                             "Node[{}] is syncing, ignoring new block sync request",
                             self.index
                         );
@@ -1054,11 +2559,12 @@ impl Node {
                     }
                     if msg.from == "world_state" {
                         info!(
+                            target: &self.log_target,
                             "Node[{}] received RequestBlockSync from world_state",
                             self.index
                         );
                         let blockchain_read = self.blockchain.read().await;
-                        let sync_blocks = blockchain_read.blocks.clone();
+                        let sync_blocks = blockchain_read.blocks().to_vec();
                         let self_address = self.get_address();
                         let world_state_sender = self.world_state_sender.clone();
                         tokio::spawn(async move {
@@ -1086,6 +2592,7 @@ impl Node {
                         ]),
                         _ => {
                             error!(
+                                target: &self.log_target,
                                 "Node[{}] received invalid RequestBlockSync data",
                                 self.index
                             );
@@ -1094,16 +2601,14 @@ impl Node {
                     };
 
                     let blockchain_read = self.blockchain.read().await;
-                    let total_blocks = blockchain_read.blocks.len();
-                    let start_index = requested_index as usize;
-
-                    let sync_blocks = if start_index < total_blocks {
-                        blockchain_read.blocks[start_index..].to_vec()
-                    } else {
+                    let total_blocks = blockchain_read.len();
+                    let sync_blocks = blockchain_read.get_blocks_from(requested_index);
+                    if sync_blocks.is_empty() {
                         continue;
-                    };
+                    }
 
                     debug!(
+                        target: &self.log_target,
                         "Node[{}] processing block sync request: requested_index={}, total_blocks={}, sending {} blocks to {}",
                         self.index, requested_index, total_blocks, sync_blocks.len(), msg.from
                     );
@@ -1131,10 +2636,12 @@ impl Node {
                 }
                 MessageType::ResponseBlockSync => {
                     // 处理块同步响应
+                    let responder = msg.from.clone();
                     let blocks_json = match String::from_utf8(msg.data) {
                         Ok(s) => s,
                         Err(e) => {
                             error!(
+                                target: &self.log_target,
                                 "Node[{}] error parsing ResponseBlockSync: {}",
                                 self.index, e
                             );
@@ -1145,30 +2652,43 @@ impl Node {
                     let sync_blocks: Vec<Block> = match serde_json::from_str(&blocks_json) {
                         Ok(blocks) => blocks,
                         Err(e) => {
-                            error!("Node[{}] error deserializing blocks: {}", self.index, e);
+                            error!(
+                                target: &self.log_target,
+                                "Node[{}] error deserializing blocks: {}",
+                                self.index, e
+                            );
                             continue;
                         }
                     };
 
                     if sync_blocks.is_empty() {
-                        error!("Node[{}] received empty block sync response", self.index);
+                        error!(
+                            target: &self.log_target,
+                            "Node[{}] received empty block sync response",
+                            self.index
+                        );
+                        self.on_block_sync_response(&responder, false).await;
                         continue;
                     }
 
-                    let current_index = { self.blockchain.read().await.get_last_index() };
+                    let current_index = self.chain_cache.last_index();
 
                     let response_index = sync_blocks.last().unwrap().header.index;
 
                     // 验证：当前索引必须小于响应中的最大索引
                     if current_index >= response_index {
                         debug!(
+                            target: &self.log_target,
                             "Node[{}] skipping sync: current_index({}) >= response_index({})",
                             self.index, current_index, response_index
                         );
+                        self.on_block_sync_response(&responder, false).await;
                         continue;
                     }
 
                     // 按顺序添加块，同时遍历本地区块链和响应块
+                    // 判断是否成功
+                    let mut success = false;
                     {
                         let mut blockchain = self.blockchain.write().await;
 
@@ -1186,14 +2706,12 @@ impl Node {
                         match start_sync_idx {
                             None => {
                                 error!(
+                                    target: &self.log_target,
                                     "Node[{}] target block index {} not found in sync response",
                                     self.index, target_index
                                 );
-                                self.sync_in_progress = false;
                             }
                             Some(start_idx) => {
-                                // 判断是否成功
-                                let mut success = false;
                                 // 从找到的位置开始同步
                                 for (sync_idx, sync_block) in
                                     sync_blocks[start_idx..].iter().enumerate()
@@ -1203,6 +2721,7 @@ impl Node {
                                     // 验证块的索引是否符合预期
                                     if sync_block.header.index != expected_block_index {
                                         error!(
+                                            target: &self.log_target,
                                             "Node[{}] sync block index mismatch at position {}: expected {}, got {}",
                                             self.index,
                                             start_idx + sync_idx,
@@ -1215,6 +2734,7 @@ impl Node {
                                     match blockchain.add_block(sync_block.clone()) {
                                         Ok(_) => {
                                             debug!(
+                                                target: &self.log_target,
                                                 "Node[{}] synced block #{}: hash={}",
                                                 self.index,
                                                 sync_block.header.index,
@@ -1225,38 +2745,38 @@ impl Node {
                                         Err(e) => match e {
                                             BlockChainError::DuplicateBlocksReceived => {
                                                 warn!(
+                                                    target: &self.log_target,
                                                     "Node[{}] block #{} already exists",
                                                     self.index, sync_block.header.index
                                                 );
                                             }
                                             BlockChainError::ParentHashMismatch
                                             | BlockChainError::TransactionExists => {
-                                                //删除最新的一个块，再同步
-                                                if blockchain.blocks.len() == 1 {
-                                                    error!(
-                                                        "Node[{}] no blocks to remove during sync error handling",
-                                                        self.index
-                                                    );
-                                                } else {
-                                                    if let Some(removed_block) =
-                                                        blockchain.blocks.pop()
-                                                    {
+                                                // 本地链已分叉，回滚到共同祖先后应用同步的区块
+                                                match blockchain
+                                                    .reorg(&sync_blocks, self.max_reorg_depth)
+                                                {
+                                                    Ok(applied) => {
                                                         warn!(
-                                                        "Node[{}] removed block #{} due to {} during sync",
-                                                        self.index, e, removed_block.header.index
-                                                    );
-                                                    } else {
+                                                            target: &self.log_target,
+                                                            "Node[{}] reorged due to {} during sync, applied {} blocks",
+                                                            self.index, e, applied
+                                                        );
+                                                        success = true;
+                                                    }
+                                                    Err(reorg_err) => {
                                                         error!(
-                                                        "Node[{}] no blocks to remove during sync error handling",
-                                                        self.index
-                                                    );
-                                                        break;
+                                                            target: &self.log_target,
+                                                            "Node[{}] reorg failed during sync (max depth {}): {}",
+                                                            self.index, self.max_reorg_depth, reorg_err
+                                                        );
                                                     }
                                                 }
                                                 break;
                                             }
                                             _ => {
                                                 error!(
+                                                    target: &self.log_target,
                                                     "Node[{}] error adding synced block #{}: {}",
                                                     self.index, sync_block.header.index, e
                                                 );
@@ -1268,14 +2788,44 @@ impl Node {
                                 if success {
                                     let synced_count = sync_blocks.len() - start_idx;
                                     info!(
+                                        target: &self.log_target,
                                         "Node[{}] completed block sync: synced {} blocks ",
                                         self.index, synced_count
                                     );
-                                    self.sync_in_progress = false;
                                 }
                             }
                         }
+                        self.chain_cache.update(&blockchain, &sync_blocks);
+                    }
+                    self.on_block_sync_response(&responder, success).await;
+                }
+                MessageType::BlockSyncTimeout => {
+                    let seq = match msg.data.len() {
+                        8 => u64::from_le_bytes([
+                            msg.data[0],
+                            msg.data[1],
+                            msg.data[2],
+                            msg.data[3],
+                            msg.data[4],
+                            msg.data[5],
+                            msg.data[6],
+                            msg.data[7],
+                        ]),
+                        _ => continue,
+                    };
+                    // 已完成的同步或过期的请求直接忽略
+                    if !self.sync_in_progress || seq != self.sync_request_seq {
+                        continue;
+                    }
+                    if let Some(target) = self.sync_target.clone() {
+                        warn!(
+                            target: &self.log_target,
+                            "Node[{}] block sync request to {} timed out",
+                            self.index, target
+                        );
+                        self.update_neighbor_reliability(&target, false);
                     }
+                    self.send_next_sync_request().await;
                 }
                 _ => {}
             }
@@ -1289,6 +2839,9 @@ impl Neighbor {
             index,
             address,
             sender,
+            is_bootstrap: false,
+            reliability: 0.5,
+            latency: Duration::ZERO,
         }
     }
 
@@ -1307,6 +2860,8 @@ mod tests {
     use crate::blockchain::block::Body;
     use crate::blockchain::path::TransactionPaths;
     use crate::blockchain::transaction::Transaction;
+    use crate::consensus::pos::PosConsensus;
+    use crate::network::test_fixtures::test_node;
     use crate::wallet::Wallet;
     use std::time::Duration;
 
@@ -1343,16 +2898,7 @@ mod tests {
         )
         .unwrap();
 
-        let mut node = Node::new(
-            0,
-            0,
-            0,
-            blockchain,
-            world_sender,
-            1000,
-            ConsensusType::POG,
-            0,
-        );
+        let mut node = test_node(0, blockchain, world_sender);
         let node_sender = node.sender.clone();
         let handle1 = tokio::spawn(async move {
             node.run().await;
@@ -1516,12 +3062,1265 @@ mod tests {
         handle3.abort();
     }
 
+    /// 4个两两相连、创世区块相同的节点
+    fn full_mesh(world_sender: &Sender<Message>, configure: impl Fn(&mut Node)) -> Vec<Node> {
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let mut nodes: Vec<Node> = (0..4)
+            .map(|i| {
+                let mut node = test_node(i, blockchain.clone(), world_sender.clone());
+                node.slot = 1;
+                configure(&mut node);
+                node
+            })
+            .collect();
+        let neighbors: Vec<Neighbor> = nodes
+            .iter()
+            .map(|n| Neighbor::new(n.index, n.get_address(), n.sender.clone()))
+            .collect();
+        for node in nodes.iter_mut() {
+            node.neighbors = neighbors
+                .iter()
+                .filter(|n| n.index != node.index)
+                .cloned()
+                .collect();
+        }
+        nodes
+    }
+
+    /// 等待消息计数连续几次读数不变，说明传播已经结束；最多等待 5 秒
+    async fn wait_until_quiet(stats: &MessageStats) {
+        let mut last = MessageCounts::default();
+        let mut unchanged = 0;
+        for _ in 0..100 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let counts = stats.counts();
+            if counts != MessageCounts::default() && counts == last {
+                unchanged += 1;
+                if unchanged >= 3 {
+                    return;
+                }
+            } else {
+                unchanged = 0;
+            }
+            last = counts;
+        }
+    }
+
+    /// 从节点0发出一笔交易，返回收到交易的节点数和发出的消息数
+    async fn propagate(strategy: PropagationStrategy) -> (usize, MessageCounts) {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let stats = Arc::new(MessageStats::default());
+        let nodes = full_mesh(&world_sender, |node| {
+            node.set_propagation(strategy, stats.clone())
+        });
+        let caches: Vec<_> = nodes
+            .iter()
+            .map(|n| n.transaction_paths_cache.clone())
+            .collect();
+        let wallet = nodes[0].wallet.clone();
+        let sender = nodes[0].sender.clone();
+        let handles: Vec<_> = nodes
+            .into_iter()
+            .map(|mut node| tokio::spawn(async move { node.run().await }))
+            .collect();
+
+        let transaction = Transaction::new("to".to_string(), 1, wallet);
+        let tx_hash = transaction.hash.clone();
+        sender
+            .send(Message::new_transaction_paths_msg(
+                TransactionPaths::new(transaction),
+                "".to_string(),
+            ))
+            .await
+            .unwrap();
+        wait_until_quiet(&stats).await;
+        handles.iter().for_each(|h| h.abort());
+
+        let mut reached = 0;
+        for cache in caches {
+            if cache.read().await.contains_key(&tx_hash) {
+                reached += 1;
+            }
+        }
+        (reached, stats.counts())
+    }
+
+    #[tokio::test]
+    async fn test_propagation_strategy() {
+        // flood: 每个节点转发给除来源外的所有邻居
+        // POG 下较长的路径先到时，收到更短的路径会再转发一次，因此只检查下界
+        let (reached, flood) = propagate(PropagationStrategy::Flood).await;
+        assert_eq!(reached, 4);
+        assert!(flood.transactions >= 3 + 3 * 2);
+        assert_eq!(flood.announcements + flood.requests + flood.blocks, 0);
+
+        // push-pull: 交易只发给请求的邻居，公告代替了完整交易
+        let (reached, push_pull) = propagate(PropagationStrategy::PushPull).await;
+        assert_eq!(reached, 4);
+        assert_eq!(push_pull.transactions, push_pull.requests);
+        assert!(push_pull.transactions >= 3);
+        assert!(push_pull.transactions <= push_pull.announcements);
+        assert!(push_pull.announcements >= 3 + 3 * 2);
+
+        // random-walk: 每个收到新交易的节点只转发给一个邻居
+        let (reached, random_walk) = propagate(PropagationStrategy::RandomWalk).await;
+        assert!(reached >= 2);
+        assert_eq!(random_walk.transactions, reached as u64);
+        assert_eq!(random_walk.announcements + random_walk.requests, 0);
+    }
+
+    /// 节点0出块，返回所有节点的最新区块是否相同和发出的消息数
+    async fn propagate_block(announce_blocks: bool) -> (bool, MessageCounts) {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let stats = Arc::new(MessageStats::default());
+        let nodes = full_mesh(&world_sender, |node| {
+            node.set_propagation(PropagationStrategy::Flood, stats.clone());
+            node.set_announce_blocks(announce_blocks);
+        });
+        let blockchains: Vec<_> = nodes.iter().map(|n| n.blockchain.clone()).collect();
+        let sender = nodes[0].sender.clone();
+        let handles: Vec<_> = nodes
+            .into_iter()
+            .map(|mut node| tokio::spawn(async move { node.run().await }))
+            .collect();
+
+        sender
+            .send(Message::new_generate_block_msg())
+            .await
+            .unwrap();
+        wait_until_quiet(&stats).await;
+        handles.iter().for_each(|h| h.abort());
+
+        let mut tips = HashSet::new();
+        for blockchain in blockchains {
+            let blockchain = blockchain.read().await;
+            assert_eq!(blockchain.height(), 2);
+            tips.insert(blockchain.get_last_hash());
+        }
+        (tips.len() == 1, stats.counts())
+    }
+
+    #[tokio::test]
+    async fn test_announce_blocks() {
+        // 区块按哈希去重，每个节点第一次收到时转发给除来源外的所有邻居
+        let (reached, flood) = propagate_block(false).await;
+        assert!(reached);
+        assert_eq!(flood.blocks, 3 + 3 * 2);
+
+        // 同一区块只向第一个公告者请求，每个节点只请求一次完整区块
+        let (reached, announce) = propagate_block(true).await;
+        assert!(reached);
+        assert_eq!(announce.block_announcements, 3 + 3 * 2);
+        assert_eq!(announce.block_requests, 3);
+        assert_eq!(announce.blocks, 3);
+        assert!(announce.block_bytes < flood.block_bytes);
+    }
+
+    #[tokio::test]
+    async fn test_block_sync_prefers_reliable_neighbor() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let mut peer_chain = Blockchain::new(Block::gen_genesis_block());
+        let miner = Wallet::new();
+        for i in 1..=4 {
+            let block = Block::new(
+                i,
+                0,
+                i,
+                peer_chain.get_last_hash(),
+                Body::new(vec![], vec![]),
+                miner.clone(),
+            )
+            .unwrap();
+            peer_chain.add_block(block).unwrap();
+        }
+
+        let blockchain = Blockchain::new(peer_chain.blocks()[0].clone());
+        let mut node = test_node(0, blockchain, world_sender);
+        node.sync_timeout = Duration::from_millis(300);
+        let (b_sender, mut b_rx) = tokio::sync::mpsc::channel(8);
+        let (c_sender, mut c_rx) = tokio::sync::mpsc::channel(8);
+        let c_address = Wallet::new().address;
+        node.neighbors
+            .push(Neighbor::new(1, Wallet::new().address, b_sender));
+        node.neighbors
+            .push(Neighbor::new(2, c_address.clone(), c_sender));
+        let node_sender = node.sender.clone();
+        let handle = tokio::spawn(async move {
+            node.run().await;
+        });
+
+        // 第一轮：B 不响应，超时后转向 C，由 C 完成同步
+        node_sender
+            .send(Message::new_block_msg(
+                peer_chain.blocks()[2].clone(),
+                "".to_string(),
+            ))
+            .await
+            .unwrap();
+        let req = tokio::time::timeout(Duration::from_secs(1), b_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(req.msg_type, MessageType::RequestBlockSync));
+        let req = tokio::time::timeout(Duration::from_secs(1), c_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(req.msg_type, MessageType::RequestBlockSync));
+        node_sender
+            .send(Message::new_response_block_sync_msg(
+                peer_chain.blocks()[1..=2].to_vec(),
+                c_address.clone(),
+            ))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // 第二轮：C 的可靠度更高，应当首先被请求
+        node_sender
+            .send(Message::new_block_msg(
+                peer_chain.blocks()[4].clone(),
+                "".to_string(),
+            ))
+            .await
+            .unwrap();
+        let req = tokio::time::timeout(Duration::from_secs(1), c_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(req.msg_type, MessageType::RequestBlockSync));
+        assert!(b_rx.try_recv().is_err());
+
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_sync_after_popping_fork_block() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let genesis = Block::gen_genesis_block();
+        let wallet = Wallet::new();
+        let miner_a = Wallet::new();
+        let miner_b = Wallet::new();
+        let transaction = Transaction::new("123".to_string(), 32, wallet.clone());
+        let gen_body = |miner: &Wallet| {
+            let mut transaction_paths = TransactionPaths::new(transaction.clone());
+            transaction_paths.add_path(miner.address.clone(), wallet.clone());
+            Body::new(
+                vec![transaction.clone()],
+                vec![transaction_paths.to_aggregated_signed_paths()],
+            )
+        };
+
+        // 本地链在 #1 分叉，分叉块和对端的 #1 包含同一笔交易
+        let mut local_chain = Blockchain::new(genesis.clone());
+        let fork_block = Block::new(
+            1,
+            0,
+            1,
+            local_chain.get_last_hash(),
+            gen_body(&miner_a),
+            miner_a,
+        )
+        .unwrap();
+        local_chain.add_block(fork_block).unwrap();
+
+        let mut peer_chain = Blockchain::new(genesis);
+        let block1 = Block::new(
+            1,
+            0,
+            2,
+            peer_chain.get_last_hash(),
+            gen_body(&miner_b),
+            miner_b.clone(),
+        )
+        .unwrap();
+        peer_chain.add_block(block1).unwrap();
+        let block2 = Block::new(
+            2,
+            0,
+            3,
+            peer_chain.get_last_hash(),
+            Body::new(vec![], vec![]),
+            miner_b,
+        )
+        .unwrap();
+        peer_chain.add_block(block2).unwrap();
+
+        let mut node = test_node(0, local_chain, world_sender);
+        let blockchain = node.blockchain.clone();
+        let node_sender = node.sender.clone();
+        let handle = tokio::spawn(async move {
+            node.run().await;
+        });
+
+        // 第一次同步：#2 父哈希不匹配，移除分叉块
+        // 第二次同步：分叉块的交易已被移除，可以同步对端的 #1 和 #2
+        for _ in 0..2 {
+            node_sender
+                .send(Message::new_response_block_sync_msg(
+                    peer_chain.blocks().to_vec(),
+                    "".to_string(),
+                ))
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        handle.abort();
+
+        let blockchain = blockchain.read().await;
+        assert_eq!(blockchain.get_last_index(), 2);
+        assert_eq!(blockchain.get_last_hash(), peer_chain.get_last_hash());
+    }
+
+    #[tokio::test]
+    async fn test_multi_epoch_offline() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let mut node = test_node(0, blockchain, world_sender);
+        node.set_node_type(NodeType::unstable(1.0, 3));
+        let (neighbor_sender, mut neighbor_rx) = tokio::sync::mpsc::channel(8);
+        node.neighbors
+            .push(Neighbor::new(1, Wallet::new().address, neighbor_sender));
+
+        // epoch 1 下线，持续到 epoch 4
+        node.epoch = 1;
+        node.update_online_status(0).await;
+        assert!(!node.is_online);
+        assert_eq!(node.offline_until_epoch, Some(4));
+
+        node.set_node_type(NodeType::unstable(0.0, 3));
+        for epoch in 2..4 {
+            let old_epoch = node.epoch;
+            node.epoch = epoch;
+            node.update_online_status(old_epoch).await;
+            assert!(!node.is_online);
+        }
+        assert!(neighbor_rx.try_recv().is_err());
+
+        // epoch 4 恢复在线，并向邻居请求块同步
+        node.epoch = 4;
+        node.update_online_status(3).await;
+        assert!(node.is_online);
+        assert_eq!(node.offline_until_epoch, None);
+        let req = tokio::time::timeout(Duration::from_secs(1), neighbor_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(req.msg_type, MessageType::RequestBlockSync));
+        assert!(node.sync_in_progress);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_expiry() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let mut node = test_node(0, blockchain, world_sender);
+        node.set_tx_expiry_slots(2);
+
+        let transaction = Transaction::new("123".to_string(), 32, node.wallet.clone());
+        let tx_hash = transaction.hash.clone();
+        node.transaction_paths_cache
+            .write()
+            .await
+            .insert(tx_hash.clone(), TransactionPaths::new(transaction));
+        node.track_transaction_slot(&tx_hash);
+
+        // 未过期前可以被打包
+        for _ in 0..2 {
+            node.slot_count += 1;
+            node.expire_transactions().await;
+        }
+        let block = node.create_block_template(0, 2).await.unwrap();
+        assert_eq!(block.body.transactions.len(), 1);
+
+        // 过期后从缓存中移除，不会被打包
+        node.slot_count += 1;
+        node.expire_transactions().await;
+        assert!(!node
+            .transaction_paths_cache
+            .read()
+            .await
+            .contains_key(&tx_hash));
+        assert!(node.transaction_received_slot.is_empty());
+        let block = node.generate_block(0, 3).await.unwrap();
+        assert!(block.body.transactions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mempool_stats() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let node = test_node(0, blockchain, world_sender);
+        assert_eq!(node.mempool_stats().await, MempoolStats::default());
+
+        let relay = Wallet::new();
+        for (amount, hops) in [(1, 0), (2, 1), (3, 2), (4, 2)] {
+            let transaction = Transaction::new("123".to_string(), amount, node.wallet.clone());
+            let mut transaction_paths = TransactionPaths::new(transaction.clone());
+            for _ in 0..hops {
+                transaction_paths.add_path(relay.address.clone(), node.wallet.clone());
+            }
+            node.transaction_paths_cache
+                .write()
+                .await
+                .insert(transaction.hash.clone(), transaction_paths);
+        }
+        let stats = node.mempool_stats().await;
+        assert_eq!(stats.tx_count, 4);
+        assert_eq!(stats.path_lengths, BTreeMap::from([(0, 1), (1, 1), (2, 2)]));
+        assert!((stats.avg_path_length() - 1.25).abs() < 1e-9);
+    }
+
+    /// 节点收到 RequestMempoolHealth 后向 WorldState 上报内存池交易数和丢弃计数
+    #[tokio::test]
+    async fn test_request_mempool_health() {
+        let (world_sender, mut world_rx) = tokio::sync::mpsc::channel(8);
+        let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+        let transaction = Transaction::new("123".to_string(), 1, node.wallet.clone());
+        node.transaction_paths_cache
+            .write()
+            .await
+            .insert(transaction.hash.clone(), TransactionPaths::new(transaction));
+        MempoolHealth::add(&node.mempool_health.expired, 2);
+        MempoolHealth::add(&node.mempool_health.dropped_by_backpressure, 3);
+        let address = node.get_address();
+        let node_sender = node.sender.clone();
+        let handle = tokio::spawn(async move {
+            node.run().await;
+        });
+
+        node_sender
+            .send(Message::new_request_mempool_health_msg())
+            .await
+            .unwrap();
+        let msg = tokio::time::timeout(Duration::from_secs(5), world_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(msg.msg_type, MessageType::ResponseMempoolHealth));
+        assert_eq!(msg.from, address);
+        let report: MempoolReport = serde_json::from_slice(&msg.data).unwrap();
+        assert_eq!(
+            report,
+            MempoolReport {
+                mempool_size: 1,
+                expired: 2,
+                dropped_by_backpressure: 3,
+                ..Default::default()
+            }
+        );
+        handle.abort();
+    }
+
+    /// 收到共识切换后按新的共识处理重复交易：POS 忽略更短的路径，切换到 POG 后接受
+    #[tokio::test]
+    async fn test_switch_consensus() {
+        let (world_sender, mut world_rx) = tokio::sync::mpsc::channel(8);
+        let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+        node.consensus = ConsensusType::POS;
+        let sender = Wallet::new();
+        let relay = Wallet::new();
+        let transaction = Transaction::new("123".to_string(), 1, sender.clone());
+        let mut long_paths = TransactionPaths::new(transaction.clone());
+        long_paths.add_path(relay.address.clone(), sender.clone());
+        long_paths.add_path(node.get_address(), relay.clone());
+        let mut short_paths = TransactionPaths::new(transaction.clone());
+        short_paths.add_path(node.get_address(), sender);
+        let cache = node.transaction_paths_cache.clone();
+        let node_sender = node.sender.clone();
+        let handle = tokio::spawn(async move {
+            node.run().await;
+        });
+
+        // 收到内存池状态的响应说明之前的消息都已处理
+        for msg in [
+            Message::new_transaction_paths_msg(long_paths, relay.address.clone()),
+            Message::new_transaction_paths_msg(short_paths.clone(), relay.address.clone()),
+            Message::new_request_mempool_health_msg(),
+        ] {
+            node_sender.send(msg).await.unwrap();
+        }
+        world_rx.recv().await.unwrap();
+        assert_eq!(cache.read().await[&transaction.hash].paths.len(), 2);
+
+        for msg in [
+            Message::new_switch_consensus_msg(ConsensusType::POG),
+            Message::new_transaction_paths_msg(short_paths, relay.address.clone()),
+            Message::new_request_mempool_health_msg(),
+        ] {
+            node_sender.send(msg).await.unwrap();
+        }
+        world_rx.recv().await.unwrap();
+        assert_eq!(cache.read().await[&transaction.hash].paths.len(), 1);
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_node_confirmations() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let node = test_node(0, blockchain, world_sender);
+        let transaction = Transaction::new("123".to_string(), 32, node.wallet.clone());
+        let tx_hash = transaction.hash.clone();
+        assert_eq!(node.confirmations(&tx_hash).await, None);
+
+        node.transaction_paths_cache
+            .write()
+            .await
+            .insert(tx_hash.clone(), TransactionPaths::new(transaction));
+        let block = node.create_block_template(0, 1).await.unwrap();
+        node.blockchain.write().await.add_block(block).unwrap();
+        assert_eq!(node.confirmations(&tx_hash).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_relay_never_becomes_validator() {
+        async fn becomes_validator(configure: fn(&mut Node)) -> bool {
+            let (world_sender, mut world_rx) = tokio::sync::mpsc::channel(8);
+            let blockchain = Blockchain::new(Block::gen_genesis_block());
+            let mut node = test_node(0, blockchain, world_sender);
+            configure(&mut node);
+            let stake_map: HashMap<String, f64> = HashMap::from([(node.get_address(), 5.0)]);
+            let node_sender = node.sender.clone();
+            let handle = tokio::spawn(async move {
+                node.run().await;
+            });
+
+            node_sender
+                .send(Message::new_become_validator_msg(
+                    serde_json::to_vec(&stake_map).unwrap(),
+                ))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            handle.abort();
+
+            let mut registered = false;
+            while let Ok(msg) = world_rx.try_recv() {
+                registered |= matches!(msg.msg_type, MessageType::ReceiveBecomeValidator);
+            }
+            registered
+        }
+
+        assert!(becomes_validator(|_| {}).await);
+        // 中继节点和未被选为验证者的节点不会向 WorldState 注册为验证者
+        assert!(!becomes_validator(|node| node.set_node_type(NodeType::Relay)).await);
+        assert!(!becomes_validator(|node| node.set_validator(false)).await);
+    }
+
+    /// local 模式下节点根据验证者集合自行计算出块者，只有选中自己时才出块
+    #[tokio::test]
+    async fn test_local_proposer() {
+        async fn produced_block(validators: Vec<Validator>, own: bool) -> bool {
+            let (world_sender, mut world_rx) = tokio::sync::mpsc::channel(8);
+            let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+            node.consensus = ConsensusType::POS;
+            node.set_local_proposer(Box::new(PosConsensus::new(0.0)));
+            let mut validators = validators;
+            if own {
+                validators.push(Validator::new(node.get_address(), 1.0, 1.0));
+            }
+            let node_sender = node.sender.clone();
+            let handle = tokio::spawn(async move {
+                node.run().await;
+            });
+
+            let slot = SlotManager {
+                randao_seeds: vec![],
+                slot_duration: Duration::from_secs(1),
+                current_epoch: 0,
+                current_slot: 1,
+                next_seed: [7; 32],
+                start_timestamp: 0,
+            };
+            node_sender
+                .send(Message::new_update_validator_set_msg(&validators))
+                .await
+                .unwrap();
+            // 重复的 UpdateSlot（伪造身份共用通道）只计算一次
+            for _ in 0..2 {
+                node_sender
+                    .send(Message::new_update_slot_msg(slot.clone()))
+                    .await
+                    .unwrap();
+            }
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            handle.abort();
+
+            let mut blocks = 0;
+            while let Ok(msg) = world_rx.try_recv() {
+                if matches!(msg.msg_type, MessageType::SendBlock) {
+                    blocks += 1;
+                }
+            }
+            assert!(blocks <= 1);
+            blocks == 1
+        }
+
+        assert!(produced_block(vec![], true).await);
+        let other = Validator::new(Wallet::new().address, 1.0, 1.0);
+        assert!(!produced_block(vec![other], false).await);
+    }
+
+    /// 写入区块链后缓存与区块链一致，链上交易一定能在布隆过滤器中查到
+    #[test]
+    fn test_chain_cache() {
+        let wallet = Wallet::new();
+        let miner = Wallet::new();
+        let mut blockchain = Blockchain::new(Block::gen_genesis_block());
+        let cache = ChainCache::new(&blockchain, 1);
+        assert_eq!(cache.last_index(), 0);
+        assert_eq!(cache.last_hash(), blockchain.get_last_hash());
+
+        let transaction = Transaction::new("to".to_string(), 1, wallet.clone());
+        let mut transaction_paths = TransactionPaths::new(transaction.clone());
+        transaction_paths.add_path(miner.address.clone(), wallet.clone());
+        let block = Block::new(
+            1,
+            0,
+            1,
+            blockchain.get_last_hash(),
+            Body::new(
+                vec![transaction.clone()],
+                vec![transaction_paths.to_aggregated_signed_paths()],
+            ),
+            miner.clone(),
+        )
+        .unwrap();
+        blockchain.add_block(block.clone()).unwrap();
+        cache.update(&blockchain, std::slice::from_ref(&block));
+        assert_eq!(cache.last_index(), 1);
+        assert_eq!(cache.last_hash(), block.header.hash);
+        assert!(cache.may_contain_transaction(&transaction.hash));
+        assert!(!cache.may_contain_transaction("not-on-chain"));
+
+        // 超出容量后按两倍容量用整条链重建，之前的交易仍能查到
+        let transaction2 = Transaction::new("to2".to_string(), 1, wallet.clone());
+        let mut transaction_paths2 = TransactionPaths::new(transaction2.clone());
+        transaction_paths2.add_path(miner.address.clone(), wallet.clone());
+        let block2 = Block::new(
+            2,
+            0,
+            2,
+            blockchain.get_last_hash(),
+            Body::new(
+                vec![transaction2.clone()],
+                vec![transaction_paths2.to_aggregated_signed_paths()],
+            ),
+            miner.clone(),
+        )
+        .unwrap();
+        blockchain.add_block(block2.clone()).unwrap();
+        cache.update(&blockchain, std::slice::from_ref(&block2));
+        assert!(cache.tx_filter_capacity() > 1);
+        assert!(cache.may_contain_transaction(&transaction.hash));
+        assert!(cache.may_contain_transaction(&transaction2.hash));
+
+        // 回滚后最新区块随之更新
+        blockchain.pop_block().unwrap();
+        blockchain.pop_block().unwrap();
+        cache.update(&blockchain, &[]);
+        assert_eq!(cache.last_index(), 0);
+        assert_eq!(cache.last_hash(), blockchain.get_last_hash());
+    }
+
+    /// 缓存满时淘汰最早收到的交易，重复交易不会占用容量
+    #[tokio::test]
+    async fn test_transaction_cache_eviction() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+        node.set_tx_cache_size(3);
+        let wallet = Wallet::new();
+        let relay = Wallet::new();
+        let txs: Vec<TransactionPaths> = (0..5)
+            .map(|i| {
+                let transaction = Transaction::new("to".to_string(), i, wallet.clone());
+                let mut transaction_paths = TransactionPaths::new(transaction);
+                transaction_paths.add_path(relay.address.clone(), wallet.clone());
+                transaction_paths.add_path(node.get_address(), relay.clone());
+                transaction_paths
+            })
+            .collect();
+        let msg_type = MessageType::SendTransactionPaths;
+        let cached = |cache: &HashMap<String, TransactionPaths>| {
+            let mut amounts: Vec<i64> = cache.values().map(|tp| tp.transaction.amount).collect();
+            amounts.sort();
+            amounts
+        };
+
+        for tx in &txs {
+            assert!(node.accept_transaction_paths(tx, &msg_type).await);
+        }
+        assert_eq!(
+            cached(&*node.transaction_paths_cache.read().await),
+            vec![2, 3, 4]
+        );
+
+        // 重复交易被过滤，路径更短的重复交易只更新，不淘汰其他交易
+        assert!(!node.accept_transaction_paths(&txs[4], &msg_type).await);
+        let mut shorter = TransactionPaths::new(txs[2].transaction.clone());
+        shorter.add_path(node.get_address(), wallet.clone());
+        assert!(node.accept_transaction_paths(&shorter, &msg_type).await);
+        assert_eq!(
+            cached(&*node.transaction_paths_cache.read().await),
+            vec![2, 3, 4]
+        );
+
+        // 已打包移除的交易不计入容量，下一笔新交易直接放入
+        node.transaction_paths_cache
+            .write()
+            .await
+            .remove(&txs[3].transaction.hash);
+        assert!(node.accept_transaction_paths(&txs[0], &msg_type).await);
+        assert_eq!(
+            cached(&*node.transaction_paths_cache.read().await),
+            vec![0, 2, 4]
+        );
+    }
+
+    /// 缓存满时先淘汰小费和手续费最低的交易，费用相同时淘汰最早收到的，费用更低的新交易直接丢弃
+    #[tokio::test]
+    async fn test_transaction_cache_eviction_by_fee() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+        node.set_tx_cache_size(3);
+        let wallet = Wallet::new();
+        let tx = |amount: i64, fee: f64, tip: f64| {
+            TransactionPaths::new(Transaction::with_tip(
+                "to".to_string(),
+                amount,
+                fee,
+                tip,
+                wallet.clone(),
+            ))
+        };
+        let cached = |cache: &HashMap<String, TransactionPaths>| {
+            let mut amounts: Vec<i64> = cache.values().map(|tp| tp.transaction.amount).collect();
+            amounts.sort();
+            amounts
+        };
+
+        for tx in [tx(0, 1.0, 0.5), tx(1, 1.0, 0.1), tx(2, 2.0, 0.1)] {
+            assert!(node.cache_transaction_paths(&tx).await);
+        }
+        // 小费相同时手续费低的先淘汰
+        assert!(node.cache_transaction_paths(&tx(3, 1.0, 0.3)).await);
+        assert_eq!(
+            cached(&*node.transaction_paths_cache.read().await),
+            vec![0, 2, 3]
+        );
+        // 比内存池中所有交易费用都低的新交易被丢弃
+        assert!(!node.cache_transaction_paths(&tx(4, 1.0, 0.05)).await);
+        assert_eq!(
+            cached(&*node.transaction_paths_cache.read().await),
+            vec![0, 2, 3]
+        );
+        // 费用相同时淘汰较早收到的交易
+        assert!(node.cache_transaction_paths(&tx(5, 2.0, 0.1)).await);
+        assert_eq!(
+            cached(&*node.transaction_paths_cache.read().await),
+            vec![0, 3, 5]
+        );
+        assert_eq!(node.mempool_health.evicted.load(AtomicOrdering::Relaxed), 3);
+    }
+
+    /// 收到的交易超过布隆过滤器容量后重建，新交易不会被误判为重复而丢弃
+    #[tokio::test]
+    async fn test_seen_transactions() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+        node.set_tx_cache_size(4);
+        let wallet = Wallet::new();
+        let msg_type = MessageType::SendTransactionPaths;
+        let txs: Vec<TransactionPaths> = (0..SEEN_TX_FILTER_MIN_CAPACITY + 100)
+            .map(|i| {
+                let transaction = Transaction::new(format!("to{}", i), 1, wallet.clone());
+                let mut transaction_paths = TransactionPaths::new(transaction);
+                transaction_paths.add_path(node.get_address(), wallet.clone());
+                transaction_paths
+            })
+            .collect();
+        for tx in &txs {
+            assert!(node.accept_transaction_paths(tx, &msg_type).await);
+        }
+        assert!(node.seen_transactions.inserted < SEEN_TX_FILTER_MIN_CAPACITY);
+
+        // 重建后内存池中的交易仍然能查到，重复交易被过滤
+        for tx in &txs[txs.len() - 4..] {
+            assert!(node.seen_transactions.might_contain(&tx.transaction.hash));
+            assert!(!node.accept_transaction_paths(tx, &msg_type).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_state() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let genesis = Blockchain::new(Block::gen_genesis_block());
+        let new_node = || test_node(0, genesis.clone(), world_sender.clone());
+        let mut node = new_node();
+        node.set_balance(42.5);
+        let packed = Transaction::new("packed".to_string(), 1, node.wallet.clone());
+        node.cache_transaction_paths(&TransactionPaths::new(packed.clone()))
+            .await;
+        let block = node.create_block_template(0, 1).await.unwrap();
+        node.blockchain.write().await.add_block(block).unwrap();
+        node.transaction_paths_cache.write().await.clear();
+        let pending: Vec<TransactionPaths> = (0..3)
+            .map(|i| {
+                let transaction = Transaction::new(format!("to{}", i), 1, node.wallet.clone());
+                TransactionPaths::new(transaction)
+            })
+            .collect();
+        for tx in &pending {
+            node.cache_transaction_paths(tx).await;
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node_0.json");
+        let path = path.to_str().unwrap();
+        node.save_state(path).await.unwrap();
+
+        let mut restored = new_node();
+        restored.load_state(path).await.unwrap();
+        assert_eq!(restored.get_address(), node.get_address());
+        assert_eq!(restored.get_balance(), 42.5);
+        assert_eq!(restored.confirmations(&packed.hash).await, Some(1));
+        assert_eq!(
+            restored.blockchain.read().await.get_last_hash(),
+            node.blockchain.read().await.get_last_hash()
+        );
+        assert_eq!(restored.chain_cache.last_index(), 1);
+        assert_eq!(restored.mempool_stats().await.tx_count, 3);
+        let order: Vec<String> = pending
+            .iter()
+            .map(|tx| tx.transaction.hash.clone())
+            .collect();
+        let restored_order: Vec<String> =
+            restored.transaction_cache_order.values().cloned().collect();
+        assert_eq!(restored_order, order);
+        assert!(restored.seen_transactions.might_contain(&order[0]));
+        assert!(restored.key_registry.contains(&restored.get_address()));
+
+        // 创世区块不同的状态文件属于其他模拟，拒绝加载
+        let mut other = test_node(
+            1,
+            Blockchain::new(Block::gen_genesis_block()),
+            world_sender.clone(),
+        );
+        let other_address = other.get_address();
+        assert!(other.load_state(path).await.is_err());
+        assert_eq!(other.get_address(), other_address);
+    }
+
+    /// 邻居数超过并发上限时，除来源外的所有邻居仍然都能收到区块
+    #[tokio::test]
+    async fn test_broadcast_block_reaches_all_neighbors() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+        let mut receivers = vec![];
+        for i in 0..(MAX_CONCURRENT_SENDS * 3) {
+            let (sender, receiver) = tokio::sync::mpsc::channel(1);
+            let address = format!("neighbor{}", i);
+            node.neighbors
+                .push(Neighbor::new(i as u32 + 1, address.clone(), sender));
+            receivers.push((address, receiver));
+        }
+        let block = Block::gen_genesis_block();
+        node.broadcast_block(&block, "neighbor0");
+
+        for (address, mut receiver) in receivers {
+            if address == "neighbor0" {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                assert!(receiver.try_recv().is_err());
+                continue;
+            }
+            let msg = tokio::time::timeout(Duration::from_secs(1), receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(matches!(msg.msg_type, MessageType::SendBlock));
+            assert_eq!(msg.from, node.get_address());
+            let received = Block::from_json(msg.data).unwrap();
+            assert_eq!(received.header.hash, block.header.hash);
+        }
+    }
+
+    /// 广播按邻居的延迟发出，延迟高的邻居晚收到
+    #[tokio::test]
+    async fn test_broadcast_latency() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+        let mut receivers = vec![];
+        for (i, latency) in [300, 0].into_iter().enumerate() {
+            let (sender, receiver) = tokio::sync::mpsc::channel(1);
+            let mut neighbor = Neighbor::new(i as u32 + 1, format!("neighbor{}", i), sender);
+            neighbor.latency = Duration::from_millis(latency);
+            node.neighbors.push(neighbor);
+            receivers.push(receiver);
+        }
+        node.broadcast_block(&Block::gen_genesis_block(), "");
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(receivers[0].try_recv().is_err());
+        assert!(receivers[1].try_recv().is_ok());
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(receivers[0].try_recv().is_ok());
+    }
+
+    /// 分区期间断开的邻居收不到广播，恢复后重新收到
+    #[tokio::test]
+    async fn test_update_partition() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+        let mut receivers = vec![];
+        for i in 0..3 {
+            let (sender, receiver) = tokio::sync::mpsc::channel(4);
+            node.neighbors
+                .push(Neighbor::new(i + 1, format!("neighbor{}", i), sender));
+            receivers.push(receiver);
+        }
+
+        node.update_partition(&["neighbor1".to_string(), "other".to_string()]);
+        assert_eq!(node.neighbors.len(), 2);
+        node.broadcast_block(&Block::gen_genesis_block(), "");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(receivers[0].try_recv().is_ok());
+        assert!(receivers[1].try_recv().is_err());
+
+        node.update_partition(&[]);
+        assert_eq!(node.neighbors.len(), 3);
+        node.broadcast_block(&Block::gen_genesis_block(), "");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(receivers[1].try_recv().is_ok());
+    }
+
+    /// 节点拒绝不是该时隙出块者产出的区块，接受选中的出块者的区块
+    #[tokio::test]
+    async fn test_reject_block_from_wrong_proposer() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let mut node = test_node(0, blockchain.clone(), world_sender);
+        node.slot = 1;
+        let chain = node.blockchain.clone();
+        let node_sender = node.sender.clone();
+        let handle = tokio::spawn(async move {
+            node.run().await;
+        });
+
+        let proposer = Wallet::new();
+        let intruder = Wallet::new();
+        let new_block = |miner: Wallet| {
+            Block::new(
+                blockchain.get_last_index() + 1,
+                0,
+                1,
+                blockchain.get_last_hash(),
+                Body::new(vec![], vec![]),
+                miner,
+            )
+            .unwrap()
+        };
+        let expected = ExpectedProposer {
+            epoch: 0,
+            slot: 1,
+            addresses: vec![proposer.address.clone()],
+        };
+        for msg in [
+            Message::new_update_proposer_msg(&expected),
+            Message::new_block_msg(new_block(intruder), "".to_string()),
+            Message::new_block_msg(new_block(proposer.clone()), "".to_string()),
+        ] {
+            node_sender.send(msg).await.unwrap();
+        }
+
+        // 消息按顺序处理，出块者的区块上链时入侵者的区块已经处理完
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while chain.read().await.get_last_index() == 0 {
+            assert!(tokio::time::Instant::now() < deadline);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let chain = chain.read().await;
+        assert_eq!(chain.get_last_index(), 1);
+        assert_eq!(chain.get_last_block().header.miner, proposer.address);
+        handle.abort();
+    }
+
+    /// local 模式下忽略 WorldState 通知的出块者，不据此拒绝区块
+    #[test]
+    fn test_local_proposer_ignores_update_proposer() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(8);
+        let blockchain = Blockchain::new(Block::gen_genesis_block());
+        let block = Block::new(
+            1,
+            0,
+            1,
+            blockchain.get_last_hash(),
+            Body::new(vec![], vec![]),
+            Wallet::new(),
+        )
+        .unwrap();
+        let expected = ExpectedProposer {
+            epoch: 0,
+            slot: 1,
+            addresses: vec![Wallet::new().address],
+        };
+
+        let mut node = test_node(0, blockchain.clone(), world_sender.clone());
+        node.update_expected_proposer(expected.clone());
+        assert!(!node.is_expected_proposer(&block));
+
+        let mut node = test_node(1, blockchain, world_sender);
+        node.set_local_proposer(Box::new(PosConsensus::new(0.0)));
+        node.update_expected_proposer(expected);
+        assert!(node.is_expected_proposer(&block));
+    }
+
+    /// 一批交易对每个邻居只发送一条消息，转发时同样合并
+    #[tokio::test]
+    async fn test_transaction_paths_batch() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(64);
+        let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+        let (a_sender, mut a_rx) = tokio::sync::mpsc::channel(64);
+        let (b_sender, mut b_rx) = tokio::sync::mpsc::channel(64);
+        let a = Wallet::new();
+        let b = Wallet::new();
+        node.neighbors
+            .push(Neighbor::new(1, a.address.clone(), a_sender));
+        node.neighbors
+            .push(Neighbor::new(2, b.address.clone(), b_sender));
+        let node_address = node.get_address();
+        let node_sender = node.sender.clone();
+        let handle = tokio::spawn(async move {
+            node.run().await;
+        });
+
+        // 生成 5 笔交易，每个邻居只收到一条批量消息
+        let tos = vec![a.address.clone(); 5];
+        node_sender
+            .send(Message::new_generate_transaction_paths_batch_msg(tos))
+            .await
+            .unwrap();
+        for (rx, address) in [(&mut a_rx, &a.address), (&mut b_rx, &b.address)] {
+            let msg = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(matches!(
+                msg.msg_type,
+                MessageType::SendTransactionPathsBatch
+            ));
+            let batch: Vec<TransactionPaths> = serde_json::from_slice(&msg.data).unwrap();
+            assert_eq!(batch.len(), 5);
+            assert!(batch
+                .iter()
+                .all(|tp| tp.paths.last().unwrap().to == *address));
+            assert!(rx.try_recv().is_err());
+        }
+
+        // 从 a 收到的批量交易只转发给 b
+        let batch: Vec<TransactionPaths> = (0..3)
+            .map(|i| {
+                let transaction = Transaction::new(b.address.clone(), i, a.clone());
+                let mut transaction_paths = TransactionPaths::new(transaction);
+                transaction_paths.add_path(node_address.clone(), a.clone());
+                transaction_paths
+            })
+            .collect();
+        node_sender
+            .send(Message::new_transaction_paths_batch_msg(
+                batch,
+                a.address.clone(),
+            ))
+            .await
+            .unwrap();
+        let msg = tokio::time::timeout(Duration::from_secs(1), b_rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let forwarded: Vec<TransactionPaths> = serde_json::from_slice(&msg.data).unwrap();
+        assert_eq!(forwarded.len(), 3);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(a_rx.try_recv().is_err());
+        handle.abort();
+    }
+
+    /// 超出每时隙限速的交易发起请求被丢弃并计数，新时隙补满令牌
+    #[tokio::test]
+    async fn test_tx_origination_rate_limit() {
+        let (world_sender, _world_rx) = tokio::sync::mpsc::channel(64);
+        let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+        node.set_max_tx_per_slot(2);
+        let msg_type = MessageType::GenerateTransactionPaths;
+
+        let mut generated = 0;
+        for i in 0..5 {
+            if node
+                .generate_transaction_paths(format!("to{}", i), &msg_type)
+                .await
+                .is_some()
+            {
+                generated += 1;
+            }
+        }
+        assert_eq!(generated, 2);
+        assert_eq!(node.tx_rate_limiter.dropped, 3);
+        assert_eq!(node.transaction_paths_cache.read().await.len(), 2);
+
+        node.tx_rate_limiter.refill();
+        assert!(node
+            .generate_transaction_paths("to5".to_string(), &msg_type)
+            .await
+            .is_some());
+        assert_eq!(node.tx_rate_limiter.dropped, 3);
+
+        // 0 表示不限速
+        let mut unlimited = TxRateLimiter::new(0);
+        assert!((0..100).all(|_| unlimited.try_acquire()));
+        assert_eq!(unlimited.dropped, 0);
+    }
+
+    #[test]
+    fn test_node_type_parameters() {
+        assert_eq!(NodeType::Honest.to_string(), "Honest");
+        assert_eq!(NodeType::selfish(2.0).to_string(), "Selfish(drop=1.00)");
+        // 下线概率限制在 [0, 1]，下线至少持续 1 个 epoch
+        assert_eq!(
+            NodeType::unstable(-0.5, 0).to_string(),
+            "Unstable(offline=0.00,duration=1 epochs)"
+        );
+
+        let (world_tx, _world_rx) = tokio::sync::mpsc::channel::<Message>(8);
+        let bc = Blockchain::new(Block::gen_genesis_block());
+        let mut node = Node::new_with_sybil_nodes(
+            1,
+            0,
+            0,
+            bc,
+            world_tx,
+            2,
+            1000,
+            ConsensusType::POG,
+            0,
+            &KeyRegistry::thread_default(),
+        );
+        assert_eq!(node.node_type.to_string(), "Sybil(identities=2)");
+        // 其他类型的节点没有伪造身份
+        node.set_node_type(NodeType::selfish(0.0));
+        assert!(node.sybil_identities().is_empty());
+        assert!(!node.selfish_drop());
+        node.set_node_type(NodeType::selfish(1.0));
+        assert!(node.selfish_drop());
+    }
+
+    /// 节点的钱包只登记到创建时给定的注册表，随机和确定性生成的钱包都一样
+    #[test]
+    fn test_node_wallet_registry() {
+        for wallet_seed in [0, 7] {
+            let registry = KeyRegistry::new();
+            let (world_tx, _world_rx) = tokio::sync::mpsc::channel::<Message>(8);
+            let node = Node::new(
+                1,
+                0,
+                0,
+                Blockchain::new(Block::gen_genesis_block()),
+                world_tx,
+                1000,
+                ConsensusType::POG,
+                wallet_seed,
+                &registry,
+            );
+            assert!(registry.contains(&node.get_address()));
+            assert_eq!(registry.len(), 1);
+            assert!(!KeyRegistry::thread_default().contains(&node.get_address()));
+
+            // 伪造身份的钱包与主节点登记在同一个注册表
+            let registry = KeyRegistry::new();
+            let (world_tx, _world_rx) = tokio::sync::mpsc::channel::<Message>(8);
+            let node = Node::new_with_sybil_nodes(
+                2,
+                0,
+                0,
+                Blockchain::new(Block::gen_genesis_block()),
+                world_tx,
+                3,
+                1000,
+                ConsensusType::POG,
+                wallet_seed,
+                &registry,
+            );
+            assert_eq!(registry.len(), 4);
+            assert!(registry.contains(&node.get_address()));
+            for sybil in node.sybil_identities() {
+                assert!(registry.contains(&sybil.get_address()));
+                assert!(!KeyRegistry::thread_default().contains(&sybil.get_address()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_sybil_identities() {
+        let (world_tx, _world_rx) = tokio::sync::mpsc::channel::<Message>(8);
+        let bc = Blockchain::new(Block::gen_genesis_block());
+        let node = Node::new_with_sybil_nodes(
+            1,
+            0,
+            0,
+            bc,
+            world_tx,
+            3,
+            1000,
+            ConsensusType::POG,
+            0,
+            &KeyRegistry::thread_default(),
+        );
+        assert_eq!(node.sybil_identities().len(), 3);
+        let addresses: std::collections::HashSet<String> = node
+            .sybil_identities()
+            .iter()
+            .map(|s| s.get_address())
+            .collect();
+        assert_eq!(addresses.len(), 3);
+        assert!(!addresses.contains(&node.get_address()));
+        for sybil in node.sybil_identities() {
+            assert!(KeyRegistry::thread_default().contains(&sybil.get_address()));
+        }
+
+        // 相同的 wallet_seed 得到相同的伪造身份
+        let new_node = |wallet_seed| {
+            let (world_tx, _) = tokio::sync::mpsc::channel::<Message>(8);
+            let bc = Blockchain::new(Block::gen_genesis_block());
+            Node::new_with_sybil_nodes(
+                1,
+                0,
+                0,
+                bc,
+                world_tx,
+                3,
+                1000,
+                ConsensusType::POG,
+                wallet_seed,
+                &KeyRegistry::thread_default(),
+            )
+        };
+        let a: Vec<String> = new_node(8)
+            .sybil_identities()
+            .iter()
+            .map(|s| s.get_address())
+            .collect();
+        let b: Vec<String> = new_node(8)
+            .sybil_identities()
+            .iter()
+            .map(|s| s.get_address())
+            .collect();
+        assert_eq!(a, b);
+    }
+
     #[test]
     fn test_balance_management() {
         let (_tx, _rx) = tokio::sync::mpsc::channel::<Message>(8);
         let (world_tx, _world_rx) = tokio::sync::mpsc::channel::<Message>(8);
         let bc = Blockchain::new(Block::gen_genesis_block());
-        let mut node = Node::new(0, 0, 0, bc, world_tx, 1000, ConsensusType::POG, 0);
+        let mut node = test_node(0, bc, world_tx);
 
         assert_eq!(node.get_balance(), 0.0);
 
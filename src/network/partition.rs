@@ -0,0 +1,241 @@
+use crate::blockchain::Blockchain;
+use crate::network::message::Message;
+use crate::network::world_state::SlotManager;
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::{Mutex, RwLock};
+use tokio::time::{self, Instant};
+
+/// 网络分区实验的结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PartitionReport {
+    pub fork_depth: u64,                   // 恢复时从分叉点到最长链末端的区块数
+    pub reconverge_time: Option<Duration>, // 恢复后到所有节点重新达成一致的时间
+    pub reconverge_slots: Option<u64>,     // 恢复后到所有节点重新达成一致经过的时隙数
+}
+
+/// 按节点编号划分两个分区：编号小于 fraction * n 的节点在第一个分区，其余在第二个分区
+/// 两个分区都至少有一个节点
+pub fn split(nodes_index: &HashMap<String, u32>, fraction: f64) -> (Vec<String>, Vec<String>) {
+    let n = nodes_index.len();
+    let count = ((n as f64 * fraction).round() as usize).clamp(1, n.saturating_sub(1).max(1));
+    let mut addresses: Vec<(&String, &u32)> = nodes_index.iter().collect();
+    addresses.sort_by_key(|(_, index)| **index);
+    let (first, second) = addresses.split_at(count.min(n));
+    (
+        first.iter().map(|(a, _)| (*a).clone()).collect(),
+        second.iter().map(|(a, _)| (*a).clone()).collect(),
+    )
+}
+
+/// 所有链上区块哈希一致的最长前缀长度
+pub(crate) fn common_prefix_len(chains: &[Vec<String>]) -> usize {
+    let shortest = chains.iter().map(|c| c.len()).min().unwrap_or(0);
+    (0..shortest)
+        .take_while(|i| chains.iter().all(|c| c[*i] == chains[0][*i]))
+        .count()
+}
+
+/// 分叉深度：从第一个出现不同区块的高度到最长链末端的区块数
+/// 只是落后（链是其他链的前缀）不算分叉，返回 0
+pub fn fork_depth(chains: &[Vec<String>]) -> u64 {
+    let longest = chains.iter().map(|c| c.len()).max().unwrap_or(0);
+    let fork_point = (0..longest).find(|i| {
+        let mut hashes = chains.iter().filter_map(|c| c.get(*i));
+        match hashes.next() {
+            Some(first) => hashes.any(|h| h != first),
+            None => false,
+        }
+    });
+    match fork_point {
+        Some(i) => (longest - i) as u64,
+        None => 0,
+    }
+}
+
+/// 在 partition_at_epoch 将网络分成两个互不连通的分区，在 heal_epoch 恢复
+/// 恢复时记录分叉深度，之后等待所有节点（包括离线节点）的链在恢复时的最高区块处重新一致
+pub struct PartitionDriver {
+    partition_at_epoch: u64,
+    heal_epoch: Option<u64>,
+    partitions: (Vec<String>, Vec<String>),
+    nodes_sender: HashMap<String, Sender<Message>>,
+    node_blockchains: Vec<Arc<RwLock<Blockchain>>>,
+    current_slot: Arc<RwLock<SlotManager>>,
+    slot_per_epoch: u64,
+    report: Arc<Mutex<Option<PartitionReport>>>,
+}
+
+impl PartitionDriver {
+    pub fn new(
+        partition_at_epoch: u64,
+        heal_epoch: Option<u64>,
+        partitions: (Vec<String>, Vec<String>),
+        nodes_sender: HashMap<String, Sender<Message>>,
+        node_blockchains: Vec<Arc<RwLock<Blockchain>>>,
+        current_slot: Arc<RwLock<SlotManager>>,
+        slot_per_epoch: u64,
+    ) -> Self {
+        PartitionDriver {
+            partition_at_epoch,
+            heal_epoch,
+            partitions,
+            nodes_sender,
+            node_blockchains,
+            current_slot,
+            slot_per_epoch,
+            report: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// 实验结果，恢复后才有值
+    pub fn report(&self) -> Arc<Mutex<Option<PartitionReport>>> {
+        self.report.clone()
+    }
+
+    pub async fn run(&mut self) {
+        let mut interval = time::interval(Duration::from_millis(100));
+
+        // 1. 等待分区开始
+        loop {
+            interval.tick().await;
+            if self.current_epoch_slot().await.0 >= self.partition_at_epoch {
+                break;
+            }
+        }
+        let (first, second) = self.partitions.clone();
+        self.send_partition(&first, second.clone()).await;
+        self.send_partition(&second, first.clone()).await;
+        info!(
+            "Network partitioned at epoch {}: {} nodes | {} nodes",
+            self.partition_at_epoch,
+            first.len(),
+            second.len()
+        );
+
+        // 2. 等待恢复
+        let heal_epoch = match self.heal_epoch {
+            Some(epoch) => epoch,
+            None => return,
+        };
+        loop {
+            interval.tick().await;
+            if self.current_epoch_slot().await.0 >= heal_epoch {
+                break;
+            }
+        }
+        let chains = self.chains().await;
+        let fork_depth = fork_depth(&chains);
+        let heal_len = chains.iter().map(|c| c.len()).max().unwrap_or(0);
+        let heal_slot = self.absolute_slot().await;
+        let heal_time = Instant::now();
+        self.send_partition(&first, vec![]).await;
+        self.send_partition(&second, vec![]).await;
+        info!(
+            "Network healed at epoch {}, fork depth {}",
+            heal_epoch, fork_depth
+        );
+        *self.report.lock().await = Some(PartitionReport {
+            fork_depth,
+            ..Default::default()
+        });
+
+        // 3. 等待所有节点在恢复时的最高区块处达成一致
+        loop {
+            interval.tick().await;
+            let chains = self.chains().await;
+            if common_prefix_len(&chains) < heal_len {
+                continue;
+            }
+            let reconverge_time = heal_time.elapsed();
+            let reconverge_slots = self.absolute_slot().await.saturating_sub(heal_slot);
+            info!(
+                "Network reconverged after {:?} ({} slots), fork depth {}",
+                reconverge_time, reconverge_slots, fork_depth
+            );
+            *self.report.lock().await = Some(PartitionReport {
+                fork_depth,
+                reconverge_time: Some(reconverge_time),
+                reconverge_slots: Some(reconverge_slots),
+            });
+            return;
+        }
+    }
+
+    /// 通知 nodes 中的每个节点断开 cut 中的邻居，cut 为空表示恢复
+    async fn send_partition(&self, nodes: &[String], cut: Vec<String>) {
+        for address in nodes {
+            let sender = match self.nodes_sender.get(address) {
+                Some(sender) => sender,
+                None => {
+                    warn!("Partition driver: unknown node {}", address);
+                    continue;
+                }
+            };
+            if let Err(e) = sender
+                .send(Message::new_update_partition_msg(cut.clone()))
+                .await
+            {
+                error!("Partition driver: send partition msg failed {:?}", e);
+            }
+        }
+    }
+
+    async fn chains(&self) -> Vec<Vec<String>> {
+        let mut chains = Vec::with_capacity(self.node_blockchains.len());
+        for blockchain in self.node_blockchains.iter() {
+            let blockchain = blockchain.read().await;
+            chains.push(
+                blockchain
+                    .blocks()
+                    .iter()
+                    .map(|b| b.header.hash.clone())
+                    .collect(),
+            );
+        }
+        chains
+    }
+
+    async fn current_epoch_slot(&self) -> (u64, u64) {
+        let slot = self.current_slot.read().await;
+        (slot.current_epoch, slot.current_slot)
+    }
+
+    async fn absolute_slot(&self) -> u64 {
+        let (epoch, slot) = self.current_epoch_slot().await;
+        epoch * self.slot_per_epoch + slot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let nodes_index: HashMap<String, u32> =
+            (0..10).map(|i| (format!("addr{}", i), i)).collect();
+        let (first, second) = split(&nodes_index, 0.3);
+        assert_eq!(first, vec!["addr0", "addr1", "addr2"]);
+        assert_eq!(second.len(), 7);
+        // 两个分区都至少有一个节点
+        assert_eq!(split(&nodes_index, 0.0).0.len(), 1);
+        assert_eq!(split(&nodes_index, 1.0).1.len(), 1);
+    }
+
+    #[test]
+    fn test_fork_depth() {
+        let chain = |hashes: &[&str]| hashes.iter().map(|h| h.to_string()).collect();
+        let a: Vec<String> = chain(&["g", "1", "2a", "3a", "4a"]);
+        let b: Vec<String> = chain(&["g", "1", "2b", "3b"]);
+        assert_eq!(common_prefix_len(&[a.clone(), b.clone()]), 2);
+        assert_eq!(fork_depth(&[a.clone(), b]), 3);
+        // 一条链是另一条的前缀时，没有分叉，只是落后
+        let behind: Vec<String> = chain(&["g", "1", "2a"]);
+        assert_eq!(common_prefix_len(&[a.clone(), behind.clone()]), 3);
+        assert_eq!(fork_depth(&[a, behind]), 0);
+    }
+}
@@ -0,0 +1,113 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::time::Duration;
+
+/// 区域之间的网络延迟（毫秒），matrix[i][j] 为区域 i 发往区域 j 的延迟
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionLatency {
+    matrix: Vec<Vec<u64>>,
+}
+
+impl RegionLatency {
+    /// 默认模型：同一区域内延迟为 intra_ms，跨区域延迟为 inter_ms
+    pub fn uniform(regions: usize, intra_ms: u64, inter_ms: u64) -> Self {
+        let matrix = (0..regions)
+            .map(|i| {
+                (0..regions)
+                    .map(|j| if i == j { intra_ms } else { inter_ms })
+                    .collect()
+            })
+            .collect();
+        RegionLatency { matrix }
+    }
+
+    /// 从 JSON 文件加载延迟矩阵，例如 [[10, 80], [80, 10]]，必须是 regions x regions
+    pub fn from_file(path: &str, regions: usize) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("open {} failed: {}", path, e))?;
+        let matrix: Vec<Vec<u64>> =
+            serde_json::from_reader(file).map_err(|e| format!("parse {} failed: {}", path, e))?;
+        if matrix.len() != regions || matrix.iter().any(|row| row.len() != regions) {
+            return Err(format!(
+                "latency matrix in {} must be {}x{}",
+                path, regions, regions
+            ));
+        }
+        Ok(RegionLatency { matrix })
+    }
+
+    pub fn latency(&self, from: usize, to: usize) -> Duration {
+        Duration::from_millis(self.matrix[from][to])
+    }
+}
+
+#[derive(Serialize)]
+struct RegionAssignment<'a> {
+    index: u32,
+    address: &'a str,
+    region: usize,
+}
+
+/// 按节点编号轮流分配区域，各类节点（诚实、女巫、不稳定、中继）均匀分布在各区域
+pub fn assign_regions(
+    nodes_index: &HashMap<String, u32>,
+    regions: usize,
+) -> HashMap<String, usize> {
+    nodes_index
+        .iter()
+        .map(|(address, index)| (address.clone(), *index as usize % regions))
+        .collect()
+}
+
+/// 将区域分配按节点编号顺序写入 JSON 文件
+pub fn write_regions(
+    node_regions: &HashMap<String, usize>,
+    nodes_index: &HashMap<String, u32>,
+    path: &str,
+) -> std::io::Result<()> {
+    let mut assignments: Vec<RegionAssignment> = node_regions
+        .iter()
+        .map(|(address, region)| RegionAssignment {
+            index: nodes_index.get(address).cloned().unwrap_or_default(),
+            address,
+            region: *region,
+        })
+        .collect();
+    assignments.sort_by_key(|a| a.index);
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(file, &assignments)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_region_latency() {
+        let model = RegionLatency::uniform(3, 10, 100);
+        assert_eq!(model.latency(1, 1), Duration::from_millis(10));
+        assert_eq!(model.latency(0, 2), Duration::from_millis(100));
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        std::fs::write(path, "[[5, 50], [60, 6]]").unwrap();
+        let model = RegionLatency::from_file(path, 2).unwrap();
+        assert_eq!(model.latency(1, 0), Duration::from_millis(60));
+        // 矩阵大小与区域数不一致
+        assert!(RegionLatency::from_file(path, 3).is_err());
+    }
+
+    #[test]
+    fn test_assign_regions() {
+        let nodes_index: HashMap<String, u32> =
+            (0..10).map(|i| (format!("addr{}", i), i)).collect();
+        let node_regions = assign_regions(&nodes_index, 3);
+        assert_eq!(node_regions["addr4"], 1);
+        let mut sizes = [0; 3];
+        for region in node_regions.values() {
+            sizes[*region] += 1;
+        }
+        assert_eq!(sizes, [4, 3, 3]);
+    }
+}
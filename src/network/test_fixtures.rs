@@ -0,0 +1,41 @@
+use crate::blockchain::block::Block;
+use crate::blockchain::Blockchain;
+use crate::consensus::ConsensusType;
+use crate::network::message::Message;
+use crate::network::node::Node;
+use crate::network::world_state::WorldState;
+use crate::wallet::KeyRegistry;
+use tokio::sync::mpsc::{Receiver, Sender};
+
+/// POS 共识的 WorldState：每个时隙 5 秒，每个 epoch 5 个时隙，没有出块奖励
+pub fn test_world() -> (WorldState, Sender<Message>, Receiver<Message>) {
+    WorldState::new(
+        Block::gen_genesis_block(),
+        ConsensusType::POS,
+        Blockchain::new(Block::gen_genesis_block()),
+        5,
+        5,
+        20,
+        8,
+        0.0,
+        0.0,
+        0.0,
+        0,
+        false,
+    )
+}
+
+/// POG 共识的节点：处于 epoch 0 的时隙 0，钱包随机生成并注册到线程默认的注册表
+pub fn test_node(index: u32, blockchain: Blockchain, world_sender: Sender<Message>) -> Node {
+    Node::new(
+        index,
+        0,
+        0,
+        blockchain,
+        world_sender,
+        1000,
+        ConsensusType::POG,
+        0,
+        &KeyRegistry::thread_default(),
+    )
+}
@@ -1,26 +1,49 @@
 use crate::blockchain::block::Block;
+use crate::blockchain::path::TransactionPaths;
 use crate::blockchain::{BlockChainError, Blockchain};
 use crate::consensus::minotaur::MinotaurConsensus;
 use crate::consensus::pog::PogConsensus;
 use crate::consensus::pos::PosConsensus;
 use crate::consensus::pow::PowConsensus;
-use crate::consensus::{Consensus, ConsensusType, RandaoSeed, Validator};
-use crate::metrics::{self, calculate_stake_concentration, SlotMetrics};
+use crate::consensus::scripted::ScriptedConsensus;
+use crate::consensus::seed::{RandaoSeedSource, SeedSource};
+use crate::consensus::{
+    sample_randao_committee, Consensus, ConsensusSwitch, ConsensusType, RandaoSeed, Validator,
+    ValidatorSetDiff,
+};
+use crate::events;
+use crate::metrics::{
+    self, calculate_stake_concentration, EpochMetrics, InclusionTracker, MetricsSink, PathExport,
+    SlotMetrics,
+};
 use crate::network::message::{Message, MessageType};
+use crate::network::node::MempoolReport;
+use crate::network::ProposerMode;
+use crate::tools;
 use crate::tools::get_timestamp;
-use crate::{consensus, tools};
+use crate::wallet::KeyRegistry;
 use log::{debug, error, info, warn};
+use petgraph::Graph;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt;
-use std::io::Write;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc::{Receiver, Sender};
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
 use tokio::time::Instant;
 use tokio::{task, time};
 
+/// 生成后超过这么多个时隙仍未上链的交易不再跟踪，计为未上链
+pub const INCLUSION_EXPIRY_SLOTS: u64 = 64;
+
+/// 持久化到磁盘的验证者 stake 表
+#[derive(Serialize, Deserialize)]
+struct ValidatorTable {
+    validators: Vec<Validator>,
+    waiting_validators: Vec<Validator>,
+}
+
 /// 全局状态，用于管理时隙、vdf投票，余额等等
 /// 也可以用于与所有的节点进行通信
 pub struct WorldState {
@@ -34,15 +57,149 @@ pub struct WorldState {
     pub nodes_sender: HashMap<String, Sender<Message>>,
     pub blockchain: Arc<RwLock<Blockchain>>,
     pub consensus: Box<dyn Consensus>,
+    // 创建共识时使用的参数，切换共识时按同样的参数创建
+    consensus_params: ConsensusParams,
+    // 按 epoch 排序的共识切换，到达该 epoch 时替换 consensus
+    consensus_switches: Vec<ConsensusSwitch>,
+    // 共识状态摘要（如 POG 的 NTD），每个时隙更新，供 Printer 输出
+    pub consensus_state: Arc<RwLock<String>>,
+    pub seed_source: Box<dyn SeedSource>,
+    // 每个时隙开始后接受 RANDAO 种子的时间占时隙的比例，之后到达的种子不参与本时隙的组合，1 表示整个时隙
+    seed_reveal_window: f64,
+    // 当前时隙的开始时间，用于判断种子是否超过截止时间
+    slot_started: Instant,
+    // 当前时隙超过截止时间被忽略的种子数
+    late_seeds: usize,
+    // 每个时隙提交 RANDAO 种子的验证者数，0 表示所有验证者
+    randao_committee_size: usize,
+    // 本时隙被要求提交种子的委员会，组合种子时只接受其成员的种子，None 表示所有验证者
+    randao_committee: Option<Vec<Validator>>,
     consensus_name: String,
-    metrics_slots_file: Option<std::fs::File>,
+    metrics_sinks: Vec<Box<dyn MetricsSink>>, // 指标输出目标，为空时不输出
+    // 网络拓扑，用于计算节点度相关的指标
+    graph: Option<Graph<String, ()>>,
     slot_duration: Duration,
     slot_per_epoch: u64,
     pub nodes_index: HashMap<String, u32>,
     // 出块成功率统计
     pub block_production_success: usize, // 成功出块数
     pub block_production_failed: usize,  // 失败出块数
-    pub base_reward: f64,                // 所有共识的固定奖励
+    // 当前离线的节点地址，由节点通过 NodeOnlineStatus 报告
+    pub offline_nodes: HashSet<String>,
+    // 跳过离线验证者避免的空时隙数估计：按每个时隙离线验证者的 stake 占比累计的期望值，并非实际计数
+    pub offline_slots_saved_estimate: f64,
+    // 本 epoch 有离线时隙的验证者扣减的 stake 比例，0 表示不扣减
+    offline_stake_decay: f64,
+    // 本 epoch 内各验证者处于离线状态的时隙数，按 offline_nodes 在每个时隙统计
+    offline_slots: HashMap<String, u64>,
+    // 因错过出块累计扣减的 stake
+    pub decayed_stake: f64,
+    // 单个验证者 stake 占总量的上限，超出部分在 epoch 结束时分给其他验证者，1 表示不限制
+    max_stake_share: f64,
+    // 验证者集合的大小上限，超出时只保留 stake 最高的验证者，0 表示不限制
+    max_validators: usize,
+    // 因超出上限未进入验证者集合的注册者，每个 epoch 结束时与验证者一起重新排名
+    pub waiting_validators: Vec<Validator>,
+    // 每个 epoch 结束时保存验证者 stake 表的文件，None 表示不保存
+    state_path: Option<String>,
+    pub base_reward: f64, // 所有共识的固定奖励
+    // 中继节点（非验证者）累计获得的网络费用
+    pub relay_balances: HashMap<String, f64>,
+    // 每个 (epoch, slot) 选出的出块者，用于校验收到的区块
+    expected_proposers: HashMap<(u64, u64), String>,
+    // 是否在时隙指标中记录选择出块者使用的种子
+    record_seeds: bool,
+    proposer_mode: ProposerMode,
+    // 最近一次发送给节点的验证者集合，用于计算增量
+    sent_validators: Option<Vec<Validator>>,
+    // 生成的交易与上链交易的统计，模拟结束后可读取
+    pub inclusion: Arc<RwLock<InclusionTracker>>,
+    // 节点最近一次对 RequestMempoolHealth 的响应，Printer 读取
+    pub mempool_reports: Arc<RwLock<HashMap<String, MempoolReport>>>,
+    // 本 epoch 已开始的时隙数，与本 epoch 的区块数比较得到空时隙数
+    epoch_slots: u64,
+    // 上个 epoch 结束时的验证者地址，用于统计验证者集合的变化
+    prev_validator_addresses: HashSet<String>,
+    // 本次模拟的公钥注册表，为 None 时不清理退出节点的公钥
+    key_registry: Option<KeyRegistry>,
+    // 各节点的交易缓存，清理公钥时跳过仍被缓存中的路径引用的地址
+    node_mempools: HashMap<String, Arc<RwLock<HashMap<String, TransactionPaths>>>>,
+    // 导出上链交易的传播路径，为 None 时不导出
+    path_export: Option<PathExport>,
+    // 已经结束的时隙数，模拟按时隙数运行时等待它达到目标
+    slots_run: watch::Sender<u64>,
+}
+
+/// 创建共识所需的参数
+struct ConsensusParams {
+    slot_duration: Duration,
+    pow_difficulty: usize,
+    pow_max_threads: usize,
+    base_reward: f64,
+    relayer_reward_fraction: f64,
+    universal_reward_fraction: f64,
+    pog_warmup_slots: u64,
+    pog_epoch_decay: f64,
+    // 为 true 时 POG 把每个时隙的网络贡献输出到 contribution.jsonl
+    export_contribution: bool,
+    // contribution.jsonl 只在第一次创建 POG 时清空，之后切换回 POG 时追加
+    contribution_file_created: bool,
+}
+
+impl ConsensusParams {
+    fn build(&mut self, consensus_type: ConsensusType) -> Box<dyn Consensus> {
+        match consensus_type {
+            ConsensusType::POG => {
+                let mut pog = PogConsensus::new(0, self.base_reward);
+                pog.set_warmup_slots(self.pog_warmup_slots);
+                pog.set_epoch_decay(self.pog_epoch_decay);
+                pog.set_relayer_reward_fraction(self.relayer_reward_fraction);
+                // 每个时隙的网络贡献输出到 contribution.jsonl，供离线分析
+                if self.export_contribution {
+                    let file = std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(self.contribution_file_created)
+                        .write(true)
+                        .truncate(!self.contribution_file_created)
+                        .open("contribution.jsonl");
+                    match file {
+                        Ok(file) => pog.set_contribution_writer(file),
+                        Err(e) => warn!("Failed to create contribution.jsonl: {}", e),
+                    }
+                    self.contribution_file_created = true;
+                }
+                Box::new(pog)
+            }
+            ConsensusType::POS => {
+                let mut pos = PosConsensus::new(self.base_reward);
+                pos.set_relayer_reward_fraction(self.relayer_reward_fraction);
+                pos.set_universal_reward_fraction(self.universal_reward_fraction);
+                Box::new(pos)
+            }
+            ConsensusType::POW => {
+                let mut pow = PowConsensus::new(
+                    self.pow_difficulty,
+                    self.pow_max_threads,
+                    self.slot_duration,
+                    self.base_reward,
+                );
+                pow.set_relayer_reward_fraction(self.relayer_reward_fraction);
+                pow.set_universal_reward_fraction(self.universal_reward_fraction);
+                Box::new(pow)
+            }
+            ConsensusType::MINOTAUR => {
+                let mut minotaur = MinotaurConsensus::new(self.base_reward);
+                minotaur.set_relayer_reward_fraction(self.relayer_reward_fraction);
+                minotaur.set_universal_reward_fraction(self.universal_reward_fraction);
+                Box::new(minotaur)
+            }
+            ConsensusType::SCRIPTED => {
+                // 序列由 set_proposer_schedule 设置，之前按 POS 选择
+                let pos = self.build(ConsensusType::POS);
+                Box::new(ScriptedConsensus::new(vec![], pos))
+            }
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -55,6 +212,14 @@ pub struct SlotManager {
     pub start_timestamp: u64,
 }
 
+/// 某个时隙共识选出的出块者，addresses 包含被选中的地址和与它共用通道的伪造身份
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExpectedProposer {
+    pub epoch: u64,
+    pub slot: u64,
+    pub addresses: Vec<String>,
+}
+
 impl WorldState {
     pub fn new(
         genesis_block: Block,
@@ -65,31 +230,28 @@ impl WorldState {
         pow_difficulty: usize,
         pow_max_threads: usize,
         base_reward: f64,
+        relayer_reward_fraction: f64,
+        universal_reward_fraction: f64,
+        pog_warmup_slots: u64,
+        export_contribution: bool,
     ) -> (Self, Sender<Message>, Receiver<Message>) {
         let (sender, receiver) = tokio::sync::mpsc::channel(4096);
         let nodes_sender: HashMap<String, Sender<Message>> = HashMap::new();
         let slot_duration = Duration::from_secs(slot_duration_secs);
         let consensus_name = consensus_type.to_string();
-        let consensus: Box<dyn Consensus> = match consensus_type {
-            ConsensusType::POG => Box::new(PogConsensus::new(0, base_reward)),
-            ConsensusType::POS => Box::new(PosConsensus::new(base_reward)),
-            ConsensusType::POW => Box::new(PowConsensus::new(
-                pow_difficulty,
-                pow_max_threads,
-                slot_duration,
-                base_reward,
-            )),
-            ConsensusType::MINOTAUR => Box::new(MinotaurConsensus::new(base_reward)),
+        let mut consensus_params = ConsensusParams {
+            slot_duration,
+            pow_difficulty,
+            pow_max_threads,
+            base_reward,
+            relayer_reward_fraction,
+            universal_reward_fraction,
+            pog_warmup_slots,
+            pog_epoch_decay: 1.0,
+            export_contribution,
+            contribution_file_created: false,
         };
-        // Initialize metrics files - delete old file and create new one
-        let metrics_filename = format!("metrics_slots_{}.csv", consensus_name);
-        let _ = std::fs::remove_file(&metrics_filename); // 删除旧文件
-        let metrics_slots_file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&metrics_filename)
-            .ok();
-
+        let consensus = consensus_params.build(consensus_type);
         (
             WorldState {
                 current_slot: Arc::new(RwLock::new(SlotManager {
@@ -104,14 +266,46 @@ impl WorldState {
                 nodes_sender,
                 blockchain: Arc::new(RwLock::new(blockchain)),
                 consensus,
+                consensus_params,
+                consensus_switches: Vec::new(),
+                consensus_state: Arc::new(RwLock::new(String::new())),
+                seed_source: Box::new(RandaoSeedSource),
+                seed_reveal_window: 1.0,
+                slot_started: Instant::now(),
+                late_seeds: 0,
+                randao_committee_size: 0,
+                randao_committee: None,
                 consensus_name,
-                metrics_slots_file,
+                metrics_sinks: Vec::new(),
+                graph: None,
                 slot_duration,
                 slot_per_epoch,
                 nodes_index: HashMap::new(),
                 block_production_success: 0,
                 block_production_failed: 0,
+                offline_nodes: HashSet::new(),
+                offline_slots_saved_estimate: 0.0,
+                offline_stake_decay: 0.0,
+                offline_slots: HashMap::new(),
+                decayed_stake: 0.0,
+                max_stake_share: 1.0,
+                max_validators: 0,
+                waiting_validators: Vec::new(),
+                state_path: None,
                 base_reward,
+                relay_balances: HashMap::new(),
+                expected_proposers: HashMap::new(),
+                record_seeds: false,
+                proposer_mode: ProposerMode::Central,
+                sent_validators: None,
+                inclusion: Arc::new(RwLock::new(InclusionTracker::default())),
+                mempool_reports: Arc::new(RwLock::new(HashMap::new())),
+                epoch_slots: 0,
+                prev_validator_addresses: HashSet::new(),
+                key_registry: None,
+                node_mempools: HashMap::new(),
+                path_export: None,
+                slots_run: watch::channel(0).0,
             },
             sender,
             receiver,
@@ -119,37 +313,83 @@ impl WorldState {
     }
 
     pub async fn next_slot(&mut self) {
+        self.slots_run.send_modify(|n| *n += 1);
+        let expire_before = self
+            .slots_run
+            .borrow()
+            .saturating_sub(INCLUSION_EXPIRY_SLOTS);
+        let expired = self.inclusion.write().await.expire_before(expire_before);
+        if expired > 0 {
+            debug!(
+                "World State: {} transactions not included within {} slots",
+                expired, INCLUSION_EXPIRY_SLOTS
+            );
+        }
         let current_slot = self.current_slot.read().await.clone();
-        let block_index = self.blockchain.read().await.get_last_index();
-        //计算randao seed
+        let old_epoch = current_slot.current_epoch;
+        let (block_index, last_block) = {
+            let blockchain = self.blockchain.read().await;
+            (blockchain.get_last_index(), blockchain.get_last_block())
+        };
+        //计算下一个时隙的种子（默认为randao seed）
         let validators = self.validators.read().await.clone();
-        let next_seed = consensus::combine_seed(validators.clone(), current_slot.randao_seeds);
+        if self.seed_reveal_window < 1.0 {
+            info!(
+                "Epoch[{}] slot[{}] RANDAO seeds on time: {}, late: {}",
+                current_slot.current_epoch,
+                current_slot.current_slot,
+                current_slot.randao_seeds.len(),
+                self.late_seeds
+            );
+        }
+        let contributors = match &self.randao_committee {
+            Some(committee) => committee.clone(),
+            None => validators.clone(),
+        };
+        let next_seed =
+            self.seed_source
+                .next_seed(&contributors, current_slot.randao_seeds, &last_block);
 
         if current_slot.current_slot >= self.slot_per_epoch - 1 {
             //更新epoch
-            self.next_epoch().await;
+            self.next_epoch(next_seed).await;
         } else {
-            self.current_slot = Arc::new(RwLock::new(SlotManager {
+            // 原地更新，持有 current_slot 的其他任务可以读到最新的时隙
+            *self.current_slot.write().await = SlotManager {
                 randao_seeds: vec![],
                 slot_duration: self.slot_duration,
                 current_epoch: current_slot.current_epoch,
                 current_slot: current_slot.current_slot + 1,
                 next_seed,
                 start_timestamp: get_timestamp(),
-            }));
+            };
         }
+        self.slot_started = Instant::now();
+        self.late_seeds = 0;
+        // 出块者选择失败或出块者离线时，这个时隙不会产生区块
+        self.epoch_slots += 1;
+        self.record_offline_slot(&validators);
         self.consensus.next_slot(&validators, block_index);
         let current_slot = self.get_current_slot().await;
+        let consensus_state = self.consensus.state_summary();
         info!(
             "World State change slot to: epoch[{}] slot[{}] consensus[{}] seed{:?}",
-            current_slot.current_epoch,
-            current_slot.current_slot,
-            self.consensus.state_summary(),
-            next_seed
+            current_slot.current_epoch, current_slot.current_slot, consensus_state, next_seed
         );
+        *self.consensus_state.write().await = consensus_state;
 
         let nodes_sender: Vec<Sender<Message>> = self.nodes_sender.values().cloned().collect();
 
+        // 先发送验证者集合，节点收到 UpdateSlot 后可以自行计算出块者
+        let full = current_slot.current_epoch != old_epoch;
+        if let Some(msg) = self.validator_set_msg(&validators, full) {
+            for sender in nodes_sender.iter() {
+                if let Err(e) = sender.send(msg.clone()).await {
+                    error!("World State error: send validator set msg failed {:?}", e);
+                }
+            }
+        }
+
         //通知所有节点更新slot
         for sender in nodes_sender {
             if let Err(e) = sender
@@ -160,22 +400,35 @@ impl WorldState {
             }
         }
 
-        //通知所有的validator可以开始新一轮的发送seed
-        for v in validators.clone() {
+        //通知 RANDAO 委员会可以开始新一轮的发送seed，委员会由本时隙的种子抽取，未设置大小时为所有验证者
+        let committee = sample_randao_committee(&validators, self.randao_committee_size, next_seed);
+        for v in committee.iter() {
             if let Err(e) = self.nodes_sender[&v.address]
-                .send(Message::new_send_randao_seed_msg())
+                .send(Message::new_send_randao_seed_msg(
+                    current_slot.current_epoch,
+                    current_slot.current_slot,
+                ))
                 .await
             {
                 error!("World State error: send new randao seed msg failed {:?}", e);
             }
         }
+        self.randao_committee = (self.randao_committee_size > 0).then_some(committee);
 
-        //获得出块节点
+        //获得出块节点，central 模式下跳过离线的验证者
         let bc = self.blockchain.read().await.clone();
+        let candidates = if self.proposer_mode == ProposerMode::Central {
+            let (online, offline_share) = self.proposer_candidates(&validators);
+            self.offline_slots_saved_estimate += offline_share;
+            online
+        } else {
+            validators.clone()
+        };
+        self.consensus.advance_state(&candidates, &bc);
         let miner_validator =
             match self
                 .consensus
-                .select_proposer(&validators, next_seed.clone(), &bc)
+                .select_proposer(&candidates, next_seed.clone(), &bc)
             {
                 Ok(miner) => miner,
                 Err(e) => {
@@ -183,9 +436,39 @@ impl WorldState {
                     return;
                 }
             };
+        let effective_weights: BTreeMap<String, f64> = self
+            .consensus
+            .effective_weights(&candidates)
+            .into_iter()
+            .collect();
+        debug!(
+            "Epoch[{}] slot[{}] effective weights: {:?}",
+            current_slot.current_epoch, current_slot.current_slot, effective_weights
+        );
 
-        //这里简化成通知miner出块，实际上应该是每个节点自己算
+        // 记录本时隙的出块者，只保留当前和上一个 epoch
+        let epoch = current_slot.current_epoch;
+        self.expected_proposers.insert(
+            (epoch, current_slot.current_slot),
+            miner_validator.address.clone(),
+        );
+        self.expected_proposers.retain(|(e, _), _| e + 1 >= epoch);
+
+        // 先通知所有节点本时隙的出块者，再通知出块者出块，节点据此拒绝其他地址出的块
+        let expected =
+            self.expected_proposer_msg(epoch, current_slot.current_slot, &miner_validator.address);
+        for sender in self.nodes_sender.values() {
+            if let Err(e) = sender
+                .send(Message::new_update_proposer_msg(&expected))
+                .await
+            {
+                error!("World State error: send update proposer msg failed {:?}", e);
+            }
+        }
+
+        //这里简化成通知miner出块，local 模式下由节点自己计算
         match self.nodes_sender.get(&miner_validator.address) {
+            Some(_) if self.proposer_mode == ProposerMode::Local => {}
             Some(sender) => {
                 debug!(
                     "World State find miner: {}",
@@ -202,26 +485,237 @@ impl WorldState {
         }
 
         // Collect slot metrics
-        self.collect_slot_metrics(&miner_validator).await;
+        self.collect_slot_metrics(&miner_validator, next_seed, effective_weights)
+            .await;
     }
 
-    pub async fn next_epoch(&mut self) {
+    /// 参与出块者选择的验证者：排除已报告离线的节点，并返回被排除者的 stake 占比
+    /// 全部离线时退回完整的验证者集合，由出块失败统计体现该时隙的损失
+    /// 节点在收到 UpdateSlot 后才决定上下线，因此状态变化要到下一个时隙才生效
+    pub fn proposer_candidates(&self, validators: &[Validator]) -> (Vec<Validator>, f64) {
+        let (online, offline): (Vec<Validator>, Vec<Validator>) = validators
+            .iter()
+            .cloned()
+            .partition(|v| !self.offline_nodes.contains(&v.address));
+        if online.is_empty() || offline.is_empty() {
+            return (validators.to_vec(), 0.0);
+        }
+        let total_stake: f64 = validators.iter().map(|v| v.stake).sum();
+        let offline_share = if total_stake > 0.0 {
+            offline.iter().map(|v| v.stake).sum::<f64>() / total_stake
+        } else {
+            offline.len() as f64 / validators.len() as f64
+        };
+        debug!(
+            "World State: skipping {} offline validators (stake share {:.4})",
+            offline.len(),
+            offline_share
+        );
+        (online, offline_share)
+    }
+
+    /// 本时隙的出块者，伪造身份与主节点共用通道，都算作合法的出块者
+    fn expected_proposer_msg(&self, epoch: u64, slot: u64, address: &str) -> ExpectedProposer {
+        let mut addresses = vec![address.to_string()];
+        if let Some(expected) = self.nodes_sender.get(address) {
+            let mut shared: Vec<String> = self
+                .nodes_sender
+                .iter()
+                .filter(|(a, s)| a.as_str() != address && s.same_channel(expected))
+                .map(|(a, _)| a.clone())
+                .collect();
+            shared.sort();
+            addresses.extend(shared);
+        }
+        ExpectedProposer {
+            epoch,
+            slot,
+            addresses,
+        }
+    }
+
+    /// 区块的出块者是否为共识在该 (epoch, slot) 选出的验证者
+    /// 伪造身份由主节点代为出块，因此出块者与被选中的身份共用同一个通道时也视为合法
+    pub fn is_legitimate_proposer(&self, block: &Block) -> bool {
+        let expected = match self
+            .expected_proposers
+            .get(&(block.header.epoch, block.header.slot))
+        {
+            Some(address) => address,
+            None => return false,
+        };
+        if *expected == block.header.miner {
+            return true;
+        }
+        match (
+            self.nodes_sender.get(expected),
+            self.nodes_sender.get(&block.header.miner),
+        ) {
+            (Some(a), Some(b)) => a.same_channel(b),
+            _ => false,
+        }
+    }
+
+    /// 区块接受策略：只接受选中的出块者产出、能接在 WorldState 链末端的区块
+    /// 已在链上的区块（出块者和邻居重复发送）直接忽略，不计入出块失败
+    /// 其他接不上的区块计为出块失败，父哈希不一致时向 Node[0] 请求块同步
+    /// 返回 true 表示区块已加入链
+    async fn accept_block(&mut self, block: &Block) -> bool {
+        let duplicate = self
+            .blockchain
+            .read()
+            .await
+            .contains_block(block.header.index, &block.header.hash);
+        if duplicate {
+            debug!(
+                "World State: ignore duplicate block #{}",
+                block.header.index
+            );
+            return false;
+        }
+        if !self.is_legitimate_proposer(block) {
+            error!(
+                "World State: reject block #{} at epoch[{}] slot[{}], miner {} is not the selected proposer",
+                block.header.index,
+                block.header.epoch,
+                block.header.slot,
+                &block.header.miner[..8.min(block.header.miner.len())]
+            );
+            self.block_production_failed += 1;
+            return false;
+        }
+        let add_block_result = self.blockchain.write().await.add_block(block.clone());
+        if let Err(e) = add_block_result {
+            match e {
+                BlockChainError::ParentHashMismatch => {
+                    error!(
+                        "World State: Parent hash mismatch at index {}, there may be a fork",
+                        block.header.index
+                    );
+                    // 出现分叉，显式找到 index==0 的节点请求全链
+                    if let Some((addr, _)) = self.nodes_index.iter().find(|(_, &idx)| idx == 0) {
+                        if let Some(sender) = self.nodes_sender.get(addr) {
+                            warn!(
+                                "World State: Requesting full blockchain from Node[0] due to fork"
+                            );
+                            let _ = sender.try_send(Message::new_request_block_sync_msg(
+                                0,
+                                "world_state".to_string(),
+                            ));
+                        }
+                    }
+                }
+                BlockChainError::IndexTooSmall => {
+                    warn!(
+                        "World State: Received block at index {}, index too small, current index is {}",
+                        block.header.index,
+                        self.blockchain.read().await.get_last_index()
+                    );
+                }
+                _ => {
+                    error!("World State Add Block Error: {}", e);
+                }
+            }
+            self.block_production_failed += 1;
+            return false;
+        }
+
+        // 块添加成功，更新出块成功计数
+        self.block_production_success += 1;
+        self.inclusion.write().await.record_block(block);
+        if let Some(path_export) = &mut self.path_export {
+            path_export.record_block(block, self.consensus.ntd());
+        }
+        self.export_final_paths().await;
+        true
+    }
+
+    /// 导出链上新近最终确定的区块中的交易路径
+    async fn export_final_paths(&mut self) {
+        if let Some(path_export) = &mut self.path_export {
+            let blockchain = self.blockchain.read().await;
+            path_export.finalize(&blockchain, self.consensus.ntd());
+        }
+    }
+
+    pub async fn next_epoch(&mut self, next_seed: [u8; 32]) {
         let current_slot = self.current_slot.read().await.clone();
         let _current_epoch = current_slot.current_epoch;
         //更新epoch中调用consensus的on_epoch_end
         let blocks = self.blockchain.read().await.get_last_epoch_block();
         self.consensus.on_epoch_end(&blocks);
-
+        let removed = self
+            .sweep_exited_keys(current_slot.current_epoch, &blocks)
+            .await;
+        if removed > 0 {
+            info!(
+                "Epoch[{}] removed {} BLS public keys of exited nodes",
+                current_slot.current_epoch, removed
+            );
+        }
+        if self.offline_stake_decay > 0.0 || self.max_stake_share < 1.0 {
+            let validators_lock = self.validators.clone();
+            let mut validators = validators_lock.write().await;
+            let decayed = self.apply_offline_stake_decay(&mut validators);
+            if decayed > 0.0 {
+                info!(
+                    "Epoch[{}] offline stake decay: {:.6} (total {:.6})",
+                    current_slot.current_epoch, decayed, self.decayed_stake
+                );
+            }
+            let redistributed = self.apply_max_stake_share(&mut validators);
+            if redistributed > 0.0 {
+                info!(
+                    "Epoch[{}] max stake share {:.4}: redistributed {:.6}",
+                    current_slot.current_epoch, self.max_stake_share, redistributed
+                );
+            }
+            if decayed > 0.0 || redistributed > 0.0 {
+                // 同步被调整节点的 balance
+                for validator in validators.iter() {
+                    if let Some(sender) = self.nodes_sender.get(&validator.address) {
+                        let msg = Message::new_update_node_balance_msg(validator.stake);
+                        if let Err(e) = sender.send(msg).await {
+                            warn!("Failed to send UpdateNodeBalance: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+        if self.max_validators > 0 {
+            let validators_lock = self.validators.clone();
+            let mut validators = validators_lock.write().await;
+            let admitted = self.apply_max_validators(&mut validators);
+            if !admitted.is_empty() {
+                info!(
+                    "Epoch[{}] max validators {}: {} admitted, {} waiting",
+                    current_slot.current_epoch,
+                    self.max_validators,
+                    admitted.len(),
+                    self.waiting_validators.len()
+                );
+            }
+        }
+        if let Some(path) = &self.state_path {
+            if let Err(e) = self.save_validators(path).await {
+                error!("Save validators to {} failed: {}", path, e);
+            }
+        }
         let validators = self.validators.read().await.clone();
-        let next_seed = consensus::combine_seed(validators.clone(), current_slot.randao_seeds);
-        self.current_slot = Arc::new(RwLock::new(SlotManager {
+        self.collect_epoch_metrics(current_slot.current_epoch, &validators)
+            .await;
+        self.epoch_slots = 0;
+
+        *self.current_slot.write().await = SlotManager {
             randao_seeds: vec![],
             slot_duration: self.slot_duration,
             current_epoch: current_slot.current_epoch + 1,
             current_slot: 0,
             next_seed,
             start_timestamp: get_timestamp(),
-        }));
+        };
+        self.apply_consensus_switch(current_slot.current_epoch + 1)
+            .await;
 
         // 打印每个 epoch 的节点余额信息
         let mut node_stakes: Vec<(u32, f64)> = validators
@@ -241,11 +735,454 @@ impl WorldState {
         }
     }
 
+    pub fn set_offline_stake_decay(&mut self, offline_stake_decay: f64) {
+        self.offline_stake_decay = offline_stake_decay;
+    }
+
+    /// POG 每个 epoch 结束时历史贡献乘以的系数，之后切换到 POG 时同样使用
+    pub fn set_pog_epoch_decay(&mut self, pog_epoch_decay: f64) {
+        self.consensus_params.pog_epoch_decay = pog_epoch_decay;
+        self.consensus.set_epoch_decay(pog_epoch_decay);
+    }
+
+    /// 当前时隙处于离线状态的验证者各记一个离线时隙
+    /// 离线的验证者在 central 模式下不会被选为出块者，因此不能只按出块失败统计
+    fn record_offline_slot(&mut self, validators: &[Validator]) {
+        for validator in validators {
+            if self.offline_nodes.contains(&validator.address) {
+                *self
+                    .offline_slots
+                    .entry(validator.address.clone())
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// 本 epoch 有离线时隙的验证者 stake 按 offline_stake_decay 比例扣减，返回本次扣减总量
+    /// 每个 epoch 至多扣减一次，与离线的时隙数无关
+    pub fn apply_offline_stake_decay(&mut self, validators: &mut [Validator]) -> f64 {
+        let mut decayed = 0.0;
+        for validator in validators.iter_mut() {
+            if self
+                .offline_slots
+                .get(&validator.address)
+                .copied()
+                .unwrap_or(0)
+                > 0
+            {
+                let loss = validator.stake * self.offline_stake_decay;
+                validator.stake -= loss;
+                decayed += loss;
+            }
+        }
+        self.offline_slots.clear();
+        self.decayed_stake += decayed;
+        decayed
+    }
+
+    pub fn set_max_stake_share(&mut self, max_stake_share: f64) {
+        self.max_stake_share = max_stake_share;
+    }
+
+    pub fn set_max_validators(&mut self, max_validators: usize) {
+        self.max_validators = max_validators;
+    }
+
+    /// 验证者与等待队列一起按 stake 从高到低排名，前 max_validators 个成为验证者，其余进入等待队列
+    /// stake 相同时按地址排序，返回新进入验证者集合的地址
+    pub fn apply_max_validators(&mut self, validators: &mut Vec<Validator>) -> Vec<String> {
+        if self.max_validators == 0 {
+            return vec![];
+        }
+        let active: HashSet<String> = validators.iter().map(|v| v.address.clone()).collect();
+        let mut ranked: Vec<Validator> = validators
+            .drain(..)
+            .chain(self.waiting_validators.drain(..))
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.stake
+                .partial_cmp(&a.stake)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.address.cmp(&b.address))
+        });
+        self.waiting_validators = ranked.split_off(self.max_validators.min(ranked.len()));
+        *validators = ranked;
+        validators
+            .iter()
+            .filter(|v| !active.contains(&v.address))
+            .map(|v| v.address.clone())
+            .collect()
+    }
+
+    /// 把超过 max_stake_share 的 stake 按比例分给未达到上限的验证者，总 stake 不变，返回重新分配的总量
+    /// 上限低于 1/验证者数时无法满足，所有验证者的 stake 均分
+    pub fn apply_max_stake_share(&self, validators: &mut [Validator]) -> f64 {
+        let total: f64 = validators.iter().map(|v| v.stake).sum();
+        if self.max_stake_share >= 1.0 || validators.is_empty() || total <= 0.0 {
+            return 0.0;
+        }
+        if self.max_stake_share * validators.len() as f64 <= 1.0 {
+            let equal = total / validators.len() as f64;
+            let mut redistributed = 0.0;
+            for validator in validators.iter_mut() {
+                redistributed += (validator.stake - equal).max(0.0);
+                validator.stake = equal;
+            }
+            return redistributed;
+        }
+
+        let cap = total * self.max_stake_share;
+        let mut capped = vec![false; validators.len()];
+        let mut redistributed = 0.0;
+        loop {
+            let mut excess = 0.0;
+            for (i, validator) in validators.iter_mut().enumerate() {
+                if validator.stake > cap {
+                    excess += validator.stake - cap;
+                    validator.stake = cap;
+                    capped[i] = true;
+                }
+            }
+            if excess <= f64::EPSILON * total {
+                break;
+            }
+            redistributed += excess;
+            // 分给未达到上限的验证者，可能使其超过上限，继续下一轮
+            let receivers: Vec<usize> = (0..validators.len()).filter(|i| !capped[*i]).collect();
+            if receivers.is_empty() {
+                break;
+            }
+            let weight: f64 = receivers.iter().map(|i| validators[*i].stake).sum();
+            for i in receivers.iter() {
+                validators[*i].stake += if weight > 0.0 {
+                    excess * validators[*i].stake / weight
+                } else {
+                    excess / receivers.len() as f64
+                };
+            }
+        }
+        redistributed
+    }
+
+    pub fn set_seed_source(&mut self, seed_source: Box<dyn SeedSource>) {
+        self.seed_source = seed_source;
+    }
+
+    pub fn set_seed_reveal_window(&mut self, seed_reveal_window: f64) {
+        self.seed_reveal_window = seed_reveal_window;
+    }
+
+    pub fn set_randao_committee_size(&mut self, randao_committee_size: usize) {
+        self.randao_committee_size = randao_committee_size;
+    }
+
+    /// 从恢复的节点链继续模拟：使用该链，并从最新区块的下一个 epoch 开始计时
+    pub async fn resume_chain(&mut self, blockchain: Blockchain) {
+        let next_epoch = blockchain.get_last_block().header.epoch + 1;
+        *self.blockchain.write().await = blockchain;
+        let mut current_slot = self.current_slot.write().await;
+        current_slot.current_epoch = next_epoch;
+        current_slot.current_slot = 0;
+    }
+
+    /// 每个 epoch 结束时把验证者 stake 表保存到 path，与节点状态一起用于中断后恢复
+    pub fn set_state_path(&mut self, path: String) {
+        self.state_path = Some(path);
+    }
+
+    /// 保存验证者和等待队列的 stake，先写临时文件再重命名
+    pub async fn save_validators(&self, path: &str) -> std::io::Result<()> {
+        let table = ValidatorTable {
+            validators: self.validators.read().await.clone(),
+            waiting_validators: self.waiting_validators.clone(),
+        };
+        let tmp_path = format!("{}.tmp", path);
+        tokio::fs::write(&tmp_path, serde_json::to_vec(&table)?).await?;
+        tokio::fs::rename(&tmp_path, path).await
+    }
+
+    /// 从 save_validators 保存的文件恢复验证者 stake 表，返回各地址恢复的 stake
+    pub async fn load_validators(&mut self, path: &str) -> std::io::Result<HashMap<String, f64>> {
+        let table: ValidatorTable = serde_json::from_slice(&tokio::fs::read(path).await?)?;
+        let stakes = table
+            .validators
+            .iter()
+            .chain(table.waiting_validators.iter())
+            .map(|v| (v.address.clone(), v.stake))
+            .collect();
+        *self.validators.write().await = table.validators;
+        self.waiting_validators = table.waiting_validators;
+        Ok(stakes)
+    }
+
+    /// now 时收到的 RANDAO 种子是否已超过本时隙的截止时间
+    pub fn seed_deadline_passed(&self, now: Instant) -> bool {
+        self.seed_reveal_window < 1.0
+            && now.saturating_duration_since(self.slot_started)
+                > self.slot_duration.mul_f64(self.seed_reveal_window)
+    }
+
+    /// now 时收到的 RANDAO 种子计入当前时隙，不是当前时隙的种子或超过截止时间的种子被忽略并计为迟到
+    pub async fn accept_randao_seed(&mut self, randao_seed: RandaoSeed, now: Instant) -> bool {
+        let mut current_slot = self.current_slot.write().await;
+        if (randao_seed.epoch, randao_seed.slot)
+            != (current_slot.current_epoch, current_slot.current_slot)
+        {
+            debug!(
+                "World State ignored RANDAO seed from {} for epoch[{}] slot[{}]",
+                randao_seed.address, randao_seed.epoch, randao_seed.slot
+            );
+            self.late_seeds += 1;
+            return false;
+        }
+        if self.seed_deadline_passed(now) {
+            debug!(
+                "World State ignored late RANDAO seed from {}",
+                randao_seed.address
+            );
+            self.late_seeds += 1;
+            return false;
+        }
+        current_slot.randao_seeds.push(randao_seed);
+        true
+    }
+
+    /// 添加指标输出目标，每条时隙和 epoch 指标都会交给所有已添加的 sink
+    pub fn add_metrics_sink(&mut self, sink: Box<dyn MetricsSink>) {
+        self.metrics_sinks.push(sink);
+    }
+
+    pub fn set_record_seeds(&mut self, record_seeds: bool) {
+        self.record_seeds = record_seeds;
+    }
+
+    /// 按给定的出块者地址序列出块，序列用完后由当前的共识选择
+    pub fn set_proposer_schedule(&mut self, schedule: Vec<String>) {
+        let inner = std::mem::replace(
+            &mut self.consensus,
+            Box::new(PosConsensus::new(self.base_reward)),
+        );
+        self.consensus = Box::new(ScriptedConsensus::new(schedule, inner));
+    }
+
+    /// 设置共识切换，同一 epoch 有多个切换时使用最后一个
+    pub fn set_consensus_switches(&mut self, mut switches: Vec<ConsensusSwitch>) {
+        switches.sort_by_key(|s| s.epoch);
+        self.consensus_switches = switches;
+    }
+
+    /// 新 epoch 开始时按配置替换共识；验证者集合和 stake 保存在 WorldState 中，切换后保持不变
+    /// 按出块者序列出块时保留序列，只替换序列用完后使用的共识
+    /// 节点在本 epoch 的 UpdateSlot 之前收到新的共识类型，按新的共识处理交易路径
+    async fn apply_consensus_switch(&mut self, epoch: u64) {
+        let Some(switch) = self
+            .consensus_switches
+            .iter()
+            .rev()
+            .find(|s| s.epoch == epoch)
+            .copied()
+        else {
+            return;
+        };
+        let old = std::mem::replace(
+            &mut self.consensus,
+            Box::new(PosConsensus::new(self.base_reward)),
+        );
+        let old_name = old.name();
+        self.consensus = old.switch_to(self.consensus_params.build(switch.consensus));
+        info!(
+            "Epoch[{}] switch consensus from {} to {}",
+            epoch, old_name, switch.consensus
+        );
+        for sender in self.nodes_sender.values() {
+            if let Err(e) = sender
+                .send(Message::new_switch_consensus_msg(switch.consensus))
+                .await
+            {
+                error!(
+                    "World State error: send switch consensus msg failed {:?}",
+                    e
+                );
+            }
+        }
+    }
+
+    /// 订阅已经结束的时隙数
+    pub fn slots_run(&self) -> watch::Receiver<u64> {
+        self.slots_run.subscribe()
+    }
+
+    pub fn set_path_export(&mut self, path_export: PathExport) {
+        self.path_export = Some(path_export);
+    }
+
+    /// 每个 epoch 开始时发送完整的验证者集合，其余时隙只发送增量，集合不变时不发送
+    fn validator_set_msg(&mut self, validators: &[Validator], full: bool) -> Option<Message> {
+        let diff = match &self.sent_validators {
+            Some(sent) if !full => ValidatorSetDiff::between(sent, validators),
+            _ => None,
+        };
+        let msg = match diff {
+            Some(diff) if diff.is_empty() => return None,
+            Some(diff) => Message::new_update_validator_set_diff_msg(&diff),
+            None => Message::new_update_validator_set_msg(validators),
+        };
+        self.sent_validators = Some(validators.to_vec());
+        Some(msg)
+    }
+
+    pub fn set_proposer_mode(&mut self, proposer_mode: ProposerMode) {
+        self.proposer_mode = proposer_mode;
+    }
+
+    pub fn set_graph(&mut self, graph: Graph<String, ()>) {
+        self.graph = Some(graph);
+    }
+
+    /// 设置公钥注册表后，下线的节点的公钥在之后的 epoch 结束时被清理，重新上线的节点自己重新注册
+    /// node_mempools 为节点地址 -> 交易缓存
+    pub fn set_key_registry(
+        &mut self,
+        key_registry: KeyRegistry,
+        node_mempools: HashMap<String, Arc<RwLock<HashMap<String, TransactionPaths>>>>,
+    ) {
+        self.key_registry = Some(key_registry);
+        self.node_mempools = node_mempools;
+    }
+
+    /// 清理在本 epoch 之前下线的节点的公钥，返回清理的数量
+    /// 节点交易缓存中的路径和最近一个 epoch 的区块路径还未必被验证过，其中出现的地址暂不清理
+    /// 离线节点的交易缓存在重新上线后仍会被打包或转发，同样计入
+    async fn sweep_exited_keys(&self, epoch: u64, recent_blocks: &[Block]) -> usize {
+        let registry = match &self.key_registry {
+            Some(registry) if registry.retired_len() > 0 => registry,
+            _ => return 0,
+        };
+        let mut referenced: HashSet<String> = recent_blocks
+            .iter()
+            .flat_map(|block| block.body.paths.iter())
+            .flat_map(|paths| paths.paths.iter().cloned())
+            .collect();
+        for mempool in self.node_mempools.values() {
+            for transaction_paths in mempool.read().await.values() {
+                referenced.insert(transaction_paths.transaction.from.clone());
+                referenced.extend(transaction_paths.paths.iter().map(|p| p.to.clone()));
+            }
+        }
+        registry.sweep_retired(epoch, &referenced)
+    }
+
+    async fn collect_epoch_metrics(&mut self, epoch: u64, validators: &[Validator]) {
+        let contribution = self.consensus.contribution();
+        let degree_contribution_correlation = match &self.graph {
+            Some(graph) if !contribution.is_empty() => {
+                metrics::degree_contribution_correlation(graph, &contribution)
+            }
+            _ => 0.0,
+        };
+        let contribution_values: Vec<f64> = contribution.values().cloned().collect();
+        let contribution_cv = metrics::coefficient_of_variation(&contribution_values);
+        let hash_power_values: Vec<f64> = validators.iter().map(|v| v.hash_power).collect();
+        let hash_power_gini = metrics::calculate_gini(&hash_power_values);
+        let (tx_generated, tx_included, inclusion_rate) = {
+            let mut inclusion = self.inclusion.write().await;
+            let (generated, included) = inclusion.end_epoch();
+            (generated, included, inclusion.inclusion_rate())
+        };
+        let validator_addresses: HashSet<String> =
+            validators.iter().map(|v| v.address.clone()).collect();
+        let (validators_added, validators_removed) =
+            metrics::validator_set_churn(&self.prev_validator_addresses, &validator_addresses);
+        self.prev_validator_addresses = validator_addresses;
+        let (produced, paths, tx_count, proposer_entropy) = {
+            let blockchain = self.blockchain.read().await;
+            let blocks: Vec<&Block> = blockchain
+                .blocks()
+                .iter()
+                .filter(|b| b.header.index > 0 && b.header.epoch == epoch)
+                .collect();
+            let paths: Vec<Vec<String>> = blocks.iter().flat_map(|b| b.get_all_paths()).collect();
+            let tx_count: usize = blocks.iter().map(|b| b.body.transactions.len()).sum();
+            let mut proposers: HashMap<&str, f64> = HashMap::new();
+            for block in blocks.iter() {
+                *proposers.entry(&block.header.miner).or_insert(0.0) += 1.0;
+            }
+            let counts: Vec<f64> = proposers.values().cloned().collect();
+            (
+                blocks.len() as u64,
+                paths,
+                tx_count,
+                metrics::shannon_entropy(&counts),
+            )
+        };
+        let empty_slots = self.epoch_slots.saturating_sub(produced);
+        let epoch_secs = self.epoch_slots as f64 * self.slot_duration.as_secs_f64();
+        let throughput = if epoch_secs > 0.0 {
+            tx_count as f64 / epoch_secs
+        } else {
+            0.0
+        };
+        let stake_values: Vec<f64> = validators.iter().map(|v| v.stake).collect();
+        let path_length_histogram = metrics::path_length_histogram(&paths);
+        info!(
+            "Epoch[{}] degree-contribution correlation: {:.6}, contribution CV: {:.6}, hash power Gini: {:.6}",
+            epoch, degree_contribution_correlation, contribution_cv, hash_power_gini
+        );
+        info!(
+            "Epoch[{}] transactions generated: {}, included: {}, inclusion rate: {:.4}",
+            epoch, tx_generated, tx_included, inclusion_rate
+        );
+        info!(
+            "Epoch[{}] empty slots: {} of {}",
+            epoch, empty_slots, self.epoch_slots
+        );
+        info!(
+            "Epoch[{}] validators: {} (+{}, -{})",
+            epoch,
+            validators.len(),
+            validators_added,
+            validators_removed
+        );
+
+        let epoch_metrics = EpochMetrics {
+            epoch,
+            timestamp: tools::get_timestamp(),
+            consensus_type: self.consensus.name().to_string(),
+            consensus_state: self.consensus.state_summary(),
+            degree_contribution_correlation,
+            contribution_cv,
+            hash_power_gini,
+            decayed_stake: self.decayed_stake,
+            tx_generated,
+            tx_included,
+            inclusion_rate,
+            validator_count: validators.len(),
+            validators_added,
+            validators_removed,
+            empty_slots,
+            throughput,
+            gini_coefficient: metrics::calculate_gini(&stake_values),
+            stake_concentration: calculate_stake_concentration(&stake_values),
+            proposer_entropy,
+            path_length_histogram,
+        };
+
+        for sink in self.metrics_sinks.iter_mut() {
+            sink.record_epoch(&epoch_metrics);
+        }
+    }
+
     pub async fn get_current_slot(&self) -> SlotManager {
         self.current_slot.read().await.clone()
     }
 
-    async fn collect_slot_metrics(&mut self, miner: &Validator) {
+    /// effective_weights 为选择本时隙出块者时各验证者的权重
+    async fn collect_slot_metrics(
+        &mut self,
+        miner: &Validator,
+        seed: [u8; 32],
+        effective_weights: BTreeMap<String, f64>,
+    ) {
         let current_slot = self.current_slot.read().await.clone();
         let validators = self.validators.read().await.clone();
         let blockchain = self.blockchain.read().await.clone();
@@ -256,7 +1193,7 @@ impl WorldState {
 
         // Calculate throughput (tx/s) - based on time between current and previous block
         let throughput = {
-            let blocks = &blockchain.blocks;
+            let blocks = blockchain.blocks();
             if blocks.len() > 1 {
                 let prev_block_timestamp = blocks[blocks.len() - 2].header.timestamp;
                 let time_delta = last_block
@@ -273,9 +1210,7 @@ impl WorldState {
             }
         };
 
-        let paths = last_block.body.paths;
-        let paths: Vec<Vec<String>> = paths.iter().map(|p| p.paths.clone()).collect();
-        let path_stats = metrics::calculate_path_stats(paths);
+        let path_stats = metrics::calculate_path_stats(&last_block);
 
         // Calculate stake concentration from stakes
         let stake_values: Vec<f64> = validators.iter().map(|v| v.stake).collect();
@@ -313,27 +1248,13 @@ impl WorldState {
             tx_packing_delay_stats,
             block_production_success: self.block_production_success,
             block_production_failed: self.block_production_failed,
+            offline_slots_saved_estimate: self.offline_slots_saved_estimate,
+            seed: self.record_seeds.then(|| hex::encode(seed)),
+            effective_weights,
         };
 
-        // Write to CSV
-        if self.metrics_slots_file.is_none() {
-            if let Ok(file) = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(format!("metrics_slots_{}.csv", self.consensus_name))
-            {
-                self.metrics_slots_file = Some(file);
-            }
-        }
-
-        if let Some(ref mut file) = self.metrics_slots_file {
-            // Write header if file is empty
-            if file.metadata().map(|m| m.len()).unwrap_or(0) == 0 {
-                let _ = writeln!(file, "{}", SlotMetrics::to_csv_header());
-            }
-
-            let _ = writeln!(file, "{}", slot_metrics.to_csv_row());
-            let _ = file.flush();
+        for sink in self.metrics_sinks.iter_mut() {
+            sink.record_slot(&slot_metrics);
         }
     }
 
@@ -345,6 +1266,7 @@ impl WorldState {
             let shared_self = Arc::clone(&shared_self);
             task::spawn(async move {
                 while let Some(msg) = receiver.recv().await {
+                    events::record(|| events::WORLD_TARGET.to_string(), &msg);
                     debug!("World State received msg type: {}", msg.msg_type);
                     match msg.msg_type {
                         MessageType::ReceiveRandaoSeed => {
@@ -355,11 +1277,11 @@ impl WorldState {
                                     continue;
                                 }
                             };
-                            {
-                                let shared_self = shared_self.write().await;
-                                let mut current_slot = shared_self.current_slot.write().await;
-                                current_slot.randao_seeds.push(randao_seed.clone());
-                            }
+                            shared_self
+                                .write()
+                                .await
+                                .accept_randao_seed(randao_seed, Instant::now())
+                                .await;
                         }
                         MessageType::ReceiveBecomeValidator => {
                             let validator = match Validator::from_json(msg.data) {
@@ -370,10 +1292,16 @@ impl WorldState {
                                 }
                             };
                             {
-                                let shared_self = shared_self.write().await;
-                                let mut validators = shared_self.validators.write().await;
+                                let mut shared_self = shared_self.write().await;
+                                let validators_lock = shared_self.validators.clone();
+                                let mut validators = validators_lock.write().await;
                                 validators.retain(|v| v.address != validator.address);
+                                shared_self
+                                    .waiting_validators
+                                    .retain(|v| v.address != validator.address);
                                 validators.push(validator.clone());
+                                // 超出上限时 stake 最低的注册者进入等待队列
+                                shared_self.apply_max_validators(&mut validators);
                             }
                         }
                         MessageType::UpdateValidatorStake => {
@@ -386,11 +1314,14 @@ impl WorldState {
                                         payload.get("address").and_then(|v| v.as_str()),
                                         payload.get("stake").and_then(|v| v.as_f64()),
                                     ) {
-                                        let shared_self = shared_self.write().await;
-                                        let mut validators = shared_self.validators.write().await;
-                                        // 更新对应 Validator 的 stake
-                                        if let Some(validator) =
-                                            validators.iter_mut().find(|v| v.address == address)
+                                        let mut shared_self = shared_self.write().await;
+                                        let validators_lock = shared_self.validators.clone();
+                                        let mut validators = validators_lock.write().await;
+                                        // 更新对应 Validator 的 stake，等待队列中的注册者在 epoch 结束时重新排名
+                                        if let Some(validator) = validators
+                                            .iter_mut()
+                                            .chain(shared_self.waiting_validators.iter_mut())
+                                            .find(|v| v.address == address)
                                         {
                                             validator.stake = new_stake;
                                         }
@@ -398,6 +1329,33 @@ impl WorldState {
                                 }
                             }
                         }
+                        MessageType::NodeOnlineStatus => {
+                            if let Ok(payload) =
+                                serde_json::from_slice::<serde_json::Value>(&msg.data)
+                            {
+                                if let (Some(address), Some(online)) = (
+                                    payload.get("address").and_then(|v| v.as_str()),
+                                    payload.get("online").and_then(|v| v.as_bool()),
+                                ) {
+                                    let mut shared_self = shared_self.write().await;
+                                    if online {
+                                        shared_self.offline_nodes.remove(address);
+                                    } else {
+                                        shared_self.offline_nodes.insert(address.to_string());
+                                        if let Some(registry) = &shared_self.key_registry {
+                                            let epoch =
+                                                shared_self.current_slot.read().await.current_epoch;
+                                            registry.retire(address, epoch);
+                                        }
+                                    }
+                                    debug!(
+                                        "World State: node {} is {}",
+                                        address,
+                                        if online { "online" } else { "offline" }
+                                    );
+                                }
+                            }
+                        }
                         MessageType::SendBlock => {
                             let block = match Block::from_json(msg.data) {
                                 Ok(b) => b,
@@ -409,61 +1367,12 @@ impl WorldState {
 
                             {
                                 let mut shared_self = shared_self.write().await;
-                                let add_block_result = {
-                                    shared_self
-                                        .blockchain
-                                        .write()
-                                        .await
-                                        .add_block(block.clone())
-                                };
-
-                                if let Err(e) = add_block_result {
-                                    match e {
-                                        BlockChainError::ParentHashMismatch => {
-                                            error!(
-                                                "World State: Parent hash mismatch at index {}, there may be a fork",
-                                                block.header.index
-                                            );
-                                            // 出现分叉，显式找到 index==0 的节点请求全链
-                                            if let Some((addr, _)) = shared_self
-                                                .nodes_index
-                                                .iter()
-                                                .find(|(_, &idx)| idx == 0)
-                                            {
-                                                if let Some(sender) =
-                                                    shared_self.nodes_sender.get(addr)
-                                                {
-                                                    warn!(
-                                                        "World State: Requesting full blockchain from Node[0] due to fork"
-                                                    );
-                                                    let _ = sender.try_send(
-                                                        Message::new_request_block_sync_msg(
-                                                            0,
-                                                            "world_state".to_string(),
-                                                        ),
-                                                    );
-                                                }
-                                            }
-                                        }
-                                        BlockChainError::IndexTooSmall => {
-                                            warn!(
-                                                "World State: Received block at index {}, index too small, current index is {}",
-                                                block.header.index, shared_self.blockchain.read().await.get_last_index()
-                                            );
-                                        }
-                                        _ => {
-                                            error!("World State Add Block Error: {}", e);
-                                        }
-                                    }
-                                    shared_self.block_production_failed += 1;
+                                if !shared_self.accept_block(&block).await {
                                     continue;
                                 }
 
-                                // 块添加成功，更新出块成功计数
-                                shared_self.block_production_success += 1;
-
                                 // 块添加成功后，立即分配奖励
-                                {
+                                let relay_rewards = {
                                     let mut validators = shared_self.validators.write().await;
 
                                     // 创建一个可变的向量切片来修改
@@ -492,10 +1401,63 @@ impl WorldState {
                                             }
                                         }
                                     }
+                                    shared_self
+                                        .consensus
+                                        .distribute_network_fees(&block, &validators)
+                                };
+
+                                // 中继节点不在验证者列表中，单独累计并同步其 balance
+                                for (address, reward) in relay_rewards {
+                                    let balance = {
+                                        let entry = shared_self
+                                            .relay_balances
+                                            .entry(address.clone())
+                                            .or_insert(0.0);
+                                        *entry += reward;
+                                        *entry
+                                    };
+                                    if let Some(sender) = shared_self.nodes_sender.get(&address) {
+                                        let msg = Message::new_update_node_balance_msg(balance);
+                                        if let Err(e) = sender.send(msg).await {
+                                            warn!(
+                                                "Failed to send UpdateNodeBalance to {}: {}",
+                                                &address[..8.min(address.len())],
+                                                e
+                                            );
+                                        }
+                                    }
                                 }
                             }
                             debug!("World State add block successfully");
                         }
+                        MessageType::TransactionGenerated => {
+                            if let Ok(tx_hash) = String::from_utf8(msg.data) {
+                                let shared_self = shared_self.read().await;
+                                shared_self
+                                    .inclusion
+                                    .write()
+                                    .await
+                                    .record_generated(tx_hash, *shared_self.slots_run.borrow());
+                            }
+                        }
+                        MessageType::ResponseMempoolHealth => {
+                            match serde_json::from_slice::<MempoolReport>(&msg.data) {
+                                Ok(report) => {
+                                    let shared_self = shared_self.read().await;
+                                    shared_self
+                                        .mempool_reports
+                                        .write()
+                                        .await
+                                        .insert(msg.from.clone(), report);
+                                }
+                                Err(e) => {
+                                    warn!(
+                                        "World State: invalid mempool health from {}: {}",
+                                        msg.from, e
+                                    );
+                                }
+                            }
+                        }
                         MessageType::BlockProductionFailed => {
                             // 处理出块失败事件
                             if let Ok(json_str) = String::from_utf8(msg.data.clone()) {
@@ -536,17 +1498,19 @@ impl WorldState {
                                 continue;
                             }
                             // 从第一个区块开始对比，找到分叉点后替换本地区块链
-                            let shared_self = shared_self.write().await;
-                            let mut local_chain = shared_self.blockchain.write().await;
+                            let mut shared_self = shared_self.write().await;
+                            let blockchain = shared_self.blockchain.clone();
+                            let mut local_chain = blockchain.write().await;
 
-                            let local_len = local_chain.blocks.len();
+                            let local_len = local_chain.len();
                             let sync_len = sync_blocks.len();
                             let min_len = local_len.min(sync_len);
 
                             // 寻找第一个不同的块
                             let mut divergence_idx = None;
                             for i in 0..min_len {
-                                if local_chain.blocks[i].header.hash != sync_blocks[i].header.hash {
+                                if local_chain.blocks()[i].header.hash != sync_blocks[i].header.hash
+                                {
                                     divergence_idx = Some(i);
                                     break;
                                 }
@@ -555,10 +1519,15 @@ impl WorldState {
                             match divergence_idx {
                                 Some(idx) => {
                                     // 截断本地链到分叉点，然后用同步链替换后续部分
-                                    local_chain.blocks.truncate(idx);
-                                    local_chain
-                                        .blocks
-                                        .extend(sync_blocks[idx..].iter().cloned());
+                                    let mut inclusion = shared_self.inclusion.write().await;
+                                    for block in local_chain.blocks()[idx..].iter() {
+                                        inclusion.revert_block(block);
+                                    }
+                                    local_chain.truncate_to(idx as u64);
+                                    local_chain.extend_unchecked(&sync_blocks[idx..]);
+                                    for block in sync_blocks[idx..].iter() {
+                                        inclusion.record_block(block);
+                                    }
                                     info!(
                                         "World State: chain diverged at #{}, replaced from peer (local_len={} -> sync_len={})",
                                         idx,
@@ -569,9 +1538,11 @@ impl WorldState {
                                 None => {
                                     if sync_len > local_len {
                                         // 本地是前缀，直接追加缺失部分
-                                        local_chain
-                                            .blocks
-                                            .extend(sync_blocks[local_len..].iter().cloned());
+                                        local_chain.extend_unchecked(&sync_blocks[local_len..]);
+                                        let mut inclusion = shared_self.inclusion.write().await;
+                                        for block in sync_blocks[local_len..].iter() {
+                                            inclusion.record_block(block);
+                                        }
                                         info!(
                                             "World State: appended {} blocks (local_len={} -> sync_len={})",
                                             sync_len - local_len,
@@ -592,6 +1563,8 @@ impl WorldState {
                                     }
                                 }
                             }
+                            drop(local_chain);
+                            shared_self.export_final_paths().await;
                         }
                         _ => {}
                     }
@@ -665,7 +1638,22 @@ impl WorldState {
             }
         });
 
-        let _ = tokio::join!(timer_task, receiver_task);
+        // run 被取消时一并结束内部任务
+        let mut tasks = AbortOnDrop(vec![timer_task, receiver_task]);
+        for task in tasks.0.iter_mut() {
+            let _ = task.await;
+        }
+    }
+}
+
+/// 被丢弃时终止持有的任务
+struct AbortOnDrop(Vec<task::JoinHandle<()>>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        for task in self.0.iter() {
+            task.abort();
+        }
     }
 }
 
@@ -703,10 +1691,14 @@ impl From<serde_json::error::Error> for WorldStateError {
 mod tests {
     use super::*;
     use crate::blockchain::block::Block;
+    use crate::blockchain::block::Body;
     use crate::blockchain::path::TransactionPaths;
     use crate::blockchain::transaction::Transaction;
     use crate::blockchain::Blockchain;
-    use crate::network::node::{Neighbor, Node};
+    use crate::metrics::NullSink;
+    use crate::network::node::Neighbor;
+    use crate::network::test_fixtures::{test_node, test_world};
+    use crate::wallet::Wallet;
     use log::info;
 
     #[tokio::test]
@@ -716,17 +1708,7 @@ mod tests {
             .is_test(true)
             .try_init();
 
-        let blockchain = Blockchain::new(Block::gen_genesis_block());
-        let (world, _world_sender, world_receiver) = WorldState::new(
-            blockchain.get_last_block().clone(),
-            ConsensusType::POS,
-            Blockchain::new(Block::gen_genesis_block()),
-            5,
-            5,
-            20,
-            8,
-            0.0,
-        );
+        let (world, _world_sender, world_receiver) = test_world();
         tokio::spawn(async move {
             world.run(world_receiver).await;
         });
@@ -741,39 +1723,12 @@ mod tests {
             .try_init();
 
         let blockchain = Blockchain::new(Block::gen_genesis_block());
-        let (mut world, world_sender, world_receiver) = WorldState::new(
-            blockchain.get_last_block().clone(),
-            ConsensusType::POS,
-            Blockchain::new(Block::gen_genesis_block()),
-            5,
-            5,
-            20,
-            8,
-            0.0,
-        );
+        let (mut world, world_sender, world_receiver) = test_world();
 
         let validators = world.validators.clone();
         let current_slot = world.current_slot.clone();
-        let mut node0 = Node::new(
-            0,
-            0,
-            0,
-            blockchain.clone(),
-            world_sender.clone(),
-            1000,
-            ConsensusType::POG,
-            0,
-        );
-        let mut node1 = Node::new(
-            1,
-            0,
-            0,
-            blockchain,
-            world_sender.clone(),
-            1000,
-            ConsensusType::POG,
-            0,
-        );
+        let mut node0 = test_node(0, blockchain.clone(), world_sender.clone());
+        let mut node1 = test_node(1, blockchain, world_sender.clone());
         let node0_sender = node0.sender.clone();
         let node1_sender = node1.sender.clone();
         let node0_wallet = node0.wallet.clone();
@@ -834,11 +1789,11 @@ mod tests {
 
         //send seed
         node0_sender
-            .send(Message::new_send_randao_seed_msg())
+            .send(Message::new_send_randao_seed_msg(0, 0))
             .await
             .unwrap();
         node1_sender
-            .send(Message::new_send_randao_seed_msg())
+            .send(Message::new_send_randao_seed_msg(0, 0))
             .await
             .unwrap();
         tokio::time::sleep(Duration::from_secs(1)).await;
@@ -890,6 +1845,596 @@ mod tests {
         }
     }
 
+    /// 节点按收到的完整集合和增量维护的验证者集合与 WorldState 一致
+    #[tokio::test]
+    async fn test_node_validator_set_matches_world() {
+        let (mut world, world_sender, _world_receiver) = test_world();
+        let mut node = test_node(0, Blockchain::new(Block::gen_genesis_block()), world_sender);
+        node.consensus = ConsensusType::POS;
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(64);
+        for address in [
+            node.get_address(),
+            "a".into(),
+            "b".into(),
+            "c".into(),
+            "d".into(),
+        ] {
+            world.nodes_sender.insert(address, sender.clone());
+        }
+
+        let v = |address: &str, stake: f64| Validator::new(address.to_string(), stake, 1.0);
+        let (mut full, mut diffs) = (0, 0);
+        for step in 0..6 {
+            {
+                let mut validators = world.validators.write().await;
+                match step {
+                    0 => validators.extend([v("a", 1.0), v("b", 2.0), v("c", 3.0)]),
+                    2 => validators[1].stake = 2.5,
+                    3 => validators.retain(|x| x.address != "a"),
+                    // 重新注册的验证者移到末尾，只能发送完整集合
+                    4 => {
+                        let b = validators.remove(0);
+                        validators.push(b);
+                    }
+                    5 => validators.push(v("d", 4.0)),
+                    _ => {}
+                }
+            }
+            world.next_slot().await;
+            while let Ok(msg) = receiver.try_recv() {
+                match msg.msg_type {
+                    MessageType::UpdateValidatorSet => full += 1,
+                    MessageType::UpdateValidatorSetDiff => diffs += 1,
+                    _ => {}
+                }
+                node.update_validator_set(&msg);
+            }
+            assert_eq!(node.validators, *world.validators.read().await);
+        }
+        // 第一次和重新排序后发送完整集合，集合不变的时隙不发送
+        // 5 个地址共用一个通道，每条消息收到 5 次，重复应用增量结果不变
+        assert_eq!((full, diffs), (2 * 5, 3 * 5));
+    }
+
+    #[test]
+    fn test_proposer_candidates_skip_offline() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        let validators: Vec<Validator> = [1.0, 3.0, 6.0]
+            .iter()
+            .map(|stake| Validator::new(Wallet::new().address, *stake, 1.0))
+            .collect();
+
+        // 全部在线时不排除任何验证者
+        let (candidates, offline_share) = world.proposer_candidates(&validators);
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(offline_share, 0.0);
+
+        world.offline_nodes.insert(validators[1].address.clone());
+        let (candidates, offline_share) = world.proposer_candidates(&validators);
+        assert_eq!(candidates.len(), 2);
+        assert!(candidates
+            .iter()
+            .all(|v| v.address != validators[1].address));
+        assert!((offline_share - 0.3).abs() < 1e-9);
+
+        // 全部离线时退回完整集合
+        world.offline_nodes.insert(validators[0].address.clone());
+        world.offline_nodes.insert(validators[2].address.clone());
+        let (candidates, offline_share) = world.proposer_candidates(&validators);
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(offline_share, 0.0);
+    }
+
+    /// 离线的验证者在 central 模式下不会被选为出块者，每个 epoch 仍按离线时隙扣减 stake
+    #[tokio::test]
+    async fn test_offline_stake_decay() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        world.set_offline_stake_decay(0.1);
+        let (sender, _receiver) = tokio::sync::mpsc::channel(1024);
+        let validators: Vec<Validator> = (0..2)
+            .map(|_| Validator::new(Wallet::new().address, 10.0, 1.0))
+            .collect();
+        for validator in validators.iter() {
+            world
+                .nodes_sender
+                .insert(validator.address.clone(), sender.clone());
+        }
+        *world.validators.write().await = validators.clone();
+
+        // 节点 0 每个时隙都离线，节点 1 始终在线
+        world.offline_nodes.insert(validators[0].address.clone());
+        let mut last_stake = 10.0;
+        for epoch in 0..3 {
+            for _slot in 0..5 {
+                world.next_slot().await;
+            }
+            assert_eq!(world.get_current_slot().await.current_epoch, epoch + 1);
+            let stakes: Vec<f64> = world
+                .validators
+                .read()
+                .await
+                .iter()
+                .map(|v| v.stake)
+                .collect();
+            assert!((stakes[0] - last_stake * 0.9).abs() < 1e-9);
+            assert_eq!(stakes[1], 10.0);
+            last_stake = stakes[0];
+        }
+        assert!((world.decayed_stake - (10.0 - last_stake)).abs() < 1e-9);
+
+        // 当前 epoch 的第一个时隙仍处于离线状态，扣减一次，之后全程在线的 epoch 不扣减
+        world.offline_nodes.clear();
+        for _slot in 0..5 {
+            world.next_slot().await;
+        }
+        last_stake *= 0.9;
+        assert!((world.validators.read().await[0].stake - last_stake).abs() < 1e-9);
+        for _slot in 0..5 {
+            world.next_slot().await;
+        }
+        assert_eq!(world.validators.read().await[0].stake, last_stake);
+    }
+
+    #[test]
+    fn test_max_stake_share() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        let new_validators = |stakes: &[f64]| -> Vec<Validator> {
+            stakes
+                .iter()
+                .map(|stake| Validator::new(Wallet::new().address, *stake, 1.0))
+                .collect()
+        };
+        let mut validators = new_validators(&[60.0, 25.0, 10.0, 5.0]);
+        // 默认不限制
+        assert_eq!(world.apply_max_stake_share(&mut validators), 0.0);
+
+        world.set_max_stake_share(0.3);
+        let redistributed = world.apply_max_stake_share(&mut validators);
+        let total: f64 = validators.iter().map(|v| v.stake).sum();
+        assert!((total - 100.0).abs() < 1e-9);
+        assert!(redistributed >= 30.0);
+        for validator in validators.iter() {
+            assert!(validator.stake <= 30.0 + 1e-9, "{}", validator.stake);
+        }
+        // 未达到上限的验证者保持原来的比例
+        assert!((validators[2].stake / validators[3].stake - 2.0).abs() < 1e-9);
+
+        // 上限无法满足时均分
+        world.set_max_stake_share(0.2);
+        let mut validators = new_validators(&[6.0, 3.0, 1.0]);
+        world.apply_max_stake_share(&mut validators);
+        assert!(validators
+            .iter()
+            .all(|v| (v.stake - 10.0 / 3.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_max_validators() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        let mut validators: Vec<Validator> = [("a", 5.0), ("b", 1.0), ("c", 4.0)]
+            .iter()
+            .map(|(address, stake)| Validator::new(address.to_string(), *stake, 1.0))
+            .collect();
+        // 默认不限制
+        assert!(world.apply_max_validators(&mut validators).is_empty());
+        assert_eq!(validators.len(), 3);
+
+        world.set_max_validators(2);
+        validators.push(Validator::new("d".to_string(), 3.0, 1.0));
+        world.apply_max_validators(&mut validators);
+        let addresses = |validators: &[Validator]| -> Vec<String> {
+            validators.iter().map(|v| v.address.clone()).collect()
+        };
+        assert_eq!(addresses(&validators), vec!["a", "c"]);
+        assert_eq!(addresses(&world.waiting_validators), vec!["d", "b"]);
+
+        // stake 增加后重新排名时进入验证者集合
+        world.waiting_validators[1].stake = 10.0;
+        assert_eq!(world.apply_max_validators(&mut validators), vec!["b"]);
+        assert_eq!(addresses(&validators), vec!["b", "a"]);
+        assert_eq!(addresses(&world.waiting_validators), vec!["c", "d"]);
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_validators() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        *world.validators.write().await = vec![
+            Validator::new("a".to_string(), 5.5, 1.0),
+            Validator::new("c".to_string(), 4.0, 1.0),
+        ];
+        world.waiting_validators = vec![Validator::new("b".to_string(), 0.5, 1.0)];
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("validators.json");
+        let path = path.to_str().unwrap();
+        world.save_validators(path).await.unwrap();
+
+        let (mut restored, _restored_sender, _restored_receiver) = test_world();
+        let stakes = restored.load_validators(path).await.unwrap();
+        assert_eq!(
+            *restored.validators.read().await,
+            *world.validators.read().await
+        );
+        assert_eq!(restored.waiting_validators, world.waiting_validators);
+        assert_eq!(stakes.len(), 3);
+        assert_eq!(stakes["a"], 5.5);
+        assert_eq!(stakes["b"], 0.5);
+    }
+
+    #[tokio::test]
+    async fn test_seed_reveal_window() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        let started = world.slot_started;
+        // 默认整个时隙都接受种子
+        assert!(!world.seed_deadline_passed(started + Duration::from_secs(10)));
+
+        world.set_seed_reveal_window(0.5);
+        assert!(!world.seed_deadline_passed(started + Duration::from_secs(1)));
+        assert!(world.seed_deadline_passed(started + Duration::from_secs(3)));
+
+        // 只接受当前时隙截止时间之前到达的种子
+        let wallet = Wallet::new();
+        assert!(
+            world
+                .accept_randao_seed(RandaoSeed::new(wallet.clone(), 0, 0), started)
+                .await
+        );
+        assert!(
+            !world
+                .accept_randao_seed(RandaoSeed::new(wallet.clone(), 0, 1), started)
+                .await
+        );
+        assert!(
+            !world
+                .accept_randao_seed(
+                    RandaoSeed::new(wallet.clone(), 0, 0),
+                    started + Duration::from_secs(3)
+                )
+                .await
+        );
+        assert_eq!(world.get_current_slot().await.randao_seeds.len(), 1);
+        assert_eq!(world.late_seeds, 2);
+
+        // 新时隙重新计时，迟到的种子数清零，上一个时隙的种子不再接受
+        world.next_slot().await;
+        assert_eq!(world.late_seeds, 0);
+        assert!(world.slot_started >= started);
+        assert!(!world.seed_deadline_passed(world.slot_started + Duration::from_secs(1)));
+        let now = world.slot_started;
+        assert!(
+            !world
+                .accept_randao_seed(RandaoSeed::new(wallet.clone(), 0, 0), now)
+                .await
+        );
+        assert!(
+            world
+                .accept_randao_seed(RandaoSeed::new(wallet, 0, 1), now)
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_randao_committee() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        let mut receivers = HashMap::new();
+        let mut validators = vec![];
+        for _ in 0..6 {
+            let wallet = Wallet::new();
+            let (sender, receiver) = tokio::sync::mpsc::channel(64);
+            world.nodes_sender.insert(wallet.address.clone(), sender);
+            receivers.insert(wallet.address.clone(), receiver);
+            validators.push(Validator::new(wallet.address.clone(), 1.0, 1.0));
+        }
+        *world.validators.write().await = validators.clone();
+        world.set_randao_committee_size(2);
+        world.next_slot().await;
+
+        let mut requested: Vec<String> = vec![];
+        for (address, receiver) in receivers.iter_mut() {
+            while let Ok(msg) = receiver.try_recv() {
+                if matches!(msg.msg_type, MessageType::SendRandaoSeed) {
+                    requested.push(address.clone());
+                }
+            }
+        }
+        requested.sort();
+        let seed = world.get_current_slot().await.next_seed;
+        let expected: Vec<String> = sample_randao_committee(&validators, 2, seed)
+            .into_iter()
+            .map(|v| v.address)
+            .collect();
+        let mut sorted_expected = expected.clone();
+        sorted_expected.sort();
+        assert_eq!(requested, sorted_expected);
+
+        // 下一个时隙组合种子时只接受委员会成员的种子
+        let committee: Vec<String> = world
+            .randao_committee
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|v| v.address.clone())
+            .collect();
+        assert_eq!(committee, expected);
+    }
+
+    #[test]
+    fn test_reject_illegitimate_proposer() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        let selected = Wallet::new();
+        let other = Wallet::new();
+        let sybil_owner = Wallet::new();
+        let gen_block = |miner: &Wallet| {
+            let parent_hash = Block::gen_genesis_block().header.hash;
+            Block::new(
+                1,
+                0,
+                1,
+                parent_hash,
+                Body::new(vec![], vec![]),
+                miner.clone(),
+            )
+            .unwrap()
+        };
+        world
+            .expected_proposers
+            .insert((0, 1), selected.address.clone());
+
+        assert!(world.is_legitimate_proposer(&gen_block(&selected)));
+        assert!(!world.is_legitimate_proposer(&gen_block(&other)));
+        // 未选出出块者的时隙
+        let parent_hash = Block::gen_genesis_block().header.hash;
+        let block = Block::new(1, 0, 2, parent_hash, Body::new(vec![], vec![]), selected).unwrap();
+        assert!(!world.is_legitimate_proposer(&block));
+
+        // 被选中的是伪造身份，由主节点出块
+        let sybil = Wallet::new();
+        let (sender, _receiver) = tokio::sync::mpsc::channel(1);
+        world
+            .nodes_sender
+            .insert(sybil.address.clone(), sender.clone());
+        world
+            .nodes_sender
+            .insert(sybil_owner.address.clone(), sender);
+        world.expected_proposers.insert((0, 1), sybil.address);
+        assert!(world.is_legitimate_proposer(&gen_block(&sybil_owner)));
+        assert!(!world.is_legitimate_proposer(&gen_block(&other)));
+    }
+
+    /// 重复收到的区块被忽略，不计入出块失败；接不上的区块计入出块失败
+    #[tokio::test]
+    async fn test_accept_block_policy() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        let proposer = Wallet::new();
+        for slot in 1..=2 {
+            world
+                .expected_proposers
+                .insert((0, slot), proposer.address.clone());
+        }
+        let parent_hash = world.blockchain.read().await.get_last_hash();
+        let gen_block = |slot: u64| {
+            Block::new(
+                1,
+                0,
+                slot,
+                parent_hash.clone(),
+                Body::new(vec![], vec![]),
+                proposer.clone(),
+            )
+            .unwrap()
+        };
+        let block = gen_block(1);
+
+        assert!(world.accept_block(&block).await);
+        // 出块者和邻居重复发送
+        assert!(!world.accept_block(&block).await);
+        assert!(!world.accept_block(&block).await);
+        assert_eq!(world.block_production_success, 1);
+        assert_eq!(world.block_production_failed, 0);
+        assert_eq!(world.blockchain.read().await.len(), 2);
+
+        // 同一高度的另一个区块接不上
+        assert!(!world.accept_block(&gen_block(2)).await);
+        assert_eq!(world.block_production_failed, 1);
+        assert_eq!(
+            world.blockchain.read().await.get_last_hash(),
+            block.header.hash
+        );
+    }
+
+    /// 在内存中记录收到的指标
+    #[derive(Clone, Default)]
+    struct CapturingSink {
+        slots: Arc<std::sync::Mutex<Vec<SlotMetrics>>>,
+        epochs: Arc<std::sync::Mutex<Vec<EpochMetrics>>>,
+    }
+
+    impl MetricsSink for CapturingSink {
+        fn record_slot(&mut self, metrics: &SlotMetrics) {
+            self.slots.lock().unwrap().push(metrics.clone());
+        }
+
+        fn record_epoch(&mut self, metrics: &EpochMetrics) {
+            self.epochs.lock().unwrap().push(metrics.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_sink() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        let sink = CapturingSink::default();
+        world.add_metrics_sink(Box::new(sink.clone()));
+        world.add_metrics_sink(Box::new(NullSink));
+        let validators = vec![
+            Validator::new(Wallet::new().address, 2.0, 1.0),
+            Validator::new(Wallet::new().address, 1.0, 1.0),
+        ];
+        *world.validators.write().await = validators.clone();
+
+        let weights = world.consensus.effective_weights(&validators);
+        world
+            .collect_slot_metrics(&validators[0], [7; 32], weights.into_iter().collect())
+            .await;
+        world.collect_epoch_metrics(0, &validators).await;
+        world.collect_epoch_metrics(1, &validators).await;
+
+        let slots = sink.slots.lock().unwrap();
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].miner, validators[0].address);
+        assert_eq!(slots[0].seed, None);
+        assert_eq!(slots[0].effective_weights[&validators[0].address], 2.0);
+        let epochs = sink.epochs.lock().unwrap();
+        assert_eq!(epochs.len(), 2);
+        assert_eq!(epochs[0].validators_added, 2);
+        assert_eq!(epochs[1].validators_added, 0);
+        assert_eq!(epochs[1].validator_count, 2);
+    }
+
+    /// 出块者没有出块的时隙计入 epoch 的空时隙数
+    #[tokio::test]
+    async fn test_empty_slots() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        world.slot_per_epoch = 3;
+        let sink = CapturingSink::default();
+        world.add_metrics_sink(Box::new(sink.clone()));
+        let wallet = Wallet::new();
+        let (sender, _receiver) = tokio::sync::mpsc::channel(64);
+        world.nodes_sender.insert(wallet.address.clone(), sender);
+        *world.validators.write().await = vec![Validator::new(wallet.address.clone(), 1.0, 1.0)];
+
+        // epoch 0 的时隙 1 出块，时隙 2 出块者没有出块
+        world.next_slot().await;
+        {
+            let mut blockchain = world.blockchain.write().await;
+            let last = blockchain.get_last_block();
+            let block = Block::new(
+                1,
+                0,
+                1,
+                last.header.hash.clone(),
+                Body::new(vec![], vec![]),
+                wallet.clone(),
+            )
+            .unwrap();
+            blockchain.add_block(block).unwrap();
+        }
+        world.next_slot().await;
+        world.next_slot().await;
+        assert_eq!(world.get_current_slot().await.current_epoch, 1);
+
+        let epochs = sink.epochs.lock().unwrap();
+        assert_eq!(epochs.len(), 1);
+        assert_eq!(epochs[0].empty_slots, 1);
+        // 只有一个验证者和一个出块者
+        assert_eq!(epochs[0].proposer_entropy, 0.0);
+        assert_eq!(epochs[0].throughput, 0.0);
+        assert!((epochs[0].stake_concentration - 1.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_consensus_switch() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        world.slot_per_epoch = 2;
+        let sink = CapturingSink::default();
+        world.add_metrics_sink(Box::new(sink.clone()));
+        world.set_consensus_switches(vec![
+            "minotaur@2".parse().unwrap(),
+            "pog@1".parse().unwrap(),
+        ]);
+        let wallet = Wallet::new();
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(256);
+        world.nodes_sender.insert(wallet.address.clone(), sender);
+        let validator = Validator::new(wallet.address.clone(), 10.0, 1.0);
+        *world.validators.write().await = vec![validator.clone()];
+
+        let mut active = vec![];
+        for _ in 0..5 {
+            world.next_slot().await;
+            let current_slot = world.get_current_slot().await;
+            active.push((current_slot.current_epoch, world.consensus.name()));
+        }
+        assert_eq!(
+            active,
+            vec![
+                (0, "POS"),
+                (1, "POG"),
+                (1, "POG"),
+                (2, "Minotaur"),
+                (2, "Minotaur")
+            ]
+        );
+        // 切换后验证者的 stake 保持不变，时隙指标记录当时的共识
+        assert_eq!(*world.validators.read().await, vec![validator]);
+        let slots = sink.slots.lock().unwrap();
+        assert_eq!(slots.first().unwrap().consensus_type, "POS");
+        assert_eq!(slots.last().unwrap().consensus_type, "Minotaur");
+
+        // 节点在新 epoch 的 UpdateSlot 之前收到新的共识类型
+        let mut received = vec![];
+        while let Ok(msg) = receiver.try_recv() {
+            match msg.msg_type {
+                MessageType::SwitchConsensus => {
+                    let consensus: ConsensusType = serde_json::from_slice(&msg.data).unwrap();
+                    received.push(consensus.to_string());
+                }
+                MessageType::UpdateSlot => {
+                    let slot = SlotManager::from_json(msg.data).unwrap();
+                    received.push(format!("{}-{}", slot.current_epoch, slot.current_slot));
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(
+            received,
+            vec!["0-1", "pog", "1-0", "1-1", "minotaur", "2-0", "2-1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sweep_exited_keys() {
+        let (mut world, _world_sender, _world_receiver) = test_world();
+        let registry = KeyRegistry::new();
+        let online = Wallet::new_in(&registry);
+        let exited = Wallet::new_in(&registry);
+        let relayed = Wallet::new_in(&registry);
+        let queued = Wallet::new_in(&registry);
+
+        // 交易缓存中经过 from 的路径
+        let mempool_via = |from: &Wallet, to: &Wallet| {
+            let mut transaction_paths =
+                TransactionPaths::new(Transaction::new(to.address.clone(), 1, from.clone()));
+            transaction_paths.add_path(to.address.clone(), from.clone());
+            Arc::new(RwLock::new(HashMap::from([(
+                transaction_paths.transaction.hash.clone(),
+                transaction_paths,
+            )])))
+        };
+        // 在线节点的缓存引用 relayed，离线节点的缓存引用 queued
+        let mempool = mempool_via(&relayed, &online);
+        let offline_mempool = mempool_via(&queued, &exited);
+        world.set_key_registry(
+            registry.clone(),
+            HashMap::from([
+                (online.address.clone(), mempool.clone()),
+                (exited.address.clone(), offline_mempool.clone()),
+            ]),
+        );
+
+        for wallet in [&exited, &relayed, &queued] {
+            registry.retire(&wallet.address, 0);
+            world.offline_nodes.insert(wallet.address.clone());
+        }
+        assert_eq!(world.sweep_exited_keys(0, &[]).await, 0);
+        assert_eq!(world.sweep_exited_keys(1, &[]).await, 0);
+        assert!(registry.contains(&relayed.address));
+        assert!(registry.contains(&queued.address));
+
+        // 路径打包后不再被引用，下个 epoch 清理
+        mempool.write().await.clear();
+        offline_mempool.write().await.clear();
+        assert_eq!(world.sweep_exited_keys(2, &[]).await, 3);
+        assert_eq!(registry.len(), 1);
+    }
+
     #[tokio::test]
     async fn test_flat_map() {
         let a = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
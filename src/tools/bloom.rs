@@ -0,0 +1,79 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// 布隆过滤器：只会误报，不会漏报
+/// 用于快速判断某个哈希一定不存在，存在时仍需查询真实数据
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: usize,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    /// 按预计元素个数和期望误报率计算位数和哈希函数个数
+    pub fn with_capacity(expected_items: usize, false_positive_rate: f64) -> Self {
+        let n = expected_items.max(1) as f64;
+        let p = false_positive_rate.clamp(1e-9, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(n * p.ln()) / (ln2 * ln2)).ceil().max(64.0) as usize;
+        let num_hashes = ((num_bits as f64 / n) * ln2).round().max(1.0) as u32;
+        BloomFilter {
+            bits: vec![0; num_bits.div_ceil(64)],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    pub fn insert(&mut self, item: &str) {
+        for index in self.indexes(item) {
+            self.bits[index / 64] |= 1 << (index % 64);
+        }
+    }
+
+    pub fn might_contain(&self, item: &str) -> bool {
+        self.indexes(item)
+            .all(|index| self.bits[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    /// 双重哈希：第 i 个位置为 h1 + i * h2
+    fn indexes(&self, item: &str) -> impl Iterator<Item = usize> {
+        let h1 = Self::hash_with_seed(item, 0);
+        let h2 = Self::hash_with_seed(item, 1) | 1;
+        let num_bits = self.num_bits as u64;
+        (0..self.num_hashes as u64)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % num_bits) as usize)
+    }
+
+    fn hash_with_seed(item: &str, seed: u64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        item.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::with_capacity(1000, 0.01);
+        for i in 0..1000 {
+            filter.insert(&format!("tx{}", i));
+        }
+        // 插入过的元素一定能查到
+        assert!((0..1000).all(|i| filter.might_contain(&format!("tx{}", i))));
+
+        // 误报率应接近设定值
+        let false_positives = (1000..11000)
+            .filter(|i| filter.might_contain(&format!("tx{}", i)))
+            .count();
+        assert!(
+            false_positives < 300,
+            "false positives: {}",
+            false_positives
+        );
+    }
+}
@@ -1,16 +1,56 @@
 use chrono::Local;
+use sha2::Sha256;
 use sha3::{Digest, Sha3_256};
 use std::time::SystemTime;
 
-pub struct Hasher {}
+pub mod bloom;
+
+/// 哈希算法
+/// 地址、区块哈希和默克尔根使用 SHA3-256，PoW/Minotaur 的工作量计算默认使用 SHA-256
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgo {
+    #[default]
+    Sha3_256,
+    Sha256,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Hasher {
+    algo: HashAlgo,
+}
 
 impl Hasher {
+    /// SHA3-256 哈希，地址推导依赖它，不随算法配置改变
     pub fn hash(data: Vec<u8>) -> [u8; 32] {
-        //sha3
-        let mut hasher = Sha3_256::new();
-        hasher.update(data);
-        let hash_result = hasher.finalize();
-        <[u8; 32]>::from(hash_result)
+        Hasher::with_algo(HashAlgo::Sha3_256).digest(&[&data])
+    }
+
+    pub fn with_algo(algo: HashAlgo) -> Hasher {
+        Hasher { algo }
+    }
+
+    pub fn algo(&self) -> HashAlgo {
+        self.algo
+    }
+
+    /// 依次哈希各部分，结果与哈希它们拼接后的数据相同
+    pub fn digest(&self, parts: &[&[u8]]) -> [u8; 32] {
+        match self.algo {
+            HashAlgo::Sha3_256 => {
+                let mut hasher = Sha3_256::new();
+                for part in parts {
+                    hasher.update(part);
+                }
+                hasher.finalize().into()
+            }
+            HashAlgo::Sha256 => {
+                let mut hasher = Sha256::new();
+                for part in parts {
+                    hasher.update(part);
+                }
+                hasher.finalize().into()
+            }
+        }
     }
 }
 
@@ -22,6 +62,14 @@ pub fn get_timestamp() -> u64 {
         .as_secs()
 }
 
+/// 毫秒时间戳，用于记录路径每一跳的到达时间
+pub fn get_timestamp_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64
+}
+
 pub fn get_time_string() -> String {
     let now = Local::now();
     now.format("%Y-%m-%d %H:%M:%S").to_string()
@@ -30,3 +78,25 @@ pub fn get_time_string() -> String {
 pub fn short_hash(s: String) -> String {
     s[0..5].to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hasher_algos() {
+        let sha3 = Hasher::with_algo(HashAlgo::Sha3_256);
+        let sha256 = Hasher::with_algo(HashAlgo::Sha256);
+        assert_eq!(Hasher::hash(b"abc".to_vec()), sha3.digest(&[b"abc"]));
+        assert_eq!(
+            hex::encode(sha3.digest(&[b"abc"])),
+            "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"
+        );
+        assert_eq!(
+            hex::encode(sha256.digest(&[b"abc"])),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // 分段哈希与拼接后哈希一致
+        assert_eq!(sha256.digest(&[b"a", b"bc"]), sha256.digest(&[b"abc"]));
+    }
+}
@@ -1,31 +1,104 @@
 use crate::tools::Hasher;
 use blst::min_sig::{AggregateSignature, SecretKey as BlsSecretKey};
 use blst::min_sig::{PublicKey as BlsPublicKey, Signature};
-use blst::BLST_ERROR;
+use blst::{blst_p1_affine, blst_p2_affine, Pairing, BLST_ERROR};
 use dashmap::DashMap;
 use hex::{decode, encode, FromHexError};
-use lazy_static::lazy_static;
 use log::info;
 use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
 use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use std::collections::HashSet;
 use std::fmt;
 use std::num::ParseIntError;
 use std::str::FromStr;
-
-// 设置一个全局的bls的公钥管理对象
-// 一般来说，这个功能在以太坊2.0由验证者注册合约实现
-// 我们简化成一个全局变量来使用
-// 我们希望愿意参与网络贡献的节点，都注册bls公钥
-// 这样可以大大减少签名带来的存储开销
-lazy_static! {
-    static ref BLS_PUB_KEY_MAP: DashMap<String, BlsPublicKey> = DashMap::new();
+use std::sync::Arc;
+
+/// 一个聚合签名及其签名的 (消息, 公钥)，用于批量验证
+pub type SignedMessages = (Vec<Vec<u8>>, Vec<BlsPublicKey>, Signature);
+
+/// bls公钥注册表：地址 -> 公钥，验证路径的聚合签名时按地址查找公钥
+/// 一般来说，这个功能在以太坊2.0由验证者注册合约实现
+/// 我们希望愿意参与网络贡献的节点，都注册bls公钥，这样可以大大减少签名带来的存储开销
+/// 克隆得到的是同一个注册表，所有克隆都被丢弃后其中的公钥随之释放
+/// 每次模拟使用独立的注册表，同一进程中并行的模拟互不影响
+#[derive(Debug, Clone, Default)]
+pub struct KeyRegistry {
+    keys: Arc<DashMap<String, BlsPublicKey>>,
+    // 已退出网络、等待清理的地址 -> 退出时的 epoch
+    retired: Arc<DashMap<String, u64>>,
 }
 
-pub fn get_bls_pub_key(address: String) -> Option<BlsPublicKey> {
-    BLS_PUB_KEY_MAP.get(&address).map(|entry| *entry.value())
+thread_local! {
+    // 当前线程的默认注册表，未指定注册表创建的钱包注册到这里，兼容测试等不关心隔离的场景
+    static DEFAULT_REGISTRY: KeyRegistry = KeyRegistry::new();
 }
-pub fn insert_bls_pub_key(address: String, public_key: BlsPublicKey) {
-    BLS_PUB_KEY_MAP.insert(address, public_key);
+
+impl KeyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 当前线程的默认注册表
+    pub fn thread_default() -> Self {
+        DEFAULT_REGISTRY.with(|registry| registry.clone())
+    }
+
+    pub fn get(&self, address: &str) -> Option<BlsPublicKey> {
+        self.keys.get(address).map(|entry| *entry.value())
+    }
+
+    /// 重新注册的地址不再等待清理
+    pub fn insert(&self, address: String, public_key: BlsPublicKey) {
+        self.retired.remove(&address);
+        self.keys.insert(address, public_key);
+    }
+
+    pub fn remove(&self, address: &str) -> Option<BlsPublicKey> {
+        self.retired.remove(address);
+        self.keys.remove(address).map(|(_, public_key)| public_key)
+    }
+
+    /// 标记节点在 epoch 退出网络，公钥暂不删除，由 sweep_retired 清理
+    pub fn retire(&self, address: &str, epoch: u64) {
+        if self.keys.contains_key(address) {
+            self.retired.insert(address.to_string(), epoch);
+        }
+    }
+
+    /// 删除在 before_epoch 之前退出的节点的公钥，返回删除的数量
+    /// 仍被尚未验证的路径引用的地址（referenced）保留到下次清理
+    pub fn sweep_retired(&self, before_epoch: u64, referenced: &HashSet<String>) -> usize {
+        let expired: Vec<String> = self
+            .retired
+            .iter()
+            .filter(|entry| *entry.value() < before_epoch && !referenced.contains(entry.key()))
+            .map(|entry| entry.key().clone())
+            .collect();
+        for address in &expired {
+            self.remove(address);
+        }
+        expired.len()
+    }
+
+    pub fn retired_len(&self) -> usize {
+        self.retired.len()
+    }
+
+    pub fn register(&self, wallet: &Wallet) {
+        self.insert(wallet.address.clone(), wallet.bls_public_key);
+    }
+
+    pub fn contains(&self, address: &str) -> bool {
+        self.keys.contains_key(address)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -45,7 +118,13 @@ impl Default for Wallet {
 }
 
 impl Wallet {
+    /// 随机生成钱包，bls公钥注册到当前线程的默认注册表
     pub fn new() -> Wallet {
+        Wallet::new_in(&KeyRegistry::thread_default())
+    }
+
+    /// 随机生成钱包，bls公钥注册到给定的注册表
+    pub fn new_in(registry: &KeyRegistry) -> Wallet {
         let secp = Secp256k1::new();
 
         let (secret_key, public_key) = secp.generate_keypair(&mut rand::thread_rng());
@@ -53,21 +132,43 @@ impl Wallet {
         let bls_private_key =
             BlsSecretKey::key_gen(secret_key.secret_bytes().as_slice(), &[]).unwrap();
         let bls_public_key = bls_private_key.sk_to_pk();
-        insert_bls_pub_key(address.clone(), bls_public_key);
-        Wallet {
+        let wallet = Wallet {
             secret_key,
             public_key,
             bls_private_key,
             bls_public_key,
             address,
-        }
+        };
+        registry.register(&wallet);
+        wallet
     }
 
     pub fn new_deterministic(seed: u64, index: u32) -> Wallet {
+        Wallet::new_deterministic_in(seed, index, &KeyRegistry::thread_default())
+    }
+
+    /// 由 (seed, index) 确定的钱包，bls公钥注册到给定的注册表
+    pub fn new_deterministic_in(seed: u64, index: u32, registry: &KeyRegistry) -> Wallet {
         let mut combined = seed.to_be_bytes().to_vec();
         combined.extend_from_slice(&index.to_be_bytes());
-        let hash = Hasher::hash(combined);
+        Wallet::from_hash(Hasher::hash(combined), registry)
+    }
+
+    /// 由父钱包私钥和序号派生子钱包，相同的父钱包和序号总是得到相同的子钱包
+    /// 用于恶意节点生成可复现的伪造身份
+    pub fn derive_child(&self, index: u32) -> Wallet {
+        self.derive_child_in(index, &KeyRegistry::thread_default())
+    }
 
+    /// 派生子钱包，bls公钥注册到给定的注册表
+    pub fn derive_child_in(&self, index: u32, registry: &KeyRegistry) -> Wallet {
+        let mut combined = self.secret_key.secret_bytes().to_vec();
+        combined.extend_from_slice(b"sybil");
+        combined.extend_from_slice(&index.to_be_bytes());
+        Wallet::from_hash(Hasher::hash(combined), registry)
+    }
+
+    fn from_hash(hash: [u8; 32], registry: &KeyRegistry) -> Wallet {
         let secret_key = SecretKey::from_slice(&hash).expect("32 bytes");
         let secp = Secp256k1::new();
         let public_key = secret_key.public_key(&secp);
@@ -76,18 +177,23 @@ impl Wallet {
         let bls_private_key =
             BlsSecretKey::key_gen(secret_key.secret_bytes().as_slice(), &[]).unwrap();
         let bls_public_key = bls_private_key.sk_to_pk();
-        insert_bls_pub_key(address.clone(), bls_public_key);
-        Wallet {
+        let wallet = Wallet {
             secret_key,
             public_key,
             bls_private_key,
             bls_public_key,
             address,
-        }
+        };
+        registry.register(&wallet);
+        wallet
     }
 
-    #[allow(dead_code)]
-    fn from_secret_key_string(mut secret_key: String) -> Result<Wallet, WalletError> {
+    /// 十六进制私钥（不带 0x 前缀），可由 from_secret_key_string 恢复钱包
+    pub fn secret_key_string(&self) -> String {
+        encode(self.secret_key.secret_bytes())
+    }
+
+    pub fn from_secret_key_string(mut secret_key: String) -> Result<Wallet, WalletError> {
         if secret_key.len() == 66 {
             secret_key = secret_key[2..].to_string();
         }
@@ -157,21 +263,25 @@ impl Wallet {
         let message = Message::from_digest(hash_result);
 
         // 分解签名为 r, s 和 v
+        if signature.len() != 130 || !signature.is_ascii() {
+            return Err(WalletError::InvalidSignature);
+        }
         let signature_bytes = decode(&signature[0..128])?;
 
         let v = u8::from_str_radix(&signature[128..130], 16)?;
 
-        // 生成可恢复签名对象
-        let recovery_id = RecoveryId::try_from((v - 27) as i32).expect("Valid RecoveryId");
+        // 生成可恢复签名对象，签名被篡改时返回错误而不是 panic
+        let recovery_id =
+            RecoveryId::try_from(v as i32 - 27).map_err(|_| WalletError::InvalidSignature)?;
         let recoverable_signature =
             RecoverableSignature::from_compact(&signature_bytes, recovery_id)
-                .expect("Valid signature");
+                .map_err(|_| WalletError::InvalidSignature)?;
 
         // 从签名恢复公钥
         let secp = Secp256k1::new();
         let recovered_public_key = secp
             .recover_ecdsa(&message, &recoverable_signature)
-            .expect("Recovered public key");
+            .map_err(|_| WalletError::InvalidSignature)?;
         Ok(recovered_public_key)
     }
 
@@ -186,6 +296,54 @@ impl Wallet {
         }
     }
 
+    /// 批量验证多个聚合签名，每项为 (消息, 公钥, 聚合签名)，全部签名都有效时才返回 true
+    /// 每个签名及其 (消息, 公钥) 乘以同一个随机系数后合并为一次配对检查，
+    /// 签名在不同项之间交换或抵消时验证失败
+    pub fn bls_batch_aggregated_verify(batch: &[SignedMessages]) -> bool {
+        const RAND_BITS: usize = 64;
+        let mut pairing = Pairing::new(true, &[]);
+        for (messages, public_keys, signature) in batch {
+            if messages.is_empty() || messages.len() != public_keys.len() {
+                return false;
+            }
+            // 随机系数不能为 0
+            let scalar = rand::random::<u64>().max(1).to_le_bytes();
+            let signature: &blst_p1_affine = signature.into();
+            for (i, (message, public_key)) in messages.iter().zip(public_keys).enumerate() {
+                let public_key: &blst_p2_affine = public_key.into();
+                // 签名只在第一项计入一次，其余项只累加 (消息, 公钥)
+                let result = if i == 0 {
+                    pairing.mul_n_aggregate(
+                        public_key,
+                        true,
+                        signature,
+                        true,
+                        &scalar,
+                        RAND_BITS,
+                        message,
+                        &[],
+                    )
+                } else {
+                    pairing.mul_n_aggregate(
+                        public_key,
+                        true,
+                        &(),
+                        false,
+                        &scalar,
+                        RAND_BITS,
+                        message,
+                        &[],
+                    )
+                };
+                if result != BLST_ERROR::BLST_SUCCESS {
+                    return false;
+                }
+            }
+        }
+        pairing.commit();
+        pairing.finalverify(None)
+    }
+
     #[allow(dead_code)]
     fn verify_bls(&self, msg: Vec<u8>, signature: String) -> bool {
         let signature = match Wallet::bls_signature_from_string(signature) {
@@ -232,8 +390,7 @@ impl Wallet {
         }
         let signature_bytes = decode(&signature)?;
 
-        let signature = Signature::from_bytes(signature_bytes.as_slice()).unwrap();
-        Ok(signature)
+        Signature::from_bytes(signature_bytes.as_slice()).map_err(|_| WalletError::InvalidSignature)
     }
 
     pub fn bls_aggregated_sign(signatures: Vec<Signature>) -> String {
@@ -273,7 +430,7 @@ impl Wallet {
     }
 
     #[allow(dead_code)]
-    pub(crate) fn print(&self) {
+    pub fn print(&self) {
         info!("Secret Key: 0x{}", encode(self.secret_key.secret_bytes()));
         let public_key_bytes = &self.public_key.serialize_uncompressed()[1..];
         info!("Public Key: 0x{}", encode(public_key_bytes));
@@ -351,6 +508,44 @@ mod tests {
         ));
     }
 
+    /// 长度不对或不是十六进制的签名返回 InvalidSignature，而不是 panic
+    #[test]
+    fn test_malformed_signature() {
+        let message = b"hello world";
+        let wallet = Wallet::from_secret_key_string(KEYPAIR.0.to_string()).unwrap();
+        let signature = wallet.sign(message.to_vec());
+        let bls_signature = wallet.sign_by_bls(message.to_vec());
+        let malformed = [
+            String::new(),
+            "0x".to_string(),
+            signature[..100].to_string(),
+            signature[..signature.len() - 1].to_string(),
+            format!("{}00", signature),
+            // 长度正确但包含多字节字符
+            format!("0x{}é", &signature[2..129]),
+            format!("0x{}zz", &signature[2..130]),
+        ];
+        for signature in malformed {
+            assert!(matches!(
+                Wallet::recover_pubkey(message.to_vec(), signature.clone()),
+                Err(WalletError::InvalidSignature)
+            ));
+            assert!(!wallet.verify(message.to_vec(), signature.clone()));
+            assert!(!Wallet::verify_by_address(
+                message.to_vec(),
+                signature,
+                wallet.address.clone()
+            ));
+        }
+        for signature in [
+            bls_signature[..50].to_string(),
+            format!("{}0", bls_signature),
+        ] {
+            assert!(Wallet::bls_signature_from_string(signature.clone()).is_err());
+            assert!(!wallet.verify_bls(message.to_vec(), signature));
+        }
+    }
+
     #[test]
     fn test_verify_bls_sign() {
         let message = b"hello world";
@@ -382,4 +577,97 @@ mod tests {
         let result = Wallet::bls_aggregated_verify(messages, public_keys, aggregated_signature);
         assert!(result);
     }
+
+    #[test]
+    fn test_bls_batch_aggregated_verify() {
+        let signed = |messages: &[&str]| -> SignedMessages {
+            let wallets: Vec<Wallet> = messages.iter().map(|_| Wallet::new()).collect();
+            let signatures = messages
+                .iter()
+                .zip(&wallets)
+                .map(|(m, w)| {
+                    Wallet::bls_signature_from_string(w.sign_by_bls(m.as_bytes().to_vec())).unwrap()
+                })
+                .collect();
+            let signature =
+                Wallet::bls_signature_from_string(Wallet::bls_aggregated_sign(signatures)).unwrap();
+            (
+                messages.iter().map(|m| m.as_bytes().to_vec()).collect(),
+                wallets.iter().map(|w| w.bls_public_key).collect(),
+                signature,
+            )
+        };
+        let batch = vec![
+            signed(&["a1", "a2"]),
+            signed(&["b1", "b2", "b3"]),
+            signed(&["c1"]),
+        ];
+        assert!(Wallet::bls_batch_aggregated_verify(&batch));
+
+        // 两项交换签名后每项都无效
+        let mut swapped = batch.clone();
+        swapped[0].2 = batch[1].2;
+        swapped[1].2 = batch[0].2;
+        assert!(!Wallet::bls_batch_aggregated_verify(&swapped));
+        // 消息与公钥数量不一致
+        let mut mismatched = batch.clone();
+        mismatched[2].0.push(b"c2".to_vec());
+        assert!(!Wallet::bls_batch_aggregated_verify(&mismatched));
+    }
+
+    #[test]
+    fn test_key_registry_isolated() {
+        let registry = KeyRegistry::new();
+        let wallet = Wallet::new_in(&registry);
+        assert_eq!(registry.get(&wallet.address), Some(wallet.bls_public_key));
+        assert!(!KeyRegistry::thread_default().contains(&wallet.address));
+
+        let default_wallet = Wallet::new();
+        assert!(KeyRegistry::thread_default().contains(&default_wallet.address));
+        assert!(!registry.contains(&default_wallet.address));
+    }
+
+    #[test]
+    fn test_key_registry_drop_frees_keys() {
+        let registry = KeyRegistry::new();
+        let shared = registry.clone();
+        for _ in 0..3 {
+            Wallet::new_in(&registry);
+        }
+        assert_eq!(shared.len(), 3);
+
+        // 所有克隆都被丢弃后公钥随之释放
+        let keys = Arc::downgrade(&registry.keys);
+        drop(registry);
+        assert!(keys.upgrade().is_some());
+        drop(shared);
+        assert!(keys.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_key_registry_sweep_retired() {
+        let registry = KeyRegistry::new();
+        let exited = Wallet::new_in(&registry);
+        let referenced = Wallet::new_in(&registry);
+        let returned = Wallet::new_in(&registry);
+        registry.retire(&exited.address, 1);
+        registry.retire(&referenced.address, 1);
+        registry.retire(&returned.address, 1);
+        assert_eq!(registry.retired_len(), 3);
+
+        // 退出的当前 epoch 内不清理
+        assert_eq!(registry.sweep_retired(1, &HashSet::new()), 0);
+
+        // 重新上线的节点重新注册，仍被路径引用的地址保留
+        registry.register(&returned);
+        let pending = HashSet::from([referenced.address.clone()]);
+        assert_eq!(registry.sweep_retired(2, &pending), 1);
+        assert!(!registry.contains(&exited.address));
+        assert!(registry.contains(&referenced.address));
+        assert!(registry.contains(&returned.address));
+        assert_eq!(registry.retired_len(), 1);
+
+        assert_eq!(registry.sweep_retired(2, &HashSet::new()), 1);
+        assert_eq!(registry.len(), 1);
+    }
 }
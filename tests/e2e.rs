@@ -0,0 +1,141 @@
+use pog::consensus::ConsensusType;
+use pog::metrics::MetricsSinkKind;
+use pog::network::{run_simulation, SimulationConfig};
+
+/// 启动5个节点的小网络运行若干时隙，检查各节点链一致且生成了指标文件
+#[tokio::test(flavor = "multi_thread")]
+async fn test_small_network_converges() {
+    let result = run_simulation(SimulationConfig {
+        node_num: 5,
+        trans_num_per_second: 5,
+        slot_duration: 1,
+        consensus: ConsensusType::POG,
+        run_seconds: 12,
+        ..Default::default()
+    })
+    .await;
+
+    assert_eq!(result.blockchains.len(), 5);
+    assert_eq!(result.stakes.len(), 5);
+
+    // 停止时可能有区块仍在传播，比较所有节点共同的最高区块
+    let min_tip = result
+        .blockchains
+        .values()
+        .map(|bc| bc.get_last_index())
+        .min()
+        .unwrap();
+    let max_tip = result
+        .blockchains
+        .values()
+        .map(|bc| bc.get_last_index())
+        .max()
+        .unwrap();
+    assert!(min_tip >= 2, "network produced too few blocks: {}", min_tip);
+    assert!(max_tip - min_tip <= 1);
+
+    let hashes: Vec<String> = result
+        .blockchains
+        .values()
+        .map(|bc| bc.blocks()[min_tip as usize].header.hash.clone())
+        .collect();
+    assert!(hashes.iter().all(|h| *h == hashes[0]));
+
+    // WorldState 的链与各节点一致（停止时最多落后一个区块）
+    assert_eq!(result.consistency.nodes.len(), 5);
+    assert!(result
+        .consistency
+        .nodes
+        .iter()
+        .all(|n| n.common_prefix as u64 >= min_tip));
+
+    // 出块奖励已分配
+    let total_stake: f64 = result.stakes.values().sum();
+    assert!(total_stake > 5.0);
+
+    let metrics = std::fs::read_to_string("metrics_slots_pog.csv").unwrap();
+    assert!(metrics.lines().count() > 1);
+}
+
+/// 3个节点的小网络交易很少，停止前几个时隙之外生成的交易都应被 WorldState 的链打包
+#[tokio::test(flavor = "multi_thread")]
+async fn test_small_network_includes_transactions() {
+    let run_slots = 10;
+    let result = run_simulation(SimulationConfig {
+        node_num: 3,
+        trans_num_per_second: 2,
+        slot_duration: 1,
+        consensus: ConsensusType::POS,
+        run_slots,
+        ..Default::default()
+    })
+    .await;
+
+    let inclusion = &result.inclusion;
+    assert!(inclusion.generated > 0);
+    assert!(inclusion.included > 0);
+    assert_eq!(inclusion.expired, 0);
+    // 最后几个时隙生成的交易可能还未打包
+    assert_eq!(inclusion.pending_before(run_slots - 4), 0);
+}
+
+/// 同一进程内并发运行两次模拟，各自的节点只登记在自己的公钥注册表中
+#[tokio::test(flavor = "multi_thread")]
+async fn test_parallel_simulations_isolated() {
+    // 钱包由种子确定性生成，两次模拟使用不同的种子
+    let config = |consensus, wallet_seed| SimulationConfig {
+        node_num: 3,
+        trans_num_per_second: 2,
+        slot_duration: 1,
+        consensus,
+        wallet_seed,
+        run_seconds: 8,
+        metrics_sinks: vec![MetricsSinkKind::Null],
+        ..Default::default()
+    };
+    let (a, b) = tokio::join!(
+        run_simulation(config(ConsensusType::POG, 1)),
+        run_simulation(config(ConsensusType::POS, 2))
+    );
+
+    for (own, other) in [(&a, &b), (&b, &a)] {
+        assert_eq!(own.key_registry.len(), 3);
+        assert!(own
+            .stakes
+            .keys()
+            .all(|addr| own.key_registry.contains(addr)));
+        assert!(!own
+            .stakes
+            .keys()
+            .any(|addr| other.key_registry.contains(addr)));
+        assert!(own.blockchains.values().all(|bc| bc.get_last_index() >= 1));
+    }
+}
+
+/// --trans-num 100 时按批发送交易，节点之间携带交易的消息数应明显少于逐条发送
+#[tokio::test(flavor = "multi_thread")]
+async fn test_transaction_batching_reduces_messages() {
+    let config = |tx_batch_size| SimulationConfig {
+        node_num: 10,
+        trans_num_per_second: 100,
+        tx_batch_size,
+        slot_duration: 1,
+        consensus: ConsensusType::POS,
+        run_slots: 4,
+        metrics_sinks: vec![MetricsSinkKind::Null],
+        ..Default::default()
+    };
+    // 两次运行生成的交易数不同，按每笔交易的消息数比较；实测批量发送后约为逐条发送的 1/4
+    let mut per_tx = vec![];
+    for tx_batch_size in [1, 20] {
+        let result = run_simulation(config(tx_batch_size)).await;
+        assert!(result.inclusion.generated > 0);
+        per_tx.push(result.messages.transactions as f64 / result.inclusion.generated as f64);
+    }
+    assert!(
+        per_tx[1] * 2.0 < per_tx[0],
+        "messages per transaction: unbatched {:.2}, batched {:.2}",
+        per_tx[0],
+        per_tx[1]
+    );
+}